@@ -0,0 +1,116 @@
+//! output for status bars, these always follow the player
+
+use lib::{
+    icons,
+    player::{PlaybackStatus, Player},
+    template::Template,
+};
+
+const DEFAULT_FORMAT: &str = "{artist} - {title}";
+const DEFAULT_TOOLTIP_FORMAT: &str = "{display_name}: {title}\n{album}";
+
+#[derive(Debug, clap::Parser)]
+pub struct WaybarCommand {
+    /// template for the module's text, see `metadata --format`. defaults to `{artist} - {title}`
+    #[arg(long, short)]
+    pub format: Option<String>,
+    /// defaults to `{display_name}: {title}\n{album}`
+    #[arg(long)]
+    pub tooltip_format: Option<String>,
+}
+
+fn markup_escape(s: String) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+impl WaybarCommand {
+    /// a line of the json waybar's custom modules read with `"return-type": "json"`
+    ///
+    /// `class` and `alt` are the lowercase playback status, for styling and `format-icons`.
+    pub fn output(&self, player: &Player) -> anyhow::Result<String> {
+        // waybar renders pango markup, so titles with `&` in them would break the module
+        let render = |template: &str| -> anyhow::Result<String> {
+            Ok(Template::parse(template)?.render(|key| player.field(key).map(markup_escape)))
+        };
+        let status = player
+            .capabilities()
+            .playback_status
+            .as_str()
+            .to_lowercase();
+
+        Ok(serde_json::json!({
+            "text": render(self.format.as_deref().unwrap_or(DEFAULT_FORMAT))?,
+            "tooltip": render(
+                self.tooltip_format
+                    .as_deref()
+                    .unwrap_or(DEFAULT_TOOLTIP_FORMAT)
+            )?,
+            "class": status,
+            "alt": status,
+        })
+        .to_string())
+    }
+
+    /// what to print while there is no player, waybar hides modules with empty text
+    pub fn idle() -> String {
+        serde_json::json!({ "text": "", "tooltip": "", "class": "stopped", "alt": "stopped" })
+            .to_string()
+    }
+}
+
+/// one plain line per update, for polybar's `tail = true` and i3blocks' persistent blocks
+#[derive(Debug, clap::Parser)]
+pub struct TailCommand {
+    /// template for the line, see `metadata --format`. defaults to `{artist} - {title}`
+    #[arg(long, short)]
+    pub format: Option<String>,
+    /// truncates the line to this many characters, ending it with `…`
+    #[arg(long)]
+    max_width: Option<usize>,
+    /// put in front of the line while playing
+    #[arg(long, default_value = icons::status_icon(PlaybackStatus::Playing))]
+    playing_icon: String,
+    /// put in front of the line while paused
+    #[arg(long, default_value = icons::status_icon(PlaybackStatus::Paused))]
+    paused_icon: String,
+    /// put in front of the line while stopped
+    #[arg(long, default_value = icons::status_icon(PlaybackStatus::Stopped))]
+    stopped_icon: String,
+}
+
+impl TailCommand {
+    pub fn output(&self, player: &Player) -> anyhow::Result<String> {
+        let icon = match player.capabilities().playback_status {
+            PlaybackStatus::Playing => &self.playing_icon,
+            PlaybackStatus::Paused => &self.paused_icon,
+            PlaybackStatus::Stopped => &self.stopped_icon,
+        };
+        let text = Template::parse(self.format.as_deref().unwrap_or(DEFAULT_FORMAT))?
+            .render(|key| player.field(key));
+        let line = if icon.is_empty() {
+            text
+        } else {
+            format!("{icon} {text}")
+        };
+
+        Ok(match self.max_width {
+            Some(width) => truncate(&line, width),
+            None => line,
+        })
+    }
+}
+
+/// cuts `s` down to `width` characters, the last one being `…` when anything was cut
+fn truncate(s: &str, width: usize) -> String {
+    if s.chars().count() <= width {
+        return s.to_string();
+    }
+
+    let mut truncated: String = s.chars().take(width.saturating_sub(1)).collect();
+    if width > 0 {
+        truncated.push('…');
+    }
+    truncated
+}
@@ -0,0 +1,186 @@
+//! `~/.config/mpris-controller/config.toml`, flags on the command line win over it
+//!
+//! ```toml
+//! # tried in order when --player isn't passed, names or ids from `list --json`
+//! player = ["spotify", "mpv"]
+//! # `session`, `system` or an address like `unix:path=/run/user/1000/bus`
+//! bus = "session"
+//! ignore = ["playerctld", "chromium.instance*"]
+//! # preferred over whichever player was active last, unlike `player` other players still work
+//! priority = ["spotify", "mpv", "firefox*"]
+//! # ms to wait for more changes before printing again while following
+//! debounce-ms = 100
+//! # how long to wait for a player to answer, and how often to ask again when the bus says it
+//! # didn't
+//! call-timeout-ms = 5000
+//! call-retries = 2
+//! # keep targeting the last active player after a restart, before any player starts playing
+//! remember-active = true
+//! # whether `--all-players` covers players on other devices (kdeconnect), `--player` still can
+//! all-players-remote = false
+//! # keep the screen on while anything plays, while following
+//! inhibit-idle = true
+//! # mute spotify during ads, while following
+//! mute-ads = true
+//!
+//! [format]
+//! metadata = "{artist} - {title}"
+//! waybar = "{emoji(status)} {title}"
+//! waybar-tooltip = "{display_name}: {album}"
+//! tail = "{title}"
+//!
+//! [notifications]
+//! enabled = true
+//! timeout-ms = 5000
+//!
+//! # run by `hooks`, see `lib::hooks` for the environment they get
+//! [[hooks]]
+//! on = ["track-changed"]
+//! command = ["notify-send", "{title}", "{artist}"]
+//! player = ["spotify"]
+//!
+//! # corrections for players that get things wrong, on top of the built in ones, see
+//! # `lib::quirks`
+//! [[quirks]]
+//! player = "vlc"
+//! length-unit = "milliseconds"
+//!
+//! # with the `scrobble` feature, see `lib::scrobble`
+//! [scrobble.listenbrainz]
+//! token = "..."
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::{Cli, Command};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub player: Vec<String>,
+    pub bus: Option<lib::Bus>,
+    pub ignore: Vec<String>,
+    pub priority: Vec<String>,
+    pub debounce_ms: Option<u64>,
+    pub call_timeout_ms: Option<u64>,
+    pub call_retries: Option<u32>,
+    pub remember_active: bool,
+    pub all_players_remote: Option<bool>,
+    pub inhibit_idle: bool,
+    pub mute_ads: bool,
+    pub format: Formats,
+    pub notifications: Notifications,
+    pub hooks: Vec<lib::hooks::Hook>,
+    pub quirks: Vec<lib::quirks::QuirkRule>,
+    #[cfg(feature = "scrobble")]
+    pub scrobble: lib::scrobble::ScrobbleConfig,
+}
+
+/// templates used when the command doesn't get `--format`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Formats {
+    pub metadata: Option<String>,
+    pub waybar: Option<String>,
+    pub waybar_tooltip: Option<String>,
+    pub tail: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Notifications {
+    /// notify on track changes while following
+    pub enabled: bool,
+    /// how long notifications stay up, `-1` leaves it to the notification server
+    pub timeout_ms: i32,
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout_ms: -1,
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/mpris-controller/config.toml`, falling back to `~/.config`
+pub fn default_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_home.join("mpris-controller").join("config.toml"))
+}
+
+impl Config {
+    /// reads the config at `path`, or at [`default_path`] when it is `None`
+    ///
+    /// a missing file at the default path is an empty config, one passed explicitly is an error.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let (path, explicit) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if !explicit && e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self::default());
+            }
+            Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+        };
+
+        toml::from_str(&text).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    /// fills in everything `cli` didn't get a flag for
+    pub fn apply(self, cli: &mut Cli) {
+        if cli.player.is_empty() {
+            cli.player = self.player;
+        }
+        if cli.ignore_player.is_empty() {
+            cli.ignore_player = self.ignore;
+        }
+        cli.bus = cli.bus.take().or(self.bus);
+        cli.priority = self.priority;
+        cli.remember_active = self.remember_active;
+        cli.all_players_remote = self.all_players_remote.unwrap_or(true);
+        cli.inhibit_idle |= self.inhibit_idle;
+        cli.mute_ads |= self.mute_ads;
+        cli.debounce = cli.debounce.or(self.debounce_ms);
+        cli.call_timeout = cli.call_timeout.or(self.call_timeout_ms);
+        cli.call_retries = self.call_retries;
+
+        cli.notify = match (cli.notify, cli.no_notify) {
+            (true, _) => true,
+            (_, true) => false,
+            _ => self.notifications.enabled,
+        };
+        cli.notification_timeout = self.notifications.timeout_ms;
+        cli.hooks = self.hooks;
+        cli.quirks = self.quirks;
+
+        let formats = self.format;
+        match &mut cli.command {
+            Command::Metadata(metadata) if metadata.format.is_none() => {
+                metadata.format = formats.metadata;
+            }
+            Command::Waybar(waybar) => {
+                waybar.format = waybar.format.take().or(formats.waybar);
+                waybar.tooltip_format = waybar.tooltip_format.take().or(formats.waybar_tooltip);
+            }
+            Command::Tail(tail) => tail.format = tail.format.take().or(formats.tail),
+            #[cfg(feature = "scrobble")]
+            Command::Scrobble(scrobble) => scrobble.config = self.scrobble,
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,56 @@
+//! exit codes scripts and keybinding daemons can branch on
+//!
+//! | code | meaning |
+//! |------|---------|
+//! | 0    | success |
+//! | 1    | no player found, also anything not covered below |
+//! | 2    | the player doesn't support the operation |
+//! | 3    | talking to D-Bus failed |
+
+use std::fmt;
+
+pub const NO_PLAYERS: i32 = 1;
+pub const UNSUPPORTED: i32 = 2;
+pub const DBUS: i32 = 3;
+
+/// the failures that get their own exit code, D-Bus errors are recognized by their type
+#[derive(Debug)]
+pub enum Failure {
+    NoPlayers(String),
+    Unsupported(String),
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::NoPlayers(msg) | Failure::Unsupported(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for Failure {}
+
+pub fn no_players(msg: impl Into<String>) -> anyhow::Error {
+    Failure::NoPlayers(msg.into()).into()
+}
+
+pub fn unsupported(msg: impl Into<String>) -> anyhow::Error {
+    Failure::Unsupported(msg.into()).into()
+}
+
+pub fn code(error: &anyhow::Error) -> i32 {
+    for cause in error.chain() {
+        if let Some(failure) = cause.downcast_ref::<Failure>() {
+            return match failure {
+                Failure::NoPlayers(_) => NO_PLAYERS,
+                Failure::Unsupported(_) => UNSUPPORTED,
+            };
+        }
+        if cause.is::<zbus::Error>() || cause.is::<zbus::fdo::Error>() {
+            return DBUS;
+        }
+    }
+
+    // like most tools, 1 doubles as the generic failure
+    NO_PLAYERS
+}
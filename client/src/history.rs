@@ -0,0 +1,138 @@
+//! `history`: records what the players play and looks it up again, see `lib::history`
+
+use std::{path::PathBuf, str::FromStr, time::Duration};
+
+use lib::{
+    MprisClient,
+    history::{History, HistoryRecorder, Play},
+    playtime::unix_now,
+};
+
+#[derive(Debug, clap::Parser)]
+pub struct HistoryCommand {
+    /// keeps running and records every track played, instead of printing the history
+    #[arg(long)]
+    record: bool,
+    /// what was playing this long ago, give or take 5 minutes, like `1h` or `90m`
+    #[arg(long, conflicts_with = "record")]
+    ago: Option<Ago>,
+    /// only plays with this in the title, artist or album
+    #[arg(long, conflicts_with_all = ["record", "ago"])]
+    search: Option<String>,
+    /// how many plays to print at most
+    #[arg(long, short = 'n', default_value_t = 20)]
+    limit: usize,
+    /// defaults to `$XDG_DATA_HOME/mpris-controller/history.sqlite`
+    #[arg(long)]
+    database: Option<PathBuf>,
+}
+
+/// how far to look back around `--ago`
+const WINDOW: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Ago(Duration);
+
+impl FromStr for Ago {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unit_at = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+        let (n, unit) = s.split_at(unit_at);
+        let n: f64 = n.trim().parse()?;
+        anyhow::ensure!(n.is_finite() && n >= 0.0, "invalid duration {s}");
+        let secs = match unit {
+            "" | "s" => n,
+            "m" => n * 60.0,
+            "h" => n * 3600.0,
+            "d" => n * 86400.0,
+            _ => anyhow::bail!("unknown unit {unit} in {s}"),
+        };
+
+        Ok(Self(Duration::try_from_secs_f64(secs)?))
+    }
+}
+
+impl HistoryCommand {
+    /// whether the command needs the bus, only recording does
+    pub fn records(&self) -> bool {
+        self.record
+    }
+
+    fn open(&self) -> anyhow::Result<History> {
+        let path = self
+            .database
+            .clone()
+            .or_else(History::default_path)
+            .ok_or_else(|| anyhow::anyhow!("can not find a place for the history database"))?;
+        History::open(&path)
+    }
+
+    /// prints the plays asked for, newest first
+    pub fn print(&self, json: bool) -> anyhow::Result<()> {
+        let history = self.open()?;
+        let plays = match (&self.ago, &self.search) {
+            (Some(Ago(ago)), _) => {
+                let mut plays = history.around(unix_now().saturating_sub(ago.as_secs()), WINDOW)?;
+                plays.truncate(self.limit);
+                plays
+            }
+            (None, Some(text)) => history.search(text, self.limit)?,
+            (None, None) => history.recent(self.limit)?,
+        };
+
+        let now = unix_now();
+        for play in &plays {
+            if json {
+                println!("{}", serde_json::to_string(play)?);
+            } else {
+                println!("{}", line(play, now));
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn record(&self, mut client: MprisClient) -> anyhow::Result<()> {
+        let mut recorder = HistoryRecorder::new(self.open()?);
+        // players coming and going show up as events
+        client.follow_owner_changes().await?;
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+        loop {
+            let step = async {
+                let events = client.event().await;
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                events
+            };
+            tokio::select! {
+                events = step => recorder.handle_events(&client, &events)?,
+                // plays still going get their end time
+                _ = &mut ctrl_c => return recorder.finish_all(),
+            }
+        }
+    }
+}
+
+/// `1h 5m ago  Artist - Title  (spotify, 87%)`
+fn line(play: &Play, now: u64) -> String {
+    let ago = now.saturating_sub(play.started_at);
+    let ago = match ago {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", ago / 60),
+        3600..86400 => format!("{}h {}m ago", ago / 3600, ago % 3600 / 60),
+        _ => format!("{}d ago", ago / 86400),
+    };
+    let track = match (&play.artist, &play.title, &play.url) {
+        (Some(artist), Some(title), _) => format!("{artist} - {title}"),
+        (None, Some(title), _) => title.clone(),
+        (_, None, Some(url)) => url.clone(),
+        _ => "unknown".to_string(),
+    };
+    let status = match (play.ended_at, play.completion) {
+        (None, _) => "playing".to_string(),
+        (Some(_), Some(completion)) => format!("{:.0}%", completion * 100.0),
+        (Some(_), None) => "ended".to_string(),
+    };
+
+    format!("{ago:>12}  {track}  ({}, {status})", play.player)
+}
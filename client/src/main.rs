@@ -1,28 +1,170 @@
 use std::{
     fmt::Write as _,
-    io::{ErrorKind, Read, Write},
+    io::{Read, Write},
     os::unix::net::UnixStream,
+    path::PathBuf,
+    time::Duration,
 };
 
 use clap::Parser;
-use lib::{Client, MprisClient, Server, server::Command};
+use futures::StreamExt;
+use lib::{
+    Bus, Client, MprisClient, Server,
+    ads::AdMuter,
+    call::CallPolicy,
+    notify::Notifier,
+    persist,
+    player::{MprisEvent, PlaybackStatus, Player},
+    progress,
+    quirks::QuirkRegistry,
+    selector::Selector,
+    server::Command as ServerCommand,
+};
 use prost::Message;
-use tracing::info;
-use tracing_subscriber::{EnvFilter, fmt::format::FmtSpan};
+use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
 use zbus::Connection;
 
+mod bar;
+mod config;
+mod exit;
+#[cfg(feature = "history")]
+mod history;
+mod position;
+#[cfg(feature = "scrobble")]
+mod scrobble;
+mod soak;
+mod volume;
+
+/// control MPRIS media players, like playerctl
 #[derive(Debug, clap::Parser)]
-enum Cli {
-    Players,
-    Playing,
-    Prev,
-    After,
-    Stop,
-    TogglePause,
-    Pause,
+#[command(version)]
+struct Cli {
+    /// players to control, by full bus name or the part after `org.mpris.MediaPlayer2.`
+    /// (`spotify`, `firefox`), the first one found wins. `*` works as a wildcard
+    #[arg(long, short, global = true, value_delimiter = ',')]
+    player: Vec<String>,
+    /// apply the command to every player, or every one matching `--player`
+    #[arg(long, short, global = true)]
+    all_players: bool,
+    /// players to leave out of everything, by bus name like `--player` (`playerctld`,
+    /// `chromium.instance*`)
+    #[arg(long, global = true, value_delimiter = ',')]
+    ignore_player: Vec<String>,
+    /// print one json object per line instead of text
+    #[arg(long, global = true)]
+    json: bool,
+    /// while following, wait this many milliseconds for more changes before printing again
+    #[arg(long, global = true)]
+    debounce: Option<u64>,
+    /// give up on a player that doesn't answer within this many milliseconds, 5000 by default
+    #[arg(long, global = true)]
+    call_timeout: Option<u64>,
+    #[arg(skip)]
+    call_retries: Option<u32>,
+    /// send a desktop notification on track changes while following
+    #[arg(long, global = true, overrides_with = "no_notify")]
+    notify: bool,
+    #[arg(long, global = true, overrides_with = "notify")]
+    no_notify: bool,
+    #[arg(skip = -1)]
+    notification_timeout: i32,
+    /// keep the screen from going idle while any player is playing, while following
+    #[arg(long, global = true)]
+    inhibit_idle: bool,
+    /// mute spotify while it plays ads, while following
+    #[arg(long, global = true)]
+    mute_ads: bool,
+    #[arg(skip)]
+    priority: Vec<String>,
+    #[arg(skip)]
+    remember_active: bool,
+    #[arg(skip = true)]
+    all_players_remote: bool,
+    #[arg(skip)]
+    hooks: Vec<lib::hooks::Hook>,
+    #[arg(skip)]
+    quirks: Vec<lib::quirks::QuirkRule>,
+    /// the bus the players are on, `session` (the default), `system` or an address like
+    /// `unix:path=/run/user/1000/bus`
+    #[arg(long, global = true)]
+    bus: Option<Bus>,
+    /// defaults to `$XDG_CONFIG_HOME/mpris-controller/config.toml`
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Command {
     Play,
-    Url,
+    Pause,
+    /// pauses when playing, plays otherwise
+    #[command(alias = "toggle-pause")]
+    PlayPause,
+    Stop,
+    /// skips to the next track
+    #[command(alias = "after")]
+    Next,
+    /// goes back to the previous track
+    #[command(alias = "prev")]
+    Previous,
+    /// prints the playback status
+    Status(StatusCommand),
+    /// prints the current track
     Metadata(MetadataCommand),
+    /// prints the position in seconds or seeks
+    Position(position::PositionCommand),
+    /// seeks relative to the position, like `seek +10s` or `seek -30s`
+    Seek(position::SeekCommand),
+    /// prints or sets the volume
+    Volume(volume::VolumeCommand),
+    /// follows the player as json for a waybar custom module with `"return-type": "json"`
+    Waybar(bar::WaybarCommand),
+    /// follows the player as one line per update, for polybar and i3blocks
+    Tail(bar::TailCommand),
+    /// lists the players on the bus with their identity, desktop entry and status
+    #[command(alias = "players")]
+    List,
+    /// makes the next player the active one, like `playerctld shift`
+    Shift,
+    /// makes the previous player the active one
+    Unshift,
+    /// brings the player's window to the front
+    Raise,
+    /// closes the player
+    Quit,
+    /// opens a uri (a file, a stream, a spotify: link) in the player
+    Open(OpenCommand),
+    /// serves `org.mpris.MediaPlayer2.mpris_controller`, which forwards everything to the active
+    /// player, like `playerctld`
+    Proxy,
+    /// runs the `[[hooks]]` of the config as things happen, until killed
+    Hooks,
+    /// keeps a file with the current track (and optionally its art) up to date, for OBS
+    NowPlaying(NowPlayingCommand),
+    /// grabs the media keys through the desktop portal, the gnome/mate settings daemon or the X
+    /// server and sends them to the active player, until killed
+    #[cfg(feature = "media-keys")]
+    MediaKeys,
+    /// serves an MPD server as `org.mpris.MediaPlayer2.mpd`
+    #[cfg(feature = "mpd-bridge")]
+    Mpd(MpdCommand),
+    /// answers newline-delimited JSON-RPC on a unix socket, for scripts and remote control
+    #[cfg(feature = "rpc")]
+    Rpc(RpcCommand),
+    /// streams events as json on a local websocket, for browser overlays
+    #[cfg(feature = "ws")]
+    Ws(WsCommand),
+    /// submits what is played to Last.fm and ListenBrainz, configured in `[scrobble]`
+    #[cfg(feature = "scrobble")]
+    Scrobble(scrobble::ScrobbleCommand),
+    /// prints what was played, or records it with `--record`
+    #[cfg(feature = "history")]
+    History(history::HistoryCommand),
+    /// dev: runs the client against randomized mock players for a long time
+    Soak(soak::SoakCommand),
 }
 
 // #[derive(Debug)]
@@ -185,289 +327,897 @@ enum Cli {
 //     }
 // }
 
+/// `--follow` for the commands that print something about the player
+#[derive(Debug, clap::Args)]
+pub struct FollowArgs {
+    /// keep running and print again whenever the output changes
+    #[arg(long, short = 'F')]
+    follow: bool,
+}
+
+#[derive(Debug, clap::Parser)]
+struct StatusCommand {
+    #[command(flatten)]
+    follow: FollowArgs,
+}
+
 #[derive(Debug, clap::Parser)]
 struct MetadataCommand {
-    // #[arg(long, default_value_t = true)]
+    #[command(flatten)]
+    follow: FollowArgs,
+    /// a template like `{artist} - {title} ({album})`, any metadata key works (`xesam:title`,
+    /// `mpris:artUrl`) as do `status` and `position`. keys can be wrapped in `duration()`,
+    /// `emoji()`, `lc()`, `uc()` and `markup_escape()`
+    #[arg(long, short)]
+    format: Option<String>,
     #[arg(long)]
     art_url: bool,
-    // #[arg(long, default_value_t = true)]
     #[arg(long)]
     length: bool,
-    // #[arg(long, default_value_t = true)]
     #[arg(long)]
     trackid: bool,
-    // #[arg(long, default_value_t = true)]
     #[arg(long)]
     album: bool,
-    // #[arg(long, default_value_t = true)]
     #[arg(long)]
     artists: bool,
-    // #[arg(long, default_value_t = true)]
     #[arg(long)]
     title: bool,
-    // #[arg(long, default_value_t = true)]
     #[arg(long)]
     url: bool,
-    // #[arg(long, default_value_t = true)]
     #[arg(long)]
     track_number: bool,
-    // #[arg(long, default_value_t = true)]
     #[arg(long)]
     disc_number: bool,
-    // #[arg(long, default_value_t = true)]
     #[arg(long)]
     auto_rating: bool,
-    // #[arg(long, default_value_t = true)]
     #[arg(long)]
     album_artists: bool,
 }
 
-fn send_command(command: Server, buf: &mut Vec<u8>, socket: &mut UnixStream) {
-    command.encode(buf).unwrap();
+#[derive(Debug, clap::Parser)]
+struct OpenCommand {
+    uri: String,
+    /// open the uri in whichever player supports it best if the selected one doesn't
+    #[arg(long)]
+    route: bool,
+}
 
-    socket.write_all(buf).unwrap();
+#[derive(Debug, clap::Parser)]
+struct NowPlayingCommand {
+    /// the text file to write
+    output: PathBuf,
+    /// a template like for `metadata --format`
+    #[arg(long, short, default_value = lib::now_playing::DEFAULT_FORMAT)]
+    format: String,
+    /// also copy the cover art here, it's removed for tracks without one
+    #[arg(long)]
+    art: Option<PathBuf>,
+    /// what to write when nothing is playing
+    #[arg(long, default_value = "")]
+    idle: String,
 }
 
-#[tokio::main]
-async fn main() {
-    std::panic::set_hook(Box::new(|panic_info| {
-        println!("panic occurred: {panic_info}");
-    }));
-
-    let user = std::env::home_dir().unwrap();
-    let user = user.to_str().unwrap();
-    let path = format!("{user}/.local/share/log");
-    let _file = std::fs::OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(false)
-        .open(&path)
-        .expect("truncating log file failed");
+#[cfg(feature = "mpd-bridge")]
+#[derive(Debug, clap::Parser)]
+struct MpdCommand {
+    /// `host:port` or a socket path, from `MPD_HOST` and `MPD_PORT` by default
+    #[arg(long)]
+    address: Option<String>,
+}
 
-    tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::new("client=trace"))
-        .with_span_events(FmtSpan::FULL)
-        .init();
+#[cfg(feature = "rpc")]
+#[derive(Debug, clap::Parser)]
+struct RpcCommand {
+    /// defaults to `$XDG_RUNTIME_DIR/mpris-controller.sock`
+    #[arg(long)]
+    socket: Option<PathBuf>,
+}
 
-    let conn = Connection::session().await.unwrap();
+#[cfg(feature = "ws")]
+#[derive(Debug, clap::Parser)]
+struct WsCommand {
+    #[arg(long, default_value_t = lib::ws::DEFAULT_ADDRESS)]
+    address: std::net::SocketAddr,
+    /// accept commands too, any page open in a browser can reach the server. local files like
+    /// OBS browser sources only get in with `--allow-origin null`
+    #[arg(long)]
+    control: bool,
+    /// let pages from this origin connect besides the ones on localhost, can be repeated. `null`
+    /// lets in local files, and sandboxed pages of any site
+    #[arg(long = "allow-origin", value_name = "ORIGIN")]
+    origins: Vec<String>,
+}
+
+const SOCKET: &str = "/tmp/mpris-controller.sock";
 
-    let mut client = MprisClient::new().unwrap();
-    client.get_all(&conn).await.unwrap();
+/// the player the server considers focused, `None` when the server isn't running or has none
+fn focused_player() -> Option<String> {
+    let mut server = UnixStream::connect(SOCKET)
+        .inspect_err(|e| info!("server not reachable: {e}"))
+        .ok()?;
 
-    let mut server = std::os::unix::net::UnixStream::connect("/tmp/mpris-controller.sock").unwrap();
     let mut bytes = vec![];
+    Server {
+        command: Some(ServerCommand::GetPlayer(true)),
+    }
+    .encode(&mut bytes)
+    .ok()?;
+    server.write_all(&bytes).ok()?;
 
     let mut buff = [0; 512];
+    let amt = server.read(&mut buff).ok()?;
+    match Client::decode(&buff[..amt]).ok()?.message? {
+        lib::client::Message::FocusedPlayer(focused) => Some(focused),
+        lib::client::Message::CouldNotFindPlayer(_) => None,
+    }
+}
 
-    let mut player_name: Option<String> = None;
+/// tells the server which player to consider focused, does nothing when it isn't running
+fn set_focused_player(name: &str) {
+    let Ok(mut server) =
+        UnixStream::connect(SOCKET).inspect_err(|e| info!("server not reachable: {e}"))
+    else {
+        return;
+    };
 
+    let mut bytes = vec![];
     let message = Server {
-        command: Some(Command::GetPlayer(true)),
+        command: Some(ServerCommand::SetFocusedPlayer(name.to_string())),
     };
-    send_command(message, &mut bytes, &mut server);
+    if message.encode(&mut bytes).is_ok()
+        && let Err(e) = server.write_all(&bytes)
+    {
+        warn!("failed to tell the server about the focused player: {e}");
+    }
+}
 
-    loop {
-        match server.read(&mut buff) {
-            Ok(amt) => {
-                let msg = Client::decode(&buff[..amt]).unwrap();
-                match msg.message {
-                    Some(msg) => match msg {
-                        lib::client::Message::FocusedPlayer(focused) => {
-                            info!("name {:?}", focused.len());
-                            player_name = Some(focused);
-                            break;
-                        }
-                        lib::client::Message::CouldNotFindPlayer(_) => {
-                            println!("Could not find player");
-                            break;
-                        }
-                    },
-                    None => todo!(),
-                }
-            }
-            Err(e) => {
-                if e.kind() != ErrorKind::WouldBlock {
-                    panic!()
-                }
+/// the players a command applies to
+///
+/// without `--player` that is the focused player, otherwise the preferred one (see
+/// [`MprisClient::preferred_player`]).
+fn select_players<'a>(client: &'a MprisClient, cli: &Cli) -> anyhow::Result<Vec<&'a Player>> {
+    if client.players().is_empty() {
+        return Err(exit::no_players("no players found"));
+    }
+
+    // `spotify` also covers `spotify.instance42`, ids from `list --json` work too
+    let selector = cli.player.iter().fold(Selector::new(), |selector, filter| {
+        selector.include(filter).prefer(filter)
+    });
+    let selected: Vec<&Player> = match (cli.player.is_empty(), cli.all_players) {
+        // players on a phone only when asked for by name
+        (true, true) if !cli.all_players_remote => selector.remote(false).select_all(client),
+        (_, true) => selector.select_all(client),
+        (false, false) => selector.select(client).into_iter().collect(),
+        (true, false) => focused_player()
+            .and_then(|name| client.get(&name))
+            .or_else(|| client.preferred_player())
+            .into_iter()
+            .collect(),
+    };
+
+    if selected.is_empty() {
+        return Err(exit::no_players(format!(
+            "no player matches {}, available players:\n  {}",
+            cli.player.join(", "),
+            client.player_names().join("\n  ")
+        )));
+    }
+
+    Ok(selected)
+}
+
+#[tokio::main]
+async fn main() {
+    // stdout is for the output of the commands
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("client=warn")),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+
+    let cli = Cli::parse();
+    if let Err(e) = run(cli).await {
+        eprintln!("{e:#}");
+        std::process::exit(exit::code(&e));
+    }
+}
+
+async fn run(mut cli: Cli) -> anyhow::Result<()> {
+    // doesn't need the server or any real players
+    if let Command::Soak(command) = cli.command {
+        return soak::run(command).await;
+    }
+    #[cfg(feature = "history")]
+    if let Command::History(command) = &cli.command
+        && !command.records()
+    {
+        return command.print(cli.json);
+    }
+    config::Config::load(cli.config.as_deref())?.apply(&mut cli);
+    #[cfg(feature = "mpd-bridge")]
+    if let Command::Mpd(command) = &cli.command {
+        let address = command
+            .address
+            .clone()
+            .unwrap_or_else(lib::mpd::default_address);
+        let builder = cli.bus.clone().unwrap_or_default().builder()?;
+        return lib::mpd::MpdBridge::start_on(&address, builder)
+            .await?
+            .run()
+            .await;
+    }
+    let mut client = MprisClient::connect_to(cli.bus.clone().unwrap_or_default()).await?;
+    let policy = CallPolicy::default();
+    client.set_call_policy(CallPolicy {
+        timeout: cli
+            .call_timeout
+            .map_or(policy.timeout, Duration::from_millis),
+        retries: cli.call_retries.unwrap_or(policy.retries),
+        ..policy
+    });
+    client.ignore(&cli.ignore_player);
+    client.set_priority(&cli.priority);
+    client.set_quirks(QuirkRegistry::default().with_rules(std::mem::take(&mut cli.quirks)));
+    if cli.remember_active {
+        client.persist_active(persist::default_path());
+    }
+    let discovery = client.get_all().await?;
+    for (name, e) in &discovery.failed {
+        warn!(player = name, "skipped: {e:#}");
+    }
+    let conn = client
+        .connection()
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("not connected"))?;
+
+    if let Command::Proxy = cli.command {
+        return proxy(client).await;
+    }
+    if let Command::Hooks = cli.command {
+        return hooks(client, cli.hooks).await;
+    }
+    if let Command::NowPlaying(command) = &cli.command {
+        return now_playing(client, &cli, command).await;
+    }
+    #[cfg(feature = "media-keys")]
+    if let Command::MediaKeys = cli.command {
+        return media_keys(client).await;
+    }
+    #[cfg(feature = "rpc")]
+    if let Command::Rpc(command) = &cli.command {
+        return rpc(client, command).await;
+    }
+    #[cfg(feature = "ws")]
+    if let Command::Ws(command) = &cli.command {
+        return ws(client, command).await;
+    }
+    #[cfg(feature = "scrobble")]
+    if let Command::Scrobble(command) = cli.command {
+        return scrobble::run(command, client).await;
+    }
+    #[cfg(feature = "history")]
+    if let Command::History(command) = &cli.command {
+        return command.record(client).await;
+    }
+
+    if let Command::List = cli.command {
+        print_list(&client, cli.json);
+        return Ok(());
+    }
+
+    if let Command::Shift | Command::Unshift = cli.command {
+        // start from whatever a command without `--player` would have used
+        if let Some(focused) = focused_player().and_then(|name| client.get_id(&name)) {
+            client.set_active_player(focused);
+        }
+        let player = match cli.command {
+            Command::Shift => client.shift(),
+            _ => client.unshift(),
+        }
+        .ok_or_else(|| exit::no_players("no players found"))?;
+
+        set_focused_player(player.name());
+        println!("{}", player.name());
+        return Ok(());
+    }
+
+    if let Command::Position(_) = cli.command {
+        refresh_positions(&mut client, &conn).await;
+    }
+
+    if cli.command.follow() {
+        return follow(&mut client, &cli).await;
+    }
+
+    if let Command::Volume(volume) = &cli.command
+        && volume.changes_volume()
+    {
+        let names: Vec<String> = select_players(&client, &cli)?
+            .into_iter()
+            .map(|p| p.name().to_string())
+            .collect();
+        for name in names {
+            if let Some(player) = client.get_mut(&name) {
+                volume.run(player, &conn).await?;
             }
         }
+        return Ok(());
     }
 
-    let cli = Cli::parse();
+    for player in select_players(&client, &cli)? {
+        run_command(&cli, &client, player, &conn).await?;
+    }
+
+    Ok(())
+}
+
+/// a row per player with its bus name, identity, desktop entry and status
+fn print_list(client: &MprisClient, json: bool) {
+    if json {
+        for player in client.players() {
+            let root = player.root();
+            let json = serde_json::json!({
+                "player": player.name(),
+                "id": player.stable_id(),
+                "identity": root.identity,
+                "display_name": player.display_name(),
+                "icon": player.icon(),
+                "desktop_entry": root.desktop_entry,
+                "remote": player.is_remote(),
+                "bluetooth": player.is_bluetooth(),
+                "status": player.capabilities().playback_status,
+            });
+            println!("{json}");
+        }
+        return;
+    }
+
+    let mut rows = vec![[
+        "PLAYER".to_string(),
+        "IDENTITY".to_string(),
+        "DESKTOP ENTRY".to_string(),
+        "STATUS".to_string(),
+    ]];
+    for player in client.players() {
+        let root = player.root();
+        rows.push([
+            player.name().to_string(),
+            root.identity.clone().unwrap_or_else(|| "-".to_string()),
+            root.desktop_entry
+                .clone()
+                .unwrap_or_else(|| "-".to_string()),
+            player.capabilities().playback_status.to_string(),
+        ]);
+    }
+
+    let mut widths = [0; 4];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in &rows {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+}
+
+impl Command {
+    fn follow(&self) -> bool {
+        match self {
+            Command::Status(StatusCommand { follow }) => follow.follow,
+            Command::Metadata(MetadataCommand { follow, .. }) => follow.follow,
+            Command::Position(position) => position.follow.follow && !position.changes_position(),
+            Command::Waybar(_) | Command::Tail(_) => true,
+            _ => false,
+        }
+    }
+
+    /// bars read one line per update, so they only show the first player
+    fn single_line(&self) -> bool {
+        matches!(self, Command::Waybar(_) | Command::Tail(_))
+    }
+
+    /// what following prints while no player is selected
+    fn idle(&self) -> String {
+        match self {
+            Command::Waybar(_) => bar::WaybarCommand::idle(),
+            // playerctl prints an empty line too, so bars clear the module
+            _ => String::new(),
+        }
+    }
 
-    if let Some(player_name) = player_name {
-        info!(?player_name);
-        let playing = client.get(&player_name).unwrap();
-        match cli {
-            Cli::Prev => {
-                playing.prev(&conn).await;
+    /// what the command prints for `player`, `None` for commands that don't print anything
+    fn output(&self, player: &Player, json: bool) -> Option<anyhow::Result<String>> {
+        let status = player.capabilities().playback_status;
+        match (self, json) {
+            (Command::Status(_), false) => Some(Ok(status.to_string())),
+            (Command::Status(_), true) => Some(Ok(serde_json::json!({
+                "player": player.name(),
+                "status": status,
+            })
+            .to_string())),
+            (Command::Metadata(data), false) => Some(metadata_line(player, data)),
+            (Command::Metadata(data), true) => Some(metadata_json(player, data)),
+            (Command::Position(position), _) if !position.changes_position() => {
+                Some(Ok(position::PositionCommand::output(player, json)))
             }
-            Cli::After => {
-                playing.next(&conn).await;
+            (Command::Volume(volume), _) if !volume.changes_volume() => {
+                Some(volume::VolumeCommand::output(player, json))
             }
-            Cli::Stop => {
-                playing.stop(&conn).await;
+            (Command::Waybar(waybar), _) => Some(waybar.output(player)),
+            (Command::Tail(tail), _) => Some(tail.output(player)),
+            _ => None,
+        }
+    }
+}
+
+/// positions aren't signalled, they have to be asked for
+async fn refresh_positions(client: &mut MprisClient, conn: &Connection) {
+    let names: Vec<String> = client
+        .player_names()
+        .into_iter()
+        .map(String::from)
+        .collect();
+    for name in names {
+        let now = client.clock().now();
+        if let Some(player) = client.get_mut(&name)
+            && let Err(e) = player.fetch_position_at(conn, now).await
+        {
+            warn!(player = name, "failed to get position: {e:#}");
+        }
+    }
+}
+
+/// how often `position --follow` asks for the position
+const POSITION_INTERVAL: Duration = Duration::from_secs(1);
+
+/// prints the output of the command whenever it changes, until the process is killed
+async fn follow(client: &mut MprisClient, cli: &Cli) -> anyhow::Result<()> {
+    // players coming and going show up as events
+    client.follow_owner_changes().await?;
+
+    let positions = matches!(cli.command, Command::Position(_));
+    let mut ticks = if positions {
+        client.position_ticks(POSITION_INTERVAL)
+    } else {
+        futures::stream::pending().boxed()
+    };
+    let mut conn = client
+        .connection()
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("not connected"))?;
+    let (mut notifier, mut inhibitor, mut ad_muter) = follow_helpers(cli, &conn);
+    let debounce = cli.debounce.map(Duration::from_millis);
+
+    let mut last = None;
+    loop {
+        if positions {
+            refresh_positions(client, &conn).await;
+        }
+
+        let line = match select_players(client, cli) {
+            Ok(players) => players
+                .into_iter()
+                .take(if cli.command.single_line() {
+                    1
+                } else {
+                    usize::MAX
+                })
+                .filter_map(|player| cli.command.output(player, cli.json))
+                .collect::<anyhow::Result<Vec<_>>>()?
+                .join("\n"),
+            Err(e) => {
+                info!("{e:#}");
+                cli.command.idle()
             }
-            Cli::TogglePause => {
-                println!("player name {player_name:?}");
-
-                match playing.capabilities().playback_status {
-                    lib::player::PlaybackStatus::Stopped => playing.play(&conn).await,
-                    lib::player::PlaybackStatus::Paused => playing.play(&conn).await,
-                    lib::player::PlaybackStatus::Playing => {
-                        playing.pause(&conn).await;
-                    }
+        };
+
+        if last.as_ref() != Some(&line) {
+            println!("{line}");
+            last = Some(line);
+        }
+
+        // the signals are polled, not awaited. while playing, a position tick ends the wait
+        let mut events = client.event().await;
+        while events.is_empty() {
+            tokio::select! {
+                Some(_) = ticks.next() => break,
+                _ = tokio::time::sleep(Duration::from_millis(50)) => {
+                    events = client.event().await;
                 }
             }
-            Cli::Pause => {
-                playing.pause(&conn).await;
-            }
-            Cli::Play => {
-                playing.play(&conn).await;
-            }
-            Cli::Players => {
-                for player in client.player_names() {
-                    print!("{} ", player)
+        }
+
+        // a track change is a handful of signals, print once they stop coming
+        if let Some(debounce) = debounce
+            && !events.is_empty()
+        {
+            loop {
+                tokio::time::sleep(debounce).await;
+                let more = client.event().await;
+                if more.is_empty() {
+                    break;
                 }
-                println!();
+                events.extend(more);
             }
-            Cli::Playing => {
-                let metadata = &playing.capabilities().metadata;
-                let title = metadata.title().unwrap_or("");
-                let artists = metadata.artists();
-
-                let url = metadata.url().unwrap_or("");
-                print!("{} - ", title);
-                if let Some(a) = artists {
-                    for a in a {
-                        print!("{a} ");
-                    }
+        }
+
+        // the helpers would keep talking through the connection that was lost
+        if events
+            .iter()
+            .any(|e| matches!(e, MprisEvent::ConnectionRestored))
+            && let Some(restored) = client.connection()
+        {
+            conn = restored.clone();
+            (notifier, inhibitor, ad_muter) = follow_helpers(cli, &conn);
+        }
+
+        if let Some(notifier) = &mut notifier
+            && let Err(e) = notifier.handle_events(client, &events).await
+        {
+            warn!("failed to notify: {e:#}");
+        }
+        if let Some(inhibitor) = &mut inhibitor
+            && let Err(e) = inhibitor.update(client).await
+        {
+            warn!("failed to inhibit idle: {e:#}");
+        }
+        if let Some(ad_muter) = &mut ad_muter
+            && let Err(e) = ad_muter.handle_events(client, &events).await
+        {
+            warn!("failed to mute ad: {e:#}");
+        }
+    }
+}
+
+/// what `follow` does besides printing, as turned on by `cli`
+fn follow_helpers(
+    cli: &Cli,
+    conn: &Connection,
+) -> (
+    Option<Notifier>,
+    Option<lib::inhibit::IdleInhibitor>,
+    Option<AdMuter>,
+) {
+    let notifier = cli.notify.then(|| {
+        let mut notifier = Notifier::new(conn.clone());
+        notifier.set_timeout(cli.notification_timeout);
+        notifier
+    });
+    // released by the bus when the process exits
+    let inhibitor = cli
+        .inhibit_idle
+        .then(|| lib::inhibit::IdleInhibitor::new(conn.clone()));
+    let ad_muter = cli.mute_ads.then(|| AdMuter::new(conn.clone()));
+    (notifier, inhibitor, ad_muter)
+}
+
+async fn proxy(mut client: MprisClient) -> anyhow::Result<()> {
+    client.follow_owner_changes().await?;
+    let mut proxy = lib::proxy::Proxy::start(client).await?;
+    info!(name = proxy.service().name(), "proxying");
+    loop {
+        proxy.step().await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+#[cfg(feature = "media-keys")]
+async fn media_keys(mut client: MprisClient) -> anyhow::Result<()> {
+    let conn = client
+        .connection()
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("not connected"))?;
+    let mut keys = lib::media_keys::MediaKeys::grab(&conn, "mpris-controller").await?;
+    info!(backend = ?keys.backend(), "grabbed the media keys");
+    // the active player follows what starts playing
+    client.follow_owner_changes().await?;
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    loop {
+        tokio::select! {
+            key = keys.next() => {
+                let key = key.ok_or_else(|| anyhow::anyhow!("lost the connection the keys come from"))?;
+                if let Err(e) = key.press(&client).await {
+                    warn!("{key}: {e:#}");
                 }
-                println!("{url}");
             }
-            Cli::Url => {
-                let url = playing.capabilities().metadata.url().unwrap_or("");
-                println!("{url}");
+            _ = tokio::time::sleep(Duration::from_millis(50)) => {
+                client.event().await;
             }
-            Cli::Metadata(data) => {
-                let mut fmt = String::new();
-                let metadata = &playing.capabilities().metadata;
-                if data.art_url {
-                    fmt.write_fmt(format_args!("{} ", metadata.url().unwrap_or("")))
-                        .unwrap();
-                }
-                if data.length {
-                    let mut printable_len = [0; 3];
-                    match metadata.length() {
-                        None => fmt.write_char(' ').unwrap(),
-                        Some(len) => {
-                            let total_secs = len / 1_000_000;
-                            let mut minutes = total_secs / 60;
-                            let hours = minutes / 60;
-                            if minutes >= 60 {
-                                minutes %= 60;
-                            }
-                            let secs = (total_secs % 60) as u8;
-
-                            // info!(?hours, minutes, ?secs);
-
-                            printable_len[0] = secs;
-                            printable_len[1] = minutes as u8;
-                            printable_len[2] = hours as u8;
-
-                            if printable_len[2] < 10 {
-                                fmt.write_fmt(format_args!("0{}:", printable_len[2]))
-                                    .unwrap();
-                            } else {
-                                fmt.write_fmt(format_args!("{}:", printable_len[2]))
-                                    .unwrap();
-                            }
-
-                            if printable_len[1] < 10 {
-                                fmt.write_fmt(format_args!("0{}:", printable_len[1]))
-                                    .unwrap();
-                            } else {
-                                fmt.write_fmt(format_args!("{}:", printable_len[1]))
-                                    .unwrap();
-                            }
-
-                            if printable_len[0] < 10 {
-                                fmt.write_fmt(format_args!("0{}:", printable_len[0]))
-                                    .unwrap();
-                            } else {
-                                fmt.write_fmt(format_args!("{}:", printable_len[0]))
-                                    .unwrap();
-                            }
-                        }
-                    }
-                }
+            _ = &mut ctrl_c => return keys.release().await,
+        }
+    }
+}
 
-                if data.trackid {
-                    fmt.write_fmt(format_args!("{} ", metadata.track_id().unwrap_or("")))
-                        .unwrap();
-                }
-                if data.album {
-                    fmt.write_fmt(format_args!("{} ", metadata.album().unwrap_or("")))
-                        .unwrap();
-                }
-                if data.artists
-                    && let Some(artists) = metadata.artists()
-                {
-                    for (i, art) in artists.iter().enumerate() {
-                        if i >= artists.len() {
-                            fmt.write_fmt(format_args!("{art} ")).unwrap();
-                        } else {
-                            fmt.write_fmt(format_args!("{art}, ")).unwrap();
-                        }
-                    }
-                }
-                if data.title {
-                    fmt.write_fmt(format_args!("{}, ", metadata.title().unwrap_or("")))
-                        .unwrap();
-                }
-                if data.url {
-                    fmt.write_fmt(format_args!("{}, ", metadata.url().unwrap_or("")))
-                        .unwrap();
-                }
-                if data.track_number {
-                    match metadata.track_number() {
-                        Some(n) => {
-                            fmt.write_fmt(format_args!("{}, ", n)).unwrap();
-                        }
-                        None => {
-                            fmt.write_fmt(format_args!("Track number unsupported, "))
-                                .unwrap();
-                        }
-                    }
-                }
-                if data.auto_rating {
-                    match metadata.auto_rating() {
-                        Some(n) => {
-                            fmt.write_fmt(format_args!("{}, ", n)).unwrap();
-                        }
-                        None => {
-                            fmt.write_fmt(format_args!("Auto rating unsupported, "))
-                                .unwrap();
-                        }
+#[cfg(feature = "rpc")]
+async fn rpc(mut client: MprisClient, command: &RpcCommand) -> anyhow::Result<()> {
+    let path = command
+        .socket
+        .clone()
+        .or_else(lib::rpc::RpcServer::default_path)
+        .ok_or_else(|| anyhow::anyhow!("XDG_RUNTIME_DIR isn't set, pass --socket"))?;
+    client.follow_owner_changes().await?;
+    let mut server = lib::rpc::RpcServer::bind(client, &path).await?;
+    info!(socket = %path.display(), "serving json-rpc");
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    loop {
+        server.step().await;
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_millis(20)) => {}
+            // dropping the server removes the socket
+            _ = &mut ctrl_c => return Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "ws")]
+async fn ws(mut client: MprisClient, command: &WsCommand) -> anyhow::Result<()> {
+    client.follow_owner_changes().await?;
+    let mut server = lib::ws::WsServer::bind(client, command.address)
+        .await?
+        .allow_control(command.control)
+        .allow_origins(command.origins.clone());
+    info!(address = %server.address(), "serving websocket");
+    loop {
+        server.step().await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+async fn hooks(mut client: MprisClient, hooks: Vec<lib::hooks::Hook>) -> anyhow::Result<()> {
+    let hooks = lib::hooks::Hooks::new(hooks)?;
+    if hooks.is_empty() {
+        anyhow::bail!("no [[hooks]] in the config");
+    }
+    client.follow_owner_changes().await?;
+    loop {
+        let events = client.event().await;
+        hooks.handle_events(&client, &events);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+async fn now_playing(
+    mut client: MprisClient,
+    cli: &Cli,
+    command: &NowPlayingCommand,
+) -> anyhow::Result<()> {
+    let mut writer = lib::now_playing::NowPlayingWriter::new(&command.output, &command.format)?
+        .idle(&command.idle);
+    if let Some(art) = &command.art {
+        writer = writer.art(art);
+    }
+    client.follow_owner_changes().await?;
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    loop {
+        let player = select_players(&client, cli)
+            .ok()
+            .and_then(|players| players.into_iter().next());
+        if let Err(e) = writer.update(player) {
+            warn!("failed to write {}: {e:#}", command.output.display());
+        }
+
+        let step = async {
+            client.event().await;
+            // the position moves without events, templates with `{position}` need the polling
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        };
+        tokio::select! {
+            _ = step => {}
+            // a stream that ended shouldn't show the last track forever
+            _ = &mut ctrl_c => return writer.update(None),
+        }
+    }
+}
+
+async fn run_command(
+    cli: &Cli,
+    client: &MprisClient,
+    player: &Player,
+    conn: &Connection,
+) -> anyhow::Result<()> {
+    let command = &cli.command;
+    let caps = player.capabilities();
+    let playing = caps.playback_status == PlaybackStatus::Playing;
+    let supported = match command {
+        Command::Play => caps.can_play,
+        Command::Pause => caps.can_pause,
+        Command::PlayPause if playing => caps.can_pause,
+        Command::PlayPause => caps.can_play,
+        Command::Stop => caps.can_control,
+        Command::Next => caps.can_next,
+        Command::Previous => caps.can_previous,
+        Command::Seek(_) => caps.can_control && caps.can_seek,
+        Command::Raise => player.root().can_raise,
+        Command::Quit => player.root().can_quit,
+        _ => true,
+    };
+    if !supported {
+        return Err(exit::unsupported(format!(
+            "{} doesn't support {command:?}",
+            player.name()
+        )));
+    }
+
+    match command {
+        Command::Play => player.play(conn).await?,
+        Command::Pause => player.pause(conn).await?,
+        Command::PlayPause if playing => player.pause(conn).await?,
+        Command::PlayPause => player.play(conn).await?,
+        Command::Stop => player.stop(conn).await?,
+        Command::Next => player.next(conn).await?,
+        Command::Previous => player.prev(conn).await?,
+        Command::Raise => player.raise(conn).await?,
+        Command::Quit => player.quit(conn).await?,
+        Command::Position(position) if position.changes_position() => {
+            position.run(player, conn).await?
+        }
+        Command::Seek(seek) => seek.run(player, conn).await?,
+        Command::Status(_) | Command::Metadata(_) | Command::Position(_) | Command::Volume(_) => {
+            if let Some(output) = command.output(player, cli.json) {
+                println!("{}", output?);
+            }
+        }
+        Command::Open(open) => {
+            let target = if open.route {
+                client.route_uri(&open.uri, Some(player.name()))
+            } else {
+                Some(player)
+            };
+
+            match target {
+                Some(player) => {
+                    if player.uri_score(&open.uri).is_none() {
+                        // plenty of players don't list everything they can open
+                        warn!(
+                            player = player.name(),
+                            "player doesn't list support for {}", open.uri
+                        );
                     }
+                    player.open_uri(conn, &open.uri).await?
                 }
-                if data.album_artists
-                    && let Some(artists) = metadata.album_artists()
-                {
-                    if artists.len() == 1 {
-                        fmt.write_fmt(format_args!("{} ", artists[0])).unwrap();
-                    } else {
-                        for (i, art) in artists.iter().enumerate() {
-                            if i >= artists.len() {
-                                fmt.write_fmt(format_args!("{} ", art)).unwrap();
-                            } else {
-                                fmt.write_fmt(format_args!("{}, ", art)).unwrap();
-                            }
-                        }
-                    }
+                None => {
+                    return Err(exit::unsupported(format!(
+                        "no player supports {}",
+                        open.uri
+                    )));
                 }
-                println!("{fmt}");
             }
         }
+        #[cfg(feature = "media-keys")]
+        Command::MediaKeys => unreachable!("handled above"),
+        #[cfg(feature = "mpd-bridge")]
+        Command::Mpd(_) => unreachable!("handled above"),
+        #[cfg(feature = "scrobble")]
+        Command::Scrobble(_) => unreachable!("handled above"),
+        #[cfg(feature = "history")]
+        Command::History(_) => unreachable!("handled above"),
+        #[cfg(feature = "rpc")]
+        Command::Rpc(_) => unreachable!("handled above"),
+        #[cfg(feature = "ws")]
+        Command::Ws(_) => unreachable!("handled above"),
+        Command::List
+        | Command::Shift
+        | Command::Unshift
+        | Command::Proxy
+        | Command::Hooks
+        | Command::NowPlaying(_)
+        | Command::Soak(_)
+        | Command::Waybar(_)
+        | Command::Tail(_) => {
+            unreachable!("handled above")
+        }
     }
+
+    Ok(())
+}
+
+fn metadata_json(player: &Player, data: &MetadataCommand) -> anyhow::Result<String> {
+    let mut json = serde_json::json!({
+        "player": player.name(),
+        "status": player.capabilities().playback_status,
+        "position": player.capabilities().position,
+        "metadata": player.capabilities().metadata,
+        "capabilities": player.ui_model(),
+    });
+    if let Some(format) = &data.format {
+        json["text"] = player.format(format)?.into();
+    }
+
+    Ok(json.to_string())
+}
+
+fn metadata_line(player: &Player, data: &MetadataCommand) -> anyhow::Result<String> {
+    if let Some(format) = &data.format {
+        return player.format(format);
+    }
+
+    let mut fmt = String::new();
+    let metadata = &player.capabilities().metadata;
+    if data.art_url {
+        fmt.write_fmt(format_args!("{} ", metadata.art_url().unwrap_or("")))
+            .unwrap();
+    }
+    if data.length {
+        match metadata.length() {
+            None => fmt.write_char(' ').unwrap(),
+            Some(len) => fmt
+                .write_fmt(format_args!("{} ", progress::format_length(len)))
+                .unwrap(),
+        }
+    }
+
+    if data.trackid {
+        fmt.write_fmt(format_args!(
+            "{} ",
+            metadata.track_id().map(|id| id.as_str()).unwrap_or("")
+        ))
+        .unwrap();
+    }
+    if data.album {
+        fmt.write_fmt(format_args!("{} ", metadata.album().unwrap_or("")))
+            .unwrap();
+    }
+    if data.artists
+        && let Some(artists) = metadata.artists()
+    {
+        fmt.write_fmt(format_args!("{} ", artists.join(", ")))
+            .unwrap();
+    }
+    if data.title {
+        fmt.write_fmt(format_args!("{}, ", metadata.title().unwrap_or("")))
+            .unwrap();
+    }
+    if data.url {
+        fmt.write_fmt(format_args!("{}, ", metadata.url().unwrap_or("")))
+            .unwrap();
+    }
+    if data.track_number {
+        match metadata.track_number() {
+            Some(n) => fmt.write_fmt(format_args!("{n}, ")).unwrap(),
+            None => fmt
+                .write_fmt(format_args!("Track number unsupported, "))
+                .unwrap(),
+        }
+    }
+    if data.disc_number {
+        match metadata.disc_number() {
+            Some(n) => fmt.write_fmt(format_args!("{n}, ")).unwrap(),
+            None => fmt
+                .write_fmt(format_args!("Disc number unsupported, "))
+                .unwrap(),
+        }
+    }
+    if data.auto_rating {
+        match metadata.auto_rating() {
+            Some(n) => fmt.write_fmt(format_args!("{n}, ")).unwrap(),
+            None => fmt
+                .write_fmt(format_args!("Auto rating unsupported, "))
+                .unwrap(),
+        }
+    }
+    if data.album_artists
+        && let Some(artists) = metadata.album_artists()
+    {
+        fmt.write_fmt(format_args!("{} ", artists.join(", ")))
+            .unwrap();
+    }
+
+    if fmt.is_empty() {
+        // nothing asked for, show what's playing
+        return player.format("{artist} - {title}");
+    }
+
+    Ok(fmt)
 }
@@ -0,0 +1,213 @@
+//! `position`: prints or changes how far into the track a player is
+
+use std::{str::FromStr, time::Duration};
+
+use lib::{player::Player, progress};
+use zbus::{Connection, zvariant::ObjectPath};
+
+use crate::{FollowArgs, exit};
+
+#[derive(Debug, clap::Parser)]
+pub struct PositionCommand {
+    /// seconds to jump to, `5+`/`5-` to seek by 5 seconds, `50%` to jump to the middle
+    offset: Option<Offset>,
+    #[command(flatten)]
+    pub follow: FollowArgs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Offset {
+    /// seconds from the start
+    Absolute(f64),
+    /// seconds from the current position
+    Relative(f64),
+    Percent(f64),
+}
+
+impl FromStr for Offset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |n: &str| -> anyhow::Result<f64> {
+            let n: f64 = n.trim().parse()?;
+            anyhow::ensure!(n.is_finite() && n >= 0.0, "invalid position {s}");
+            Ok(n)
+        };
+
+        if let Some(n) = s.strip_suffix('+') {
+            Ok(Self::Relative(parse(n)?))
+        } else if let Some(n) = s.strip_suffix('-') {
+            Ok(Self::Relative(-parse(n)?))
+        } else if let Some(n) = s.strip_suffix('%') {
+            Ok(Self::Percent(parse(n)?.min(100.0)))
+        } else {
+            Ok(Self::Absolute(parse(s)?))
+        }
+    }
+}
+
+const MICROS: f64 = 1_000_000.0;
+
+#[derive(Debug, clap::Parser)]
+pub struct SeekCommand {
+    /// how far to seek, `+10s` forward and `-30s` back. `ms`, `s`, `m` and `h` work, plain
+    /// numbers are seconds
+    #[arg(allow_hyphen_values = true)]
+    offset: SeekOffset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SeekOffset {
+    by: Duration,
+    forward: bool,
+}
+
+impl FromStr for SeekOffset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (forward, rest) = match s.trim().split_at_checked(1) {
+            Some(("+", rest)) => (true, rest),
+            Some(("-", rest)) => (false, rest),
+            _ => anyhow::bail!("{s} needs a + or - in front to say which way to seek"),
+        };
+
+        let unit_at = rest
+            .find(|c: char| c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let (n, unit) = rest.split_at(unit_at);
+        let n: f64 = n.trim().parse()?;
+        anyhow::ensure!(n.is_finite() && n >= 0.0, "invalid offset {s}");
+        let secs = match unit {
+            "ms" => n / 1000.0,
+            "" | "s" => n,
+            "m" => n * 60.0,
+            "h" => n * 3600.0,
+            _ => anyhow::bail!("unknown unit {unit} in {s}"),
+        };
+
+        Ok(Self {
+            by: Duration::try_from_secs_f64(secs)?,
+            forward,
+        })
+    }
+}
+
+impl SeekCommand {
+    pub async fn run(&self, player: &Player, conn: &Connection) -> anyhow::Result<()> {
+        let SeekOffset { by, forward } = self.offset;
+        if forward {
+            player.seek_forward(conn, by).await
+        } else {
+            player.seek_backward(conn, by).await
+        }
+    }
+}
+
+impl PositionCommand {
+    pub fn changes_position(&self) -> bool {
+        self.offset.is_some()
+    }
+
+    /// the position in seconds like playerctl prints it
+    pub fn output(player: &Player, json: bool) -> String {
+        let position = player.estimated_position();
+        if json {
+            serde_json::json!({
+                "player": player.name(),
+                "position": position,
+                "length": player.capabilities().metadata.length(),
+                "text": progress::format_length(position),
+            })
+            .to_string()
+        } else {
+            format!("{:.2}", position as f64 / MICROS)
+        }
+    }
+
+    pub async fn run(&self, player: &Player, conn: &Connection) -> anyhow::Result<()> {
+        let Some(offset) = self.offset else {
+            return Ok(());
+        };
+        let caps = player.capabilities();
+        if !caps.can_control || !caps.can_seek {
+            return Err(exit::unsupported(format!("{} can't seek", player.name())));
+        }
+
+        let current = caps.position;
+        let target = match offset {
+            Offset::Relative(secs) => {
+                return player.seek(conn, (secs * MICROS) as i64).await;
+            }
+            Offset::Absolute(secs) => (secs * MICROS) as u64,
+            Offset::Percent(pct) => {
+                let length = caps
+                    .metadata
+                    .length()
+                    .filter(|len| *len > 0)
+                    .ok_or_else(|| exit::unsupported("the track's length isn't known"))?;
+                (length as f64 * pct / 100.0) as u64
+            }
+        };
+
+        // `SetPosition` needs the current track, without one seeking by the difference is the
+        // next best thing
+        match caps
+            .metadata
+            .track_id()
+            .filter(|id| !id.is_no_track())
+            .and_then(|id| ObjectPath::try_from(id.as_str()).ok())
+        {
+            Some(track) => player.set_position(conn, track, target).await,
+            None => {
+                let offset = target.cast_signed() - current.cast_signed();
+                player.seek(conn, offset).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_positions() {
+        let position = |s: &str| s.parse::<Offset>();
+        assert_eq!(position("30").unwrap(), Offset::Absolute(30.0));
+        assert_eq!(position("5+").unwrap(), Offset::Relative(5.0));
+        assert_eq!(position("5-").unwrap(), Offset::Relative(-5.0));
+        assert_eq!(position("50%").unwrap(), Offset::Percent(50.0));
+        assert_eq!(position("150%").unwrap(), Offset::Percent(100.0));
+        for s in ["-5", "NaN", "inf", "5s", "%", "-5%", "x+"] {
+            assert!(position(s).is_err(), "{s:?}");
+        }
+    }
+
+    fn seek(s: &str) -> anyhow::Result<SeekOffset> {
+        s.parse()
+    }
+
+    #[test]
+    fn parses_seek_offsets() {
+        let offset = |secs: f64, forward| SeekOffset {
+            by: Duration::from_secs_f64(secs),
+            forward,
+        };
+        assert_eq!(seek("+10s").unwrap(), offset(10.0, true));
+        assert_eq!(seek("+10").unwrap(), offset(10.0, true));
+        assert_eq!(seek("-1.5m").unwrap(), offset(90.0, false));
+        assert_eq!(seek("+500ms").unwrap(), offset(0.5, true));
+        assert_eq!(seek("-2h").unwrap(), offset(7200.0, false));
+        assert_eq!(seek(" +3s ").unwrap(), offset(3.0, true));
+    }
+
+    #[test]
+    fn rejects_bad_seek_offsets() {
+        for s in [
+            "10s", "s", "", "+10d", "+10sec", "+NaN", "+inf", "-inf", "+-5s", "+",
+        ] {
+            assert!(seek(s).is_err(), "{s:?}");
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! `scrobble`: submits what the players play to Last.fm and ListenBrainz, see `lib::scrobble`
+
+use std::{io::BufRead, time::Duration};
+
+use lib::{
+    MprisClient,
+    scrobble::{ScrobbleConfig, Scrobbler},
+};
+
+#[derive(Debug, clap::Parser)]
+pub struct ScrobbleCommand {
+    /// prints a Last.fm session key for the `[scrobble.lastfm]` config, reading the password
+    /// from stdin
+    #[arg(long, value_name = "USERNAME")]
+    lastfm_login: Option<String>,
+    /// the `[scrobble]` section of the config
+    #[arg(skip)]
+    pub config: ScrobbleConfig,
+}
+
+pub async fn run(command: ScrobbleCommand, mut client: MprisClient) -> anyhow::Result<()> {
+    if let Some(username) = &command.lastfm_login {
+        return login(&command.config, username).await;
+    }
+
+    let dir = Scrobbler::default_dir()
+        .ok_or_else(|| anyhow::anyhow!("can not find a directory for the scrobble queue"))?;
+    let mut scrobbler = Scrobbler::new(&command.config, &dir)?;
+    for (service, pending) in scrobbler.pending() {
+        if pending > 0 {
+            eprintln!("{pending} listens queued for {service}");
+        }
+    }
+
+    // players coming and going show up as events
+    client.follow_owner_changes().await?;
+    loop {
+        let events = client.event().await;
+        scrobbler.handle_events(&client, &events).await?;
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+async fn login(config: &ScrobbleConfig, username: &str) -> anyhow::Result<()> {
+    let lastfm = config.lastfm.as_ref().ok_or_else(|| {
+        anyhow::anyhow!("set api-key and secret in [scrobble.lastfm] of the config first")
+    })?;
+    eprint!("password for {username}: ");
+    let mut password = String::new();
+    std::io::stdin().lock().read_line(&mut password)?;
+
+    let key = lib::scrobble::LastFm::authenticate(
+        &lastfm.api_key,
+        &lastfm.secret,
+        username,
+        password.trim_end_matches(['\r', '\n']),
+    )
+    .await?;
+    println!("{key}");
+    Ok(())
+}
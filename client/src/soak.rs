@@ -0,0 +1,273 @@
+//! `soak`: runs an [`MprisClient`] for a long time against mock players on the session bus that
+//! send random signals, reporting memory growth, dropped events and handler latency
+//!
+//! every metadata update carries a sequence number in its title, so the time from sending the
+//! signal to the client handing out the event can be measured and updates that never arrive can
+//! be counted.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use lib::{
+    DBUS_PROPERTIES, MPRIS_PATH, MPRIS_PLAYER_PREFIX, MprisClient, SignalMode,
+    player::{MetadataBuilder, MprisEvent, PlayerUpdated},
+};
+use zbus::{
+    Connection,
+    names::BusName,
+    zvariant::{OwnedValue, Value},
+};
+
+const TITLE_PREFIX: &str = "soak ";
+/// updates that haven't arrived after this long are counted as dropped
+const DROP_AFTER: Duration = Duration::from_secs(5);
+
+#[derive(Debug, clap::Parser)]
+pub struct SoakCommand {
+    /// how long to run, in seconds
+    #[arg(long, default_value_t = 3600)]
+    duration: u64,
+    /// number of mock players
+    #[arg(long, default_value_t = 4)]
+    players: usize,
+    /// signals sent per second across all players
+    #[arg(long, default_value_t = 50)]
+    rate: u32,
+    /// seconds between reports
+    #[arg(long, default_value_t = 60)]
+    report: u64,
+    /// seed for the random traffic, defaults to the current time
+    #[arg(long)]
+    seed: Option<u64>,
+    /// use a single match rule for every player, see `SignalMode::Multiplexed`
+    #[arg(long)]
+    multiplexed: bool,
+}
+
+/// the bare minimum of `org.mpris.MediaPlayer2.Player` for `GetAll` to succeed
+struct MockPlayer;
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+impl MockPlayer {
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        "Playing".to_string()
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        HashMap::new()
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn position(&self) -> i64 {
+        0
+    }
+}
+
+// xorshift64, plenty for picking players and updates
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    sent: u64,
+    received: u64,
+    dropped: u64,
+    // sequence number -> when it was sent
+    in_flight: HashMap<u64, Instant>,
+    latencies: Vec<Duration>,
+}
+
+impl Stats {
+    fn received(&mut self, seq: u64, now: Instant) {
+        if let Some(sent) = self.in_flight.remove(&seq) {
+            self.received += 1;
+            self.latencies.push(now - sent);
+        }
+    }
+
+    fn expire(&mut self, now: Instant) {
+        let before = self.in_flight.len();
+        self.in_flight.retain(|_, sent| now - *sent < DROP_AFTER);
+        self.dropped += (before - self.in_flight.len()) as u64;
+    }
+
+    fn percentile(sorted: &[Duration], p: usize) -> Duration {
+        match sorted.len() {
+            0 => Duration::ZERO,
+            len => sorted[(len - 1) * p / 100],
+        }
+    }
+
+    /// prints a report line and starts a new latency window
+    fn report(&mut self, elapsed: Duration, baseline_rss: u64) {
+        let mut latencies = std::mem::take(&mut self.latencies);
+        latencies.sort();
+        let rss = rss_kib().unwrap_or(0);
+
+        println!(
+            "[{:>6}s] sent {} received {} dropped {} in flight {} | latency p50 {:?} p90 {:?} p99 {:?} max {:?} | rss {} KiB ({:+} KiB)",
+            elapsed.as_secs(),
+            self.sent,
+            self.received,
+            self.dropped,
+            self.in_flight.len(),
+            Self::percentile(&latencies, 50),
+            Self::percentile(&latencies, 90),
+            Self::percentile(&latencies, 99),
+            latencies.last().copied().unwrap_or_default(),
+            rss,
+            rss as i64 - baseline_rss as i64,
+        );
+    }
+}
+
+fn rss_kib() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
+
+async fn send_update(conn: &Connection, rng: &mut Rng, seq: u64) -> zbus::Result<bool> {
+    let mut changed: HashMap<&str, Value> = HashMap::new();
+    // mostly track changes, the ones that carry a sequence number
+    let tracked = rng.below(4) != 0;
+    if tracked {
+        let metadata = MetadataBuilder::default()
+            .title(format!("{TITLE_PREFIX}{seq}"))
+            .artists(vec![format!("artist {}", rng.below(100))])
+            .length(rng.below(600_000_000) as u64)
+            .finish();
+        changed.insert(
+            "Metadata",
+            Value::from(HashMap::<String, Value>::from(metadata)),
+        );
+    } else {
+        let status = ["Playing", "Paused", "Stopped"][rng.below(3)];
+        changed.insert("PlaybackStatus", Value::from(status));
+    }
+
+    conn.emit_signal(
+        None::<BusName>,
+        MPRIS_PATH,
+        DBUS_PROPERTIES,
+        "PropertiesChanged",
+        &(MPRIS_PLAYER_PREFIX, changed, Vec::<&str>::new()),
+    )
+    .await?;
+
+    Ok(tracked)
+}
+
+pub async fn run(command: SoakCommand) -> anyhow::Result<()> {
+    let seed = command.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(1)
+    });
+    // xorshift gets stuck on 0
+    let mut rng = Rng(seed.max(1));
+    println!("soak: seed {seed}, {} players", command.players);
+
+    let mut players = Vec::with_capacity(command.players);
+    for i in 0..command.players {
+        let name = format!(
+            "org.mpris.MediaPlayer2.soak.instance{}_{i}",
+            std::process::id()
+        );
+        let conn = zbus::connection::Builder::session()?
+            .name(name.as_str())?
+            .serve_at(MPRIS_PATH, MockPlayer)?
+            .build()
+            .await?;
+        players.push((name, conn));
+    }
+
+    let mut client = MprisClient::connect().await?;
+    if command.multiplexed {
+        client.set_signal_mode(SignalMode::Multiplexed);
+    }
+    for (name, _) in &players {
+        client.add(name.clone()).await?;
+    }
+
+    let start = Instant::now();
+    let end = start + Duration::from_secs(command.duration);
+    let send_interval = Duration::from_secs(1) / command.rate.max(1);
+    let report_interval = Duration::from_secs(command.report.max(1));
+    let mut next_send = start;
+    let mut next_report = start + report_interval;
+
+    let baseline_rss = rss_kib().unwrap_or(0);
+    let mut stats = Stats::default();
+    let mut seq = 0;
+
+    while Instant::now() < end {
+        let now = Instant::now();
+        if now >= next_send && !players.is_empty() {
+            let (_, player) = &players[rng.below(players.len())];
+            if send_update(player, &mut rng, seq).await? {
+                stats.sent += 1;
+                stats.in_flight.insert(seq, Instant::now());
+            }
+            seq += 1;
+            next_send += send_interval;
+        }
+
+        for event in client.event().await {
+            if let MprisEvent::PlayerUpdated {
+                update: PlayerUpdated::Metadata(metadata),
+                ..
+            } = event
+            {
+                let seq = metadata
+                    .title()
+                    .and_then(|t| t.strip_prefix(TITLE_PREFIX))
+                    .and_then(|s| s.parse().ok());
+                if let Some(seq) = seq {
+                    stats.received(seq, Instant::now());
+                }
+            }
+        }
+
+        let now = Instant::now();
+        stats.expire(now);
+        if now >= next_report {
+            stats.report(now - start, baseline_rss);
+            next_report += report_interval;
+        }
+
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+
+    stats.report(start.elapsed(), baseline_rss);
+
+    Ok(())
+}
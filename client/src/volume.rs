@@ -0,0 +1,118 @@
+//! `volume`: prints or changes a player's volume
+
+use std::str::FromStr;
+
+use lib::player::Player;
+use zbus::Connection;
+
+use crate::exit;
+
+#[derive(Debug, clap::Parser)]
+pub struct VolumeCommand {
+    /// the volume to set, 0 to 1, or `0.05+`/`0.05-` to change it by that much
+    level: Option<Level>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Level {
+    Absolute(f64),
+    Relative(f64),
+}
+
+impl FromStr for Level {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |n: &str| -> anyhow::Result<f64> {
+            let n: f64 = n.trim().parse()?;
+            anyhow::ensure!(n.is_finite() && n >= 0.0, "invalid volume {s}");
+            Ok(n)
+        };
+
+        if let Some(n) = s.strip_suffix('+') {
+            Ok(Self::Relative(parse(n)?))
+        } else if let Some(n) = s.strip_suffix('-') {
+            Ok(Self::Relative(-parse(n)?))
+        } else {
+            Ok(Self::Absolute(parse(s)?))
+        }
+    }
+}
+
+impl VolumeCommand {
+    pub fn changes_volume(&self) -> bool {
+        self.level.is_some()
+    }
+
+    pub fn output(player: &Player, json: bool) -> anyhow::Result<String> {
+        let volume = player
+            .volume()
+            .ok_or_else(|| exit::unsupported(format!("{} has no volume", player.name())))?;
+
+        Ok(if json {
+            serde_json::json!({ "player": player.name(), "volume": volume }).to_string()
+        } else {
+            format!("{volume:.2}")
+        })
+    }
+
+    /// sets the volume over MPRIS, or with the `pulse` feature on the player's audio streams
+    /// when its MPRIS volume can't be set
+    pub async fn run(&self, player: &mut Player, conn: &Connection) -> anyhow::Result<()> {
+        let Some(level) = self.level else {
+            return Ok(());
+        };
+        let result = match (player.volume(), level) {
+            _ if !player.capabilities().can_control => Err(exit::unsupported(format!(
+                "{} can't be controlled",
+                player.name()
+            ))),
+            (_, Level::Absolute(volume)) => player.set_volume(conn, volume).await,
+            (Some(current), Level::Relative(by)) => player.set_volume(conn, current + by).await,
+            (None, Level::Relative(_)) => Err(exit::unsupported(format!(
+                "{} has no volume",
+                player.name()
+            ))),
+        };
+
+        #[cfg(feature = "pulse")]
+        if let Err(e) = &result {
+            tracing::debug!(player = player.name(), "using the stream volume: {e:#}");
+            if stream_volume(player, conn, level).await? {
+                return Ok(());
+            }
+        }
+        result
+    }
+}
+
+/// sets the volume of the player's streams, `false` when it has none
+#[cfg(feature = "pulse")]
+async fn stream_volume(player: &Player, conn: &Connection, level: Level) -> anyhow::Result<bool> {
+    let streams = lib::pulse::streams_of(conn, player).await?;
+    for stream in &streams {
+        let volume = match level {
+            Level::Absolute(volume) => volume,
+            Level::Relative(by) => stream.volume + by,
+        };
+        lib::pulse::set_sink_input_volume(stream.index, volume).await?;
+    }
+    Ok(!streams.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_levels() {
+        let level = |s: &str| s.parse::<Level>();
+        assert_eq!(level("0.5").unwrap(), Level::Absolute(0.5));
+        assert_eq!(level("0.1+").unwrap(), Level::Relative(0.1));
+        assert_eq!(level("0.1-").unwrap(), Level::Relative(-0.1));
+        assert_eq!(level(" 1 ").unwrap(), Level::Absolute(1.0));
+        for s in ["-0.5", "-0.1-", "NaN", "inf", "loud", "50%", ""] {
+            assert!(level(s).is_err(), "{s:?}");
+        }
+    }
+}
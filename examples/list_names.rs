@@ -2,6 +2,11 @@ use mpris_controller::MprisClient;
 
 #[tokio::main]
 async fn main() {
-    let mut client = MprisClient::new().await.unwrap();
+    let mut client = MprisClient::connect().await.unwrap();
     client.get_all().await.unwrap();
+
+    for player in client.players() {
+        let identity = player.root().identity.as_deref().unwrap_or("-");
+        println!("{} {identity}", player.name());
+    }
 }
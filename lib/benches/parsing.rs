@@ -0,0 +1,131 @@
+//! the conversions every player and every `PropertiesChanged` goes through, on the spotify
+//! fixture from `tests/fixtures`
+//!
+//! `cargo bench -p lib --bench parsing`, compare against a saved run with
+//! `-- --save-baseline before` and `-- --baseline before`.
+
+use std::{collections::HashMap, hint::black_box};
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use lib::{
+    player::{parse_properties_changed, Capabilities, Metadata},
+    record::Recorded,
+    variant, MprisClient, DBUS_PROPERTIES, MPRIS_PATH, MPRIS_PLAYER_PREFIX,
+};
+use zbus::{
+    zvariant::{OwnedValue, Value},
+    Message,
+};
+
+const PLAYER: &str = "org.mpris.MediaPlayer2.spotify";
+
+struct Fixture {
+    get_all: serde_json::Value,
+    properties_changed: Vec<serde_json::Value>,
+}
+
+fn fixture() -> Fixture {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/spotify.json");
+    let mut json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+
+    Fixture {
+        get_all: json["get_all"].take(),
+        properties_changed: serde_json::from_value(json["properties_changed"].take()).unwrap(),
+    }
+}
+
+fn properties(json: &serde_json::Value) -> HashMap<String, OwnedValue> {
+    variant::properties_from_json(json).unwrap()
+}
+
+fn borrowed(properties: &HashMap<String, OwnedValue>) -> HashMap<&str, Value<'_>> {
+    properties
+        .iter()
+        .map(|(k, v)| (k.as_str(), Value::from(v.try_clone().unwrap())))
+        .collect()
+}
+
+fn properties_changed(changed: &serde_json::Value) -> Message {
+    Message::signal(MPRIS_PATH, DBUS_PROPERTIES, "PropertiesChanged")
+        .unwrap()
+        .build(&(
+            MPRIS_PLAYER_PREFIX,
+            properties(changed),
+            Vec::<String>::new(),
+        ))
+        .unwrap()
+}
+
+fn conversions(c: &mut Criterion) {
+    let fixture = fixture();
+    let get_all = properties(&fixture.get_all);
+    let metadata: HashMap<String, Value> = Value::from(get_all["Metadata"].try_clone().unwrap())
+        .try_into()
+        .unwrap();
+
+    c.bench_function("metadata", |b| {
+        b.iter_batched(
+            || {
+                metadata
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.try_clone().unwrap()))
+                    .collect::<HashMap<_, _>>()
+            },
+            |metadata| Metadata::try_from(black_box(metadata)).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+    c.bench_function("capabilities", |b| {
+        b.iter_batched(
+            || borrowed(&get_all),
+            |properties| Capabilities::try_from(black_box(properties)).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn signals(c: &mut Criterion) {
+    let fixture = fixture();
+    let status = properties_changed(&fixture.properties_changed[0]);
+    let metadata = properties_changed(&fixture.properties_changed[1]);
+
+    c.bench_function("properties_changed/status", |b| {
+        b.iter(|| parse_properties_changed(black_box(&status)).unwrap())
+    });
+    c.bench_function("properties_changed/metadata", |b| {
+        b.iter(|| parse_properties_changed(black_box(&metadata)).unwrap())
+    });
+
+    // parsing and applying to a player, what the client does for every signal
+    let mut client = MprisClient::replaying();
+    client
+        .replay(&Recorded::Player {
+            at_ms: 0,
+            player: PLAYER.to_string(),
+            properties: fixture.get_all.clone(),
+        })
+        .unwrap();
+    let changes: Vec<Recorded> = fixture
+        .properties_changed
+        .iter()
+        .map(|changed| Recorded::Signal {
+            at_ms: 0,
+            player: PLAYER.to_string(),
+            member: "PropertiesChanged".to_string(),
+            body: serde_json::json!({
+                "(sa{sv}as)": [MPRIS_PLAYER_PREFIX, changed, []]
+            }),
+        })
+        .collect();
+    c.bench_function("replay", |b| {
+        b.iter(|| {
+            for change in &changes {
+                black_box(client.replay(change).unwrap());
+            }
+        })
+    });
+}
+
+criterion_group!(benches, conversions, signals);
+criterion_main!(benches);
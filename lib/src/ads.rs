@@ -0,0 +1,72 @@
+//! muting spotify while it plays ads
+//!
+//! [`AdMuter`] sets the volume to 0 on [`MprisEvent::AdStarted`] and puts the old volume back on
+//! [`MprisEvent::AdEnded`]. it only goes through the MPRIS volume, see [`Metadata::is_ad`] for
+//! what counts as an ad.
+//!
+//! [`Metadata::is_ad`]: crate::player::Metadata::is_ad
+
+use std::collections::HashMap;
+
+use tracing::info;
+use zbus::Connection;
+
+use crate::{player::MprisEvent, MprisClient};
+
+#[derive(Debug)]
+pub struct AdMuter {
+    connection: Connection,
+    // the volume each muted player had before its ad
+    muted: HashMap<String, f64>,
+}
+
+impl AdMuter {
+    pub fn new(connection: Connection) -> Self {
+        Self {
+            connection,
+            muted: HashMap::new(),
+        }
+    }
+
+    pub fn is_muted(&self, player: &str) -> bool {
+        self.muted.contains_key(player)
+    }
+
+    /// mutes and unmutes for the ad events in `events`, call it after [`MprisClient::event`]
+    pub async fn handle_events(
+        &mut self,
+        client: &mut MprisClient,
+        events: &[MprisEvent],
+    ) -> anyhow::Result<()> {
+        for event in events {
+            match event {
+                MprisEvent::AdStarted { player } if !self.is_muted(player) => {
+                    let Some(p) = client.get_mut(player) else {
+                        continue;
+                    };
+                    // players without a volume can't be muted this way
+                    let Some(volume) = p.capabilities().volume else {
+                        continue;
+                    };
+                    info!(player, "muting ad");
+                    p.set_volume(&self.connection, 0.0).await?;
+                    self.muted.insert(player.clone(), volume);
+                }
+                MprisEvent::AdEnded { player } => {
+                    let Some(volume) = self.muted.remove(player) else {
+                        continue;
+                    };
+                    if let Some(p) = client.get_mut(player) {
+                        info!(player, volume, "ad over, unmuting");
+                        p.set_volume(&self.connection, volume).await?;
+                    }
+                }
+                MprisEvent::PlayerRemoved(player) => {
+                    self.muted.remove(player);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
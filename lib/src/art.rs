@@ -0,0 +1,252 @@
+//! resolves `mpris:artUrl` to a file on disk
+//!
+//! remote art is downloaded once into a cache directory keyed by the art url, `data:` uris are
+//! decoded into the same directory and local `file://` urls or paths are used as is. the directory is kept under a size limit by evicting the oldest files.
+//!
+//! with the `musicbrainz` feature, tracks without an art url can fall back to the Cover Art
+//! Archive, see [`ArtCache::with_musicbrainz`].
+
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, bail};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+#[cfg(feature = "musicbrainz")]
+use crate::musicbrainz::MusicBrainz;
+use crate::{blob, fnv1a, percent_decode, player::Metadata};
+
+/// default upper bound for the cache directory, 64MiB
+pub const DEFAULT_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// sent once the art for a track is available on disk
+#[derive(Debug, Clone)]
+pub struct ArtReady {
+    /// the art url the player sent, or the release for art found on musicbrainz
+    pub key: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArtCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    http: reqwest::Client,
+    ready: broadcast::Sender<ArtReady>,
+    #[cfg(feature = "musicbrainz")]
+    musicbrainz: Option<MusicBrainz>,
+}
+
+impl ArtCache {
+    pub fn new(dir: PathBuf, max_bytes: u64) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        let (ready, _) = broadcast::channel(16);
+
+        Ok(Self {
+            dir,
+            max_bytes,
+            http: reqwest::Client::new(),
+            ready,
+            #[cfg(feature = "musicbrainz")]
+            musicbrainz: None,
+        })
+    }
+
+    /// looks up art on musicbrainz for tracks that don't come with an art url
+    #[cfg(feature = "musicbrainz")]
+    pub fn with_musicbrainz(mut self, musicbrainz: MusicBrainz) -> Self {
+        self.musicbrainz = Some(musicbrainz);
+        self
+    }
+
+    /// `$XDG_CACHE_HOME/mpris-controller/art`, falling back to `~/.cache`
+    pub fn default_dir() -> anyhow::Result<PathBuf> {
+        let cache = match std::env::var_os("XDG_CACHE_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => std::env::home_dir()
+                .ok_or(anyhow!("can not find home directory"))?
+                .join(".cache"),
+        };
+
+        Ok(cache.join("mpris-controller").join("art"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// receives an [`ArtReady`] for every art resolved through [`ArtCache::request`]
+    pub fn subscribe(&self) -> broadcast::Receiver<ArtReady> {
+        self.ready.subscribe()
+    }
+
+    /// resolves the art of `metadata` in the background, announcing it through
+    /// [`ArtCache::subscribe`] once it's ready
+    pub fn request(&self, metadata: &Metadata) {
+        let cache = self.clone();
+        let metadata = metadata.clone();
+        tokio::spawn(async move {
+            match cache.resolve(&metadata).await {
+                Ok(Some(path)) => {
+                    let key = cache_key(&metadata).unwrap_or_default();
+                    _ = cache.ready.send(ArtReady { key, path });
+                }
+                Ok(None) => {}
+                Err(e) => warn!(art_url = metadata.art_url(), "failed to resolve art: {e:?}"),
+            }
+        });
+    }
+
+    /// returns a local path for the art of `metadata`, downloading it if it isn't cached yet
+    ///
+    /// `None` when the player didn't send an art url (and none was found on musicbrainz).
+    pub async fn resolve(&self, metadata: &Metadata) -> anyhow::Result<Option<PathBuf>> {
+        let Some(url) = metadata.art_url() else {
+            #[cfg(feature = "musicbrainz")]
+            return self.resolve_musicbrainz(metadata).await;
+            #[cfg(not(feature = "musicbrainz"))]
+            return Ok(None);
+        };
+        if let Some(path) = url.strip_prefix("file://") {
+            return Ok(Some(PathBuf::from(percent_decode(path))));
+        }
+
+        // mpv and some players write embedded covers to a temp file and send the bare path
+        if url.starts_with('/') {
+            return Ok(Some(PathBuf::from(url)));
+        }
+
+        // oversized data uris get moved out of the metadata, see `MetadataLimits`
+        let url = blob::resolve(url).ok_or(anyhow!("art {url} is no longer stored"))?;
+        // tracks sharing a cover share the file, however the player names them
+        let url = normalize_url(&url);
+        let path = self.path(&url);
+        if tokio::fs::try_exists(&path).await? {
+            return Ok(Some(path));
+        }
+
+        if url.starts_with("data:") {
+            let bytes = decode_data_uri(&url)?;
+            self.write(&path, &bytes).await?;
+            return Ok(Some(path));
+        }
+
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            bail!("unsupported art url {url}");
+        }
+
+        debug!(url, "downloading art");
+        let response = self.http.get(&url).send().await?.error_for_status()?;
+        let bytes = response.bytes().await?;
+        self.write(&path, &bytes).await?;
+
+        Ok(Some(path))
+    }
+
+    #[cfg(feature = "musicbrainz")]
+    async fn resolve_musicbrainz(&self, metadata: &Metadata) -> anyhow::Result<Option<PathBuf>> {
+        let (Some(musicbrainz), Some(key)) = (&self.musicbrainz, cache_key(metadata)) else {
+            return Ok(None);
+        };
+
+        let path = self.path(&key);
+        if tokio::fs::try_exists(&path).await? {
+            return Ok(Some(path));
+        }
+
+        match musicbrainz.cover(metadata).await? {
+            Some(bytes) => {
+                self.write(&path, &bytes).await?;
+                Ok(Some(path))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// where the art for `key` is cached
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}", fnv1a(key.as_bytes())))
+    }
+
+    async fn write(&self, path: &Path, bytes: &[u8]) -> anyhow::Result<()> {
+        // write next to the final path and rename so readers never see half a file
+        let tmp = path.with_extension("part");
+        tokio::fs::write(&tmp, bytes).await?;
+        tokio::fs::rename(&tmp, path).await?;
+
+        self.evict().await
+    }
+
+    /// removes the least recently written files until the directory fits in `max_bytes`
+    async fn evict(&self) -> anyhow::Result<()> {
+        let mut files = Vec::new();
+        let mut total = 0;
+
+        let mut entries = tokio::fs::read_dir(&self.dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let meta = entry.metadata().await?;
+            if meta.is_file() {
+                total += meta.len();
+                files.push((
+                    meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                    meta.len(),
+                    entry.path(),
+                ));
+            }
+        }
+
+        files.sort_by_key(|(modified, ..)| *modified);
+        for (_, len, path) in files {
+            if total <= self.max_bytes {
+                break;
+            }
+
+            tokio::fs::remove_file(&path).await?;
+            total -= len;
+        }
+
+        Ok(())
+    }
+}
+
+fn cache_key(metadata: &Metadata) -> Option<String> {
+    if let Some(url) = metadata.art_url() {
+        return Some(url.to_string());
+    }
+    // the art of a musicbrainz release is shared by every track on it
+    #[cfg(feature = "musicbrainz")]
+    return MusicBrainz::cache_key(metadata);
+    #[cfg(not(feature = "musicbrainz"))]
+    None
+}
+
+/// spotify sends `open.spotify.com/image/<id>` which redirects to a page instead of the image
+fn normalize_url(url: &str) -> String {
+    match url
+        .strip_prefix("https://open.spotify.com/image/")
+        .or(url.strip_prefix("http://open.spotify.com/image/"))
+    {
+        Some(id) => format!("https://i.scdn.co/image/{id}"),
+        None => url.to_string(),
+    }
+}
+
+/// decodes a `data:[<mediatype>][;base64],<data>` uri, chromium sends these for some sites
+pub fn decode_data_uri(uri: &str) -> anyhow::Result<Vec<u8>> {
+    let rest = uri.strip_prefix("data:").ok_or(anyhow!("not a data uri"))?;
+    let (header, data) = rest
+        .split_once(',')
+        .ok_or(anyhow!("data uri is missing ','"))?;
+
+    if header.split(';').any(|param| param == "base64") {
+        // some players wrap the payload, the base64 alphabet has no whitespace
+        let data: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+        Ok(BASE64_STANDARD.decode(data)?)
+    } else {
+        Ok(percent_decode(data).into_bytes())
+    }
+}
@@ -0,0 +1,66 @@
+//! storage for metadata values too large to pass around in every event and snapshot
+//!
+//! the value is replaced by a `blob:<handle>` uri and kept here, only the most recent
+//! [`MAX_BLOBS`] are retained.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, LazyLock, Mutex},
+};
+
+pub const BLOB_SCHEME: &str = "blob:";
+pub const MAX_BLOBS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlobHandle(u64);
+
+impl BlobHandle {
+    pub fn uri(&self) -> String {
+        format!("{BLOB_SCHEME}{}", self.0)
+    }
+
+    /// parses a `blob:<handle>` uri
+    pub fn from_uri(uri: &str) -> Option<Self> {
+        uri.strip_prefix(BLOB_SCHEME)?.parse().ok().map(Self)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Store {
+    next: u64,
+    blobs: VecDeque<(BlobHandle, Arc<str>)>,
+}
+
+static STORE: LazyLock<Mutex<Store>> = LazyLock::new(|| Mutex::new(Store::default()));
+
+pub fn store(value: String) -> BlobHandle {
+    let mut store = STORE.lock().unwrap();
+    let handle = BlobHandle(store.next);
+    store.next += 1;
+
+    store.blobs.push_back((handle, Arc::from(value)));
+    if store.blobs.len() > MAX_BLOBS {
+        store.blobs.pop_front();
+    }
+
+    handle
+}
+
+/// `None` once the blob was evicted
+pub fn get(handle: BlobHandle) -> Option<Arc<str>> {
+    STORE
+        .lock()
+        .unwrap()
+        .blobs
+        .iter()
+        .find(|(h, _)| *h == handle)
+        .map(|(_, blob)| blob.clone())
+}
+
+/// resolves a value that might have been externalized back into the original
+pub fn resolve(value: &str) -> Option<Arc<str>> {
+    match BlobHandle::from_uri(value) {
+        Some(handle) => get(handle),
+        None => Some(Arc::from(value)),
+    }
+}
@@ -0,0 +1,114 @@
+//! timeouts and retries for method calls
+//!
+//! a player that stops answering, suspended browser tabs are the usual ones, would otherwise
+//! hold up whatever waits on it until the bus gives up, 25 seconds with `dbus-daemon`. the calls
+//! to players and the bus go through [`call_method`], which gives up after
+//! [`CallPolicy::timeout`] and tries again when the bus answers with `NoReply`. each client
+//! has its own policy, see [`MprisClient::set_call_policy`](crate::MprisClient::set_call_policy).
+
+use std::{io, sync::Arc, time::Duration};
+
+use tracing::debug;
+use zbus::{
+    names::MemberName,
+    zvariant::{DynamicType, ObjectPath},
+    Connection, Message,
+};
+
+/// what the bus answers when the callee didn't reply in time
+const NO_REPLY: &str = "org.freedesktop.DBus.Error.NoReply";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallPolicy {
+    /// how long to wait for each attempt
+    pub timeout: Duration,
+    /// how often to try again after `NoReply`. a call that timed out isn't retried, the player
+    /// already had all of `timeout` to answer
+    pub retries: u32,
+    /// the wait before the first retry, doubled for every one after it
+    pub backoff: Duration,
+}
+
+impl CallPolicy {
+    pub const DEFAULT: Self = Self {
+        timeout: Duration::from_secs(5),
+        retries: 2,
+        backoff: Duration::from_millis(100),
+    };
+
+    /// the wait before retry number `retry`, counting from 0
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.backoff.saturating_mul(1 << retry.min(16))
+    }
+}
+
+impl Default for CallPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// whether `e` is the bus saying the callee didn't answer
+pub fn is_no_reply(e: &zbus::Error) -> bool {
+    match e {
+        zbus::Error::MethodError(name, _, _) => name.as_str() == NO_REPLY,
+        zbus::Error::FDO(e) => matches!(**e, zbus::fdo::Error::NoReply(_)),
+        _ => false,
+    }
+}
+
+/// whether `e` is [`call_method`] giving up on a call
+pub fn is_timeout(e: &zbus::Error) -> bool {
+    matches!(e, zbus::Error::InputOutput(e) if e.kind() == io::ErrorKind::TimedOut)
+}
+
+/// [`Connection::call_method`] following `policy`, a timeout is an [`io::ErrorKind::TimedOut`]
+/// error
+pub async fn call_method<'p, 'm, P, M, B>(
+    conn: &Connection,
+    policy: &CallPolicy,
+    destination: &str,
+    path: P,
+    interface: &str,
+    method: M,
+    body: &B,
+) -> zbus::Result<Message>
+where
+    P: TryInto<ObjectPath<'p>>,
+    P::Error: Into<zbus::Error>,
+    M: TryInto<MemberName<'m>>,
+    M::Error: Into<zbus::Error>,
+    B: serde::Serialize + DynamicType,
+{
+    let path = path.try_into().map_err(Into::into)?;
+    let method = method.try_into().map_err(Into::into)?;
+    let mut retry = 0;
+    loop {
+        let call = conn.call_method(
+            Some(destination),
+            path.clone(),
+            Some(interface),
+            method.clone(),
+            body,
+        );
+        let e = match tokio::time::timeout(policy.timeout, call).await {
+            Ok(Ok(reply)) => return Ok(reply),
+            Ok(Err(e)) => e,
+            Err(_) => {
+                let message = format!(
+                    "{destination} didn't answer {interface}.{method} within {:?}",
+                    policy.timeout
+                );
+                let e = io::Error::new(io::ErrorKind::TimedOut, message);
+                return Err(zbus::Error::InputOutput(Arc::new(e)));
+            }
+        };
+        if retry >= policy.retries || !is_no_reply(&e) {
+            return Err(e);
+        }
+        let delay = policy.delay(retry);
+        debug!(destination, %method, ?delay, "no reply, retrying");
+        tokio::time::sleep(delay).await;
+        retry += 1;
+    }
+}
@@ -0,0 +1,66 @@
+//! `.desktop` files, which is where a player's `DesktopEntry` points
+//!
+//! lookups go through `$XDG_DATA_HOME/applications` and then every `$XDG_DATA_DIRS`, like the
+//! desktop does. results are cached per entry since every instance of a player shares one.
+
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{LazyLock, Mutex},
+};
+
+static ICONS: LazyLock<Mutex<HashMap<String, Option<String>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn data_dirs() -> Vec<PathBuf> {
+    let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+
+    let home = var("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| var("HOME").map(|home| PathBuf::from(home).join(".local/share")));
+    let dirs = var("XDG_DATA_DIRS").unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+
+    home.into_iter()
+        .chain(dirs.split(':').filter(|d| !d.is_empty()).map(PathBuf::from))
+        .collect()
+}
+
+/// the path of the `.desktop` file for `entry` (`vlc` or `vlc.desktop`)
+pub fn find(entry: &str) -> Option<PathBuf> {
+    let file = format!("{}.desktop", entry.trim_end_matches(".desktop"));
+    data_dirs()
+        .into_iter()
+        .map(|dir| dir.join("applications").join(&file))
+        .find(|path| path.is_file())
+}
+
+/// `Icon` of the `[Desktop Entry]` group, an icon theme name or an absolute path
+fn parse_icon(contents: &str) -> Option<String> {
+    let mut in_entry = false;
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_entry = line == "[Desktop Entry]";
+        } else if in_entry {
+            if let Some(icon) = line
+                .strip_prefix("Icon")
+                .and_then(|l| l.trim_start().strip_prefix('='))
+            {
+                return Some(icon.trim().to_string()).filter(|icon| !icon.is_empty());
+            }
+        }
+    }
+
+    None
+}
+
+/// the icon of the application `entry` belongs to
+pub fn icon(entry: &str) -> Option<String> {
+    let mut icons = ICONS.lock().unwrap();
+    icons
+        .entry(entry.to_string())
+        .or_insert_with(|| {
+            let path = find(entry)?;
+            parse_icon(&std::fs::read_to_string(path).ok()?)
+        })
+        .clone()
+}
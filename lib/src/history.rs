@@ -0,0 +1,351 @@
+//! a history of every track played, kept in sqlite
+//!
+//! [`HistoryRecorder`] follows the players of a client and writes a row per play: the player,
+//! the track, when it started and ended and how much of it was actually played. [`History`]
+//! answers questions like "what was playing an hour ago".
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+
+use crate::{
+    player::{Metadata, MprisEvent, PlaybackStatus},
+    playtime::{track_key, unix_now, PlayTime, TrackKey},
+    MprisClient,
+};
+
+/// how often the played time of a running play is written out, so a crash loses little
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS plays (
+        id INTEGER PRIMARY KEY,
+        player TEXT NOT NULL,
+        title TEXT,
+        artist TEXT,
+        album TEXT,
+        url TEXT,
+        length INTEGER,
+        started_at INTEGER NOT NULL,
+        ended_at INTEGER,
+        played INTEGER NOT NULL DEFAULT 0,
+        completion REAL
+    );
+    CREATE INDEX IF NOT EXISTS plays_started_at ON plays (started_at);
+";
+
+const COLUMNS: &str =
+    "id, player, title, artist, album, url, length, started_at, ended_at, played, completion";
+
+/// one track played once
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Play {
+    pub id: i64,
+    /// the bus name, minus `org.mpris.MediaPlayer2.`
+    pub player: String,
+    pub title: Option<String>,
+    /// every artist, joined with `, `
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub url: Option<String>,
+    /// microseconds
+    pub length: Option<u64>,
+    /// unix time in seconds
+    pub started_at: u64,
+    /// `None` while it's still playing
+    pub ended_at: Option<u64>,
+    /// microseconds actually listened to, seeking doesn't count
+    pub played: u64,
+    /// `played` out of `length`, 0 to 1
+    pub completion: Option<f64>,
+}
+
+impl Play {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            player: row.get(1)?,
+            title: row.get(2)?,
+            artist: row.get(3)?,
+            album: row.get(4)?,
+            url: row.get(5)?,
+            length: row.get::<_, Option<i64>>(6)?.map(|length| length as u64),
+            started_at: row.get::<_, i64>(7)? as u64,
+            ended_at: row.get::<_, Option<i64>>(8)?.map(|at| at as u64),
+            played: row.get::<_, i64>(9)? as u64,
+            completion: row.get(10)?,
+        })
+    }
+}
+
+pub struct History {
+    conn: Connection,
+}
+
+impl History {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let conn = Connection::open(path)?;
+        // the recorder writes while `history` reads
+        conn.pragma_update(None, "journal_mode", "wal")?;
+        Self::init(conn)
+    }
+
+    pub fn open_in_memory() -> anyhow::Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> anyhow::Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    /// `$XDG_DATA_HOME/mpris-controller/history.sqlite`, falling back to `~/.local/share`
+    pub fn default_path() -> Option<PathBuf> {
+        let data_home = std::env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+            })?;
+
+        Some(data_home.join("mpris-controller").join("history.sqlite"))
+    }
+
+    /// records that `player` started playing `metadata` at `started_at`, returns the play's id
+    pub fn start(&self, player: &str, metadata: &Metadata, started_at: u64) -> anyhow::Result<i64> {
+        let player = player
+            .strip_prefix(crate::MPRIS_PREFIX)
+            .map(|name| name.trim_start_matches('.'))
+            .unwrap_or(player);
+        self.conn.execute(
+            "INSERT INTO plays (player, title, artist, album, url, length, started_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                player,
+                metadata.title(),
+                metadata.artists().map(|artists| artists.join(", ")),
+                metadata.album(),
+                metadata.url(),
+                metadata.length().map(|length| length as i64),
+                started_at as i64,
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// updates how much of play `id` was listened to
+    pub fn progress(&self, id: i64, played: u64) -> anyhow::Result<()> {
+        self.conn.execute(
+            "UPDATE plays SET played = ?2, completion = MIN(?2 * 1.0 / length, 1.0)
+             WHERE id = ?1",
+            params![id, played as i64],
+        )?;
+        Ok(())
+    }
+
+    /// play `id` ended at `ended_at` after `played` microseconds
+    pub fn finish(&self, id: i64, ended_at: u64, played: u64) -> anyhow::Result<()> {
+        self.progress(id, played)?;
+        self.conn.execute(
+            "UPDATE plays SET ended_at = ?2 WHERE id = ?1",
+            params![id, ended_at as i64],
+        )?;
+        Ok(())
+    }
+
+    pub fn get(&self, id: i64) -> anyhow::Result<Option<Play>> {
+        Ok(self
+            .conn
+            .query_row(
+                &format!("SELECT {COLUMNS} FROM plays WHERE id = ?1"),
+                [id],
+                Play::from_row,
+            )
+            .optional()?)
+    }
+
+    /// the last `limit` plays, newest first
+    pub fn recent(&self, limit: usize) -> anyhow::Result<Vec<Play>> {
+        self.query(
+            "ORDER BY started_at DESC, id DESC LIMIT ?1",
+            params![limit as i64],
+        )
+    }
+
+    /// plays that were going on at some point between `from` and `to`, newest first
+    pub fn between(&self, from: u64, to: u64) -> anyhow::Result<Vec<Play>> {
+        self.query(
+            "WHERE started_at <= ?2 AND COALESCE(ended_at, ?2) >= ?1
+             ORDER BY started_at DESC, id DESC",
+            params![from as i64, to as i64],
+        )
+    }
+
+    /// plays around `at`, give or take `window`
+    pub fn around(&self, at: u64, window: Duration) -> anyhow::Result<Vec<Play>> {
+        let window = window.as_secs();
+        self.between(at.saturating_sub(window), at.saturating_add(window))
+    }
+
+    /// the last `limit` plays with `text` in the title, artist or album, ignoring case
+    pub fn search(&self, text: &str, limit: usize) -> anyhow::Result<Vec<Play>> {
+        let pattern = format!(
+            "%{}%",
+            text.replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+        self.query(
+            "WHERE title LIKE ?1 ESCAPE '\\' OR artist LIKE ?1 ESCAPE '\\'
+                OR album LIKE ?1 ESCAPE '\\'
+             ORDER BY started_at DESC, id DESC LIMIT ?2",
+            params![pattern, limit as i64],
+        )
+    }
+
+    fn query(&self, rest: &str, params: impl rusqlite::Params) -> anyhow::Result<Vec<Play>> {
+        let mut statement = self
+            .conn
+            .prepare(&format!("SELECT {COLUMNS} FROM plays {rest}"))?;
+        let plays = statement
+            .query_map(params, Play::from_row)?
+            .collect::<Result<_, _>>()?;
+        Ok(plays)
+    }
+}
+
+/// what is being played on one player
+#[derive(Debug)]
+struct Recording {
+    track: TrackKey,
+    /// the row, once the track started playing
+    id: Option<i64>,
+    played: PlayTime,
+    saved_at: Instant,
+}
+
+impl Recording {
+    fn finish(self, history: &History, unix: u64) -> anyhow::Result<()> {
+        match self.id {
+            Some(id) => history.finish(id, unix, self.played.played().as_micros() as u64),
+            None => Ok(()),
+        }
+    }
+}
+
+/// follows what each player plays and records it in a [`History`]
+pub struct HistoryRecorder {
+    history: History,
+    players: HashMap<String, Recording>,
+}
+
+impl HistoryRecorder {
+    pub fn new(history: History) -> Self {
+        Self {
+            history,
+            players: HashMap::new(),
+        }
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// looks at every player of `client` after [`MprisClient::event`] returned `events`, using
+    /// the interpolated position
+    pub fn handle_events(
+        &mut self,
+        client: &MprisClient,
+        events: &[MprisEvent],
+    ) -> anyhow::Result<()> {
+        let unix = unix_now();
+        for event in events {
+            if let MprisEvent::PlayerRemoved(name) = event {
+                if let Some(recording) = self.players.remove(name) {
+                    recording.finish(&self.history, unix)?;
+                }
+            }
+        }
+
+        let now = Instant::now();
+        for player in client.players() {
+            let caps = player.capabilities();
+            self.observe(
+                player.name(),
+                &caps.metadata,
+                caps.playback_status,
+                player.estimated_position_at(now),
+                now,
+                unix,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// records that `player` is at `position` of the track in `metadata` at `now`, `unix` is
+    /// the same moment as unix time
+    pub fn observe(
+        &mut self,
+        player: &str,
+        metadata: &Metadata,
+        status: PlaybackStatus,
+        position: u64,
+        now: Instant,
+        unix: u64,
+    ) -> anyhow::Result<()> {
+        let track = track_key(metadata);
+        let new = || Recording {
+            track: track.clone(),
+            id: None,
+            played: PlayTime::new(),
+            saved_at: now,
+        };
+
+        let recording = self.players.entry(player.to_string()).or_insert_with(new);
+        // stopping ends the play too, playing again counts as another one
+        if recording.track != track || status == PlaybackStatus::Stopped {
+            std::mem::replace(recording, new()).finish(&self.history, unix)?;
+        }
+
+        let playing = status == PlaybackStatus::Playing;
+        if !playing {
+            recording.played.observe(playing, position, now);
+            return Ok(());
+        }
+        let id = match recording.id {
+            Some(id) => id,
+            None if metadata.title().is_some() || metadata.url().is_some() => {
+                let started_at = unix.saturating_sub(position / 1_000_000);
+                let id = self.history.start(player, metadata, started_at)?;
+                recording.id = Some(id);
+                id
+            }
+            None => return Ok(()),
+        };
+
+        recording.played.observe(playing, position, now);
+        if now.duration_since(recording.saved_at) >= SAVE_INTERVAL {
+            recording.saved_at = now;
+            let played = recording.played.played().as_micros() as u64;
+            self.history.progress(id, played)?;
+        }
+        Ok(())
+    }
+
+    /// ends every play that is still going, for shutting down
+    pub fn finish_all(&mut self) -> anyhow::Result<()> {
+        let unix = unix_now();
+        for (_, recording) in std::mem::take(&mut self.players) {
+            recording.finish(&self.history, unix)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+//! runs commands when something happens to a player
+//!
+//! a hook is a program with arguments, the arguments are [`Template`]s rendered with
+//! [`Player::field`] so `["notify-send", "{title}", "{artist}"]` works. the player state is also
+//! passed in `MPRIS_*` environment variables for scripts:
+//!
+//! - `MPRIS_EVENT`: `track-changed`, `track-cleared`, `status-changed`, `player-added`,
+//!   `player-removed`, `ad-started` or `ad-ended`
+//! - `MPRIS_PLAYER`, `MPRIS_PLAYER_NAME`: the bus name, with and without
+//!   `org.mpris.MediaPlayer2.`
+//! - `MPRIS_STATUS`, `MPRIS_TITLE`, `MPRIS_ARTIST`, `MPRIS_ALBUM`, `MPRIS_ALBUM_ARTIST`,
+//!   `MPRIS_URL`, `MPRIS_ART_URL`, `MPRIS_TRACKID`
+//! - `MPRIS_LENGTH`: in microseconds
+//!
+//! only `MPRIS_EVENT` and the player names are set for `player-removed`, the player is gone by
+//! then. hooks run in the background, nothing waits for them to finish.
+
+use std::{fmt, str::FromStr};
+
+use anyhow::{bail, Context};
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use crate::{
+    pattern,
+    player::{MprisEvent, Player, PlayerUpdated},
+    selector,
+    template::Template,
+    MprisClient, MPRIS_PREFIX,
+};
+
+/// what a hook runs on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookEvent {
+    TrackChanged,
+    TrackCleared,
+    StatusChanged,
+    PlayerAdded,
+    PlayerRemoved,
+    AdStarted,
+    AdEnded,
+}
+
+impl HookEvent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::TrackChanged => "track-changed",
+            Self::TrackCleared => "track-cleared",
+            Self::StatusChanged => "status-changed",
+            Self::PlayerAdded => "player-added",
+            Self::PlayerRemoved => "player-removed",
+            Self::AdStarted => "ad-started",
+            Self::AdEnded => "ad-ended",
+        }
+    }
+
+    /// the hook event `event` is, with the player it's about
+    pub fn of(event: &MprisEvent) -> Option<(Self, &str)> {
+        match event {
+            MprisEvent::TrackChanged { player, .. } => Some((Self::TrackChanged, player)),
+            MprisEvent::TrackCleared { player } => Some((Self::TrackCleared, player)),
+            MprisEvent::PlayerUpdated {
+                player,
+                update: PlayerUpdated::PlaybackStatus(_),
+            } => Some((Self::StatusChanged, player)),
+            MprisEvent::PlayerAdded(player) => Some((Self::PlayerAdded, player)),
+            MprisEvent::PlayerRemoved(player) => Some((Self::PlayerRemoved, player)),
+            MprisEvent::AdStarted { player } => Some((Self::AdStarted, player)),
+            MprisEvent::AdEnded { player } => Some((Self::AdEnded, player)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for HookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for HookEvent {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "track-changed" => Self::TrackChanged,
+            "track-cleared" => Self::TrackCleared,
+            "status-changed" => Self::StatusChanged,
+            "player-added" => Self::PlayerAdded,
+            "player-removed" => Self::PlayerRemoved,
+            "ad-started" => Self::AdStarted,
+            "ad-ended" => Self::AdEnded,
+            _ => bail!("unknown hook event {s}"),
+        })
+    }
+}
+
+/// one `[[hooks]]` entry of the config
+///
+/// ```toml
+/// [[hooks]]
+/// on = ["track-changed"]
+/// command = ["notify-send", "{title}", "{artist}"]
+/// # only for these players, every player without it
+/// player = ["spotify"]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Hook {
+    pub on: Vec<HookEvent>,
+    /// the program and its arguments
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub player: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Parsed {
+    hook: Hook,
+    program: String,
+    args: Vec<Template>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Hooks {
+    hooks: Vec<Parsed>,
+}
+
+impl Hooks {
+    /// fails on hooks without a command and on arguments that aren't valid templates
+    pub fn new(hooks: Vec<Hook>) -> anyhow::Result<Self> {
+        let hooks = hooks
+            .into_iter()
+            .map(|hook| {
+                let Some((program, args)) = hook.command.split_first() else {
+                    bail!("a hook for {:?} has an empty command", hook.on);
+                };
+                let args = args
+                    .iter()
+                    .map(|arg| {
+                        Template::parse(arg).with_context(|| format!("in hook argument {arg:?}"))
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+                Ok(Parsed {
+                    program: program.clone(),
+                    args,
+                    hook,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self { hooks })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// runs the hooks for everything in `events`, call it after [`MprisClient::event`]
+    pub fn handle_events(&self, client: &MprisClient, events: &[MprisEvent]) {
+        for event in events {
+            let Some((event, name)) = HookEvent::of(event) else {
+                continue;
+            };
+            let player = client.get(name);
+            for hook in &self.hooks {
+                if hook.applies(event, name, player) {
+                    if let Err(e) = hook.run(event, name, player) {
+                        warn!(player = name, program = hook.program, "hook failed: {e:#}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Parsed {
+    fn applies(&self, event: HookEvent, name: &str, player: Option<&Player>) -> bool {
+        self.hook.on.contains(&event)
+            && (self.hook.player.is_empty()
+                || self.hook.player.iter().any(|pattern| match player {
+                    Some(player) => selector::matches(pattern, player),
+                    None => {
+                        pattern::matches(pattern, name)
+                            || pattern::matches(&format!("{pattern}.*"), name)
+                    }
+                }))
+    }
+
+    fn run(&self, event: HookEvent, name: &str, player: Option<&Player>) -> anyhow::Result<()> {
+        let short = name
+            .strip_prefix(MPRIS_PREFIX)
+            .map(|n| n.trim_start_matches('.'))
+            .unwrap_or(name);
+
+        let mut command = tokio::process::Command::new(&self.program);
+        command
+            .env("MPRIS_EVENT", event.as_str())
+            .env("MPRIS_PLAYER", name)
+            .env("MPRIS_PLAYER_NAME", short);
+        for (var, key) in [
+            ("MPRIS_STATUS", "status"),
+            ("MPRIS_TITLE", "title"),
+            ("MPRIS_ARTIST", "artist"),
+            ("MPRIS_ALBUM", "album"),
+            ("MPRIS_ALBUM_ARTIST", "album_artist"),
+            ("MPRIS_URL", "url"),
+            ("MPRIS_ART_URL", "art_url"),
+            ("MPRIS_TRACKID", "trackid"),
+            ("MPRIS_LENGTH", "mpris:length"),
+        ] {
+            match player.and_then(|player| player.field(key)) {
+                Some(value) => command.env(var, value),
+                // nothing left over from the environment this was started in
+                None => command.env_remove(var),
+            };
+        }
+
+        let lookup = |key: &str| match player {
+            Some(player) => player.field(key),
+            None => match key {
+                "player" => Some(name.to_string()),
+                "playerName" => Some(short.to_string()),
+                _ => None,
+            },
+        };
+        command.args(self.args.iter().map(|arg| arg.render(lookup)));
+
+        debug!(player = name, %event, program = self.program, "running hook");
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("starting {}", self.program))?;
+        let program = self.program.clone();
+        tokio::spawn(async move {
+            match child.wait().await {
+                Ok(status) if !status.success() => warn!(program, "hook exited with {status}"),
+                Ok(_) => {}
+                Err(e) => warn!(program, "failed to wait for hook: {e}"),
+            }
+        });
+        Ok(())
+    }
+}
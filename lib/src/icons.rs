@@ -0,0 +1,52 @@
+//! glyphs for bar output, the player icons need a nerd font
+
+use crate::{player::PlaybackStatus, MPRIS_PREFIX};
+
+const PLAYER_ICONS: &[(&str, &str)] = &[
+    ("spotify", "\u{f1bc}"),
+    ("firefox", "\u{f269}"),
+    ("chromium", "\u{f268}"),
+    ("chrome", "\u{f268}"),
+    ("brave", "\u{f268}"),
+    ("vlc", "\u{f057c}"),
+    ("mpv", "\u{f03d}"),
+    ("kdeconnect", "\u{f011c}"),
+];
+
+const DEFAULT_PLAYER_ICON: &str = "\u{f001}";
+
+/// for players that are bluetooth devices, whatever their name
+pub const BLUETOOTH: &str = "\u{f00af}";
+
+pub fn status_icon(status: PlaybackStatus) -> &'static str {
+    match status {
+        PlaybackStatus::Playing => "▶",
+        PlaybackStatus::Paused => "⏸",
+        PlaybackStatus::Stopped => "⏹",
+    }
+}
+
+/// a speaker with as many waves as `volume` (0 to 1) is loud
+pub fn volume_icon(volume: f64) -> &'static str {
+    match volume {
+        v if v <= 0.0 => "🔇",
+        v if v < 1.0 / 3.0 => "🔈",
+        v if v < 2.0 / 3.0 => "🔉",
+        _ => "🔊",
+    }
+}
+
+/// the icon for a player by bus name, `org.mpris.MediaPlayer2.spotify` or just `spotify`
+pub fn player_icon(name: &str) -> &'static str {
+    let base = name
+        .strip_prefix(MPRIS_PREFIX)
+        .map(|n| n.trim_start_matches('.'))
+        .unwrap_or(name);
+    let base = base.split('.').next().unwrap_or(base).to_lowercase();
+
+    PLAYER_ICONS
+        .iter()
+        .find(|(player, _)| base.starts_with(player))
+        .map(|(_, icon)| *icon)
+        .unwrap_or(DEFAULT_PLAYER_ICON)
+}
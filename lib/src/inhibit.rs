@@ -0,0 +1,154 @@
+//! keeping the screen on while something plays
+//!
+//! video players are supposed to inhibit the screensaver themselves, plenty of them (and most
+//! browsers for audio) don't. [`IdleInhibitor`] holds an inhibitor while any player is playing,
+//! through the `org.freedesktop.portal.Inhibit` portal or `org.freedesktop.ScreenSaver` where
+//! there is no portal.
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+use tracing::{debug, info};
+use zbus::{
+    zvariant::{OwnedObjectPath, Value},
+    Connection,
+};
+
+use crate::{
+    call::{call_method, CallPolicy},
+    player::PlaybackStatus,
+    MprisClient,
+};
+
+const PORTAL_NAME: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const PORTAL_INHIBIT: &str = "org.freedesktop.portal.Inhibit";
+/// the `flags` bit of `Inhibit` for idle
+const INHIBIT_IDLE: u32 = 8;
+const SCREENSAVER: &str = "org.freedesktop.ScreenSaver";
+const SCREENSAVER_PATH: &str = "/org/freedesktop/ScreenSaver";
+const APP_NAME: &str = "mpris-controller";
+
+/// an inhibitor that is being held
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Held {
+    /// the request handle, closing it ends the inhibition
+    Portal(OwnedObjectPath),
+    /// the cookie for `UnInhibit`
+    ScreenSaver(u32),
+}
+
+#[derive(Debug)]
+pub struct IdleInhibitor {
+    connection: Connection,
+    reason: String,
+    held: Option<Held>,
+}
+
+impl IdleInhibitor {
+    pub fn new(connection: Connection) -> Self {
+        Self {
+            connection,
+            reason: "Playing media".to_string(),
+            held: None,
+        }
+    }
+
+    /// shown by desktops that list what is inhibiting, `Playing media` by default
+    pub fn reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = reason.into();
+        self
+    }
+
+    pub fn is_inhibiting(&self) -> bool {
+        self.held.is_some()
+    }
+
+    /// inhibits while any player of `client` is playing, call it after [`MprisClient::event`]
+    pub async fn update(&mut self, client: &MprisClient) -> anyhow::Result<()> {
+        let playing = client
+            .players()
+            .iter()
+            .any(|player| player.capabilities().playback_status == PlaybackStatus::Playing);
+        self.set(playing).await
+    }
+
+    /// takes or releases the inhibitor, doing nothing when that's already the case
+    pub async fn set(&mut self, inhibit: bool) -> anyhow::Result<()> {
+        match (inhibit, self.held.take()) {
+            (true, None) => {
+                let held = self.inhibit().await?;
+                info!(?held, "inhibiting idle");
+                self.held = Some(held);
+            }
+            (false, Some(held)) => {
+                info!("no longer inhibiting idle");
+                self.release(held).await?;
+            }
+            (_, held) => self.held = held,
+        }
+        Ok(())
+    }
+
+    async fn inhibit(&self) -> anyhow::Result<Held> {
+        let mut options = HashMap::new();
+        options.insert("reason", Value::from(self.reason.as_str()));
+        let portal = call_method(
+            &self.connection,
+            &CallPolicy::DEFAULT,
+            PORTAL_NAME,
+            PORTAL_PATH,
+            PORTAL_INHIBIT,
+            "Inhibit",
+            &("", INHIBIT_IDLE, options),
+        )
+        .await;
+        match portal {
+            Ok(reply) => return Ok(Held::Portal(reply.body().deserialize()?)),
+            Err(e) => debug!("no inhibit portal: {e}"),
+        }
+
+        let reply = call_method(
+            &self.connection,
+            &CallPolicy::DEFAULT,
+            SCREENSAVER,
+            SCREENSAVER_PATH,
+            SCREENSAVER,
+            "Inhibit",
+            &(APP_NAME, self.reason.as_str()),
+        )
+        .await
+        .context("neither the inhibit portal nor org.freedesktop.ScreenSaver answered")?;
+        Ok(Held::ScreenSaver(reply.body().deserialize()?))
+    }
+
+    async fn release(&self, held: Held) -> anyhow::Result<()> {
+        match held {
+            Held::Portal(handle) => {
+                call_method(
+                    &self.connection,
+                    &CallPolicy::DEFAULT,
+                    PORTAL_NAME,
+                    &handle,
+                    "org.freedesktop.portal.Request",
+                    "Close",
+                    &(),
+                )
+                .await?;
+            }
+            Held::ScreenSaver(cookie) => {
+                call_method(
+                    &self.connection,
+                    &CallPolicy::DEFAULT,
+                    SCREENSAVER,
+                    SCREENSAVER_PATH,
+                    SCREENSAVER,
+                    "UnInhibit",
+                    &(cookie,),
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+}
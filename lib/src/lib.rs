@@ -1,27 +1,105 @@
 use std::{
+    collections::HashMap,
     fmt::Debug,
+    future::Future,
+    path::PathBuf,
     ptr::null,
-    sync::LazyLock,
-    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+    sync::Arc,
+    task::{Poll, RawWaker, RawWakerVTable, Waker},
+    time::{Duration, Instant},
 };
 
+pub mod ads;
+#[cfg(feature = "art")]
+pub mod art;
+pub mod blob;
+pub mod call;
+pub mod clock;
+pub mod desktop;
+#[cfg(feature = "history")]
+pub mod history;
+#[cfg(feature = "hooks")]
+pub mod hooks;
+pub mod icons;
+#[cfg(feature = "inhibit")]
+pub mod inhibit;
+#[cfg(feature = "lyrics")]
+pub mod lyrics;
+#[cfg(feature = "media-keys")]
+pub mod media_keys;
+pub mod mime;
+#[cfg(feature = "mpd-bridge")]
+pub mod mpd;
+#[cfg(feature = "musicbrainz")]
+pub mod musicbrainz;
+#[cfg(feature = "notify")]
+pub mod notify;
+pub mod now_playing;
+pub mod patch;
+pub mod pattern;
+pub mod persist;
 pub mod player;
+pub mod playlists;
+pub mod playtime;
+pub mod position;
+pub mod progress;
+pub mod proxy;
+#[cfg(feature = "pulse")]
+pub mod pulse;
+pub mod queue;
+pub mod quirks;
+pub mod record;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+pub mod sanitize;
+#[cfg(feature = "scrobble")]
+pub mod scrobble;
+pub mod selector;
+pub mod service;
+pub mod stable_id;
+pub mod systemd;
+pub mod template;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+pub mod tracklist;
+pub mod ui;
+pub mod variant;
+#[cfg(feature = "ws")]
+pub mod ws;
 
 pub mod format {
     include!(concat!(env!("OUT_DIR"), "/format.rs"));
 }
 
 pub use format::*;
-use futures::{executor::block_on, StreamExt};
+#[cfg(feature = "owner_changed")]
+use futures::StreamExt;
+#[cfg(feature = "owner_changed")]
+use std::task::Context;
 
-use std::sync::Mutex;
+use futures::stream::BoxStream;
+use tokio::sync::watch;
 use zbus::{
     names::{BusName, MemberName, WellKnownName},
     proxy::SignalStream,
-    Connection, Proxy,
+    zvariant::Value,
+    AsyncDrop, Connection, MatchRule, MessageStream, Proxy,
 };
 
-use crate::player::{PlaybackStatus, Player, PlayerUpdated};
+use tracing::{debug, info, warn};
+
+use crate::{
+    call::{call_method, CallPolicy},
+    clock::{Clock, SystemClock},
+    persist::SavedActive,
+    player::{
+        MetadataLimits, MprisEvent, PlaybackStatus, Player, PlayerId, PlayerUpdated, TrackId,
+    },
+    position::{Playing, PositionTick},
+    quirks::QuirkRegistry,
+    record::{Recorded, Recorder},
+    selector::Selector,
+};
 
 const unsafe fn noop_clone(_data: *const ()) -> RawWaker {
     noop_raw_waker()
@@ -53,9 +131,46 @@ pub const fn noop_waker() -> Waker {
 
 pub const WAKER: Waker = noop_waker();
 
+// stable across runs and rust versions, unlike `DefaultHasher`
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// decodes `%xx` escapes, like in `file://` urls
+pub(crate) fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let decoded = match bytes[i] {
+            b'%' => s
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+
+        match decoded {
+            Some(b) => {
+                out.push(b);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
 pub const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2";
 pub const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
 pub const MPRIS_PLAYER_PREFIX: &str = "org.mpris.MediaPlayer2.Player";
+pub const MPRIS_TRACKLIST: &str = "org.mpris.MediaPlayer2.TrackList";
+pub const MPRIS_PLAYLISTS: &str = "org.mpris.MediaPlayer2.Playlists";
 
 pub const DBUS_NAME: &str = "org.freedesktop.DBus";
 pub const DBUS_PATH: &str = "/org/freedesktop/DBus";
@@ -65,7 +180,10 @@ pub const DBUS_PROPERTIES: &str = "org.freedesktop.DBus.Properties";
 pub enum DbusMethods {
     ListNames,
     GetAll,
+    Get,
     NameHasOwner,
+    GetNameOwner,
+    GetConnectionUnixProcessID,
 }
 
 impl TryFrom<DbusMethods> for MemberName<'_> {
@@ -75,13 +193,31 @@ impl TryFrom<DbusMethods> for MemberName<'_> {
         let s = match value {
             DbusMethods::ListNames => "ListNames",
             DbusMethods::GetAll => "GetAll",
+            DbusMethods::Get => "Get",
             DbusMethods::NameHasOwner => "NameHasOwner",
+            DbusMethods::GetNameOwner => "GetNameOwner",
+            DbusMethods::GetConnectionUnixProcessID => "GetConnectionUnixProcessID",
         };
 
         Ok(MemberName::from_str_unchecked(s))
     }
 }
 
+/// the process that owns the bus name `name`
+pub async fn bus_name_pid(conn: &Connection, name: &str) -> anyhow::Result<u32> {
+    let reply = call_method(
+        conn,
+        &CallPolicy::DEFAULT,
+        DBUS_NAME,
+        DBUS_PATH,
+        DBUS_NAME,
+        DbusMethods::GetConnectionUnixProcessID,
+        &(name,),
+    )
+    .await?;
+    Ok(reply.body().deserialize()?)
+}
+
 #[derive(Debug)]
 pub enum DbusSignals {
     PropertiesChanged,
@@ -105,262 +241,2053 @@ impl TryFrom<DbusSignals> for MemberName<'_> {
 pub enum NameOwnerChanged {
     NewPlayer(String),
     RemovedPlayer(String),
+    /// a known name moved to another process, a player that restarted before its name was
+    /// released
+    ReplacedPlayer(String),
+}
+
+/// the signals besides `PropertiesChanged` that are followed, by interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ExtraInterface {
+    TrackList,
+    Playlists,
+    /// only `Seeked` of `org.mpris.MediaPlayer2.Player`
+    Seeked,
+}
+
+impl ExtraInterface {
+    fn name(self) -> &'static str {
+        match self {
+            Self::TrackList => MPRIS_TRACKLIST,
+            Self::Playlists => MPRIS_PLAYLISTS,
+            Self::Seeked => MPRIS_PLAYER_PREFIX,
+        }
+    }
+}
+
+type InterfaceStreams = HashMap<(String, ExtraInterface), SignalStream<'static>>;
+
+/// how the client listens for `PropertiesChanged` of its players
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SignalMode {
+    /// a match rule and stream per player
+    #[default]
+    PerPlayer,
+    /// one match rule for every player, signals are told apart by their sender
+    Multiplexed,
+}
+
+/// how much [`MprisClient::get_all`] and players showing up later cost
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DiscoveryMode {
+    /// read every property of every player right away
+    #[default]
+    Eager,
+    /// only note the names, a player is read once it is asked for with [`MprisClient::load`] or
+    /// starts playing. a browser with a dozen tabs otherwise answers a dozen `GetAll`s at once
+    ///
+    /// players that are already playing when they are found wait for the next change of their
+    /// status like the others.
+    Lazy,
+}
+
+/// limits for how much work a single call to [`MprisClient::event`] does
+#[derive(Debug, Clone, Copy)]
+pub struct EventLoopConfig {
+    /// maximum amount of signals handled per call
+    pub max_events_per_tick: usize,
+    /// maximum amount of signals taken from one player before moving on to the next, this keeps
+    /// a chatty player (a browser with a lot of tabs) from starving the others
+    pub max_events_per_player: usize,
+    /// hand out a [`MprisEvent::ParseError`] for signals that couldn't be parsed, they are
+    /// always logged
+    pub emit_parse_errors: bool,
+}
+
+impl Default for EventLoopConfig {
+    fn default() -> Self {
+        Self {
+            max_events_per_tick: 64,
+            max_events_per_player: 8,
+            emit_parse_errors: false,
+        }
+    }
+}
+
+/// what to do when a player's signal stream closes on us
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReconnectPolicy {
+    /// subscribe again up to `attempts` times before dropping the player
+    Retry { attempts: u32 },
+    /// stop relying on signals and re-read the properties every `interval`
+    Poll { interval: Duration },
+    /// forget about the player
+    Drop,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self::Retry { attempts: 3 }
+    }
+}
+
+/// which bus to talk to, see [`MprisClient::connect_to`]
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub enum Bus {
+    #[default]
+    Session,
+    /// for players running as system services, on embedded systems without a session
+    System,
+    /// like `unix:path=/run/user/1000/bus`, for buses in containers or made for tests
+    Address(String),
+}
+
+impl Bus {
+    pub async fn connect(&self) -> zbus::Result<Connection> {
+        self.builder()?.build().await
+    }
+
+    /// for serving something on the bus, like [`proxy::Proxy::start_on`]
+    pub fn builder(&self) -> zbus::Result<zbus::connection::Builder<'static>> {
+        match self {
+            Self::Session => zbus::connection::Builder::session(),
+            Self::System => zbus::connection::Builder::system(),
+            Self::Address(address) => zbus::connection::Builder::address(address.as_str()),
+        }
+    }
+}
+
+impl std::str::FromStr for Bus {
+    type Err = anyhow::Error;
+
+    /// `session`, `system` or an address
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "session" => Self::Session,
+            "system" => Self::System,
+            // every transport is `name:key=value,...`
+            address if address.contains(':') => Self::Address(address.to_string()),
+            _ => anyhow::bail!("{s} is neither session, system nor a bus address"),
+        })
+    }
+}
+
+impl TryFrom<String> for Bus {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
 }
 
-static mut SIGNAL_STREAM: LazyLock<Mutex<Vec<SignalStream<'static>>>> =
-    std::sync::LazyLock::new(|| Mutex::new(Vec::new()));
+/// makes a new connection after the client lost its own, see [`MprisClient::reconnect_with`]
+#[derive(Clone)]
+struct Connector(
+    Arc<dyn Fn() -> futures::future::BoxFuture<'static, zbus::Result<Connection>> + Send + Sync>,
+);
 
+impl Debug for Connector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Connector")
+    }
+}
+
+/// the waits between attempts to get the bus back, the last one repeats
+const RECONNECT_BACKOFF: [Duration; 5] = [
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(5),
+];
+
+/// what [`MprisClient::get_all`] found
 #[derive(Debug, Default)]
+pub struct DiscoveryResult {
+    pub added: Vec<String>,
+    /// players left for later by [`DiscoveryMode::Lazy`]
+    pub deferred: Vec<String>,
+    /// players that were skipped and why
+    pub failed: Vec<(String, anyhow::Error)>,
+}
+
+#[derive(Debug)]
 pub struct MprisClient {
+    // `None` only for clients made by `test_util`
+    connection: Option<Connection>,
+    // a vec rather than a map, the order players were found in decides ties (see `route_uri`)
     players: Vec<Player>,
     next_id: usize,
+    language: Option<String>,
+    player_languages: HashMap<String, String>,
+    event_loop: EventLoopConfig,
+    event_cursor: usize,
+    clock: Arc<dyn Clock>,
+    limits: MetadataLimits,
+    // see `MprisClient::set_call_policy`
+    call_policy: CallPolicy,
+    reconnect_policies: Vec<(String, ReconnectPolicy)>,
+    reconnect_attempts: HashMap<String, u32>,
+    // players whose stream closed, with the poll interval and when they were last polled
+    polled: HashMap<String, (Duration, Option<Instant>)>,
+    signal_mode: SignalMode,
+    // unique name -> well known name, to route multiplexed signals
+    owners: HashMap<String, String>,
+    // by player name, missing for players whose signals come in through `multiplexed`
+    signal_streams: HashMap<String, SignalStream<'static>>,
+    // the tracklist only for players with `HasTrackList`
+    interface_streams: InterfaceStreams,
+    // `PropertiesChanged` of every player, see `SignalMode::Multiplexed`
+    multiplexed: Option<MessageStream>,
+    discovery_mode: DiscoveryMode,
+    // names and owners of players not loaded yet, see `DiscoveryMode::Lazy`
+    deferred: Vec<(String, String)>,
+    // `PropertiesChanged` of every player, to see deferred players start playing
+    deferred_stream: Option<MessageStream>,
+    // patterns of players that are never added, see `MprisClient::ignore`
+    ignored: Vec<String>,
+    // see `MprisClient::active_player`
+    active: Option<PlayerId>,
+    // the active player was picked by hand and stays until another one starts playing
+    active_pinned: bool,
+    // events caused by calls outside of `event`, handed out by the next call to it
+    pending: Vec<MprisEvent>,
+    // patterns, most preferred first, see `MprisClient::preferred_player`
+    priority: Vec<String>,
+    // see `MprisClient::persist_active`
+    persist_path: Option<PathBuf>,
+    // the playing players for `MprisClient::position_ticks`, as of the last `event`
+    playing: watch::Sender<Vec<Playing>>,
+    // see `MprisClient::set_track_ending_soon`
+    track_ending_soon: Option<Duration>,
+    // the track each player last announced the end of, as its id and title
+    ending: HashMap<String, (Option<TrackId>, Option<String>)>,
+    // see `MprisClient::record_to`
+    recorder: Option<Recorder>,
+    // see `MprisClient::set_quirks`
+    quirks: QuirkRegistry,
+    // see `MprisClient::reconnect_with`
+    connector: Option<Connector>,
+    // ends when the connection does, see `MprisClient::watch_connection`
+    watch: Option<MessageStream>,
+    // while the connection is lost, the attempts so far and when to try next
+    lost: Option<(usize, Instant)>,
+    // see `MprisClient::bus`
+    bus: Option<Bus>,
+    // see `MprisClient::follow_owner_changes`
+    #[cfg(feature = "owner_changed")]
+    owner_changed: Option<SignalStream<'static>>,
+}
+
+// the client is meant to be stored in other types and moved into spawned tasks
+const _: fn() = || {
+    fn assert_owned<T: Send + 'static>() {}
+    assert_owned::<MprisClient>();
+    assert_owned::<Player>();
+};
+
+struct ConnectedPlayer {
+    stream: Option<SignalStream<'static>>,
+    interfaces: Vec<(ExtraInterface, SignalStream<'static>)>,
+    player: Player,
+    owner: Option<String>,
 }
 
 impl MprisClient {
-    pub fn new() -> anyhow::Result<Self> {
-        Ok(Self {
+    /// connects to the session bus, and to it again when the connection is lost
+    pub async fn connect() -> anyhow::Result<Self> {
+        Self::connect_to(Bus::Session).await
+    }
+
+    /// connects to `bus`, and to it again when the connection is lost
+    pub async fn connect_to(bus: Bus) -> anyhow::Result<Self> {
+        let mut client = Self::with_connection(bus.connect().await?);
+        client.bus = Some(bus.clone());
+        client.reconnect_with(move || {
+            let bus = bus.clone();
+            async move { bus.connect().await }
+        });
+        Ok(client)
+    }
+
+    /// the bus the client was connected to with [`MprisClient::connect_to`], `None` when it was
+    /// handed a connection
+    pub fn bus(&self) -> Option<&Bus> {
+        self.bus.as_ref()
+    }
+
+    /// uses an existing connection, e.g. to the system bus or one shared with other code
+    ///
+    /// the client can't know how to get such a connection back once it is lost, see
+    /// [`MprisClient::reconnect_with`].
+    pub fn with_connection(connection: Connection) -> Self {
+        Self {
+            connection: Some(connection),
+            ..Self::disconnected()
+        }
+    }
+
+    /// removes the client's match rules from the bus before dropping it, for shutting down
+    /// cleanly. plain dropping leaves that to the runtime, which may be gone by then
+    pub async fn shutdown(mut self) {
+        for (_, stream) in self.signal_streams.drain() {
+            stream.async_drop().await;
+        }
+        for (_, stream) in self.interface_streams.drain() {
+            stream.async_drop().await;
+        }
+        for stream in [self.multiplexed.take(), self.deferred_stream.take()]
+            .into_iter()
+            .flatten()
+        {
+            stream.async_drop().await;
+        }
+        #[cfg(feature = "owner_changed")]
+        if let Some(stream) = self.owner_changed.take() {
+            stream.async_drop().await;
+        }
+        debug!("removed the match rules");
+    }
+
+    /// a client that never talks to the bus
+    pub(crate) fn disconnected() -> Self {
+        Self {
+            connection: None,
             players: Vec::new(),
             next_id: 0,
-        })
+            language: None,
+            player_languages: HashMap::new(),
+            event_loop: EventLoopConfig::default(),
+            event_cursor: 0,
+            clock: Arc::new(SystemClock),
+            limits: MetadataLimits::default(),
+            call_policy: CallPolicy::DEFAULT,
+            reconnect_policies: Vec::new(),
+            reconnect_attempts: HashMap::new(),
+            polled: HashMap::new(),
+            signal_mode: SignalMode::default(),
+            owners: HashMap::new(),
+            signal_streams: HashMap::new(),
+            interface_streams: HashMap::new(),
+            multiplexed: None,
+            discovery_mode: DiscoveryMode::default(),
+            deferred: Vec::new(),
+            deferred_stream: None,
+            ignored: Vec::new(),
+            active: None,
+            active_pinned: false,
+            pending: Vec::new(),
+            priority: Vec::new(),
+            persist_path: None,
+            playing: watch::Sender::new(Vec::new()),
+            track_ending_soon: None,
+            ending: HashMap::new(),
+            recorder: None,
+            quirks: QuirkRegistry::default(),
+            connector: None,
+            watch: None,
+            lost: None,
+            bus: None,
+            #[cfg(feature = "owner_changed")]
+            owner_changed: None,
+        }
     }
 
-    pub async fn add(&mut self, connection: &Connection, name: String) -> anyhow::Result<()> {
-        let proxy = Proxy::new(
-            connection,
-            BusName::WellKnown(WellKnownName::from_str_unchecked(&name)),
-            MPRIS_PATH,
-            DBUS_PROPERTIES,
-        )
-        .await?;
+    /// how to connect again when the connection is lost, like when the bus restarts
+    ///
+    /// [`MprisClient::event`] hands out [`MprisEvent::ConnectionLost`] once it notices, then
+    /// tries `connect` with a growing backoff until it works. every player is set up again on
+    /// the new connection, keeping their ids, and [`MprisEvent::ConnectionRestored`] comes with
+    /// what changed in the meantime. without this the client stays disconnected.
+    pub fn reconnect_with<F, Fut>(&mut self, connect: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = zbus::Result<Connection>> + Send + 'static,
+    {
+        self.connector = Some(Connector(Arc::new(move || Box::pin(connect()))));
+    }
 
-        let stream = proxy.receive_signal(DbusSignals::PropertiesChanged).await?;
+    /// whether the connection was lost and hasn't come back yet
+    pub fn is_connection_lost(&self) -> bool {
+        self.lost.is_some()
+    }
 
-        unsafe {
-            SIGNAL_STREAM.lock().unwrap().push(stream);
-        }
-        let player = Player::new(connection, name.clone()).await?;
+    pub fn connection(&self) -> Option<&Connection> {
+        self.connection.as_ref()
+    }
 
-        self.players.push(player);
+    // cloning is cheap and keeps `self` free to be borrowed mutably next to it
+    fn connected(&self) -> anyhow::Result<Connection> {
+        self.connection
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("client is not connected to a bus"))
+    }
 
-        Ok(())
+    /// uses `clock` for every timestamp the client records instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
     }
 
-    pub fn get(&self, name: &str) -> Option<&Player> {
-        self.players
-            .iter()
-            .find(|&p| p.name() == name)
-            .map(|v| v as _)
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
     }
 
-    pub fn get_id(&self, name: &str) -> Option<usize> {
-        for (i, p) in self.players.iter().enumerate() {
-            if p.name() == name {
-                return Some(i);
-            }
+    pub fn metadata_limits(&self) -> &MetadataLimits {
+        &self.limits
+    }
+
+    /// sets the size limits for metadata of every player, see [`MetadataLimits`]
+    pub fn set_metadata_limits(&mut self, limits: MetadataLimits) {
+        self.limits = limits;
+        for player in self.players.iter_mut() {
+            player.set_metadata_limits(limits);
         }
+    }
 
-        None
+    pub fn call_policy(&self) -> &CallPolicy {
+        &self.call_policy
     }
 
-    pub fn get_mut(&mut self, name: &str) -> Option<&mut Player> {
-        self.players
-            .iter_mut()
-            .find(|p| p.name() == name)
-            .map(|v| v as _)
+    /// how calls to players time out and retry from now on, for every player
+    pub fn set_call_policy(&mut self, policy: CallPolicy) {
+        self.call_policy = policy;
+        for player in self.players.iter_mut() {
+            player.set_call_policy(policy);
+        }
     }
 
-    pub fn get_from_id(&self, id: usize) -> Option<&Player> {
-        self.players.get(id)
+    pub fn quirks(&self) -> &QuirkRegistry {
+        &self.quirks
     }
 
-    pub fn get_from_id_mut(&mut self, id: usize) -> Option<&mut Player> {
-        self.players.get_mut(id)
+    /// the rules for players that deviate from the spec, see [`quirks`]. applies to players
+    /// added afterwards, so this should be set before [`MprisClient::get_all`]
+    pub fn set_quirks(&mut self, quirks: QuirkRegistry) {
+        self.quirks = quirks;
     }
 
-    pub async fn list_names(connection: &Connection) -> anyhow::Result<Vec<String>> {
-        let msg = connection
-            .call_method(
-                Some(DBUS_NAME),
-                DBUS_PATH,
-                Some(DBUS_NAME),
-                DbusMethods::ListNames,
-                &(),
-            )
-            .await?;
+    pub fn signal_mode(&self) -> SignalMode {
+        self.signal_mode
+    }
 
-        let body = msg.body();
-        let names = body.deserialize::<Vec<String>>()?;
+    /// applies to players added afterwards, so this should be set before [`MprisClient::get_all`]
+    pub fn set_signal_mode(&mut self, mode: SignalMode) {
+        self.signal_mode = mode;
+    }
 
-        Ok(names)
+    pub fn discovery_mode(&self) -> DiscoveryMode {
+        self.discovery_mode
     }
 
-    // #[instrument(skip_all, ret)]
-    pub async fn get_all(&mut self, connection: &Connection) -> anyhow::Result<()> {
-        if !self.players.is_empty() {
-            self.players.clear();
-            self.next_id = 0;
+    /// applies to players found afterwards, so this should be set before [`MprisClient::get_all`]
+    pub fn set_discovery_mode(&mut self, mode: DiscoveryMode) {
+        self.discovery_mode = mode;
+    }
+
+    /// players that were found but not read yet, see [`DiscoveryMode::Lazy`]
+    pub fn deferred(&self) -> impl Iterator<Item = &str> {
+        self.deferred.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// reads a player [`DiscoveryMode::Lazy`] left for later, players that are already loaded
+    /// are handed out as they are
+    ///
+    /// the [`MprisEvent::PlayerAdded`] comes with the next [`MprisClient::event`].
+    pub async fn load(&mut self, name: &str) -> anyhow::Result<&Player> {
+        if let Some(idx) = self.index_of(name) {
+            return Ok(&self.players[idx]);
         }
-        let names = Self::list_names(connection).await.unwrap();
-        for item in names {
-            if item.starts_with(MPRIS_PREFIX) {
-                self.add(connection, item).await.unwrap();
-                self.next_id += 1;
-            }
+        let Some(pos) = self.deferred.iter().position(|(n, _)| n == name) else {
+            anyhow::bail!("player {name} is not known");
+        };
+
+        let mut events = Vec::new();
+        self.load_deferred(pos, &mut events).await?;
+        self.pending.extend(events);
+        self.get(name)
+            .ok_or_else(|| anyhow::anyhow!("player {name} is not known"))
+    }
+
+    async fn load_deferred(
+        &mut self,
+        pos: usize,
+        events: &mut Vec<MprisEvent>,
+    ) -> anyhow::Result<()> {
+        let connection = self.connected()?;
+        let (name, _) = self.deferred.remove(pos);
+        if self.signal_mode == SignalMode::Multiplexed {
+            self.ensure_multiplexed_stream(&connection).await?;
         }
+        let connected = Self::connect_player(
+            &connection,
+            name.clone(),
+            self.signal_mode,
+            &self.quirks,
+            self.call_policy,
+        )
+        .await?;
+        self.push_player(connected);
+        events.push(MprisEvent::PlayerAdded(name));
+        self.update_active_player(events);
 
         Ok(())
     }
 
-    pub async fn handle_player_changed(player: &mut Player, index: usize) {
-        unsafe {
-            if let Poll::Ready(ev) =
-                player::poll_player(&mut SIGNAL_STREAM.lock().unwrap().get_mut(index).unwrap())
-            {
-                match ev {
-                    PlayerUpdated::PlaybackStatus(playback_status) => {
-                        player.capabilities.playback_status = playback_status
-                    }
-                    PlayerUpdated::Metadata(metadata) => player.capabilities.metadata = *metadata,
-                    PlayerUpdated::CanGoPrevious(can_previous) => {
-                        player.capabilities.can_previous = can_previous;
+    /// notes a player for later, see [`DiscoveryMode::Lazy`]
+    async fn defer(&mut self, connection: &Connection, name: String) -> anyhow::Result<()> {
+        if self.deferred_stream.is_none() {
+            self.deferred_stream = Some(
+                MessageStream::for_match_rule(Self::properties_rule()?, connection, None).await?,
+            );
+        }
+        let owner = Self::name_owner(connection, &name).await?;
+        self.deferred.retain(|(n, _)| *n != name);
+        self.deferred.push((name, owner));
+
+        Ok(())
+    }
+
+    /// loads deferred players that started playing
+    async fn deferred_events(&mut self, events: &mut Vec<MprisEvent>) {
+        let Some(stream) = self.deferred_stream.as_mut() else {
+            return;
+        };
+
+        let waker = WAKER;
+        let mut cx = std::task::Context::from_waker(&waker);
+        let mut started = Vec::new();
+        loop {
+            match futures::StreamExt::poll_next_unpin(stream, &mut cx) {
+                Poll::Ready(Some(Ok(msg))) => {
+                    let playing = player::parse_properties_changed(&msg).is_ok_and(|updates| {
+                        updates.iter().any(|update| {
+                            matches!(
+                                update,
+                                PlayerUpdated::PlaybackStatus(PlaybackStatus::Playing)
+                            )
+                        })
+                    });
+                    if let Some(sender) = msg.header().sender().filter(|_| playing) {
+                        started.push(sender.to_string());
                     }
                 }
+                Poll::Ready(Some(Err(e))) => warn!("deferred players stream error: {e:?}"),
+                Poll::Ready(None) => {
+                    warn!("deferred players stream closed");
+                    self.deferred_stream = None;
+                    break;
+                }
+                Poll::Pending => break,
+            }
+        }
+
+        for sender in started {
+            let Some(pos) = self.deferred.iter().position(|(_, owner)| *owner == sender) else {
+                continue;
+            };
+            let name = self.deferred[pos].0.clone();
+            if let Err(e) = self.load_deferred(pos, events).await {
+                warn!(player = name, "skipping player: {e:?}");
             }
         }
     }
 
-    pub async fn handle_players_changed(&mut self) {
-        for (i, player) in self.players.iter_mut().enumerate() {
-            let _ = MprisClient::handle_player_changed(player, i).await;
+    pub fn event_loop_config(&self) -> EventLoopConfig {
+        self.event_loop
+    }
+
+    pub fn set_event_loop_config(&mut self, config: EventLoopConfig) {
+        self.event_loop = config;
+    }
+
+    /// sets the preferred language for localized metadata of every player that doesn't have its
+    /// own language set with [`MprisClient::set_player_language`]
+    pub fn set_language(&mut self, lang: Option<String>) {
+        self.language = lang;
+        for i in 0..self.players.len() {
+            let lang = self.language_for(&self.players[i]);
+            self.players[i].set_language(lang);
         }
     }
 
-    pub async fn event(&mut self, connection: &Connection) -> Option<NameOwnerChanged> {
-        for (i, player) in self.players.iter_mut().enumerate() {
-            unsafe {
-                let mut lock = SIGNAL_STREAM.lock().unwrap();
-                if let Poll::Ready(ev) = player::poll_player(lock.get_mut(i).unwrap()) {
-                    match ev {
-                        PlayerUpdated::PlaybackStatus(playback_status) => {
-                            player.capabilities.playback_status = playback_status
-                        }
-                        PlayerUpdated::Metadata(metadata) => {
-                            player.capabilities.metadata = *metadata
-                        }
-                        PlayerUpdated::CanGoPrevious(can_previous) => {
-                            player.capabilities.can_previous = can_previous;
-                        }
-                    };
-                }
-            }
+    /// overrides the preferred language for a single player, `None` goes back to the global one
+    ///
+    /// `key` is either the player's [`StableId`](stable_id::StableId) or its bus name.
+    pub fn set_player_language(&mut self, key: &str, lang: Option<String>) {
+        match lang {
+            Some(lang) => self.player_languages.insert(key.to_string(), lang),
+            None => self.player_languages.remove(key),
+        };
+
+        for i in 0..self.players.len() {
+            let lang = self.language_for(&self.players[i]);
+            self.players[i].set_language(lang);
         }
+    }
 
-        #[cfg(feature = "owner_changed")]
-        return self.handle_owner_changed(connection).await;
+    fn language_for(&self, player: &Player) -> Option<String> {
+        self.player_languages
+            .get(&player.stable_id().to_string())
+            .or(self.player_languages.get(player.name()))
+            .or(self.language.as_ref())
+            .cloned()
+    }
 
-        None
+    /// applies the client wide settings to a player about to be added
+    fn configure(&mut self, player: &mut Player) {
+        player.set_id(PlayerId::new(self.next_id));
+        self.next_id += 1;
+
+        let key = player.stable_key();
+        let instance = self
+            .players
+            .iter()
+            .filter(|p| p.stable_key() == key)
+            .map(|p| p.instance() + 1)
+            .max()
+            .unwrap_or(0);
+        player.set_instance(instance);
+        player.set_language(self.language_for(player));
+        player.set_metadata_limits(self.limits);
+        player.set_call_policy(self.call_policy);
+        player.anchor_position(self.clock.now());
     }
 
-    #[cfg(feature = "owner_changed")]
-    pub async fn handle_owner_changed(
-        &mut self,
-        connection: &Connection,
-    ) -> Option<NameOwnerChanged> {
-        if let Ok(Poll::Ready(changed)) = poll_owner_changed(&self.player_names()).await {
-            match changed {
-                NameOwnerChanged::NewPlayer(ref name) => {
-                    let p = Player::new(connection, name.clone()).await.unwrap();
-                    self.players.push(p);
-                    return Some(changed);
-                }
-                NameOwnerChanged::RemovedPlayer(ref name) => {
-                    if let Some(idx) = self.get_id(name) {
-                        self.players.remove(idx);
-                    }
-                    return Some(changed);
-                }
-            }
-        }
+    /// leaves players matching any of `patterns` (see [`pattern`]) out of discovery and events,
+    /// e.g. `playerctld` which mirrors another player or `chromium.instance*`
+    ///
+    /// players already known that match are dropped without a [`MprisEvent::PlayerRemoved`].
+    pub fn ignore<I, S>(&mut self, patterns: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.ignored.extend(patterns.into_iter().map(Into::into));
 
-        None
+        let names: Vec<String> = self
+            .players
+            .iter()
+            .map(|p| p.name().to_string())
+            .filter(|name| self.is_ignored(name))
+            .collect();
+        for name in names {
+            self.drop_player(&name, &mut Vec::new());
+        }
+        self.update_active_player(&mut Vec::new());
     }
 
-    pub fn player_names(&self) -> Vec<&str> {
-        self.players().iter().map(|f| f.name()).collect::<Vec<_>>()
+    pub fn is_ignored(&self, name: &str) -> bool {
+        self.ignored.iter().any(|p| pattern::matches(p, name))
     }
 
-    pub fn players(&self) -> &[Player] {
-        &self.players
+    /// the player whose `Identity` or [display name](Player::display_name) is `name`, ignoring
+    /// case, so `spotify` finds Spotify and `vlc media player` finds VLC
+    pub fn get_by_identity(&self, name: &str) -> Option<&Player> {
+        self.players.iter().find(|p| p.is_called(name))
     }
 
-    /// returns the first player it finds playing audio
-    pub fn currently_playing(&self) -> Option<&Player> {
+    /// every instance of a player by the base of its bus name, `chromium` gets
+    /// `org.mpris.MediaPlayer2.chromium.instance1234` and any other chromium, see
+    /// [`PlayerName`](player::PlayerName)
+    pub fn instances_of(&self, base: &str) -> Vec<&Player> {
         self.players
             .iter()
-            .find(|&player| player.capabilities.playback_status == PlaybackStatus::Playing)
-            .map(|v| v as _)
+            .filter(|p| p.player_name().base.eq_ignore_ascii_case(base))
+            .collect()
     }
 
-    pub fn currently_playing_mut(&mut self) -> Option<&mut Player> {
-        self.players
-            .iter_mut()
-            .find(|player| player.capabilities.playback_status == PlaybackStatus::Playing)
-            .map(|v| v as _)
+    pub fn get_by_stable_id(&self, id: stable_id::StableId) -> Option<&Player> {
+        self.players.iter().find(|p| p.stable_id() == id)
     }
-}
 
-#[cfg(feature = "owner_changed")]
-static mut OWNER_CHANGED_SIGNAL: LazyLock<Mutex<Option<SignalStream<'static>>>> =
-    std::sync::LazyLock::new(|| Mutex::new(None));
+    pub async fn add(&mut self, name: String) -> anyhow::Result<()> {
+        if self.is_ignored(&name) {
+            anyhow::bail!("player {name} is ignored");
+        }
+        let connection = self.connected()?;
+        if self.signal_mode == SignalMode::Multiplexed {
+            self.ensure_multiplexed_stream(&connection).await?;
+        }
+        let connected = Self::connect_player(
+            &connection,
+            name,
+            self.signal_mode,
+            &self.quirks,
+            self.call_policy,
+        )
+        .await?;
+        self.push_player(connected);
+        self.update_active_player(&mut Vec::new());
 
-#[cfg(feature = "owner_changed")]
-pub async fn init_owner_changed_signal() {
-    let connection = zbus::Connection::session().await.unwrap();
-    let name_changed = zbus::Proxy::new(&connection, DBUS_NAME, DBUS_PATH, DBUS_NAME)
-        .await
-        .unwrap();
+        Ok(())
+    }
 
-    let stream = name_changed
-        .receive_signal(DbusSignals::NameOwnerChanged)
-        .await
-        .unwrap();
+    /// everything needed to add a player that talks to the bus, doesn't touch the client so
+    /// several players can be set up at once
+    async fn connect_player(
+        connection: &Connection,
+        name: String,
+        mode: SignalMode,
+        quirks: &QuirkRegistry,
+        policy: CallPolicy,
+    ) -> anyhow::Result<ConnectedPlayer> {
+        if !Self::has_player(connection, &name).await? {
+            anyhow::bail!("player {name} is not running");
+        }
+
+        let (stream, owner) = match mode {
+            SignalMode::PerPlayer => (
+                Some(Self::properties_stream(connection, &name).await?),
+                None,
+            ),
+            SignalMode::Multiplexed => (None, Some(Self::name_owner(connection, &name).await?)),
+        };
+        let player = Player::new_with_quirks(connection, name, quirks, policy).await?;
+        let interfaces = Self::subscribe_interfaces(connection, &player).await;
 
-    unsafe {
-        *OWNER_CHANGED_SIGNAL.lock().unwrap() = Some(stream);
+        Ok(ConnectedPlayer {
+            stream,
+            interfaces,
+            player,
+            owner,
+        })
     }
-}
 
-#[cfg(feature = "owner_changed")]
-pub async fn poll_owner_changed(names: &Vec<&str>) -> anyhow::Result<Poll<NameOwnerChanged>> {
-    unsafe {
+    fn push_player(&mut self, mut connected: ConnectedPlayer) {
+        self.configure(&mut connected.player);
+        let player = self.attach(connected);
+        debug!(player = player.name(), id = %player.stable_id(), "added player");
+        self.players.push(player);
+    }
+
+    /// puts a reconnected player in place of the one at `idx`, keeping its id
+    fn replace_player(&mut self, idx: usize, mut connected: ConnectedPlayer) {
+        let old = &self.players[idx];
+        connected.player.set_id(old.id());
+        connected.player.set_instance(old.instance());
+        connected
+            .player
+            .set_language(self.language_for(&connected.player));
+        connected.player.set_metadata_limits(self.limits);
+        if let Some(recorder) = &mut self.recorder {
+            recorder.removed(connected.player.name());
+        }
+        let player = self.attach(connected);
+        debug!(player = player.name(), id = %player.stable_id(), "replaced player");
+        self.players[idx] = player;
+    }
+
+    /// takes over the owner and streams of a player about to be added
+    fn attach(&mut self, connected: ConnectedPlayer) -> Player {
+        let player = connected.player;
+        if let Some(owner) = connected.owner {
+            self.owners.insert(owner, player.name().to_string());
+        }
+        if let Some(stream) = connected.stream {
+            self.signal_streams
+                .insert(player.name().to_string(), stream);
+        }
+        for (interface, stream) in connected.interfaces {
+            self.interface_streams
+                .insert((player.name().to_string(), interface), stream);
+        }
+        if let Some(recorder) = &mut self.recorder {
+            recorder.player(&player);
+        }
+        player
+    }
+
+    /// the unique name (`:1.42`) currently owning `name`
+    async fn name_owner(connection: &Connection, name: &str) -> anyhow::Result<String> {
+        let msg = call_method(
+            connection,
+            &CallPolicy::DEFAULT,
+            DBUS_NAME,
+            DBUS_PATH,
+            DBUS_NAME,
+            DbusMethods::GetNameOwner,
+            &(name),
+        )
+        .await?;
+
+        Ok(msg.body().deserialize::<String>()?)
+    }
+
+    async fn ensure_multiplexed_stream(&mut self, connection: &Connection) -> anyhow::Result<()> {
+        if self.multiplexed.is_some() {
+            return Ok(());
+        }
+
+        self.multiplexed =
+            Some(MessageStream::for_match_rule(Self::properties_rule()?, connection, None).await?);
+
+        Ok(())
+    }
+
+    /// `PropertiesChanged` of the player interface of every player
+    fn properties_rule() -> anyhow::Result<MatchRule<'static>> {
+        Ok(MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .interface(DBUS_PROPERTIES)?
+            .member("PropertiesChanged")?
+            .path(MPRIS_PATH)?
+            .arg(0, MPRIS_PLAYER_PREFIX)?
+            .build())
+    }
+
+    /// routes signals from the multiplexed stream to their player by sender
+    fn multiplexed_events(&mut self, now: Instant, budget: usize, events: &mut Vec<MprisEvent>) {
+        let Some(stream) = self.multiplexed.as_mut() else {
+            return;
+        };
+
         let waker = WAKER;
-        let mut ctx = Context::from_waker(&waker);
-        if let Poll::Ready(Some(msg)) = OWNER_CHANGED_SIGNAL
-            .lock()
-            .unwrap()
-            .as_mut()
-            .unwrap()
-            .poll_next_unpin(&mut ctx)
-        {
-            let body = msg.body();
-            let (name, old_owner, new_owner): (String, &str, &str) = body.deserialize()?;
+        let mut cx = std::task::Context::from_waker(&waker);
+        for _ in 0..budget {
+            let msg = match futures::StreamExt::poll_next_unpin(stream, &mut cx) {
+                Poll::Ready(Some(Ok(msg))) => msg,
+                Poll::Ready(Some(Err(e))) => {
+                    warn!("multiplexed stream error: {e:?}");
+                    continue;
+                }
+                Poll::Ready(None) => {
+                    warn!("multiplexed stream closed");
+                    self.multiplexed = None;
+                    return;
+                }
+                Poll::Pending => return,
+            };
 
-            if name.starts_with(MPRIS_PREFIX) {
-                match (old_owner.is_empty(), new_owner.is_empty()) {
-                    (true, false) => {
-                        return Ok(Poll::Ready(NameOwnerChanged::NewPlayer(name)));
-                    }
-                    // removed player
-                    (false, true) => {
-                        for n_names in names.iter() {
-                            if n_names == &name {
-                                return Ok(Poll::Ready(NameOwnerChanged::RemovedPlayer(name)));
-                            }
-                        }
-                    }
+            let header = msg.header();
+            let Some(name) = header.sender().and_then(|s| self.owners.get(s.as_str())) else {
+                continue;
+            };
+            let Some(player) = self.players.iter_mut().find(|p| p.name() == name) else {
+                continue;
+            };
+            if let Some(recorder) = &mut self.recorder {
+                recorder.signal(player.name(), &msg);
+            }
+            if let Err(e) = player.properties_changed(&msg, now, events) {
+                parse_error(player.name(), e, self.event_loop, events);
+            }
+        }
+    }
 
-                    _ => {}
+    pub fn get(&self, name: &str) -> Option<&Player> {
+        self.players
+            .iter()
+            .find(|&p| p.name() == name)
+            .map(|v| v as _)
+    }
+
+    pub fn get_id(&self, name: &str) -> Option<PlayerId> {
+        self.get(name).map(Player::id)
+    }
+
+    fn index_of(&self, name: &str) -> Option<usize> {
+        self.players.iter().position(|p| p.name() == name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Player> {
+        self.players
+            .iter_mut()
+            .find(|p| p.name() == name)
+            .map(|v| v as _)
+    }
+
+    pub fn get_from_id(&self, id: PlayerId) -> Option<&Player> {
+        self.players.iter().find(|p| p.id() == id)
+    }
+
+    pub fn get_from_id_mut(&mut self, id: PlayerId) -> Option<&mut Player> {
+        self.players.iter_mut().find(|p| p.id() == id)
+    }
+
+    pub async fn list_names(connection: &Connection) -> anyhow::Result<Vec<String>> {
+        let msg = call_method(
+            connection,
+            &CallPolicy::DEFAULT,
+            DBUS_NAME,
+            DBUS_PATH,
+            DBUS_NAME,
+            DbusMethods::ListNames,
+            &(),
+        )
+        .await?;
+
+        let body = msg.body();
+        let names = body.deserialize::<Vec<String>>()?;
+
+        Ok(names)
+    }
+
+    /// whether `name` currently has an owner on the bus, i.e. the player is running
+    pub async fn has_player(connection: &Connection, name: &str) -> anyhow::Result<bool> {
+        let msg = call_method(
+            connection,
+            &CallPolicy::DEFAULT,
+            DBUS_NAME,
+            DBUS_PATH,
+            DBUS_NAME,
+            DbusMethods::NameHasOwner,
+            &(name),
+        )
+        .await?;
+
+        Ok(msg.body().deserialize::<bool>()?)
+    }
+
+    /// (re)discovers every player on the bus
+    ///
+    /// a player that can't be set up doesn't stop the others from being added, it ends up in
+    /// [`DiscoveryResult::failed`] instead. only failing to list the names on the bus is an error.
+    // #[instrument(skip_all, ret)]
+    pub async fn get_all(&mut self) -> anyhow::Result<DiscoveryResult> {
+        let connection = self.connected()?;
+        let connection = &connection;
+        // the ids change, the active player is found again by name
+        let active = self.active_player().map(|p| p.name().to_string());
+        self.active = None;
+        if !self.players.is_empty() {
+            self.players.clear();
+            self.owners.clear();
+            self.signal_streams.clear();
+            self.interface_streams.clear();
+        }
+        self.deferred.clear();
+        let names = Self::list_names(connection).await?;
+
+        let mut discovery = DiscoveryResult::default();
+        if self.discovery_mode == DiscoveryMode::Lazy {
+            for name in names {
+                if !name.starts_with(MPRIS_PREFIX) || self.is_ignored(&name) {
+                    continue;
+                }
+                match self.defer(connection, name.clone()).await {
+                    Ok(()) => discovery.deferred.push(name),
+                    Err(e) => {
+                        warn!(player = name, "skipping player: {e:?}");
+                        discovery.failed.push((name, e));
+                    }
+                }
+            }
+            return Ok(discovery);
+        }
+        if self.signal_mode == SignalMode::Multiplexed {
+            self.ensure_multiplexed_stream(connection).await?;
+        }
+
+        // with a handful of browser tabs open, setting players up one after another is slow
+        let mode = self.signal_mode;
+        let quirks = &self.quirks;
+        let policy = self.call_policy;
+        let players = futures::future::join_all(
+            names
+                .into_iter()
+                .filter(|name| name.starts_with(MPRIS_PREFIX) && !self.is_ignored(name))
+                .map(|name| async move {
+                    let result =
+                        Self::connect_player(connection, name.clone(), mode, quirks, policy).await;
+                    (name, result)
+                }),
+        )
+        .await;
+
+        for (name, result) in players {
+            match result {
+                Ok(connected) => {
+                    self.push_player(connected);
+                    discovery.added.push(name);
+                }
+                Err(e) => {
+                    warn!(player = name, "skipping player: {e:?}");
+                    discovery.failed.push((name, e));
+                }
+            }
+        }
+        self.active = active.and_then(|name| self.get(&name)).map(Player::id);
+        if self.active.is_none() {
+            self.restore_active();
+        }
+        self.update_active_player(&mut Vec::new());
+
+        Ok(discovery)
+    }
+
+    /// re-reads every player, see [`Player::refresh`], handing back the ones that couldn't be
+    /// read and why. those keep what was cached
+    ///
+    /// what changed comes as events with the next [`MprisClient::event`].
+    pub async fn refresh_all(&mut self) -> anyhow::Result<Vec<(String, anyhow::Error)>> {
+        let connection = self.connected()?;
+        let connection = &connection;
+        let now = self.clock.now();
+        let refreshed =
+            futures::future::join_all(self.players.iter_mut().map(|player| async move {
+                let mut events = Vec::new();
+                let result = player.refresh_at(connection, now, &mut events).await;
+                (player.name().to_string(), events, result)
+            }))
+            .await;
+
+        let mut events = Vec::new();
+        let mut failed = Vec::new();
+        for (name, refreshed, result) in refreshed {
+            events.extend(refreshed);
+            if let Err(e) = result {
+                warn!(player = name, "failed to refresh: {e:?}");
+                failed.push((name, e));
+            }
+        }
+        self.update_active_player(&mut events);
+        self.pending.extend(events);
+
+        Ok(failed)
+    }
+
+    pub async fn handle_player_changed(&mut self, id: PlayerId) {
+        let now = self.clock.now();
+        let Some(player) = self.players.iter_mut().find(|p| p.id() == id) else {
+            return;
+        };
+        let Some(stream) = self.signal_streams.get_mut(player.name()) else {
+            return;
+        };
+        match player::poll_player(stream) {
+            Poll::Ready(Some(Ok(updates))) => {
+                for update in updates {
+                    player.apply(update, now, &mut Vec::new());
+                }
+            }
+            Poll::Ready(Some(Err(e))) => warn!(player = player.name(), "skipping signal: {e:?}"),
+            _ => {}
+        }
+    }
+
+    pub async fn handle_players_changed(&mut self) {
+        let ids: Vec<PlayerId> = self.players.iter().map(Player::id).collect();
+        for id in ids {
+            self.handle_player_changed(id).await;
+        }
+    }
+
+    /// handles pending signals, returning what changed
+    pub async fn event(&mut self) -> Vec<MprisEvent> {
+        if let Some(events) = self.watch_connection().await {
+            return events;
+        }
+        let connection = self.connection.clone();
+        let mut events = std::mem::take(&mut self.pending);
+        let len = self.players.len();
+        if len > 0 {
+            let mut budget = self.event_loop.max_events_per_tick;
+            // rotate which player is served first so the ones at the end of the list don't
+            // always get what is left of the budget
+            let start = self.event_cursor % len;
+            self.event_cursor = (start + 1) % len;
+
+            let now = self.clock.now();
+            let mut closed = Vec::new();
+            for offset in 0..len {
+                if budget == 0 {
+                    break;
+                }
+
+                let i = (start + offset) % len;
+                let player = &mut self.players[i];
+                let Some(stream) = self.signal_streams.get_mut(player.name()) else {
+                    continue;
+                };
+
+                for _ in 0..self.event_loop.max_events_per_player.min(budget) {
+                    match player::poll_message(stream) {
+                        Poll::Ready(Some(msg)) => {
+                            if let Some(recorder) = &mut self.recorder {
+                                recorder.signal(player.name(), &msg);
+                            }
+                            // a signal with nothing of interest still counts against the
+                            // budget, the ones behind it are read all the same
+                            if let Err(e) = player.properties_changed(&msg, now, &mut events) {
+                                parse_error(player.name(), e, self.event_loop, &mut events);
+                            }
+                            budget -= 1;
+                        }
+                        Poll::Ready(None) => {
+                            closed.push(player.name().to_string());
+                            break;
+                        }
+                        Poll::Pending => break,
+                    }
+                }
+            }
+
+            self.multiplexed_events(now, budget, &mut events);
+
+            for name in closed {
+                self.handle_closed_stream(connection.as_ref(), name, &mut events)
+                    .await;
+            }
+        }
+
+        if let Some(connection) = &connection {
+            self.poll_fallback(connection, &mut events).await;
+        }
+        self.deferred_events(&mut events).await;
+        self.interface_events(&mut events);
+        self.tracks_ending(&mut events);
+
+        #[cfg(feature = "owner_changed")]
+        if let Some(changed) = self.handle_owner_changed().await {
+            events.push(match changed {
+                NameOwnerChanged::NewPlayer(name) => MprisEvent::PlayerAdded(name),
+                NameOwnerChanged::RemovedPlayer(name) => MprisEvent::PlayerRemoved(name),
+                NameOwnerChanged::ReplacedPlayer(name) => MprisEvent::PlayerRestarted(name),
+            });
+        }
+        self.update_active_player(&mut events);
+        self.share_playing();
+
+        events
+    }
+
+    /// the positions of the playing players once per `interval`, for progress bars. ticks while
+    /// nothing plays are skipped, the stream ends when the client is dropped
+    ///
+    /// the positions are estimated (see [`Player::estimated_position`]) from what the client
+    /// knew at its last [`MprisClient::event`], so ticking doesn't talk to the players and
+    /// doesn't wait for `event` to be called.
+    pub fn position_ticks(&self, interval: Duration) -> BoxStream<'static, Vec<PositionTick>> {
+        let playing = self.playing.subscribe();
+        self.share_playing();
+        position::ticks(playing, self.clock.clone(), interval)
+    }
+
+    fn share_playing(&self) {
+        if self.playing.is_closed() {
+            return;
+        }
+        let playing: Vec<Playing> = self.players.iter().filter_map(Player::playing).collect();
+        self.playing.send_if_modified(|shared| {
+            let modified = *shared != playing;
+            *shared = playing;
+            modified
+        });
+    }
+
+    /// makes [`MprisClient::event`] hand out a [`MprisEvent::TrackEndingSoon`] once a playing
+    /// track has `lead` or less left, for prefetching or crossfading. `None` turns it off
+    ///
+    /// it is announced once per track, seeking back out of the last `lead` announces it again.
+    pub fn set_track_ending_soon(&mut self, lead: Option<Duration>) {
+        self.track_ending_soon = lead;
+        self.ending.clear();
+    }
+
+    fn tracks_ending(&mut self, events: &mut Vec<MprisEvent>) {
+        let Some(lead) = self.track_ending_soon else {
+            return;
+        };
+        let now = self.clock.now();
+        self.ending
+            .retain(|name, _| self.players.iter().any(|p| p.name() == name));
+
+        for player in &self.players {
+            let caps = &player.capabilities;
+            let remaining = match caps.metadata.length().filter(|len| *len > 0) {
+                Some(length) => length.saturating_sub(player.estimated_position_at(now)),
+                None => continue,
+            };
+            let remaining = Duration::from_micros(remaining);
+            if remaining > lead {
+                self.ending.remove(player.name());
+                continue;
+            }
+
+            let track = (
+                caps.metadata.track_id().cloned(),
+                caps.metadata.title().map(str::to_string),
+            );
+            if caps.playback_status != PlaybackStatus::Playing
+                || remaining.is_zero()
+                || self.ending.get(player.name()) == Some(&track)
+            {
+                continue;
+            }
+
+            self.ending.insert(player.name().to_string(), track);
+            events.push(MprisEvent::TrackEndingSoon {
+                player: player.name().to_string(),
+                remaining,
+            });
+        }
+    }
+
+    /// the player commands without an explicit target should go to, the same one playerctld
+    /// would pick
+    ///
+    /// that is the player that started playing last. while it isn't playing but another one is,
+    /// the other one takes over. with nothing playing the last active player stays active, or
+    /// the one that changed most recently when that one is gone. a player made active with
+    /// [`MprisClient::set_active_player`] or [`MprisClient::shift`] stays active until another
+    /// one starts playing.
+    pub fn active_player(&self) -> Option<&Player> {
+        self.active.and_then(|id| self.get_from_id(id))
+    }
+
+    pub fn active_player_mut(&mut self) -> Option<&mut Player> {
+        self.active.and_then(|id| self.get_from_id_mut(id))
+    }
+
+    /// sets which players [`MprisClient::preferred_player`] picks first, as patterns (see
+    /// [`pattern`]) like `["spotify", "mpv", "firefox*"]`
+    pub fn set_priority<I, S>(&mut self, patterns: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.priority = patterns.into_iter().map(Into::into).collect();
+    }
+
+    pub fn priority(&self) -> &[String] {
+        &self.priority
+    }
+
+    /// "the" player: one matching the earliest entry of the priority list that matches any,
+    /// falling back to [`MprisClient::active_player`]
+    ///
+    /// when several players match the same entry the active one wins, then the one found first.
+    pub fn preferred_player(&self) -> Option<&Player> {
+        Selector::new().prefer_all(&self.priority).select(self)
+    }
+
+    /// makes `id` the active player until another player starts playing, `false` when there is
+    /// no such player
+    ///
+    /// the [`MprisEvent::ActivePlayerChanged`] comes with the next [`MprisClient::event`].
+    pub fn set_active_player(&mut self, id: PlayerId) -> bool {
+        if self.get_from_id(id).is_none() {
+            return false;
+        }
+
+        self.active_pinned = true;
+        let mut events = Vec::new();
+        self.switch_active(Some(id), &mut events);
+        self.pending.extend(events);
+        true
+    }
+
+    /// makes the player after the active one active, in the order they were found, like
+    /// `playerctld shift`
+    pub fn shift(&mut self) -> Option<&Player> {
+        self.rotate_active(1)
+    }
+
+    /// the opposite of [`MprisClient::shift`]
+    pub fn unshift(&mut self) -> Option<&Player> {
+        self.rotate_active(-1)
+    }
+
+    fn rotate_active(&mut self, by: isize) -> Option<&Player> {
+        let len = self.players.len() as isize;
+        let next = match self.active.and_then(|id| self.index_of_id(id)) {
+            Some(i) => (i as isize + by).rem_euclid(len.max(1)) as usize,
+            None => 0,
+        };
+        let id = self.players.get(next)?.id();
+        self.set_active_player(id);
+
+        self.active_player()
+    }
+
+    fn index_of_id(&self, id: PlayerId) -> Option<usize> {
+        self.players.iter().position(|p| p.id() == id)
+    }
+
+    /// saves the active player to `path` whenever it or its state changes, and makes it active
+    /// again in [`MprisClient::get_all`] when it is still around and nothing else is playing,
+    /// see [`persist`]. `None` turns this off
+    pub fn persist_active(&mut self, path: Option<PathBuf>) {
+        self.persist_path = path;
+    }
+
+    fn restore_active(&mut self) {
+        let Some(path) = &self.persist_path else {
+            return;
+        };
+        let saved = match SavedActive::load(path) {
+            Ok(saved) => saved,
+            Err(e) => {
+                warn!("failed to restore the active player: {e:#}");
+                return;
+            }
+        };
+
+        if let Some(player) = saved.and_then(|saved| self.players.iter().find(|p| saved.is(p))) {
+            debug!(player = player.name(), "restored active player");
+            self.active = Some(player.id());
+        }
+    }
+
+    /// picks the active player after `events`, pushing [`MprisEvent::ActivePlayerChanged`]
+    /// when that is a different one
+    fn update_active_player(&mut self, events: &mut Vec<MprisEvent>) {
+        let is_playing = |p: &&Player| p.capabilities.playback_status == PlaybackStatus::Playing;
+        let started_playing = events.iter().rev().find_map(|event| match event {
+            MprisEvent::PlayerUpdated {
+                player,
+                update: PlayerUpdated::PlaybackStatus(PlaybackStatus::Playing),
+            } => self.get(player),
+            _ => None,
+        });
+        let current = self.active_player();
+        // `rev` so ties go to the player found first
+        let latest = |players: &mut dyn Iterator<Item = &Player>| -> Option<PlayerId> {
+            players.max_by_key(|p| p.last_updated()).map(Player::id)
+        };
+
+        let pinned = self.active_pinned && started_playing.is_none() && current.is_some();
+        let active = match (started_playing, current) {
+            (Some(player), _) => Some(player.id()),
+            (None, Some(current)) if pinned || is_playing(&current) => Some(current.id()),
+            (None, current) => latest(&mut self.players.iter().rev().filter(is_playing))
+                .or(current.map(Player::id))
+                .or_else(|| latest(&mut self.players.iter().rev())),
+        };
+        self.active_pinned = pinned;
+
+        let active_updated = self.active_player().is_some_and(|active| {
+            events.iter().any(|event| match event {
+                MprisEvent::PlayerUpdated {
+                    player,
+                    update: PlayerUpdated::PlaybackStatus(_),
+                }
+                | MprisEvent::TrackChanged { player, .. } => player == active.name(),
+                _ => false,
+            })
+        });
+        if active_updated && active == self.active {
+            self.save_active();
+        }
+        self.switch_active(active, events);
+    }
+
+    /// pushes [`MprisEvent::ActivePlayerChanged`] if `active` is a different player
+    fn switch_active(&mut self, active: Option<PlayerId>, events: &mut Vec<MprisEvent>) {
+        if active == self.active {
+            return;
+        }
+
+        self.active = active;
+        let player = self.active_player().map(|p| p.name().to_string());
+        debug!(?player, "active player changed");
+        events.push(MprisEvent::ActivePlayerChanged { player });
+        self.save_active();
+    }
+
+    fn save_active(&self) {
+        let (Some(path), Some(active)) = (&self.persist_path, self.active_player()) else {
+            return;
+        };
+        if let Err(e) = SavedActive::of(active).save(path) {
+            warn!("failed to save the active player: {e:#}");
+        }
+    }
+
+    /// notices the connection going away and gets it back, `None` while it is fine
+    async fn watch_connection(&mut self) -> Option<Vec<MprisEvent>> {
+        if self.lost.is_some() {
+            let mut events = Vec::new();
+            self.try_reconnect(&mut events).await;
+            return Some(events);
+        }
+        let connection = self.connection.clone()?;
+        if self.watch.is_none() {
+            // any stream ends when the connection does, this one hardly sees anything else
+            let rule = MatchRule::builder()
+                .msg_type(zbus::message::Type::Signal)
+                .sender(DBUS_NAME)
+                .ok()?
+                .interface(DBUS_NAME)
+                .ok()?
+                .member("NameLost")
+                .ok()?
+                .build();
+            match MessageStream::for_match_rule(rule, &connection, None).await {
+                Ok(stream) => self.watch = Some(stream),
+                Err(e) => warn!("can't watch the connection: {e}"),
+            }
+        }
+
+        let waker = WAKER;
+        let mut cx = std::task::Context::from_waker(&waker);
+        let watch = self.watch.as_mut()?;
+        loop {
+            match futures::StreamExt::poll_next_unpin(watch, &mut cx) {
+                Poll::Ready(Some(Ok(_))) => continue,
+                Poll::Ready(Some(Err(_)) | None) => break,
+                Poll::Pending => return None,
+            }
+        }
+
+        warn!("lost the bus connection");
+        self.connection = None;
+        self.watch = None;
+        // every stream went with the connection
+        self.signal_streams.clear();
+        self.interface_streams.clear();
+        self.multiplexed = None;
+        self.deferred_stream = None;
+        self.owners.clear();
+        self.polled.clear();
+        self.reconnect_attempts.clear();
+        self.lost = Some((0, self.clock.now()));
+        Some(vec![MprisEvent::ConnectionLost])
+    }
+
+    async fn try_reconnect(&mut self, events: &mut Vec<MprisEvent>) {
+        let (Some((attempts, next)), Some(connector)) = (self.lost, self.connector.clone()) else {
+            return;
+        };
+        let now = self.clock.now();
+        if now < next {
+            return;
+        }
+
+        let connection = match (connector.0)().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                let delay = RECONNECT_BACKOFF[attempts.min(RECONNECT_BACKOFF.len() - 1)];
+                debug!(attempts, ?delay, "can't reconnect yet: {e}");
+                self.lost = Some((attempts + 1, now + delay));
+                return;
+            }
+        };
+        info!(attempts, "reconnected to the bus");
+        self.lost = None;
+        self.connection = Some(connection.clone());
+        events.push(MprisEvent::ConnectionRestored);
+        if let Err(e) = self.resync(&connection, events).await {
+            warn!("failed to set the players up again: {e:?}");
+        }
+        self.update_active_player(events);
+    }
+
+    /// sets every player up again on a new connection, keeping the ids of the ones still there
+    async fn resync(
+        &mut self,
+        connection: &Connection,
+        events: &mut Vec<MprisEvent>,
+    ) -> anyhow::Result<()> {
+        let names: Vec<String> = Self::list_names(connection)
+            .await?
+            .into_iter()
+            .filter(|name| name.starts_with(MPRIS_PREFIX) && !self.is_ignored(name))
+            .collect();
+        if self.signal_mode == SignalMode::Multiplexed {
+            self.ensure_multiplexed_stream(connection).await?;
+        }
+
+        let known: Vec<String> = self.player_names().into_iter().map(String::from).collect();
+        for name in known {
+            let connected = if names.contains(&name) {
+                Self::connect_player(
+                    connection,
+                    name.clone(),
+                    self.signal_mode,
+                    &self.quirks,
+                    self.call_policy,
+                )
+                .await
+                .inspect_err(|e| warn!(player = name, "dropping player: {e:?}"))
+                .ok()
+            } else {
+                None
+            };
+            match (self.index_of(&name), connected) {
+                (Some(idx), Some(connected)) => self.replace_player(idx, connected),
+                _ => self.drop_player(&name, events),
+            }
+        }
+
+        self.deferred.clear();
+        for name in names {
+            if self.index_of(&name).is_some() {
+                continue;
+            }
+            if self.discovery_mode == DiscoveryMode::Lazy {
+                if let Err(e) = self.defer(connection, name.clone()).await {
+                    warn!(player = name, "skipping player: {e:?}");
+                }
+                continue;
+            }
+            match Self::connect_player(
+                connection,
+                name.clone(),
+                self.signal_mode,
+                &self.quirks,
+                self.call_policy,
+            )
+            .await
+            {
+                Ok(connected) => {
+                    self.push_player(connected);
+                    events.push(MprisEvent::PlayerAdded(name));
+                }
+                Err(e) => warn!(player = name, "skipping player: {e:?}"),
+            }
+        }
+
+        // the owner changes were followed on the old connection
+        #[cfg(feature = "owner_changed")]
+        if self.owner_changed.is_some() {
+            self.owner_changed = Some(owner_changed_stream(connection).await?);
+        }
+        Ok(())
+    }
+
+    /// sets the policy for players matching `pattern` (see [`pattern`]), later calls take
+    /// precedence over earlier ones
+    pub fn set_reconnect_policy(&mut self, pattern: &str, policy: ReconnectPolicy) {
+        self.reconnect_policies.retain(|(p, _)| p != pattern);
+        self.reconnect_policies.push((pattern.to_string(), policy));
+    }
+
+    pub fn reconnect_policy(&self, name: &str) -> ReconnectPolicy {
+        self.reconnect_policies
+            .iter()
+            .rev()
+            .find(|(p, _)| pattern::matches(p, name))
+            .map(|(_, policy)| *policy)
+            .unwrap_or_default()
+    }
+
+    async fn handle_closed_stream(
+        &mut self,
+        connection: Option<&Connection>,
+        name: String,
+        events: &mut Vec<MprisEvent>,
+    ) {
+        let policy = self.reconnect_policy(&name);
+        warn!(player = name, ?policy, "signal stream closed");
+
+        match policy {
+            ReconnectPolicy::Retry { attempts } => {
+                let tried = self.reconnect_attempts.entry(name.clone()).or_insert(0);
+                *tried += 1;
+                if let Some(connection) = connection.filter(|_| *tried <= attempts) {
+                    match Self::properties_stream(connection, &name).await {
+                        Ok(stream) => {
+                            self.signal_streams.insert(name, stream);
+                            return;
+                        }
+                        Err(e) => warn!(player = name, "failed to resubscribe: {e:?}"),
+                    }
+                }
+
+                self.drop_player(&name, events);
+            }
+            ReconnectPolicy::Poll { interval } => {
+                self.signal_streams.remove(&name);
+                self.polled.insert(name, (interval, None));
+            }
+            ReconnectPolicy::Drop => self.drop_player(&name, events),
+        }
+    }
+
+    /// re-runs `GetAll` for players whose stream fell back to polling
+    async fn poll_fallback(&mut self, connection: &Connection, events: &mut Vec<MprisEvent>) {
+        let now = self.clock.now();
+        let due: Vec<String> = self
+            .polled
+            .iter()
+            .filter(|(_, (interval, last))| last.is_none_or(|last| now - last >= *interval))
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        for name in due {
+            if let Some((_, last)) = self.polled.get_mut(&name) {
+                *last = Some(now);
+            }
+
+            let relaxed = self.get(&name).is_some_and(Player::is_relaxed);
+            let caps =
+                match Player::fetch_capabilities_as(connection, &self.call_policy, &name, relaxed)
+                    .await
+                {
+                    Ok(caps) => caps,
+                    Err(e) => {
+                        warn!(player = name, "polling failed: {e:?}");
+                        continue;
+                    }
+                };
+
+            let Some(player) = self.get_mut(&name) else {
+                self.polled.remove(&name);
+                continue;
+            };
+            for update in player.updates_to(caps) {
+                player.apply(update, now, events);
+            }
+        }
+    }
+
+    fn drop_player(&mut self, name: &str, events: &mut Vec<MprisEvent>) {
+        if self.remove_player(name) {
+            events.push(MprisEvent::PlayerRemoved(name.to_string()));
+        }
+    }
+
+    /// forgets a player and everything kept about it, returning whether it was known
+    fn remove_player(&mut self, name: &str) -> bool {
+        let known = match self.index_of(name) {
+            Some(idx) => {
+                self.players.remove(idx);
+                debug!(player = name, "removed player");
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.removed(name);
+                }
+                true
+            }
+            None => false,
+        };
+        self.forget_process(name);
+        known
+    }
+
+    /// forgets what belongs to the process behind `name`, the player itself is kept
+    fn forget_process(&mut self, name: &str) {
+        self.forget_streams(name);
+        self.owners.retain(|_, player| player != name);
+        self.reconnect_attempts.remove(name);
+        self.polled.remove(name);
+        self.ending.remove(name);
+    }
+
+    fn forget_streams(&mut self, name: &str) {
+        self.signal_streams.remove(name);
+        self.interface_streams
+            .retain(|(player, _), _| player != name);
+    }
+
+    /// follows the tracklist and playlist signals of a player
+    async fn subscribe_interfaces(
+        connection: &Connection,
+        player: &Player,
+    ) -> Vec<(ExtraInterface, SignalStream<'static>)> {
+        let mut streams = Vec::new();
+        let mut interfaces = vec![ExtraInterface::Seeked, ExtraInterface::Playlists];
+        if player.root().has_track_list {
+            interfaces.push(ExtraInterface::TrackList);
+        }
+
+        for interface in interfaces {
+            let stream = async {
+                let proxy = Proxy::new(
+                    connection,
+                    BusName::WellKnown(
+                        WellKnownName::from_str_unchecked(player.name()).into_owned(),
+                    ),
+                    MPRIS_PATH,
+                    interface.name(),
+                )
+                .await?;
+
+                anyhow::Ok(match interface {
+                    ExtraInterface::TrackList => proxy.receive_all_signals().await?,
+                    ExtraInterface::Playlists => proxy.receive_signal("PlaylistChanged").await?,
+                    ExtraInterface::Seeked => proxy.receive_signal("Seeked").await?,
+                })
+            };
+
+            match stream.await {
+                Ok(stream) => streams.push((interface, stream)),
+                Err(e) => warn!(
+                    player = player.name(),
+                    "failed to subscribe to {}: {e:?}",
+                    interface.name()
+                ),
+            }
+        }
+
+        streams
+    }
+
+    /// handles pending tracklist, playlist and `Seeked` signals, at most
+    /// `max_events_per_player` per player and interface
+    fn interface_events(&mut self, events: &mut Vec<MprisEvent>) {
+        let now = self.clock.now();
+        let mut closed = Vec::new();
+        for ((name, interface), stream) in self.interface_streams.iter_mut() {
+            let key = (name.clone(), *interface);
+            let Some(player) = self.players.iter_mut().find(|p| p.name() == name) else {
+                closed.push(key);
+                continue;
+            };
+
+            for _ in 0..self.event_loop.max_events_per_player {
+                let polled = match interface {
+                    ExtraInterface::TrackList => tracklist::poll_tracklist(stream)
+                        .map(|update| update.map(|u| player.apply_tracklist(u, events))),
+                    ExtraInterface::Playlists => {
+                        playlists::poll_playlists(stream).map(|playlist| {
+                            playlist.map(|playlist| {
+                                events.push(MprisEvent::PlaylistUpdated {
+                                    player: name.clone(),
+                                    playlist,
+                                })
+                            })
+                        })
+                    }
+                    ExtraInterface::Seeked => position::poll_seeked(stream).map(|position| {
+                        position.map(|position| {
+                            if let Some(recorder) = &mut self.recorder {
+                                recorder.seeked(name, position);
+                            }
+                            player.seeked(position, now, events)
+                        })
+                    }),
+                };
+
+                match polled {
+                    Poll::Ready(Some(())) => {}
+                    Poll::Ready(None) => {
+                        warn!(player = name, "{} stream closed", interface.name());
+                        closed.push(key);
+                        break;
+                    }
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        for key in closed {
+            self.interface_streams.remove(&key);
+        }
+    }
+
+    async fn properties_stream(
+        connection: &Connection,
+        name: &str,
+    ) -> anyhow::Result<SignalStream<'static>> {
+        let proxy = Proxy::new(
+            connection,
+            BusName::WellKnown(WellKnownName::from_str_unchecked(name).into_owned()),
+            MPRIS_PATH,
+            DBUS_PROPERTIES,
+        )
+        .await?;
+
+        Ok(proxy.receive_signal(DbusSignals::PropertiesChanged).await?)
+    }
+
+    /// makes players coming and going on the client's bus show up in [`MprisClient::event`]
+    #[cfg(feature = "owner_changed")]
+    pub async fn follow_owner_changes(&mut self) -> anyhow::Result<()> {
+        let connection = self.connected()?;
+        self.owner_changed = Some(owner_changed_stream(&connection).await?);
+        Ok(())
+    }
+
+    #[cfg(feature = "owner_changed")]
+    pub async fn handle_owner_changed(&mut self) -> Option<NameOwnerChanged> {
+        let known = self
+            .players
+            .iter()
+            .map(Player::name)
+            .chain(self.deferred.iter().map(|(name, _)| name.as_str()))
+            .collect();
+        let polled = poll_owner_changed(self.owner_changed.as_mut()?, &known);
+        if let Ok(Poll::Ready(changed)) = polled {
+            match changed {
+                NameOwnerChanged::NewPlayer(ref name) => {
+                    if self.is_ignored(name) {
+                        return None;
+                    }
+                    let connection = self.connection.clone()?;
+                    if self.discovery_mode == DiscoveryMode::Lazy {
+                        if let Err(e) = self.defer(&connection, name.clone()).await {
+                            warn!(player = name, "skipping player: {e:?}");
+                        }
+                        return None;
+                    }
+                    match Self::connect_player(
+                        &connection,
+                        name.clone(),
+                        self.signal_mode,
+                        &self.quirks,
+                        self.call_policy,
+                    )
+                    .await
+                    {
+                        Ok(connected) => self.push_player(connected),
+                        Err(e) => {
+                            warn!(player = name, "skipping player: {e:?}");
+                            return None;
+                        }
+                    }
+                    return Some(changed);
+                }
+                NameOwnerChanged::RemovedPlayer(ref name) => {
+                    if let Some(pos) = self.deferred.iter().position(|(n, _)| n == name) {
+                        self.deferred.remove(pos);
+                        return None;
+                    }
+                    self.remove_player(name);
+                    return Some(changed);
+                }
+                NameOwnerChanged::ReplacedPlayer(ref name) => {
+                    let connection = self.connection.clone()?;
+                    if self.deferred.iter().any(|(n, _)| n == name) {
+                        // deferred players are matched by their owner, which just changed
+                        if let Err(e) = self.defer(&connection, name.clone()).await {
+                            warn!(player = name, "skipping player: {e:?}");
+                            self.deferred.retain(|(n, _)| n != name);
+                        }
+                        return None;
+                    }
+                    let idx = self.index_of(name)?;
+                    // the streams and owner are the old process', everything is read again
+                    self.forget_process(name);
+                    match Self::connect_player(
+                        &connection,
+                        name.clone(),
+                        self.signal_mode,
+                        &self.quirks,
+                        self.call_policy,
+                    )
+                    .await
+                    {
+                        Ok(connected) => self.replace_player(idx, connected),
+                        Err(e) => {
+                            warn!(player = name, "dropping restarted player: {e:?}");
+                            self.remove_player(name);
+                            return Some(NameOwnerChanged::RemovedPlayer(name.clone()));
+                        }
+                    }
+                    return Some(changed);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// records the signals the client receives from now on, `None` stops recording
+    ///
+    /// the players already known are recorded first, see [`record`] for the format.
+    pub fn record_to(&mut self, recorder: Option<Recorder>) {
+        self.recorder = recorder;
+        if let Some(recorder) = &mut self.recorder {
+            for player in &self.players {
+                recorder.player(player);
+            }
+        }
+    }
+
+    /// a client for [`MprisClient::replay`], it never talks to the bus
+    pub fn replaying() -> Self {
+        Self::disconnected()
+    }
+
+    /// feeds something recorded with [`MprisClient::record_to`] back in, handing out the events
+    /// it would have caused
+    ///
+    /// players are created from their recorded state, signals from players that weren't recorded
+    /// are skipped.
+    pub fn replay(&mut self, recorded: &Recorded) -> anyhow::Result<Vec<MprisEvent>> {
+        let mut events = Vec::new();
+        let now = self.clock.now();
+        let name = recorded.player();
+        match recorded {
+            Recorded::Player { properties, .. } => {
+                let properties = variant::properties_from_json(properties)?;
+                let properties = properties
+                    .iter()
+                    .map(|(k, v)| Ok((k.as_str(), Value::from(v.try_clone()?))))
+                    .collect::<anyhow::Result<HashMap<&str, Value>>>()?;
+                let mut player =
+                    Player::from_capabilities(name.to_string(), properties.try_into()?);
+                self.configure(&mut player);
+                self.players.push(player);
+                events.push(MprisEvent::PlayerAdded(name.to_string()));
+            }
+            Recorded::Signal { member, .. } => {
+                let Some(msg) = recorded.message()? else {
+                    return Ok(events);
+                };
+                let Some(player) = self.players.iter_mut().find(|p| p.name() == name) else {
+                    return Ok(events);
+                };
+                match member.as_str() {
+                    "Seeked" => {
+                        let (position,): (i64,) = msg.body().deserialize()?;
+                        player.seeked(position.max(0).cast_unsigned(), now, &mut events);
+                    }
+                    _ => {
+                        if let Err(e) = player.properties_changed(&msg, now, &mut events) {
+                            parse_error(name, e, self.event_loop, &mut events);
+                        }
+                    }
+                }
+            }
+            Recorded::Removed { .. } => self.drop_player(name, &mut events),
+        }
+        self.update_active_player(&mut events);
+
+        Ok(events)
+    }
+
+    /// adds a player that isn't backed by a signal stream
+    #[cfg(feature = "test-util")]
+    pub(crate) fn insert(&mut self, mut player: Player) {
+        self.configure(&mut player);
+        self.players.push(player);
+    }
+
+    /// applies an update as if it came from `name`'s signal stream
+    #[cfg(feature = "test-util")]
+    pub(crate) fn apply(&mut self, name: &str, update: PlayerUpdated) -> Vec<MprisEvent> {
+        let mut events = Vec::new();
+        let now = self.clock.now();
+        if let Some(player) = self.get_mut(name) {
+            player.apply(update, now, &mut events);
+        }
+        self.share_playing();
+
+        events
+    }
+
+    /// the whole client state as json, the document [`patch::PatchOperation`]s apply to
+    pub fn snapshot(&self) -> serde_json::Value {
+        let players: serde_json::Map<String, serde_json::Value> = self
+            .players
+            .iter()
+            .map(|p| {
+                (
+                    p.name().to_string(),
+                    serde_json::json!({
+                        "id": p.stable_id(),
+                        "name": p.name(),
+                        "language": p.language(),
+                        "capabilities": p.capabilities(),
+                        "ui": p.ui_model(),
+                    }),
+                )
+            })
+            .collect();
+
+        serde_json::json!({ "players": players })
+    }
+
+    /// like [`MprisClient::event`] but describes what changed as json patch operations
+    pub async fn event_patches(
+        &mut self,
+        emitter: &mut patch::PatchEmitter,
+    ) -> Vec<patch::PatchOperation> {
+        self.event().await;
+        emitter.update(self)
+    }
+
+    pub fn player_names(&self) -> Vec<&str> {
+        self.players().iter().map(|f| f.name()).collect::<Vec<_>>()
+    }
+
+    pub fn players(&self) -> &[Player] {
+        &self.players
+    }
+
+    /// the player to open `uri` in: `preferred` if it supports it, otherwise the best match
+    /// according to the schemes and mime types the players advertise
+    pub fn route_uri(&self, uri: &str, preferred: Option<&str>) -> Option<&Player> {
+        if let Some(player) = preferred.and_then(|name| self.get(name)) {
+            if player.uri_score(uri).is_some() {
+                return Some(player);
+            }
+        }
+
+        self.players
+            .iter()
+            .filter_map(|player| Some((player.uri_score(uri)?, player)))
+            // `max_by_key` keeps the last of equal scores, prefer the first one found
+            .rev()
+            .max_by_key(|(score, _)| *score)
+            .map(|(_, player)| player)
+    }
+
+    /// returns the first player it finds playing audio
+    pub fn currently_playing(&self) -> Option<&Player> {
+        self.players
+            .iter()
+            .find(|&player| player.capabilities.playback_status == PlaybackStatus::Playing)
+            .map(|v| v as _)
+    }
+
+    pub fn currently_playing_mut(&mut self) -> Option<&mut Player> {
+        self.players
+            .iter_mut()
+            .find(|player| player.capabilities.playback_status == PlaybackStatus::Playing)
+            .map(|v| v as _)
+    }
+}
+
+/// logs a signal that failed to parse and passes it on if the client asked for that
+fn parse_error(
+    player: &str,
+    error: anyhow::Error,
+    config: EventLoopConfig,
+    events: &mut Vec<MprisEvent>,
+) {
+    warn!(player, "skipping signal: {error:?}");
+    if config.emit_parse_errors {
+        events.push(MprisEvent::ParseError {
+            player: player.to_string(),
+            error: format!("{error:#}"),
+        });
+    }
+}
+
+/// the `NameOwnerChanged` signals of the bus `connection` is on
+#[cfg(feature = "owner_changed")]
+async fn owner_changed_stream(connection: &Connection) -> anyhow::Result<SignalStream<'static>> {
+    let name_changed = zbus::Proxy::new(connection, DBUS_NAME, DBUS_PATH, DBUS_NAME).await?;
+    Ok(name_changed
+        .receive_signal(DbusSignals::NameOwnerChanged)
+        .await?)
+}
+
+/// the next change on `stream` that concerns players, `names` are the ones the client knows
+#[cfg(feature = "owner_changed")]
+pub fn poll_owner_changed(
+    stream: &mut SignalStream<'_>,
+    names: &Vec<&str>,
+) -> anyhow::Result<Poll<NameOwnerChanged>> {
+    let waker = WAKER;
+    let mut ctx = Context::from_waker(&waker);
+    if let Poll::Ready(Some(msg)) = stream.poll_next_unpin(&mut ctx) {
+        let body = msg.body();
+        let (name, old_owner, new_owner): (String, &str, &str) = body.deserialize()?;
+
+        if name.starts_with(MPRIS_PREFIX) {
+            match (old_owner.is_empty(), new_owner.is_empty()) {
+                (true, false) => {
+                    return Ok(Poll::Ready(NameOwnerChanged::NewPlayer(name)));
+                }
+                // removed player
+                (false, true) => {
+                    for n_names in names.iter() {
+                        if n_names == &name {
+                            return Ok(Poll::Ready(NameOwnerChanged::RemovedPlayer(name)));
+                        }
+                    }
+                }
+                // the name went straight to another process
+                (false, false) if names.contains(&name.as_str()) => {
+                    return Ok(Poll::Ready(NameOwnerChanged::ReplacedPlayer(name)));
                 }
+                _ => {}
             }
         }
     }
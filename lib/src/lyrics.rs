@@ -0,0 +1,458 @@
+//! synced lyrics from LRCLIB, following along with the position
+//!
+//! lyrics are looked up by artist, title, album and length. answers, including "no lyrics", are
+//! kept in a cache directory so a track is only looked up once. [`LyricsFollower`] watches the
+//! players of a client and hands out a [`LyricLine`] whenever the line at the interpolated
+//! position changes.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use crate::{
+    fnv1a,
+    player::{Metadata, MprisEvent, PlaybackStatus},
+    MprisClient,
+};
+
+pub const LRCLIB_URL: &str = "https://lrclib.net";
+/// lrclib asks clients to identify themselves
+const USER_AGENT: &str = concat!(
+    "mpris-controller/",
+    env!("CARGO_PKG_VERSION"),
+    " (https://github.com/slothywasnottaken/mpris-controller)"
+);
+/// how far the length of a search result may be off, in seconds
+const LENGTH_TOLERANCE: f64 = 2.0;
+
+/// one line of synced lyrics
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedLine {
+    /// microseconds into the track
+    pub start: u64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Lyrics {
+    /// sorted by start, empty when only plain lyrics are known
+    pub synced: Vec<TimedLine>,
+    pub plain: Option<String>,
+    pub instrumental: bool,
+}
+
+impl Lyrics {
+    /// the index of the line being sung at `position`, `None` before the first one
+    pub fn line_at(&self, position: u64) -> Option<usize> {
+        self.synced
+            .partition_point(|line| line.start <= position)
+            .checked_sub(1)
+    }
+
+    /// when the line after the one at `position` starts
+    pub fn next_start(&self, position: u64) -> Option<u64> {
+        let next = self.synced.partition_point(|line| line.start <= position);
+        self.synced.get(next).map(|line| line.start)
+    }
+}
+
+/// parses LRC, lines can have several timestamps and `[offset:]` is applied. other tags and
+/// word timings of enhanced LRC are dropped.
+pub fn parse_lrc(lrc: &str) -> Vec<TimedLine> {
+    let mut offset: i64 = 0;
+    let mut lines = Vec::new();
+    for line in lrc.lines() {
+        let mut rest = line.trim();
+        let mut starts = Vec::new();
+        while let Some(tag) = rest.strip_prefix('[') {
+            let Some((tag, after)) = tag.split_once(']') else {
+                break;
+            };
+            rest = after;
+            match timestamp(tag) {
+                Some(start) => starts.push(start),
+                None => {
+                    if let Some(ms) = tag.strip_prefix("offset:") {
+                        offset = ms.trim().parse().unwrap_or(0);
+                    }
+                }
+            }
+        }
+
+        let text = strip_word_timings(rest.trim());
+        for start in starts {
+            // a positive offset shows the lyrics sooner
+            let start = start - offset * 1000;
+            lines.push(TimedLine {
+                start: start.max(0) as u64,
+                text: text.clone(),
+            });
+        }
+    }
+
+    lines.sort_by_key(|line| line.start);
+    lines
+}
+
+/// `mm:ss`, `mm:ss.xx` or `mm:ss:xx` in microseconds
+fn timestamp(tag: &str) -> Option<i64> {
+    let (minutes, seconds) = tag.split_once(':')?;
+    let minutes: i64 = minutes.parse().ok()?;
+    let seconds: f64 = match seconds.split_once(':') {
+        Some((seconds, fraction)) => format!("{seconds}.{fraction}").parse().ok()?,
+        None => seconds.parse().ok()?,
+    };
+    if seconds < 0.0 {
+        return None;
+    }
+
+    Some(minutes * 60_000_000 + (seconds * 1_000_000.0).round() as i64)
+}
+
+fn strip_word_timings(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(open) = rest.find('<') {
+        let inner = &rest[open + 1..];
+        match inner.split_once('>') {
+            Some((tag, after)) if timestamp(tag).is_some() => {
+                out.push_str(&rest[..open]);
+                rest = after;
+            }
+            _ => {
+                out.push_str(&rest[..=open]);
+                rest = inner;
+            }
+        }
+    }
+    out.push_str(rest);
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// what lrclib answers with, also what is cached
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Record {
+    #[serde(default)]
+    instrumental: bool,
+    plain_lyrics: Option<String>,
+    synced_lyrics: Option<String>,
+}
+
+impl From<Record> for Lyrics {
+    fn from(record: Record) -> Self {
+        Self {
+            synced: record
+                .synced_lyrics
+                .as_deref()
+                .map(parse_lrc)
+                .unwrap_or_default(),
+            plain: record.plain_lyrics.filter(|plain| !plain.is_empty()),
+            instrumental: record.instrumental,
+        }
+    }
+}
+
+struct Query<'a> {
+    artist: &'a str,
+    title: &'a str,
+    album: Option<&'a str>,
+    /// seconds
+    length: Option<u64>,
+}
+
+impl<'a> Query<'a> {
+    fn new(metadata: &'a Metadata) -> Option<Self> {
+        Some(Self {
+            artist: metadata
+                .artists()?
+                .first()
+                .map(String::as_str)
+                .filter(|a| !a.is_empty())?,
+            title: metadata.title().filter(|t| !t.is_empty())?,
+            album: metadata.album().filter(|a| !a.is_empty()),
+            length: metadata.length().map(|length| length / 1_000_000),
+        })
+    }
+
+    fn key(&self) -> u64 {
+        let key = format!(
+            "{}\0{}\0{}\0{}",
+            self.artist,
+            self.title,
+            self.album.unwrap_or_default(),
+            self.length.unwrap_or_default()
+        );
+        fnv1a(key.as_bytes())
+    }
+}
+
+/// looks up lyrics on LRCLIB, with answers cached on disk
+#[derive(Debug, Clone)]
+pub struct Lrclib {
+    http: reqwest::Client,
+    url: String,
+    dir: PathBuf,
+}
+
+impl Lrclib {
+    pub fn new(dir: PathBuf) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self {
+            http: reqwest::Client::builder()
+                .user_agent(USER_AGENT)
+                .build()
+                .unwrap_or_default(),
+            url: LRCLIB_URL.to_string(),
+            dir,
+        })
+    }
+
+    /// another lrclib instance
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into();
+        self
+    }
+
+    /// `$XDG_CACHE_HOME/mpris-controller/lyrics`, falling back to `~/.cache`
+    pub fn default_dir() -> anyhow::Result<PathBuf> {
+        let cache = match std::env::var_os("XDG_CACHE_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => std::env::home_dir()
+                .ok_or(anyhow!("can not find home directory"))?
+                .join(".cache"),
+        };
+
+        Ok(cache.join("mpris-controller").join("lyrics"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// where the answer for `metadata` is cached, `None` without an artist and title
+    pub fn cache_path(&self, metadata: &Metadata) -> Option<PathBuf> {
+        let query = Query::new(metadata)?;
+        Some(self.dir.join(format!("{:016x}.json", query.key())))
+    }
+
+    /// the lyrics of `metadata`, `None` when lrclib doesn't know the track
+    pub async fn lyrics(&self, metadata: &Metadata) -> anyhow::Result<Option<Lyrics>> {
+        let Some(query) = Query::new(metadata) else {
+            return Ok(None);
+        };
+        let path = self.dir.join(format!("{:016x}.json", query.key()));
+        if let Ok(bytes) = tokio::fs::read(&path).await {
+            let record: Option<Record> = serde_json::from_slice(&bytes)?;
+            return Ok(record.map(Lyrics::from));
+        }
+
+        let body = match self.get(&query).await? {
+            Some(body) => body,
+            None => self.search(&query).await?,
+        };
+        // "nothing found" is cached as `null` too
+        tokio::fs::write(&path, &body).await?;
+        let record: Option<Record> = serde_json::from_slice(&body)?;
+        Ok(record.map(Lyrics::from))
+    }
+
+    /// the exact match, `None` on 404
+    async fn get(&self, query: &Query<'_>) -> anyhow::Result<Option<Vec<u8>>> {
+        let mut params = vec![("artist_name", query.artist), ("track_name", query.title)];
+        if let Some(album) = query.album {
+            params.push(("album_name", album));
+        }
+        let length = query.length.map(|length| length.to_string());
+        if let Some(length) = &length {
+            params.push(("duration", length));
+        }
+
+        debug!(
+            artist = query.artist,
+            title = query.title,
+            "looking up lyrics"
+        );
+        let response = self
+            .http
+            .get(format!("{}/api/get", self.url))
+            .query(&params)
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(response.error_for_status()?.bytes().await?.to_vec()))
+    }
+
+    /// the best search result when the album or length didn't match exactly, as json
+    async fn search(&self, query: &Query<'_>) -> anyhow::Result<Vec<u8>> {
+        let response = self
+            .http
+            .get(format!("{}/api/search", self.url))
+            .query(&[("artist_name", query.artist), ("track_name", query.title)])
+            .send()
+            .await?;
+        let results: Vec<serde_json::Value> =
+            serde_json::from_slice(&response.error_for_status()?.bytes().await?)?;
+
+        let close = |result: &serde_json::Value| match (query.length, result["duration"].as_f64()) {
+            (Some(length), Some(duration)) => (length as f64 - duration).abs() <= LENGTH_TOLERANCE,
+            _ => true,
+        };
+        let best = results
+            .iter()
+            .filter(|result| close(result))
+            .max_by_key(|result| result["syncedLyrics"].is_string())
+            .unwrap_or(&serde_json::Value::Null);
+
+        Ok(serde_json::to_vec(best)?)
+    }
+}
+
+/// the line being sung changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct LyricLine {
+    pub player: String,
+    /// into [`Lyrics::synced`]
+    pub index: usize,
+    /// microseconds into the track
+    pub start: u64,
+    pub text: String,
+}
+
+// the key of what the lyrics were looked up by, `None` for tracks that can't be looked up
+type Track = Option<u64>;
+
+#[derive(Debug, Default)]
+struct Following {
+    track: Track,
+    lyrics: Option<Arc<Lyrics>>,
+    line: Option<usize>,
+}
+
+/// follows the players of a client and hands out a [`LyricLine`] whenever the current line
+/// changes, lyrics are fetched in the background as tracks change
+#[derive(Debug)]
+pub struct LyricsFollower {
+    lrclib: Lrclib,
+    players: HashMap<String, Following>,
+    fetched: mpsc::UnboundedReceiver<(String, Track, Option<Lyrics>)>,
+    fetch: mpsc::UnboundedSender<(String, Track, Option<Lyrics>)>,
+}
+
+impl LyricsFollower {
+    pub fn new(lrclib: Lrclib) -> Self {
+        let (fetch, fetched) = mpsc::unbounded_channel();
+        Self {
+            lrclib,
+            players: HashMap::new(),
+            fetched,
+            fetch,
+        }
+    }
+
+    /// the lyrics of what `player` is playing, once they were fetched
+    pub fn lyrics(&self, player: &str) -> Option<&Lyrics> {
+        self.players.get(player)?.lyrics.as_deref()
+    }
+
+    /// looks at every player of `client` after [`MprisClient::event`] returned `events`
+    pub fn handle_events(&mut self, client: &MprisClient, events: &[MprisEvent]) -> Vec<LyricLine> {
+        for event in events {
+            if let MprisEvent::PlayerRemoved(name) = event {
+                self.players.remove(name);
+            }
+        }
+        while let Ok((player, track, lyrics)) = self.fetched.try_recv() {
+            if let Some(following) = self.players.get_mut(&player) {
+                if following.track == track {
+                    following.lyrics = lyrics.map(Arc::new);
+                }
+            }
+        }
+
+        let now = Instant::now();
+        let mut lines = Vec::new();
+        for player in client.players() {
+            let metadata = &player.capabilities().metadata;
+            let track = Query::new(metadata).map(|query| query.key());
+            let following = self.players.entry(player.name().to_string()).or_default();
+            if following.track != track {
+                *following = Following {
+                    track,
+                    ..Default::default()
+                };
+                self.fetch(player.name(), track, metadata);
+                continue;
+            }
+
+            let Some(lyrics) = &following.lyrics else {
+                continue;
+            };
+            let line = lyrics.line_at(player.estimated_position_at(now));
+            if line == following.line {
+                continue;
+            }
+            following.line = line;
+            if let Some(index) = line {
+                let TimedLine { start, text } = lyrics.synced[index].clone();
+                lines.push(LyricLine {
+                    player: player.name().to_string(),
+                    index,
+                    start,
+                    text,
+                });
+            }
+        }
+
+        lines
+    }
+
+    /// how long until the next line of any playing player, to wake up in time for it
+    pub fn until_next(&self, client: &MprisClient) -> Option<Duration> {
+        let now = Instant::now();
+        client
+            .players()
+            .iter()
+            .filter(|player| player.capabilities().playback_status == PlaybackStatus::Playing)
+            .filter_map(|player| {
+                let lyrics = self.players.get(player.name())?.lyrics.as_ref()?;
+                let position = player.estimated_position_at(now);
+                let next = lyrics.next_start(position)?;
+                let rate = player.capabilities().rate;
+                let micros = (next - position) as f64 / if rate > 0.0 { rate } else { 1.0 };
+                Some(Duration::from_micros(micros as u64))
+            })
+            .min()
+    }
+
+    fn fetch(&self, player: &str, track: Track, metadata: &Metadata) {
+        if track.is_none() {
+            return;
+        }
+        let lrclib = self.lrclib.clone();
+        let fetched = self.fetch.clone();
+        let player = player.to_string();
+        let metadata = metadata.clone();
+        tokio::spawn(async move {
+            let lyrics = match lrclib.lyrics(&metadata).await {
+                Ok(lyrics) => lyrics,
+                Err(e) => {
+                    warn!(player, "failed to fetch lyrics: {e:?}");
+                    None
+                }
+            };
+            _ = fetched.send((player, track, lyrics));
+        });
+    }
+}
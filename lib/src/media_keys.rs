@@ -0,0 +1,580 @@
+//! grabbing the media keys and sending them to the active player
+//!
+//! on wayland the keys are bound through the `org.freedesktop.portal.GlobalShortcuts` portal,
+//! the compositor may ask the user to confirm them the first time. desktops without the portal
+//! get them from the settings daemon's `MediaKeys` interface (gnome, mate), which grabs
+//! them on X11 and hands them to whichever application asked last. with neither of those, the
+//! keys are grabbed from the X server in `DISPLAY` directly, which fails if the window manager
+//! or another program already grabbed them.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    os::fd::{AsRawFd, RawFd},
+    str::FromStr,
+};
+
+use anyhow::{bail, Context};
+use futures::StreamExt;
+use tokio::io::unix::AsyncFd;
+use tracing::{debug, info};
+use x11rb::{
+    connection::Connection as _,
+    protocol::{
+        xproto::{ConnectionExt as _, GrabMode, Keycode, ModMask, Window},
+        Event,
+    },
+    rust_connection::RustConnection,
+};
+use zbus::{
+    message::Type,
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value},
+    Connection, MatchRule, MessageStream,
+};
+
+use crate::{
+    call::{call_method, CallPolicy},
+    fnv1a, MprisClient, MPRIS_PATH, MPRIS_PLAYER_PREFIX,
+};
+
+const PORTAL_NAME: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const PORTAL_SHORTCUTS: &str = "org.freedesktop.portal.GlobalShortcuts";
+const PORTAL_REQUEST: &str = "org.freedesktop.portal.Request";
+const HOST_REGISTRY: &str = "org.freedesktop.host.portal.Registry";
+
+/// the settings daemons with a `MediaKeys` interface as name, path and interface, newest first
+const SETTINGS_DAEMONS: [(&str, &str, &str); 3] = [
+    (
+        "org.gnome.SettingsDaemon.MediaKeys",
+        "/org/gnome/SettingsDaemon/MediaKeys",
+        "org.gnome.SettingsDaemon.MediaKeys",
+    ),
+    (
+        "org.gnome.SettingsDaemon",
+        "/org/gnome/SettingsDaemon/MediaKeys",
+        "org.gnome.SettingsDaemon.MediaKeys",
+    ),
+    (
+        "org.mate.SettingsDaemon",
+        "/org/mate/SettingsDaemon/MediaKeys",
+        "org.mate.SettingsDaemon.MediaKeys",
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MediaKey {
+    /// `XF86AudioPlay`, which toggles since most keyboards have no pause key
+    Play,
+    Pause,
+    Stop,
+    Next,
+    Previous,
+}
+
+impl MediaKey {
+    pub const ALL: [Self; 5] = [
+        Self::Play,
+        Self::Pause,
+        Self::Stop,
+        Self::Next,
+        Self::Previous,
+    ];
+
+    /// the id of the portal shortcut
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Play => "play",
+            Self::Pause => "pause",
+            Self::Stop => "stop",
+            Self::Next => "next",
+            Self::Previous => "previous",
+        }
+    }
+
+    pub fn keysym(self) -> &'static str {
+        match self {
+            Self::Play => "XF86AudioPlay",
+            Self::Pause => "XF86AudioPause",
+            Self::Stop => "XF86AudioStop",
+            Self::Next => "XF86AudioNext",
+            Self::Previous => "XF86AudioPrev",
+        }
+    }
+
+    /// the value of [`MediaKey::keysym`], from `XF86keysym.h`
+    fn keysym_value(self) -> u32 {
+        match self {
+            Self::Play => 0x1008_ff14,
+            Self::Pause => 0x1008_ff31,
+            Self::Stop => 0x1008_ff15,
+            Self::Next => 0x1008_ff17,
+            Self::Previous => 0x1008_ff16,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::Play => "Play or pause",
+            Self::Pause => "Pause",
+            Self::Stop => "Stop",
+            Self::Next => "Next track",
+            Self::Previous => "Previous track",
+        }
+    }
+
+    /// the method of `org.mpris.MediaPlayer2.Player` the key calls
+    pub fn method(self) -> &'static str {
+        match self {
+            Self::Play => "PlayPause",
+            Self::Pause => "Pause",
+            Self::Stop => "Stop",
+            Self::Next => "Next",
+            Self::Previous => "Previous",
+        }
+    }
+
+    /// the key in `MediaPlayerKeyPressed`, `None` for the ones no player action exists for
+    fn from_settings_daemon(key: &str) -> Option<Self> {
+        Some(match key {
+            "Play" => Self::Play,
+            "Pause" => Self::Pause,
+            "Stop" => Self::Stop,
+            "Next" => Self::Next,
+            "Previous" => Self::Previous,
+            _ => return None,
+        })
+    }
+
+    /// sends the key to the active player of `client`
+    pub async fn press(self, client: &MprisClient) -> anyhow::Result<()> {
+        let Some(player) = client.active_player() else {
+            bail!("no active player for {self}");
+        };
+        let conn = client.connection().context("not connected to a bus")?;
+        debug!(player = player.name(), key = %self, "media key");
+        call_method(
+            conn,
+            player.call_policy(),
+            player.name(),
+            MPRIS_PATH,
+            MPRIS_PLAYER_PREFIX,
+            self.method(),
+            &(),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for MediaKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for MediaKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|key| key.as_str() == s)
+            .ok_or_else(|| anyhow::anyhow!("unknown media key {s}"))
+    }
+}
+
+/// where the keys come from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Backend {
+    /// the global shortcuts portal, with the session the shortcuts belong to
+    Portal(OwnedObjectPath),
+    /// a settings daemon's `MediaKeys`, by bus name
+    SettingsDaemon(String),
+    /// grabbed from the X server directly
+    X11,
+}
+
+pub struct MediaKeys {
+    connection: Connection,
+    app_id: String,
+    backend: Backend,
+    source: Source,
+}
+
+/// what the keys are read from, there's only ever one
+#[allow(clippy::large_enum_variant)]
+enum Source {
+    /// signals from the portal or the settings daemon
+    Bus(MessageStream),
+    X11(X11Keys),
+}
+
+impl MediaKeys {
+    /// grabs the media keys as `app_id`, through the portal if there is one, the settings
+    /// daemon otherwise and the X server as the last resort
+    pub async fn grab(connection: &Connection, app_id: &str) -> anyhow::Result<Self> {
+        match Self::grab_portal(connection, app_id).await {
+            Ok(keys) => return Ok(keys),
+            // anything but a missing portal means it's there and said no
+            Err(e) if !is_missing(&e) => return Err(e),
+            Err(e) => debug!("no global shortcuts portal: {e:#}"),
+        }
+
+        let mut missing = None;
+        for daemon in SETTINGS_DAEMONS {
+            match Self::grab_settings_daemon(connection, app_id, daemon).await {
+                Ok(keys) => return Ok(keys),
+                Err(e) if is_missing(&e) => missing = Some(e),
+                Err(e) => return Err(e),
+            }
+        }
+        if std::env::var_os("DISPLAY").is_some() {
+            let keys = X11Keys::grab().context("failed to grab the media keys from X11")?;
+            info!("grabbed the media keys from the X server");
+            return Ok(Self {
+                connection: connection.clone(),
+                app_id: app_id.to_string(),
+                backend: Backend::X11,
+                source: Source::X11(keys),
+            });
+        }
+        Err(missing
+            .unwrap_or_else(|| anyhow::anyhow!("nothing to grab from"))
+            .context(
+                "neither the global shortcuts portal nor a settings daemon is running, \
+                 and DISPLAY isn't set",
+            ))
+    }
+
+    pub fn backend(&self) -> &Backend {
+        &self.backend
+    }
+
+    async fn grab_portal(connection: &Connection, app_id: &str) -> anyhow::Result<Self> {
+        // hosts apps have to say who they are, older portals don't know the registry
+        if let Err(e) = call_method(
+            connection,
+            &CallPolicy::DEFAULT,
+            PORTAL_NAME,
+            PORTAL_PATH,
+            HOST_REGISTRY,
+            "Register",
+            &(app_id, HashMap::<&str, Value>::new()),
+        )
+        .await
+        {
+            debug!("failed to register with the portal: {e}");
+        }
+
+        let token = format!("mpris_controller_{:x}", fnv1a(app_id.as_bytes()));
+        let mut options = HashMap::new();
+        options.insert("handle_token", Value::from(token.as_str()));
+        options.insert("session_handle_token", Value::from(token.as_str()));
+        let results = request(connection, &token, "CreateSession", &(options,)).await?;
+        // a string in older versions of the spec, an object path in newer ones
+        let session = match results.get("session_handle").map(|value| &**value) {
+            Some(Value::Str(path)) => OwnedObjectPath::try_from(path.as_str())?,
+            Some(Value::ObjectPath(path)) => path.clone().into(),
+            _ => bail!("the portal didn't return a session"),
+        };
+
+        // subscribed before binding, the user might press a key right after confirming
+        let rule = MatchRule::builder()
+            .msg_type(Type::Signal)
+            .interface(PORTAL_SHORTCUTS)?
+            .member("Activated")?
+            .path(PORTAL_PATH)?
+            .build();
+        let stream = MessageStream::for_match_rule(rule, connection, None).await?;
+
+        let shortcuts: Vec<(&str, HashMap<&str, Value>)> = MediaKey::ALL
+            .into_iter()
+            .map(|key| {
+                let mut shortcut = HashMap::new();
+                shortcut.insert("description", Value::from(key.description()));
+                shortcut.insert("preferred_trigger", Value::from(key.keysym()));
+                (key.as_str(), shortcut)
+            })
+            .collect();
+        let token = format!("{token}_bind");
+        let mut options = HashMap::new();
+        options.insert("handle_token", Value::from(token.as_str()));
+        request(
+            connection,
+            &token,
+            "BindShortcuts",
+            &(&session, shortcuts, "", options),
+        )
+        .await?;
+
+        info!(session = %session, "bound the media keys through the portal");
+        Ok(Self {
+            connection: connection.clone(),
+            app_id: app_id.to_string(),
+            backend: Backend::Portal(session),
+            source: Source::Bus(stream),
+        })
+    }
+
+    async fn grab_settings_daemon(
+        connection: &Connection,
+        app_id: &str,
+        (name, path, interface): (&str, &str, &str),
+    ) -> anyhow::Result<Self> {
+        let rule = MatchRule::builder()
+            .msg_type(Type::Signal)
+            .sender(name)?
+            .interface(interface)?
+            .member("MediaPlayerKeyPressed")?
+            .path(path)?
+            .build();
+        let stream = MessageStream::for_match_rule(rule, connection, None).await?;
+        call_method(
+            connection,
+            &CallPolicy::DEFAULT,
+            name,
+            path,
+            interface,
+            "GrabMediaPlayerKeys",
+            &(app_id, 0u32),
+        )
+        .await?;
+
+        info!(name, "grabbed the media keys from the settings daemon");
+        Ok(Self {
+            connection: connection.clone(),
+            app_id: app_id.to_string(),
+            backend: Backend::SettingsDaemon(name.to_string()),
+            source: Source::Bus(stream),
+        })
+    }
+
+    /// waits for the next media key, `None` once the connection the keys come from is gone
+    pub async fn next(&mut self) -> Option<MediaKey> {
+        let stream = match &mut self.source {
+            Source::Bus(stream) => stream,
+            Source::X11(keys) => return keys.next().await,
+        };
+        while let Some(message) = stream.next().await {
+            let Ok(message) = message else {
+                continue;
+            };
+            let body = message.body();
+            let key = match &self.backend {
+                Backend::Portal(session) => {
+                    let Ok((from, id, _, _)) =
+                        body.deserialize::<(ObjectPath, &str, u64, HashMap<&str, Value>)>()
+                    else {
+                        continue;
+                    };
+                    if from != session.as_ref() {
+                        continue;
+                    }
+                    id.parse().ok()
+                }
+                Backend::SettingsDaemon(_) => {
+                    let Ok((app, key)) = body.deserialize::<(&str, &str)>() else {
+                        continue;
+                    };
+                    // the daemon sends every key to every application that grabbed them
+                    if app != self.app_id {
+                        continue;
+                    }
+                    MediaKey::from_settings_daemon(key)
+                }
+                Backend::X11 => None,
+            };
+            if let Some(key) = key {
+                return Some(key);
+            }
+        }
+
+        None
+    }
+
+    /// hands the keys back, the portal session is closed, the settings daemon told and the X11
+    /// grabs undone
+    pub async fn release(self) -> anyhow::Result<()> {
+        let stream = match self.source {
+            Source::Bus(stream) => stream,
+            Source::X11(keys) => return keys.release(),
+        };
+        match &self.backend {
+            Backend::Portal(session) => {
+                call_method(
+                    &self.connection,
+                    &CallPolicy::DEFAULT,
+                    PORTAL_NAME,
+                    session.as_ref(),
+                    "org.freedesktop.portal.Session",
+                    "Close",
+                    &(),
+                )
+                .await?;
+            }
+            Backend::SettingsDaemon(name) => {
+                let (_, path, interface) = SETTINGS_DAEMONS
+                    .into_iter()
+                    .find(|(daemon, _, _)| daemon == name)
+                    .unwrap_or(SETTINGS_DAEMONS[0]);
+                call_method(
+                    &self.connection,
+                    &CallPolicy::DEFAULT,
+                    name.as_str(),
+                    path,
+                    interface,
+                    "ReleaseMediaPlayerKeys",
+                    &(self.app_id.as_str(),),
+                )
+                .await?;
+            }
+            Backend::X11 => {}
+        }
+        zbus::AsyncDrop::async_drop(stream).await;
+        Ok(())
+    }
+}
+
+/// the media keys grabbed on the root window of an X server
+struct X11Keys {
+    // dropped before the connection that owns the fd
+    fd: AsyncFd<RawFd>,
+    conn: RustConnection,
+    root: Window,
+    /// every keycode that produces one of the keys, a keyboard can have several
+    keys: Vec<(Keycode, MediaKey)>,
+}
+
+impl X11Keys {
+    fn grab() -> anyhow::Result<Self> {
+        let (conn, screen) = x11rb::connect(None)?;
+        let setup = conn.setup();
+        let root = setup.roots[screen].root;
+        let (min, max) = (setup.min_keycode, setup.max_keycode);
+
+        let mapping = conn.get_keyboard_mapping(min, max - min + 1)?.reply()?;
+        let per_keycode = usize::from(mapping.keysyms_per_keycode).max(1);
+        let mut keys = Vec::new();
+        for (keycode, keysyms) in (min..=max).zip(mapping.keysyms.chunks(per_keycode)) {
+            if let Some(key) = MediaKey::ALL
+                .into_iter()
+                .find(|key| keysyms.contains(&key.keysym_value()))
+            {
+                keys.push((keycode, key));
+            }
+        }
+        if keys.is_empty() {
+            bail!("the keyboard has no media keys");
+        }
+
+        for (keycode, key) in &keys {
+            // whatever modifiers are held, like num lock
+            conn.grab_key(
+                true,
+                root,
+                ModMask::ANY,
+                *keycode,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+            )?
+            .check()
+            .with_context(|| format!("{} is grabbed by another program", key.keysym()))?;
+        }
+
+        let fd = AsyncFd::new(conn.stream().as_raw_fd())?;
+        Ok(Self {
+            fd,
+            conn,
+            root,
+            keys,
+        })
+    }
+
+    async fn next(&mut self) -> Option<MediaKey> {
+        loop {
+            // replies read the events that came before them into a buffer, the fd won't wake
+            // for those
+            while let Some(event) = self.conn.poll_for_event().ok()? {
+                let Event::KeyPress(press) = event else {
+                    continue;
+                };
+                if let Some((_, key)) = self.keys.iter().find(|(code, _)| *code == press.detail) {
+                    return Some(*key);
+                }
+            }
+            self.fd.readable().await.ok()?.clear_ready();
+        }
+    }
+
+    fn release(self) -> anyhow::Result<()> {
+        for (keycode, _) in &self.keys {
+            self.conn.ungrab_key(*keycode, self.root, ModMask::ANY)?;
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+}
+
+/// calls a portal method that answers through a `Request` object, and waits for the answer
+async fn request<B>(
+    connection: &Connection,
+    token: &str,
+    method: &str,
+    body: &B,
+) -> anyhow::Result<HashMap<String, OwnedValue>>
+where
+    B: serde::Serialize + zbus::zvariant::DynamicType,
+{
+    // the path is known before the call, listening first can't miss the response
+    let sender = connection
+        .unique_name()
+        .context("the connection has no name")?
+        .trim_start_matches(':')
+        .replace('.', "_");
+    let path = format!("{PORTAL_PATH}/request/{sender}/{token}");
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .interface(PORTAL_REQUEST)?
+        .member("Response")?
+        .path(path.as_str())?
+        .build();
+    let mut responses = MessageStream::for_match_rule(rule, connection, None).await?;
+
+    call_method(
+        connection,
+        &CallPolicy::DEFAULT,
+        PORTAL_NAME,
+        PORTAL_PATH,
+        PORTAL_SHORTCUTS,
+        method,
+        body,
+    )
+    .await?;
+
+    let message = responses
+        .next()
+        .await
+        .context("the bus connection closed")??;
+    let (response, results): (u32, HashMap<String, OwnedValue>) = message.body().deserialize()?;
+    match response {
+        0 => Ok(results),
+        1 => bail!("{method} was cancelled"),
+        _ => bail!("{method} failed"),
+    }
+}
+
+/// whether `e` says the service or interface doesn't exist, as opposed to it refusing
+fn is_missing(e: &anyhow::Error) -> bool {
+    let Some(zbus::Error::MethodError(name, _, _)) = e.downcast_ref::<zbus::Error>() else {
+        return false;
+    };
+    matches!(
+        name.as_str(),
+        "org.freedesktop.DBus.Error.ServiceUnknown"
+            | "org.freedesktop.DBus.Error.UnknownMethod"
+            | "org.freedesktop.DBus.Error.UnknownInterface"
+            | "org.freedesktop.DBus.Error.UnknownObject"
+            | "org.freedesktop.DBus.Error.NameHasNoOwner"
+    )
+}
@@ -0,0 +1,74 @@
+//! matching uris against the `SupportedUriSchemes` and `SupportedMimeTypes` players advertise
+
+// the extensions players are likely to be handed, anything else is left to the scheme
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("mp3", "audio/mpeg"),
+    ("flac", "audio/flac"),
+    ("ogg", "audio/ogg"),
+    ("oga", "audio/ogg"),
+    ("opus", "audio/ogg"),
+    ("m4a", "audio/mp4"),
+    ("aac", "audio/aac"),
+    ("wav", "audio/x-wav"),
+    ("wma", "audio/x-ms-wma"),
+    ("m3u", "audio/x-mpegurl"),
+    ("m3u8", "application/vnd.apple.mpegurl"),
+    ("pls", "audio/x-scpls"),
+    ("mp4", "video/mp4"),
+    ("m4v", "video/mp4"),
+    ("mkv", "video/x-matroska"),
+    ("webm", "video/webm"),
+    ("avi", "video/x-msvideo"),
+    ("mov", "video/quicktime"),
+];
+
+/// the scheme of `uri`, bare paths count as `file`
+pub fn scheme(uri: &str) -> &str {
+    match uri.split_once("://") {
+        Some((scheme, _)) => scheme,
+        None if uri.starts_with('/') => "file",
+        // `spotify:track:...`
+        None => uri.split_once(':').map(|(s, _)| s).unwrap_or("file"),
+    }
+}
+
+/// guesses the mime type from the extension of `uri`
+pub fn guess(uri: &str) -> Option<&'static str> {
+    let path = uri.split(['?', '#']).next().unwrap_or(uri);
+    let file = path.rsplit('/').next().unwrap_or(path);
+    let (_, ext) = file.rsplit_once('.')?;
+    let ext = ext.to_lowercase();
+
+    EXTENSIONS
+        .iter()
+        .find(|(e, _)| *e == ext)
+        .map(|(_, mime)| *mime)
+}
+
+/// how well a player advertising `schemes` and `mime_types` fits `uri`, `None` if it can't
+/// open it at all
+///
+/// an exact mime type beats one of the same kind (`audio/*`), which beats only knowing the scheme
+/// is supported. players that advertise nothing are assumed to take anything, but score lowest.
+pub fn score(uri: &str, schemes: &[String], mime_types: &[String]) -> Option<u32> {
+    let scheme = scheme(uri);
+    if !schemes.is_empty() && !schemes.iter().any(|s| s.eq_ignore_ascii_case(scheme)) {
+        return None;
+    }
+
+    let Some(mime) = guess(uri) else {
+        return Some(if schemes.is_empty() { 0 } else { 1 });
+    };
+    if mime_types.is_empty() {
+        return Some(if schemes.is_empty() { 0 } else { 1 });
+    }
+
+    let kind = mime.split('/').next().unwrap_or(mime);
+    if mime_types.iter().any(|m| m.eq_ignore_ascii_case(mime)) {
+        Some(3)
+    } else if mime_types.iter().any(|m| m.split('/').next() == Some(kind)) {
+        Some(2)
+    } else {
+        None
+    }
+}
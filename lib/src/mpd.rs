@@ -0,0 +1,545 @@
+//! serves an [MPD](https://www.musicpd.org) server as an MPRIS player
+//!
+//! MPD has its own line based protocol. the bridge keeps two connections to it: one waits in
+//! `idle` until something changes, the other runs the commands clients send and reads the status
+//! back into the [`MprisService`].
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use lib::mpd::{default_address, MpdBridge};
+//!
+//! MpdBridge::start(&default_address()).await?.run().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufStream},
+    net::{TcpStream, UnixStream},
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+};
+use tracing::{debug, warn};
+use zbus::connection;
+
+use crate::{
+    player::{
+        Capabilities, LoopStatus, Metadata, MetadataBuilder, PlaybackStatus, RootProperties,
+        TrackId,
+    },
+    service::{MprisService, PlayerHandler},
+};
+
+/// what comes after `org.mpris.MediaPlayer2.` in the bridge's bus name
+pub const BRIDGE_NAME: &str = "mpd";
+pub const DEFAULT_ADDRESS: &str = "localhost:6600";
+/// trackids are this followed by MPD's song id
+pub const TRACK_PREFIX: &str = "/org/musicpd/song/";
+
+/// the subsystems that change what clients see
+const IDLE: &str = "idle player mixer options playlist";
+/// how often the position is re-read while playing, MPD doesn't say when it moves
+const POSITION_INTERVAL: Duration = Duration::from_secs(1);
+/// differences from the expected position smaller than this are drift, not a seek
+const SEEK_THRESHOLD: u64 = 1_500_000;
+
+/// where MPD listens, from `MPD_HOST` and `MPD_PORT` like `mpc` does
+pub fn default_address() -> String {
+    let host = std::env::var("MPD_HOST").ok();
+    let port = std::env::var("MPD_PORT").ok();
+    match (host, port) {
+        (Some(host), _) if host.contains('/') => host,
+        (Some(host), port) => format!("{host}:{}", port.as_deref().unwrap_or("6600")),
+        (None, Some(port)) => format!("localhost:{port}"),
+        (None, None) => DEFAULT_ADDRESS.to_string(),
+    }
+}
+
+trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+/// one connection to MPD, opened again when MPD closes it
+pub struct Mpd {
+    stream: BufStream<Box<dyn Stream>>,
+    version: String,
+    address: String,
+}
+
+impl Mpd {
+    /// `address` is `host:port` or the path of a socket, either can start with `password@`
+    pub async fn connect(address: &str) -> anyhow::Result<Self> {
+        let full = address;
+        let (password, address) = match address.rsplit_once('@') {
+            Some((password, address)) => (Some(password), address),
+            None => (None, address),
+        };
+        let stream: Box<dyn Stream> = if address.starts_with('/') {
+            Box::new(UnixStream::connect(address).await?)
+        } else {
+            Box::new(TcpStream::connect(address).await?)
+        };
+
+        let mut stream = BufStream::new(stream);
+        let mut greeting = String::new();
+        stream.read_line(&mut greeting).await?;
+        let Some(version) = greeting.trim_end().strip_prefix("OK MPD ") else {
+            bail!("{address} is not MPD, it said {greeting:?}");
+        };
+        let mut mpd = Self {
+            version: version.to_string(),
+            stream,
+            address: full.to_string(),
+        };
+
+        if let Some(password) = password {
+            let command = format!("password {}", quote(password));
+            if mpd.exchange(&command).await?.is_none() {
+                bail!("MPD closed the connection");
+            }
+        }
+        Ok(mpd)
+    }
+
+    /// the protocol version MPD greeted with
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// runs `command` and returns the `key: value` pairs of the response in order, keys can
+    /// repeat
+    ///
+    /// MPD closes connections that sit unused for its `connection_timeout`, a minute by default,
+    /// so a closed connection is opened again and `command` sent once more
+    pub async fn command(&mut self, command: &str) -> anyhow::Result<Vec<(String, String)>> {
+        if let Some(pairs) = self.exchange(command).await? {
+            return Ok(pairs);
+        }
+        debug!("MPD closed the connection, reconnecting");
+        *self = Self::connect(&self.address).await?;
+        self.exchange(command)
+            .await?
+            .ok_or_else(|| anyhow!("MPD closed the connection"))
+    }
+
+    /// sends `command` and reads the response, `None` if the connection was closed first
+    async fn exchange(&mut self, command: &str) -> anyhow::Result<Option<Vec<(String, String)>>> {
+        let sent = async {
+            self.stream.write_all(command.as_bytes()).await?;
+            self.stream.write_all(b"\n").await?;
+            self.stream.flush().await
+        };
+        match sent.await {
+            Ok(()) => {}
+            Err(e) if closed(&e) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+
+        let mut pairs = Vec::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match self.stream.read_line(&mut line).await {
+                Ok(0) => return Ok(None),
+                Ok(_) => {}
+                Err(e) if closed(&e) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            }
+            let line = line.trim_end_matches('\n');
+            if line == "OK" {
+                return Ok(Some(pairs));
+            }
+            if let Some(error) = line.strip_prefix("ACK ") {
+                bail!("MPD refused {command}: {error}");
+            }
+            match line.split_once(": ") {
+                Some((key, value)) => pairs.push((key.to_string(), value.to_string())),
+                None => warn!("unexpected line from MPD: {line:?}"),
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Mpd {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Mpd")
+            .field("version", &self.version)
+            .finish_non_exhaustive()
+    }
+}
+
+/// whether `e` means the other side hung up
+fn closed(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::BrokenPipe | std::io::ErrorKind::ConnectionReset
+    )
+}
+
+/// an argument to an MPD command
+fn quote(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn get<'a>(pairs: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    pairs
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+fn all(pairs: &[(String, String)], key: &str) -> Vec<String> {
+    pairs
+        .iter()
+        .filter(|(k, _)| k == key)
+        .map(|(_, v)| v.clone())
+        .collect()
+}
+
+/// seconds as MPD sends them to microseconds
+fn micros(pairs: &[(String, String)], key: &str) -> Option<u64> {
+    let seconds: f64 = get(pairs, key)?.parse().ok()?;
+    Some((seconds * 1_000_000.0) as u64)
+}
+
+/// the reply to `currentsong` as metadata
+pub fn metadata(song: &[(String, String)]) -> Metadata {
+    let mut builder = MetadataBuilder::default();
+    if let Some(id) = get(song, "Id") {
+        builder = builder.trackid(format!("{TRACK_PREFIX}{id}"));
+    }
+    let file = get(song, "file");
+    if let Some(title) = get(song, "Title").or(file) {
+        builder = builder.title(title.to_string());
+    }
+    if let Some(album) = get(song, "Album") {
+        builder = builder.album(album.to_string());
+    }
+    let artists = all(song, "Artist");
+    if !artists.is_empty() {
+        builder = builder.artists(artists);
+    }
+    let album_artists = all(song, "AlbumArtist");
+    if !album_artists.is_empty() {
+        builder = builder.album_artists(album_artists);
+    }
+    // `3/12` on some files
+    let number = |key| {
+        get(song, key)
+            .and_then(|n| n.split('/').next())
+            .and_then(|n| n.trim().parse().ok())
+    };
+    if let Some(track) = number("Track") {
+        builder = builder.track_number(track);
+    }
+    if let Some(disc) = number("Disc") {
+        builder = builder.disc_number(disc);
+    }
+    if let Some(length) = micros(song, "duration").or_else(|| micros(song, "Time")) {
+        builder = builder.length(length);
+    }
+    // local files are relative to a music directory only MPD knows
+    if let Some(file) = file.filter(|file| file.contains("://")) {
+        builder = builder.url(file.to_string());
+    }
+
+    builder.finish()
+}
+
+/// the replies to `status` and `currentsong` as what clients see
+pub fn capabilities(status: &[(String, String)], song: &[(String, String)]) -> Capabilities {
+    let flag = |key| get(status, key) == Some("1");
+    let has_tracks = get(status, "playlistlength").is_some_and(|n| n != "0");
+
+    Capabilities {
+        can_control: true,
+        can_next: has_tracks,
+        can_previous: has_tracks,
+        can_pause: has_tracks,
+        can_play: has_tracks,
+        can_seek: get(status, "duration").is_some(),
+        loop_status: Some(match (flag("repeat"), flag("single")) {
+            (true, true) => LoopStatus::Track,
+            (true, false) => LoopStatus::Playlist,
+            (false, _) => LoopStatus::None,
+        }),
+        max_rate: None,
+        min_rate: None,
+        metadata: metadata(song),
+        playback_status: match get(status, "state") {
+            Some("play") => PlaybackStatus::Playing,
+            Some("pause") => PlaybackStatus::Paused,
+            _ => PlaybackStatus::Stopped,
+        },
+        position: micros(status, "elapsed").unwrap_or(0),
+        rate: 1.0,
+        shuffle: Some(flag("random")),
+        // -1 without a mixer
+        volume: get(status, "volume")
+            .and_then(|v| v.parse::<i64>().ok())
+            .filter(|v| *v >= 0)
+            .map(|v| v as f64 / 100.0),
+    }
+}
+
+/// a call from a client, as MPD commands
+#[derive(Debug, Clone, PartialEq)]
+enum Request {
+    Command(String),
+    OpenUri(String),
+}
+
+// handlers can't wait for MPD, so the calls are queued for `MpdBridge::run`
+struct Forward(UnboundedSender<Request>);
+
+impl Forward {
+    fn send(&self, command: impl Into<String>) -> anyhow::Result<()> {
+        self.0
+            .send(Request::Command(command.into()))
+            .map_err(|_| anyhow!("the bridge stopped"))
+    }
+}
+
+impl PlayerHandler for Forward {
+    fn play(&mut self, _: &mut Capabilities) -> anyhow::Result<()> {
+        self.send("play")
+    }
+
+    fn pause(&mut self, _: &mut Capabilities) -> anyhow::Result<()> {
+        self.send("pause 1")
+    }
+
+    fn stop(&mut self, _: &mut Capabilities) -> anyhow::Result<()> {
+        self.send("stop")
+    }
+
+    fn next(&mut self, _: &mut Capabilities) -> anyhow::Result<()> {
+        self.send("next")
+    }
+
+    fn previous(&mut self, _: &mut Capabilities) -> anyhow::Result<()> {
+        self.send("previous")
+    }
+
+    fn seek(&mut self, _: &mut Capabilities, offset: i64) -> anyhow::Result<()> {
+        self.send(format!("seekcur {:+}", offset as f64 / 1_000_000.0))
+    }
+
+    fn set_position(
+        &mut self,
+        _: &mut Capabilities,
+        track: &TrackId,
+        position: u64,
+    ) -> anyhow::Result<()> {
+        // the spec says to ignore tracks that aren't current, MPD checks the id
+        let Some(id) = track.strip_prefix(TRACK_PREFIX) else {
+            return Ok(());
+        };
+        self.send(format!("seekid {id} {}", position as f64 / 1_000_000.0))
+    }
+
+    fn open_uri(&mut self, _: &mut Capabilities, uri: &str) -> anyhow::Result<()> {
+        self.0
+            .send(Request::OpenUri(uri.to_string()))
+            .map_err(|_| anyhow!("the bridge stopped"))
+    }
+
+    fn set_volume(&mut self, _: &mut Capabilities, volume: f64) -> anyhow::Result<()> {
+        self.send(format!(
+            "setvol {}",
+            (volume.clamp(0.0, 1.0) * 100.0).round()
+        ))
+    }
+
+    fn set_loop_status(&mut self, _: &mut Capabilities, status: LoopStatus) -> anyhow::Result<()> {
+        let (repeat, single) = match status {
+            LoopStatus::None => (0, 0),
+            LoopStatus::Playlist => (1, 0),
+            LoopStatus::Track => (1, 1),
+        };
+        self.send(format!("repeat {repeat}"))?;
+        self.send(format!("single {single}"))
+    }
+
+    fn set_shuffle(&mut self, _: &mut Capabilities, shuffle: bool) -> anyhow::Result<()> {
+        self.send(format!("random {}", u8::from(shuffle)))
+    }
+}
+
+/// the position and song at the last sync, to tell seeks from playback
+#[derive(Debug)]
+struct Synced {
+    song: Option<TrackId>,
+    position: u64,
+    at: Instant,
+    playing: bool,
+}
+
+impl Synced {
+    fn expected(&self) -> u64 {
+        if self.playing {
+            self.position + self.at.elapsed().as_micros() as u64
+        } else {
+            self.position
+        }
+    }
+}
+
+/// the half of the bridge that talks to the service
+#[derive(Debug)]
+struct Mirror {
+    service: MprisService,
+    mpd: Mpd,
+    synced: Option<Synced>,
+}
+
+impl Mirror {
+    /// reads the status and announces what changed
+    async fn sync(&mut self) -> anyhow::Result<()> {
+        let status = self.mpd.command("status").await?;
+        let song = self.mpd.command("currentsong").await?;
+        let capabilities = capabilities(&status, &song);
+
+        let song = capabilities.metadata.track_id().cloned();
+        let position = capabilities.position;
+        let seeked = self.synced.as_ref().is_some_and(|synced| {
+            synced.song == song && synced.expected().abs_diff(position) > SEEK_THRESHOLD
+        });
+        let playing = capabilities.playback_status == PlaybackStatus::Playing;
+
+        self.service.update(|state| *state = capabilities).await?;
+        if seeked {
+            self.service.seeked(position).await?;
+        }
+        self.synced = Some(Synced {
+            song,
+            position,
+            at: Instant::now(),
+            playing,
+        });
+        Ok(())
+    }
+
+    /// [`Mirror::sync`] for `run`, where one failed sync shouldn't stop the bridge
+    async fn sync_or_warn(&mut self) {
+        if let Err(e) = self.sync().await {
+            warn!("failed to read MPD's status: {e:#}");
+        }
+    }
+
+    fn playing(&self) -> bool {
+        self.synced.as_ref().is_some_and(|synced| synced.playing)
+    }
+
+    async fn request(&mut self, request: Request) -> anyhow::Result<()> {
+        debug!(?request, "sending to MPD");
+        match request {
+            Request::Command(command) => {
+                self.mpd.command(&command).await?;
+            }
+            Request::OpenUri(uri) => {
+                let added = self.mpd.command(&format!("addid {}", quote(&uri))).await?;
+                let id =
+                    get(&added, "Id").ok_or_else(|| anyhow!("MPD didn't say where {uri} went"))?;
+                self.mpd.command(&format!("playid {id}")).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// an MPD server served as `org.mpris.MediaPlayer2.mpd`, until [`MpdBridge::run`] returns
+#[derive(Debug)]
+pub struct MpdBridge {
+    mirror: Mirror,
+    idle: Mpd,
+    requests: UnboundedReceiver<Request>,
+}
+
+impl MpdBridge {
+    /// connects to MPD at `address`, see [`Mpd::connect`], and serves it on the session bus
+    pub async fn start(address: &str) -> anyhow::Result<Self> {
+        Self::start_on(address, connection::Builder::session()?).await
+    }
+
+    /// like [`MpdBridge::start`] on the bus `builder` connects to
+    pub async fn start_on(address: &str, builder: connection::Builder<'_>) -> anyhow::Result<Self> {
+        let mut mpd = Mpd::connect(address).await?;
+        let idle = Mpd::connect(address).await?;
+        let schemes = all(&mpd.command("urlhandlers").await?, "handler")
+            .into_iter()
+            .map(|handler| handler.trim_end_matches("://").to_string())
+            .collect();
+
+        let (tx, requests) = mpsc::unbounded_channel();
+        let service = MprisService::builder(BRIDGE_NAME, Forward(tx))
+            .root(RootProperties {
+                identity: Some("Music Player Daemon".to_string()),
+                supported_uri_schemes: schemes,
+                ..Default::default()
+            })
+            .serve_on(builder)
+            .await?;
+
+        let mut mirror = Mirror {
+            service,
+            mpd,
+            synced: None,
+        };
+        mirror.sync().await?;
+        Ok(Self {
+            mirror,
+            idle,
+            requests,
+        })
+    }
+
+    pub fn service(&self) -> &MprisService {
+        &self.mirror.service
+    }
+
+    /// forwards calls and changes until MPD can't be reached anymore, a sync that fails is
+    /// tried again on the next change
+    pub async fn run(self) -> anyhow::Result<()> {
+        let Self {
+            mut mirror,
+            mut idle,
+            mut requests,
+        } = self;
+        let (tx, mut changed) = mpsc::unbounded_channel();
+
+        let watch = async {
+            loop {
+                let subsystems = idle.command(IDLE).await?;
+                debug!(?subsystems, "MPD changed");
+                if tx.send(()).is_err() {
+                    return anyhow::Ok(());
+                }
+            }
+        };
+        let serve = async {
+            loop {
+                let tick = tokio::time::sleep(POSITION_INTERVAL);
+                tokio::select! {
+                    Some(request) = requests.recv() => {
+                        if let Err(e) = mirror.request(request).await {
+                            warn!("{e:#}");
+                        }
+                    }
+                    Some(()) = changed.recv() => {
+                        // one sync covers everything that piled up
+                        while changed.try_recv().is_ok() {}
+                        mirror.sync_or_warn().await;
+                    }
+                    () = tick, if mirror.playing() => mirror.sync_or_warn().await,
+                    else => return anyhow::Ok(()),
+                }
+            }
+        };
+
+        tokio::try_join!(watch, serve)?;
+        Ok(())
+    }
+}
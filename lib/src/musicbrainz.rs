@@ -0,0 +1,149 @@
+//! cover art for players that don't send `mpris:artUrl`
+//!
+//! the release is looked up on MusicBrainz by artist and album and the front cover is taken from
+//! the Cover Art Archive. MusicBrainz allows one request per second per client, so lookups are
+//! spaced out and the result (including "nothing found") is remembered per artist and album.
+
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serde_json::Value;
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use crate::player::Metadata;
+
+const SEARCH_URL: &str = "https://musicbrainz.org/ws/2/release/";
+const COVER_ART_URL: &str = "https://coverartarchive.org/release";
+/// musicbrainz rejects requests without a meaningful user agent
+const USER_AGENT: &str = concat!(
+    "mpris-controller/",
+    env!("CARGO_PKG_VERSION"),
+    " (https://github.com/slothywasnottaken/mpris-controller)"
+);
+pub const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+// (artist, album) -> release mbid, `None` when the search came up empty
+type Releases = HashMap<(String, String), Option<String>>;
+
+#[derive(Debug, Clone)]
+pub struct MusicBrainz {
+    http: reqwest::Client,
+    interval: Duration,
+    // when the last request went out, held across the request so lookups queue up
+    last_request: Arc<Mutex<Option<Instant>>>,
+    releases: Arc<Mutex<Releases>>,
+}
+
+impl Default for MusicBrainz {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MusicBrainz {
+    pub fn new() -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .user_agent(USER_AGENT)
+                .build()
+                .unwrap_or_default(),
+            interval: MIN_INTERVAL,
+            last_request: Arc::new(Mutex::new(None)),
+            releases: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// spaces requests further apart, anything below [`MIN_INTERVAL`] is ignored
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval.max(MIN_INTERVAL);
+        self
+    }
+
+    /// the key art for `metadata` is cached under, `None` without an artist and album
+    pub fn cache_key(metadata: &Metadata) -> Option<String> {
+        let (artist, album) = query(metadata)?;
+        Some(format!("musicbrainz:{artist}\0{album}"))
+    }
+
+    /// the front cover of the release matching `metadata`'s artist and album
+    pub async fn cover(&self, metadata: &Metadata) -> anyhow::Result<Option<Vec<u8>>> {
+        let Some((artist, album)) = query(metadata) else {
+            return Ok(None);
+        };
+        let Some(release) = self.release(artist, album).await? else {
+            return Ok(None);
+        };
+
+        let url = format!("{COVER_ART_URL}/{release}/front-500");
+        debug!(url, "downloading cover art");
+        // the cover art archive has no rate limit of its own
+        let response = self.http.get(&url).send().await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(response.error_for_status()?.bytes().await?.to_vec()))
+    }
+
+    /// the mbid of the best matching release
+    async fn release(&self, artist: &str, album: &str) -> anyhow::Result<Option<String>> {
+        let key = (artist.to_string(), album.to_string());
+        if let Some(release) = self.releases.lock().await.get(&key) {
+            return Ok(release.clone());
+        }
+
+        let query = format!(
+            "release:\"{}\" AND artist:\"{}\"",
+            escape(album),
+            escape(artist)
+        );
+
+        let body: Value = {
+            let mut last = self.last_request.lock().await;
+            if let Some(elapsed) = last.map(|last| last.elapsed()) {
+                if elapsed < self.interval {
+                    tokio::time::sleep(self.interval - elapsed).await;
+                }
+            }
+
+            debug!(artist, album, "looking up release");
+            let response = self
+                .http
+                .get(SEARCH_URL)
+                .query(&[("query", query.as_str()), ("fmt", "json"), ("limit", "1")])
+                .send()
+                .await;
+            *last = Some(Instant::now());
+
+            serde_json::from_slice(&response?.error_for_status()?.bytes().await?)?
+        };
+
+        let release = body["releases"]
+            .get(0)
+            .and_then(|release| release["id"].as_str())
+            .map(str::to_string);
+        self.releases.lock().await.insert(key, release.clone());
+
+        Ok(release)
+    }
+}
+
+fn query(metadata: &Metadata) -> Option<(&str, &str)> {
+    let artist = metadata
+        .album_artists()
+        .or(metadata.artists())?
+        .first()
+        .filter(|a| !a.is_empty())?;
+    let album = metadata.album().filter(|a| !a.is_empty())?;
+
+    Some((artist, album))
+}
+
+// lucene phrase queries only need quotes and backslashes escaped
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
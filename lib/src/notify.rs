@@ -0,0 +1,158 @@
+//! `org.freedesktop.Notifications` popups on track changes
+//!
+//! every player gets its own notification which is replaced in place on the next track instead
+//! of stacking up.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use tracing::debug;
+use zbus::{zvariant::Value, Connection};
+
+#[cfg(feature = "art")]
+use crate::art::ArtCache;
+use crate::call::{call_method, CallPolicy};
+use crate::player::{MprisEvent, Player};
+use crate::MprisClient;
+
+const NOTIFICATIONS_NAME: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+const FALLBACK_ICON: &str = "audio-x-generic";
+
+#[derive(Debug)]
+pub struct Notifier {
+    connection: Connection,
+    enabled: bool,
+    disabled_players: HashSet<String>,
+    // the id of the last notification per player, passed as `replaces_id`
+    ids: HashMap<String, u32>,
+    timeout_ms: i32,
+    #[cfg(feature = "art")]
+    art: Option<ArtCache>,
+}
+
+impl Notifier {
+    pub fn new(connection: Connection) -> Self {
+        Self {
+            connection,
+            enabled: true,
+            disabled_players: HashSet::new(),
+            ids: HashMap::new(),
+            timeout_ms: -1,
+            #[cfg(feature = "art")]
+            art: None,
+        }
+    }
+
+    /// uses `cache` to look up the art when none is passed to [`Notifier::track_changed`]
+    #[cfg(feature = "art")]
+    pub fn with_art(mut self, cache: ArtCache) -> Self {
+        self.art = Some(cache);
+        self
+    }
+
+    /// how long notifications stay up, `-1` leaves it to the notification server
+    pub fn set_timeout(&mut self, timeout_ms: i32) {
+        self.timeout_ms = timeout_ms;
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// turns notifications for a single player on or off
+    pub fn set_player_enabled(&mut self, name: &str, enabled: bool) {
+        if enabled {
+            self.disabled_players.remove(name);
+        } else {
+            self.disabled_players.insert(name.to_string());
+        }
+    }
+
+    pub fn is_enabled_for(&self, name: &str) -> bool {
+        self.enabled && !self.disabled_players.contains(name)
+    }
+
+    /// sends a notification for every [`MprisEvent::TrackChanged`] in `events`
+    pub async fn handle_events(
+        &mut self,
+        client: &MprisClient,
+        events: &[MprisEvent],
+    ) -> anyhow::Result<()> {
+        for event in events {
+            if let MprisEvent::TrackChanged { player, .. } = event {
+                if let Some(player) = client.get(player) {
+                    self.track_changed(player, None).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// shows (or updates) the notification for `player`'s current track
+    pub async fn track_changed(
+        &mut self,
+        player: &Player,
+        art: Option<&Path>,
+    ) -> anyhow::Result<()> {
+        if !self.is_enabled_for(player.name()) {
+            return Ok(());
+        }
+
+        let metadata = &player.capabilities().metadata;
+        let Some(title) = player.title() else {
+            return Ok(());
+        };
+
+        let mut body = metadata.artists().map(|a| a.join(", ")).unwrap_or_default();
+        if let Some(album) = player.album().filter(|a| !a.is_empty()) {
+            if !body.is_empty() {
+                body.push_str(" — ");
+            }
+            body.push_str(album);
+        }
+
+        #[cfg(feature = "art")]
+        let cached = match (art, &self.art) {
+            (None, Some(cache)) => cache.resolve(metadata).await.ok().flatten(),
+            _ => None,
+        };
+        #[cfg(feature = "art")]
+        let art = art.or(cached.as_deref());
+
+        let icon = art
+            .map(|path| path.display().to_string())
+            .unwrap_or(FALLBACK_ICON.to_string());
+        let replaces_id = self.ids.get(player.name()).copied().unwrap_or(0);
+        let hints: HashMap<&str, Value> = HashMap::new();
+
+        let reply = call_method(
+            &self.connection,
+            &CallPolicy::DEFAULT,
+            NOTIFICATIONS_NAME,
+            NOTIFICATIONS_PATH,
+            NOTIFICATIONS_NAME,
+            "Notify",
+            &(
+                "mpris-controller",
+                replaces_id,
+                icon.as_str(),
+                title,
+                body.as_str(),
+                Vec::<&str>::new(),
+                hints,
+                self.timeout_ms,
+            ),
+        )
+        .await?;
+
+        let id: u32 = reply.body().deserialize()?;
+        debug!(player = player.name(), id, "sent notification");
+        self.ids.insert(player.name().to_string(), id);
+
+        Ok(())
+    }
+}
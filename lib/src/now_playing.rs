@@ -0,0 +1,141 @@
+//! keeps a text file with the current track up to date, for OBS text sources and the like
+//!
+//! the file is written to a temporary file next to it and renamed over it, so whatever reads it
+//! never sees half a line. it's only touched when the text changes. the cover art can be copied
+//! to a fixed path the same way, for an image source.
+
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::Context;
+use tracing::debug;
+
+use crate::{percent_decode, player::Player, template::Template};
+
+/// `{artist} - {title}`
+pub const DEFAULT_FORMAT: &str = "{artist} - {title}";
+
+pub struct NowPlayingWriter {
+    path: PathBuf,
+    template: Template,
+    /// written when nothing is playing
+    idle: String,
+    art_path: Option<PathBuf>,
+    last_text: Option<String>,
+    /// the art that was copied last with when it was modified, `Some(None)` once it was removed
+    last_art: Option<Option<(PathBuf, Option<SystemTime>)>>,
+}
+
+impl NowPlayingWriter {
+    /// writes to `path`, rendering `format` with [`Player::field`]
+    pub fn new(path: impl Into<PathBuf>, format: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            path: path.into(),
+            template: Template::parse(format)?,
+            idle: String::new(),
+            art_path: None,
+            last_text: None,
+            last_art: None,
+        })
+    }
+
+    /// what to write when there is no player, empty by default
+    pub fn idle(mut self, text: impl Into<String>) -> Self {
+        self.idle = text.into();
+        self
+    }
+
+    /// also copies the art of the track to `path`, removing it when the track has none
+    pub fn art(mut self, path: impl Into<PathBuf>) -> Self {
+        self.art_path = Some(path.into());
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn art_path(&self) -> Option<&Path> {
+        self.art_path.as_deref()
+    }
+
+    /// writes the text and art for `player`, `None` when nothing is playing
+    ///
+    /// only art the player has on disk is copied, see [`NowPlayingWriter::write_art`] for art
+    /// that needs downloading first
+    pub fn update(&mut self, player: Option<&Player>) -> anyhow::Result<()> {
+        self.write_text(player)?;
+        let art = player.and_then(|player| local_art(player.capabilities().metadata.art_url()?));
+        self.write_art(art.as_deref())?;
+        Ok(())
+    }
+
+    /// writes the text for `player`, returns whether it changed
+    pub fn write_text(&mut self, player: Option<&Player>) -> anyhow::Result<bool> {
+        let text = match player {
+            Some(player) => self.template.render(|key| player.field(key)),
+            None => self.idle.clone(),
+        };
+        if self.last_text.as_ref() == Some(&text) {
+            return Ok(false);
+        }
+
+        debug!(path = %self.path.display(), text, "writing now playing");
+        write_atomic(&self.path, text.as_bytes())?;
+        self.last_text = Some(text);
+        Ok(true)
+    }
+
+    /// copies the image at `source` to the art path, or removes it for `None`. returns whether
+    /// anything changed, nothing happens without [`NowPlayingWriter::art`]
+    pub fn write_art(&mut self, source: Option<&Path>) -> anyhow::Result<bool> {
+        let Some(art_path) = &self.art_path else {
+            return Ok(false);
+        };
+        // players like mpv write every cover to the same temporary file
+        let art = source.map(|source| {
+            let modified = std::fs::metadata(source).and_then(|m| m.modified()).ok();
+            (source.to_path_buf(), modified)
+        });
+        if self.last_art.as_ref() == Some(&art) {
+            return Ok(false);
+        }
+
+        match source {
+            Some(source) => {
+                let bytes = std::fs::read(source)
+                    .with_context(|| format!("reading art {}", source.display()))?;
+                write_atomic(art_path, &bytes)?;
+            }
+            None => match std::fs::remove_file(art_path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(e).with_context(|| format!("removing {}", art_path.display()));
+                }
+                _ => {}
+            },
+        }
+        self.last_art = Some(art);
+        Ok(true)
+    }
+}
+
+/// the file behind a `file://` or bare path art url
+fn local_art(url: &str) -> Option<PathBuf> {
+    if let Some(path) = url.strip_prefix("file://") {
+        return Some(PathBuf::from(percent_decode(path)));
+    }
+    url.starts_with('/').then(|| PathBuf::from(url))
+}
+
+/// writes next to `path` and renames, so readers never see half a file
+fn write_atomic(path: &Path, bytes: &[u8]) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, bytes).with_context(|| format!("writing {}", path.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("writing {}", path.display()))
+}
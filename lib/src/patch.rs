@@ -0,0 +1,84 @@
+//! an alternative to [`MprisEvent`](crate::player::MprisEvent)s for consumers outside of rust:
+//! changes to the client are described as RFC 6902 JSON Patch operations against
+//! [`MprisClient::snapshot`], so keeping a mirror of the state only needs a json patch library.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::MprisClient;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+}
+
+/// returns the operations that turn `old` into `new`
+///
+/// objects are diffed key by key, anything else (arrays included) is replaced as a whole.
+pub fn diff(old: &Value, new: &Value) -> Vec<PatchOperation> {
+    let mut ops = Vec::new();
+    diff_at(&mut String::new(), old, new, &mut ops);
+    ops
+}
+
+fn diff_at(path: &mut String, old: &Value, new: &Value, ops: &mut Vec<PatchOperation>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old_value) in old {
+                let len = path.len();
+                push_token(path, key);
+                match new.get(key) {
+                    Some(new_value) => diff_at(path, old_value, new_value, ops),
+                    None => ops.push(PatchOperation::Remove { path: path.clone() }),
+                }
+                path.truncate(len);
+            }
+
+            for (key, value) in new {
+                if !old.contains_key(key) {
+                    let len = path.len();
+                    push_token(path, key);
+                    ops.push(PatchOperation::Add {
+                        path: path.clone(),
+                        value: value.clone(),
+                    });
+                    path.truncate(len);
+                }
+            }
+        }
+        (old, new) if old == new => {}
+        (_, new) => ops.push(PatchOperation::Replace {
+            path: path.clone(),
+            value: new.clone(),
+        }),
+    }
+}
+
+// bus names contain neither, but metadata keys from players can
+fn push_token(path: &mut String, key: &str) {
+    path.push('/');
+    path.push_str(&key.replace('~', "~0").replace('/', "~1"));
+}
+
+/// remembers the last snapshot so each call only yields what changed since the previous one
+#[derive(Debug, Default)]
+pub struct PatchEmitter {
+    last: Value,
+}
+
+impl PatchEmitter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the operations since the last call, the first call replaces the whole document
+    pub fn update(&mut self, client: &MprisClient) -> Vec<PatchOperation> {
+        let snapshot = client.snapshot();
+        let ops = diff(&self.last, &snapshot);
+        self.last = snapshot;
+        ops
+    }
+}
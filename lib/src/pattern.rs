@@ -0,0 +1,39 @@
+//! `*` wildcard patterns for player names
+//!
+//! a pattern matches either the full bus name or the part after `org.mpris.MediaPlayer2.`, so
+//! `spotify`, `chromium.instance*` and `org.mpris.MediaPlayer2.vlc` all work.
+
+use crate::MPRIS_PREFIX;
+
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let short = name
+        .strip_prefix(MPRIS_PREFIX)
+        .and_then(|n| n.strip_prefix('.'))
+        .unwrap_or(name);
+
+    glob(pattern, name) || glob(pattern, short)
+}
+
+fn glob(pattern: &str, s: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // split always yields at least one item
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = s.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // no `*` in the pattern
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.ends_with(last)
+}
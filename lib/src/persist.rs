@@ -0,0 +1,81 @@
+//! remembering the active player across restarts
+//!
+//! a bar that reloads starts a new client, which without this would pick whichever player it
+//! finds first until one of them starts playing. see [`MprisClient::persist_active`].
+//!
+//! [`MprisClient::persist_active`]: crate::MprisClient::persist_active
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::player::{PlaybackStatus, Player};
+
+/// what is known about the active player when it is saved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedActive {
+    pub player: String,
+    /// the player's [`StableId`](crate::stable_id::StableId), which outlives its bus name
+    pub id: String,
+    pub status: PlaybackStatus,
+    pub title: Option<String>,
+    pub artists: Vec<String>,
+}
+
+/// `$XDG_STATE_HOME/mpris-controller/active.json`, falling back to `~/.local/state`
+pub fn default_path() -> Option<PathBuf> {
+    let state_home = std::env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state"))
+        })?;
+
+    Some(state_home.join("mpris-controller").join("active.json"))
+}
+
+impl SavedActive {
+    pub fn of(player: &Player) -> Self {
+        let caps = player.capabilities();
+        Self {
+            player: player.name().to_string(),
+            id: player.stable_id().to_string(),
+            status: caps.playback_status,
+            title: player.title().map(str::to_string),
+            artists: caps
+                .metadata
+                .artists()
+                .map(<[_]>::to_vec)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// `None` when nothing was saved yet
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        match std::fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .with_context(|| format!("parsing {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+        }
+    }
+
+    /// writes to a temporary file first, so a reader never sees half of it
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(self)?)?;
+        std::fs::rename(&tmp, path)?;
+
+        Ok(())
+    }
+
+    /// whether `player` is the one that was saved, by stable id and failing that by bus name
+    pub fn is(&self, player: &Player) -> bool {
+        player.stable_id().to_string() == self.id || player.name() == self.player
+    }
+}
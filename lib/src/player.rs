@@ -1,19 +1,39 @@
 use anyhow::{anyhow, bail};
 use futures::StreamExt;
-use tracing::instrument;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, instrument, warn};
 use zbus::{
     proxy::SignalStream,
-    zvariant::{ObjectPath, OwnedValue, Str, Value},
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Str, Type, Value},
     Connection, Message,
 };
 
 use std::{
+    borrow::Borrow,
     collections::HashMap,
+    fmt,
+    hash::Hash,
+    str::FromStr,
     task::{Context, Poll},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use crate::{DbusMethods, DBUS_PROPERTIES, MPRIS_PATH, MPRIS_PLAYER_PREFIX, WAKER};
+use crate::{
+    blob,
+    call::{call_method, CallPolicy},
+    desktop, icons, mime,
+    playlists::Playlist,
+    position::{self, Playing, PositionTracker},
+    progress,
+    queue::Queue,
+    quirks::{QuirkRegistry, Quirks},
+    sanitize,
+    stable_id::{self, StableId},
+    template::Template,
+    ui::UiModel,
+    DbusMethods, DBUS_PROPERTIES, MPRIS_PATH, MPRIS_PLAYER_PREFIX, MPRIS_PREFIX, MPRIS_TRACKLIST,
+    WAKER,
+};
 
 #[derive(Debug)]
 pub enum NameOwnerChanged {
@@ -21,7 +41,7 @@ pub enum NameOwnerChanged {
     RemovedPlayer,
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum PlaybackStatus {
     #[default]
     Stopped,
@@ -29,32 +49,51 @@ pub enum PlaybackStatus {
     Playing,
 }
 
-impl From<PlaybackStatus> for Value<'_> {
-    fn from(value: PlaybackStatus) -> Self {
-        let v = match value {
-            PlaybackStatus::Stopped => "Stopped",
-            PlaybackStatus::Paused => "Paused",
-            PlaybackStatus::Playing => "Playing",
-        };
+impl PlaybackStatus {
+    /// the name MPRIS uses for the status
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Stopped => "Stopped",
+            Self::Paused => "Paused",
+            Self::Playing => "Playing",
+        }
+    }
+}
 
-        Self::Str(Str::from(v))
+impl fmt::Display for PlaybackStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
     }
 }
 
-impl<'a> TryFrom<&Str<'a>> for PlaybackStatus {
-    type Error = anyhow::Error;
+impl FromStr for PlaybackStatus {
+    type Err = anyhow::Error;
 
-    fn try_from(value: &Str) -> Result<Self, Self::Error> {
-        match &**value {
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
             "Stopped" => Ok(Self::Stopped),
             "Paused" => Ok(Self::Paused),
             "Playing" => Ok(Self::Playing),
-            _ => bail!("incorrect playback status: {value}"),
+            _ => bail!("incorrect playback status: {s}"),
         }
     }
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+impl From<PlaybackStatus> for Value<'_> {
+    fn from(value: PlaybackStatus) -> Self {
+        Self::Str(Str::from(value.as_str()))
+    }
+}
+
+impl<'a> TryFrom<&Str<'a>> for PlaybackStatus {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 pub enum LoopStatus {
     #[default]
     None,
@@ -62,43 +101,169 @@ pub enum LoopStatus {
     Track,
 }
 
-impl TryFrom<&str> for LoopStatus {
-    type Error = anyhow::Error;
-    fn try_from(value: &str) -> Result<Self, Self::Error> {
-        match value {
+impl LoopStatus {
+    /// the name MPRIS uses for the status
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "None",
+            Self::Playlist => "Playlist",
+            Self::Track => "Track",
+        }
+    }
+}
+
+impl fmt::Display for LoopStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for LoopStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
             "None" => Ok(Self::None),
             "Playlist" => Ok(Self::Playlist),
             "Track" => Ok(Self::Track),
-            _ => Err(anyhow!("invalid loop status {value}")),
+            _ => Err(anyhow!("invalid loop status {s}")),
         }
     }
 }
 
+impl TryFrom<&str> for LoopStatus {
+    type Error = anyhow::Error;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
 impl<'a> TryFrom<&Value<'a>> for LoopStatus {
     type Error = anyhow::Error;
 
     fn try_from(value: &Value<'a>) -> Result<Self, Self::Error> {
         match value {
-            Value::Str(s) => match &**s {
-                "None" => Ok(Self::None),
-                "Playlist" => Ok(Self::Playlist),
-                "Track" => Ok(Self::Track),
-                _ => Err(anyhow!("")),
-            },
-            _ => Err(anyhow!("")),
+            Value::Str(s) => s.parse(),
+            _ => Err(anyhow!("LoopStatus has the wrong type: {value}")),
         }
     }
 }
 
 impl From<LoopStatus> for Value<'_> {
     fn from(value: LoopStatus) -> Self {
-        let status = match value {
-            LoopStatus::None => "None",
-            LoopStatus::Playlist => "Playlist",
-            LoopStatus::Track => "Track",
-        };
+        Value::Str(value.as_str().into())
+    }
+}
+
+/// upper bounds for metadata values, keeping events and snapshots small when a player embeds
+/// megabytes of base64 art or lyrics
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MetadataLimits {
+    /// longest string kept as is, in bytes
+    pub max_value_len: usize,
+    /// move oversized art urls into the [`blob`] store instead of truncating them, truncating
+    /// would only leave a broken url behind
+    pub externalize_art: bool,
+}
+
+impl Default for MetadataLimits {
+    fn default() -> Self {
+        Self {
+            max_value_len: 16 * 1024,
+            externalize_art: true,
+        }
+    }
+}
+
+/// the trackid a player reports when nothing is loaded
+pub const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+/// the icon theme name for bluetooth players without a desktop entry
+const BLUETOOTH_ICON: &str = "bluetooth";
+
+/// the waits between asking a player that is still setting up again, about 1.5s in total
+const REGISTRATION_BACKOFF: [Duration; 5] = [
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(200),
+    Duration::from_millis(400),
+    Duration::from_millis(800),
+];
+
+/// whether `e` says the object or interface isn't there (yet)
+fn is_unregistered(e: &anyhow::Error) -> bool {
+    let Some(zbus::Error::MethodError(name, _, _)) = e.downcast_ref::<zbus::Error>() else {
+        return false;
+    };
+    matches!(
+        name.as_str(),
+        "org.freedesktop.DBus.Error.UnknownInterface"
+            | "org.freedesktop.DBus.Error.UnknownObject"
+            | "org.freedesktop.DBus.Error.UnknownMethod"
+    )
+}
+
+/// where bluez keeps its media players and their tracks
+const BLUEZ_PATH: &str = "/org/bluez/";
+
+/// `org.mpris.MediaPlayer2.bluez...`, some bridges name the players after bluez
+fn is_bluez_name(name: &str) -> bool {
+    name.strip_prefix(MPRIS_PREFIX)
+        .is_some_and(|name| name.to_lowercase().contains("bluez"))
+}
+
+/// a track that bluez put under its own object path
+fn is_bluez_track(metadata: &Metadata) -> bool {
+    metadata
+        .track_id()
+        .is_some_and(|id| id.starts_with(BLUEZ_PATH))
+}
+
+/// whether `name` is owned by `mpris-proxy`, which bluez ships to bridge AVRCP to MPRIS
+async fn is_bluez_proxy(conn: &Connection, name: &str) -> bool {
+    let Ok(pid) = crate::bus_name_pid(conn, name).await else {
+        return false;
+    };
+    std::fs::read_to_string(format!("/proc/{pid}/comm"))
+        .is_ok_and(|comm| comm.trim_end() == "mpris-proxy")
+}
+
+/// an `mpris:trackid`, an object path unique to the track within the player's tracklist
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(transparent)]
+pub struct TrackId(String);
+
+impl TrackId {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self(id.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
 
-        Value::Str(status.into())
+    pub fn is_no_track(&self) -> bool {
+        self.0 == NO_TRACK
+    }
+}
+
+impl std::ops::Deref for TrackId {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for TrackId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<TrackId> for String {
+    fn from(value: TrackId) -> Self {
+        value.0
     }
 }
 
@@ -106,7 +271,7 @@ impl From<LoopStatus> for Value<'_> {
 pub struct MetadataBuilder {
     art_url: Option<String>,
     length: Option<u64>,
-    trackid: Option<String>,
+    trackid: Option<TrackId>,
     album: Option<String>,
     artists: Option<Vec<String>>,
     title: Option<String>,
@@ -115,6 +280,8 @@ pub struct MetadataBuilder {
     disc_number: Option<i32>,
     auto_rating: Option<f64>,
     album_artists: Option<Vec<String>>,
+    localized_titles: HashMap<String, String>,
+    localized_albums: HashMap<String, String>,
 }
 
 impl MetadataBuilder {
@@ -129,7 +296,7 @@ impl MetadataBuilder {
     }
 
     pub fn trackid(mut self, id: String) -> Self {
-        self.trackid = Some(id);
+        self.trackid = Some(TrackId(id));
         self
     }
 
@@ -166,6 +333,14 @@ impl MetadataBuilder {
 
         self
     }
+    pub fn localized_title(mut self, lang: String, title: String) -> Self {
+        self.localized_titles.insert(lang, title);
+        self
+    }
+    pub fn localized_album(mut self, lang: String, album: String) -> Self {
+        self.localized_albums.insert(lang, album);
+        self
+    }
 
     pub fn finish(self) -> Metadata {
         Metadata {
@@ -180,16 +355,18 @@ impl MetadataBuilder {
             disc_number: self.disc_number,
             auto_rating: self.auto_rating,
             album_artists: self.album_artists,
+            localized_titles: self.localized_titles,
+            localized_albums: self.localized_albums,
         }
     }
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
 #[allow(dead_code)]
 pub struct Metadata {
     art_url: Option<String>,
     length: Option<u64>,
-    trackid: Option<String>,
+    trackid: Option<TrackId>,
     album: Option<String>,
     artists: Option<Vec<String>>,
     title: Option<String>,
@@ -198,9 +375,30 @@ pub struct Metadata {
     disc_number: Option<i32>,
     auto_rating: Option<f64>,
     album_artists: Option<Vec<String>>,
+    // vendor keys like `xesam:title@ja`, keyed by language tag
+    localized_titles: HashMap<String, String>,
+    localized_albums: HashMap<String, String>,
 }
 
 impl Metadata {
+    /// the metadata of a player with nothing loaded, only the `NoTrack` trackid
+    pub fn none() -> Self {
+        Self {
+            trackid: Some(TrackId::new(NO_TRACK)),
+            ..Default::default()
+        }
+    }
+
+    /// whether nothing is loaded: no trackid or `NoTrack`, and nothing that would tell a track
+    /// apart either
+    pub fn is_none(&self) -> bool {
+        self.trackid.as_ref().is_none_or(TrackId::is_no_track)
+            && self.title.as_deref().is_none_or(str::is_empty)
+            && self.url.as_deref().is_none_or(str::is_empty)
+            && self.artists.as_ref().is_none_or(Vec::is_empty)
+            && self.album.as_deref().is_none_or(str::is_empty)
+    }
+
     pub fn art_url(&self) -> Option<&str> {
         match &self.art_url {
             Some(url) => Some(url),
@@ -212,11 +410,8 @@ impl Metadata {
         self.length
     }
 
-    pub fn track_id(&self) -> Option<&str> {
-        match &self.trackid {
-            Some(id) => Some(id),
-            None => None,
-        }
+    pub fn track_id(&self) -> Option<&TrackId> {
+        self.trackid.as_ref()
     }
 
     pub fn album(&self) -> Option<&str> {
@@ -265,167 +460,344 @@ impl Metadata {
             None => None,
         }
     }
-}
-
-impl<'a> TryFrom<&Value<'a>> for Metadata {
-    type Error = anyhow::Error;
 
-    #[instrument(skip_all)]
-    fn try_from(value: &Value<'a>) -> Result<Self, Self::Error> {
-        let value: HashMap<String, Value> = value.try_clone()?.try_into()?;
+    /// the title in `lang` if the player sent a localized variant, otherwise the plain title
+    ///
+    /// `lang` is a language tag like `ja` or `pt-BR`, a region specific tag falls back to its
+    /// primary language (`pt-BR` -> `pt`).
+    pub fn title_in(&self, lang: Option<&str>) -> Option<&str> {
+        localized(&self.localized_titles, lang).or(self.title())
+    }
 
-        let art_url: Option<String> = match value.get("mpris:artUrl") {
-            Some(url) => match url {
-                Value::Str(s) => Some(s.to_string()),
-                _ => bail!("can not find mpris:artUrl"),
-            },
-            None => None,
-        };
+    /// the album in `lang` if the player sent a localized variant, otherwise the plain album
+    pub fn album_in(&self, lang: Option<&str>) -> Option<&str> {
+        localized(&self.localized_albums, lang).or(self.album())
+    }
 
-        // optional because players like browsers can not include the length when we request its
-        // metadata but might give us the length later
-        let length = match value.get("mpris:length") {
-            Some(Value::I64(s)) => Some(s.cast_unsigned()),
-            Some(Value::U64(s)) => Some(*s),
-            None => None,
-            _ => bail!("can not find mpris:length"),
-        };
-        let trackid: Option<String> = match value.get("mpris:trackid") {
-            Some(Value::ObjectPath(s)) => Some(s.to_string()),
-            Some(Value::Str(s)) => Some(s.to_string()),
-            _ => None,
-        };
+    pub fn localized_titles(&self) -> &HashMap<String, String> {
+        &self.localized_titles
+    }
 
-        let album: Option<String> = match value.get("xesam:album") {
-            Some(Value::Str(s)) => Some(s.to_string()),
-            None => None,
+    pub fn localized_albums(&self) -> &HashMap<String, String> {
+        &self.localized_albums
+    }
 
-            _ => bail!("can not find xesam:album"),
-        };
+    /// whether `self` and `other` describe the same track
+    ///
+    /// compares track ids first, falling back to the url and then title + artists when either
+    /// side has no usable track id (missing, or the `NoTrack` sentinel some players reuse for
+    /// every track).
+    pub fn same_track(&self, other: &Metadata) -> bool {
+        fn usable(id: &Option<TrackId>) -> Option<&TrackId> {
+            id.as_ref().filter(|id| !id.is_no_track())
+        }
 
-        let artists: Option<Vec<String>> = match value.get("xesam:artist") {
-            Some(v) => Some(v.try_clone()?.try_into()?),
-            None => None,
-        };
+        if let (Some(a), Some(b)) = (usable(&self.trackid), usable(&other.trackid)) {
+            return a == b;
+        }
 
-        let title: Option<String> = match value.get("xesam:title") {
-            Some(v) => Some(v.try_into()?),
-            None => None,
-        };
+        if let (Some(a), Some(b)) = (&self.url, &other.url) {
+            return a == b;
+        }
 
-        let url: Option<String> = match value.get("xesam:url") {
-            Some(v) => Some(v.try_into()?),
-            None => None,
-        };
+        self.title == other.title && self.artists == other.artists
+    }
 
-        // optional (basically only spotify implements this)
-        let album_artist = match value.get("xesam:albumArtist") {
-            Some(Value::Array(s)) => Some(
-                s.iter()
-                    .filter_map(|f| {
-                        if let Value::Str(s) = f {
-                            Some(s.to_string())
-                        } else {
-                            None
-                        }
-                    })
-                    .collect(),
-            ),
-            _ => None,
+    /// whether this is one of Spotify's ads: an `:ad:` (or `/ad/`) trackid or the title
+    /// `Advertisement`. missing art, artists or album say nothing, local files and podcasts
+    /// come without them too
+    ///
+    /// only meaningful for Spotify, see [`Player::is_ad`]
+    pub fn is_ad(&self) -> bool {
+        let Some(trackid) = self.trackid.as_ref().filter(|id| !id.is_no_track()) else {
+            return false;
         };
+        trackid.contains(":ad:")
+            || trackid.starts_with("/com/spotify/ad/")
+            || self
+                .title
+                .as_deref()
+                .is_some_and(|title| title.eq_ignore_ascii_case("advertisement"))
+    }
 
-        let track_number = {
-            match value.get("xesam:trackNumber") {
-                Some(Value::I32(s)) => Some(*s),
-                None => None,
-                _ => unreachable!(),
+    /// corrects what `quirks` says the player gets wrong, see [`crate::quirks`]
+    pub fn apply_quirks(&mut self, quirks: &Quirks) {
+        if let (Some(unit), Some(length)) = (quirks.length_unit, self.length) {
+            self.length = Some(unit.to_micros(length));
+        }
+        if quirks.artist_from_title == Some(true) && self.artists.is_none() {
+            let split = self
+                .title
+                .as_deref()
+                .and_then(|title| title.split_once(" - "));
+            if let Some((artist, title)) = split {
+                self.artists = Some(vec![artist.trim().to_string()]);
+                self.title = Some(title.trim().to_string());
             }
-        };
+        }
+    }
+
+    /// enforces `limits` on every value, see [`MetadataLimits`]
+    pub fn limit(&mut self, limits: &MetadataLimits) {
+        let max = limits.max_value_len;
+        if let Some(url) = self.art_url.take() {
+            self.art_url = Some(if url.len() <= max {
+                url
+            } else if limits.externalize_art {
+                blob::store(url).uri()
+            } else {
+                truncate(url, max)
+            });
+        }
+
+        for value in [&mut self.title, &mut self.album, &mut self.url]
+            .into_iter()
+            .flatten()
+        {
+            *value = truncate(std::mem::take(value), max);
+        }
+
+        if let Some(id) = &mut self.trackid {
+            id.0 = truncate(std::mem::take(&mut id.0), max);
+        }
 
-        let disc_number = {
-            match value.get("xesam:discNumber") {
-                Some(Value::I32(s)) => Some(*s),
-                None => None,
+        for list in [&mut self.artists, &mut self.album_artists]
+            .into_iter()
+            .flatten()
+        {
+            for v in list.iter_mut() {
+                *v = truncate(std::mem::take(v), max);
+            }
+        }
 
-                _ => unreachable!(),
+        for map in [&mut self.localized_titles, &mut self.localized_albums] {
+            for v in map.values_mut() {
+                *v = truncate(std::mem::take(v), max);
             }
-        };
+        }
+    }
 
-        let auto_rating = {
-            match value.get("xesam:autoRating") {
-                Some(Value::F64(v)) => Some(*v),
-                None => None,
+    /// a copy with the title and artists run through [`sanitize::clean_title`] and
+    /// [`sanitize::clean_artist`]
+    pub fn sanitized(&self) -> Metadata {
+        let mut metadata = self.clone();
+        metadata.title = self.title.as_deref().map(sanitize::clean_title);
+        metadata.artists = self
+            .artists
+            .as_ref()
+            .map(|artists| artists.iter().map(|a| sanitize::clean_artist(a)).collect());
+
+        metadata
+    }
 
-                _ => unreachable!(),
+    /// returns the display value of a template placeholder, `None` when the player didn't send it
+    ///
+    /// keys are the short names (`title`, `art_url`) or the MPRIS ones (`xesam:title`,
+    /// `mpris:artUrl`). `length` is formatted as `m:ss`, `mpris:length` is in microseconds.
+    pub fn field(&self, key: &str) -> Option<String> {
+        match key {
+            "title" | "xesam:title" => self.title().map(str::to_string),
+            "artist" | "artists" | "xesam:artist" => self.artists().map(|a| a.join(", ")),
+            "album" | "xesam:album" => self.album().map(str::to_string),
+            "album_artist" | "album_artists" | "xesam:albumArtist" => {
+                self.album_artists().map(|a| a.join(", "))
             }
-        };
+            "url" | "xesam:url" => self.url().map(str::to_string),
+            "art_url" | "mpris:artUrl" => self.art_url().map(str::to_string),
+            "trackid" | "track_id" | "mpris:trackid" => self.track_id().map(TrackId::to_string),
+            "track_number" | "xesam:trackNumber" => self.track_number().map(|n| n.to_string()),
+            "disc_number" | "xesam:discNumber" => self.disc_number().map(|n| n.to_string()),
+            "auto_rating" | "xesam:autoRating" => self.auto_rating().map(|r| r.to_string()),
+            "length" => self.length().map(progress::format_length),
+            // the raw microseconds, for `{duration(mpris:length)}`
+            "mpris:length" => self.length().map(|len| len.to_string()),
+            _ => None,
+        }
+    }
 
-        Ok(Self {
-            album_artists: album_artist,
-            art_url,
-            length,
-            trackid,
-            album,
-            artists,
-            title,
-            url,
-            track_number,
-            disc_number,
-            auto_rating,
+    /// formats the metadata using a template like `{artist} — {title} [{album|unknown}]`
+    ///
+    /// see [`Template`] for the syntax, unknown keys render like missing fields.
+    pub fn format(&self, template: &str) -> anyhow::Result<String> {
+        Ok(Template::parse(template)?.render(|key| self.field(key)))
+    }
+}
+
+/// cuts `s` down to at most `max` bytes on a char boundary, marking the cut with `…`
+fn truncate(mut s: String, max: usize) -> String {
+    if s.len() <= max {
+        return s;
+    }
+
+    let mut end = max.saturating_sub('…'.len_utf8());
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    s.truncate(end);
+    s.push('…');
+    s
+}
+
+fn localized<'a>(variants: &'a HashMap<String, String>, lang: Option<&str>) -> Option<&'a str> {
+    let lang = lang?;
+    if let Some(v) = variants.get(lang) {
+        return Some(v);
+    }
+
+    let primary = lang.split(['-', '_']).next()?;
+    variants
+        .iter()
+        .find(|(tag, _)| tag.split(['-', '_']).next() == Some(primary))
+        .map(|(_, v)| v.as_str())
+}
+
+/// collects `<key>@<lang>` string entries, which is how players that know about more than one
+/// language for a track send the alternatives
+fn localized_variants<'v, K, V>(value: &HashMap<K, V>, key: &str) -> HashMap<String, String>
+where
+    K: Borrow<str>,
+    V: Borrow<Value<'v>>,
+{
+    value
+        .iter()
+        .filter_map(|(k, v)| {
+            let (base, lang) = k.borrow().split_once('@')?;
+            match v.borrow() {
+                Value::Str(s) if base == key && !lang.is_empty() => {
+                    Some((lang.to_string(), s.to_string()))
+                }
+                _ => None,
+            }
         })
+        .collect()
+}
+
+/// the value inside however many variants it is wrapped in
+fn unwrap_variant<'v>(mut value: &'v Value<'v>) -> &'v Value<'v> {
+    while let Value::Value(inner) = value {
+        value = inner;
     }
+    value
 }
 
-impl<'a> TryFrom<HashMap<String, Value<'a>>> for Metadata {
-    type Error = anyhow::Error;
+/// the entries of an `a{sv}` without copying them
+fn borrow_dict<'v>(value: &'v Value<'v>) -> anyhow::Result<HashMap<&'v str, &'v Value<'v>>> {
+    let Value::Dict(dict) = unwrap_variant(value) else {
+        bail!("expected a dict, got {value}");
+    };
+    dict.iter()
+        .map(|(k, v)| match k {
+            Value::Str(k) => Ok((k.as_str(), unwrap_variant(v))),
+            _ => bail!("expected string keys, got {k}"),
+        })
+        .collect()
+}
 
-    #[instrument]
-    fn try_from(value: HashMap<String, Value<'a>>) -> anyhow::Result<Self> {
-        let art_url: Option<String> = match value.get("mpris:artUrl") {
-            Some(url) => match url {
-                Value::Str(s) => Some(s.to_string()),
-                _ => bail!("failed to find mpris:artUrl"),
-            },
-            None => None,
-        };
-        // optional because players like browsers can not include the length when we request its
-        // metadata but might give us the length later
-        let length = match value.get("mpris:length") {
-            Some(Value::I64(s)) => Some(s.cast_unsigned()),
-            Some(Value::U64(s)) => Some(*s),
-            None => None,
-            _ => bail!("failed to find mpris:length"),
-        };
+fn strings(value: &Value) -> anyhow::Result<Vec<String>> {
+    let Value::Array(array) = value else {
+        bail!("expected an array of strings, got {value}");
+    };
+    array
+        .iter()
+        .map(|s| match s {
+            Value::Str(s) => Ok(s.to_string()),
+            _ => bail!("expected a string, got {s}"),
+        })
+        .collect()
+}
 
-        let trackid: Option<String> = match value.get("mpris:trackid") {
-            Some(Value::ObjectPath(s)) => Some(s.to_string()),
-            Some(Value::Str(s)) => Some(s.to_string()),
+/// a field that failed to parse is left out when `relaxed`
+fn relax<T, E>(field: Result<Option<T>, E>, relaxed: bool) -> anyhow::Result<Option<T>>
+where
+    E: Into<anyhow::Error>,
+{
+    match field.map_err(Into::into) {
+        Err(e) if relaxed => {
+            debug!("ignoring metadata field: {e}");
+            Ok(None)
+        }
+        field => field,
+    }
+}
+
+impl Metadata {
+    /// parses the metadata map, only copying what ends up in [`Metadata`]
+    ///
+    /// `relaxed` drops fields with the wrong type instead of failing and takes a single string
+    /// for `xesam:artist`, for players like bluez and browsers that only fill in part of the
+    /// metadata
+    #[instrument(skip_all)]
+    fn from_properties<'v, K, V>(map: &HashMap<K, V>, relaxed: bool) -> anyhow::Result<Self>
+    where
+        K: Borrow<str> + Hash + Eq,
+        V: Borrow<Value<'v>>,
+    {
+        let get = |key: &str| map.get(key).map(Borrow::borrow);
+        let trackid: Option<TrackId> = match get("mpris:trackid") {
+            Some(Value::ObjectPath(s)) => Some(TrackId(s.to_string())),
+            Some(Value::Str(s)) => Some(TrackId(s.to_string())),
             _ => None,
         };
+        // with nothing loaded, whatever else is sent is leftovers like a zero length of the
+        // wrong type
+        let relaxed = relaxed || trackid.as_ref().is_some_and(TrackId::is_no_track);
+
+        let art_url = relax(
+            match get("mpris:artUrl") {
+                Some(Value::Str(s)) => Ok(Some(s.to_string())),
+                None => Ok(None),
+                _ => Err(anyhow!("can not find mpris:artUrl")),
+            },
+            relaxed,
+        )?;
 
-        let album: Option<String> = match value.get("xesam:album") {
-            Some(Value::Str(s)) => Some(s.to_string()),
-            None => None,
-
-            _ => bail!("failed to find xesam:album"),
-        };
-        let artists: Option<Vec<String>> = match value.get("xesam:artist") {
-            Some(v) => Some(v.try_clone()?.try_into()?),
-            None => None,
-        };
+        // optional because players like browsers can not include the length when we request its
+        // metadata but might give us the length later
+        let length = relax(
+            match get("mpris:length") {
+                Some(Value::I64(s)) => Ok(Some(s.cast_unsigned())),
+                Some(Value::U64(s)) => Ok(Some(*s)),
+                None => Ok(None),
+                _ => Err(anyhow!("can not find mpris:length")),
+            },
+            relaxed,
+        )?;
+        let album = relax(
+            match get("xesam:album") {
+                Some(Value::Str(s)) => Ok(Some(s.to_string())),
+                None => Ok(None),
+                _ => Err(anyhow!("can not find xesam:album")),
+            },
+            relaxed,
+        )?;
+
+        let artists = relax(
+            match get("xesam:artist") {
+                Some(Value::Str(s)) if relaxed => Ok(Some(vec![s.to_string()])),
+                // whatever strings there are among the rest
+                Some(Value::Array(array)) if relaxed => Ok(Some(
+                    array
+                        .iter()
+                        .filter_map(|artist| match unwrap_variant(artist) {
+                            Value::Str(artist) => Some(artist.to_string()),
+                            _ => None,
+                        })
+                        .collect(),
+                )),
+                artist => artist.map(strings).transpose(),
+            },
+            relaxed,
+        )?;
 
-        let title: Option<String> = match value.get("xesam:title") {
-            Some(v) => Some(v.try_into()?),
-            None => None,
-        };
+        let title = relax(
+            get("xesam:title").map(String::try_from).transpose(),
+            relaxed,
+        )?;
 
-        let url: Option<String> = match value.get("xesam:url") {
-            Some(v) => Some(v.try_into()?),
-            None => None,
-        };
+        let url = relax(get("xesam:url").map(String::try_from).transpose(), relaxed)?;
 
         // optional (basically only spotify implements this)
-        let album_artist = match value.get("xesam:albumArtist") {
+        let album_artists = match get("xesam:albumArtist") {
             Some(Value::Array(s)) => Some(
                 s.iter()
                     .filter_map(|f| {
@@ -440,34 +812,37 @@ impl<'a> TryFrom<HashMap<String, Value<'a>>> for Metadata {
             _ => None,
         };
 
-        let track_number = {
-            match value.get("xesam:trackNumber") {
-                Some(Value::I32(s)) => Some(*s),
-                None => None,
-                _ => bail!("failed to find xesam:trackNumber"),
-            }
-        };
-
-        let disc_number = {
-            match value.get("xesam:discNumber") {
-                Some(Value::I32(s)) => Some(*s),
-                None => None,
-
-                _ => bail!("failed to find xesam:discNumber"),
-            }
-        };
-
-        let auto_rating = {
-            match value.get("xesam:autoRating") {
-                Some(Value::F64(v)) => Some(*v),
-                None => None,
-
-                _ => bail!("failed to find xesam:autoRating"),
-            }
-        };
+        let track_number = relax(
+            match get("xesam:trackNumber") {
+                Some(Value::I32(s)) => Ok(Some(*s)),
+                None => Ok(None),
+                _ => Err(anyhow!("can not find xesam:trackNumber")),
+            },
+            relaxed,
+        )?;
+
+        let disc_number = relax(
+            match get("xesam:discNumber") {
+                Some(Value::I32(s)) => Ok(Some(*s)),
+                None => Ok(None),
+                _ => Err(anyhow!("can not find xesam:discNumber")),
+            },
+            relaxed,
+        )?;
+
+        let auto_rating = relax(
+            match get("xesam:autoRating") {
+                Some(Value::F64(v)) => Ok(Some(*v)),
+                None => Ok(None),
+                _ => Err(anyhow!("can not find xesam:autoRating")),
+            },
+            relaxed,
+        )?;
 
-        Ok(Self {
-            album_artists: album_artist,
+        let metadata = Self {
+            album_artists,
+            localized_titles: localized_variants(map, "xesam:title"),
+            localized_albums: localized_variants(map, "xesam:album"),
             art_url,
             length,
             trackid,
@@ -478,64 +853,82 @@ impl<'a> TryFrom<HashMap<String, Value<'a>>> for Metadata {
             track_number,
             disc_number,
             auto_rating,
-        })
+        };
+        if metadata.trackid.as_ref().is_some_and(TrackId::is_no_track) && metadata.is_none() {
+            return Ok(Self::none());
+        }
+        Ok(metadata)
+    }
+}
+
+/// the `Metadata` property, which some players with nothing loaded send as an empty array
+fn parse_metadata(value: &Value, relaxed: bool) -> anyhow::Result<Metadata> {
+    match unwrap_variant(value) {
+        Value::Array(array) if array.is_empty() => Ok(Metadata::none()),
+        value => Metadata::from_properties(&borrow_dict(value)?, relaxed),
+    }
+}
+
+impl<'a> TryFrom<&Value<'a>> for Metadata {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Value<'a>) -> Result<Self, Self::Error> {
+        Self::from_properties(&borrow_dict(value)?, false)
+    }
+}
+
+impl<'a> TryFrom<HashMap<String, Value<'a>>> for Metadata {
+    type Error = anyhow::Error;
+
+    fn try_from(value: HashMap<String, Value<'a>>) -> anyhow::Result<Self> {
+        Self::from_properties(&value, false)
     }
 }
 
 impl<'a> From<Metadata> for HashMap<String, Value<'a>> {
+    /// only the fields that are set, with the types the spec gives them
     #[instrument]
     fn from(value: Metadata) -> Self {
         let mut map = HashMap::new();
-        map.insert(
-            "mpris:artUrl".to_string(),
-            Value::from(value.art_url.unwrap_or(String::default())),
-        );
-        map.insert(
-            "mpris:length".to_string(),
-            Value::U64(value.length.unwrap_or(0)),
-        );
-        map.insert(
-            "mpris:trackid".to_string(),
-            Value::from(value.trackid.unwrap_or(String::default())),
+        let mut insert = |key: &str, v: Option<Value<'a>>| {
+            if let Some(v) = v {
+                map.insert(key.to_string(), v);
+            }
+        };
+        insert("mpris:artUrl", value.art_url.map(Value::from));
+        insert(
+            "mpris:length",
+            value.length.map(|len| Value::I64(len.cast_signed())),
         );
-        map.insert(
-            "xesam:album".to_string(),
-            Value::from(value.album.unwrap_or(String::default())),
-        );
-        map.insert(
-            "xesam:artist".to_string(),
-            Value::from(value.artists.unwrap_or(Vec::default())),
-        );
-        map.insert(
-            "xesam:title".to_string(),
-            Value::from(value.title.unwrap_or(String::default())),
-        );
-        map.insert(
-            "xesam:url".to_string(),
-            Value::from(value.url.unwrap_or(String::default())),
-        );
-        map.insert(
-            "xesam:albumArtist".to_string(),
-            Value::from(value.album_artists.unwrap_or(Vec::default())),
-        );
-        map.insert(
-            "xesam:trackNumber".to_string(),
-            Value::from(value.track_number.unwrap_or(0)),
-        );
-        map.insert(
-            "xesam:discNumber".to_string(),
-            Value::from(value.disc_number.unwrap_or(0)),
-        );
-        map.insert(
-            "xesam:autoRating".to_string(),
-            Value::from(value.auto_rating.unwrap_or(0.0)),
+        insert(
+            "mpris:trackid",
+            value
+                .trackid
+                .map(|id| match ObjectPath::try_from(id.0.clone()) {
+                    Ok(path) => Value::from(path),
+                    Err(_) => Value::from(id.0),
+                }),
         );
+        insert("xesam:album", value.album.map(Value::from));
+        insert("xesam:artist", value.artists.map(Value::from));
+        insert("xesam:title", value.title.map(Value::from));
+        insert("xesam:url", value.url.map(Value::from));
+        insert("xesam:albumArtist", value.album_artists.map(Value::from));
+        insert("xesam:trackNumber", value.track_number.map(Value::from));
+        insert("xesam:discNumber", value.disc_number.map(Value::from));
+        insert("xesam:autoRating", value.auto_rating.map(Value::from));
+        for (lang, title) in value.localized_titles {
+            map.insert(format!("xesam:title@{lang}"), Value::from(title));
+        }
+        for (lang, album) in value.localized_albums {
+            map.insert(format!("xesam:album@{lang}"), Value::from(album));
+        }
 
         map
     }
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize)]
 #[allow(dead_code)]
 pub struct Capabilities {
     pub can_control: bool,
@@ -558,8 +951,19 @@ pub struct Capabilities {
 impl<'a> TryFrom<HashMap<&str, Value<'a>>> for Capabilities {
     type Error = anyhow::Error;
 
-    #[instrument(skip_all)]
     fn try_from(value: HashMap<&str, Value<'a>>) -> anyhow::Result<Self> {
+        Self::from_properties(value, false)
+    }
+}
+
+impl Capabilities {
+    /// parses the properties of the player interface
+    ///
+    /// `relaxed` is for bluetooth devices (see [`Player::is_bluetooth`]) and browsers (see
+    /// [`crate::quirks`]), which can leave out `Metadata`, `Rate` and `Position` and send
+    /// partial metadata with odd types
+    #[instrument(skip_all)]
+    pub fn from_properties(value: HashMap<&str, Value<'_>>, relaxed: bool) -> anyhow::Result<Self> {
         let can_control: bool = value
             .get("CanControl")
             .unwrap_or(&Value::Bool(false))
@@ -599,18 +1003,17 @@ impl<'a> TryFrom<HashMap<&str, Value<'a>>> for Capabilities {
             .map(TryInto::try_into)
             .transpose()?;
 
-        let metadata: Metadata = TryInto::<HashMap<String, Value>>::try_into(
-            value
-                .get("Metadata")
-                .ok_or(anyhow!("can not find Metadata"))?
-                .try_clone()?,
-        )?
-        .try_into()?;
-
-        let rate: f64 = value
-            .get("Rate")
-            .ok_or(anyhow!("can not find Rate"))?
-            .try_into()?;
+        let metadata = match value.get("Metadata") {
+            Some(metadata) => parse_metadata(metadata, relaxed)?,
+            None if relaxed => Metadata::default(),
+            None => bail!("can not find Metadata"),
+        };
+
+        let rate: f64 = match value.get("Rate") {
+            Some(rate) => rate.try_into()?,
+            None if relaxed => 1.0,
+            None => bail!("can not find Rate"),
+        };
         let playback_status: PlaybackStatus = value
             .get("PlaybackStatus")
             .ok_or(anyhow!("can not find PlaybackStatus"))
@@ -618,14 +1021,13 @@ impl<'a> TryFrom<HashMap<&str, Value<'a>>> for Capabilities {
                 Value::Str(s) => PlaybackStatus::try_from(s),
                 _ => bail!("unsupported type"),
             })??;
-        let position = value
-            .get("Position")
-            .ok_or(anyhow!("can not find Position"))
-            .map(|f| match f {
-                Value::U64(f) => Ok(*f),
-                Value::I64(f) => Ok(f.cast_unsigned()),
-                _ => Err(anyhow!("incorrect or unsupported type for Position")),
-            })??;
+        let position = match value.get("Position") {
+            Some(Value::U64(f)) => *f,
+            Some(Value::I64(f)) => f.cast_unsigned(),
+            None if relaxed => 0,
+            Some(_) => bail!("incorrect or unsupported type for Position"),
+            None => bail!("can not find Position"),
+        };
 
         let volume: Option<f64> = value.get("Volume").map(TryInto::try_into).transpose()?;
 
@@ -649,6 +1051,31 @@ impl<'a> TryFrom<HashMap<&str, Value<'a>>> for Capabilities {
     }
 }
 
+impl Capabilities {
+    /// the loaded track, `None` when the metadata is [`Metadata::is_none`]
+    pub fn track(&self) -> Option<&Metadata> {
+        Some(&self.metadata).filter(|metadata| !metadata.is_none())
+    }
+
+    /// overrides the capabilities `quirks` says the player misreports, the metadata is left to
+    /// [`Metadata::apply_quirks`]
+    pub fn apply_quirks(&mut self, quirks: &Quirks) {
+        let overrides = [
+            (&mut self.can_control, quirks.can_control),
+            (&mut self.can_next, quirks.can_go_next),
+            (&mut self.can_previous, quirks.can_go_previous),
+            (&mut self.can_pause, quirks.can_pause),
+            (&mut self.can_play, quirks.can_play),
+            (&mut self.can_seek, quirks.can_seek),
+        ];
+        for (capability, value) in overrides {
+            if let Some(value) = value {
+                *capability = value;
+            }
+        }
+    }
+}
+
 impl From<Capabilities> for HashMap<String, OwnedValue> {
     fn from(value: Capabilities) -> HashMap<String, OwnedValue> {
         let mut map = HashMap::new();
@@ -657,15 +1084,18 @@ impl From<Capabilities> for HashMap<String, OwnedValue> {
             "CanControl".to_string(),
             OwnedValue::from(value.can_control),
         );
-        map.insert("CanGoNext".to_string(), OwnedValue::from(value.can_control));
+        map.insert("CanGoNext".to_string(), OwnedValue::from(value.can_next));
         map.insert(
             "CanGoPrevious".to_string(),
-            OwnedValue::from(value.can_control),
+            OwnedValue::from(value.can_previous),
+        );
+        map.insert("CanPause".to_string(), OwnedValue::from(value.can_pause));
+        map.insert("CanPlay".to_string(), OwnedValue::from(value.can_play));
+        map.insert("CanSeek".to_string(), OwnedValue::from(value.can_seek));
+        map.insert(
+            "Position".to_string(),
+            OwnedValue::from(value.position.cast_signed()),
         );
-        map.insert("CanPause".to_string(), OwnedValue::from(value.can_control));
-        map.insert("CanPlay".to_string(), OwnedValue::from(value.can_control));
-        map.insert("CanSeek".to_string(), OwnedValue::from(value.can_control));
-        map.insert("Position".to_string(), OwnedValue::from(value.position));
         map.insert(
             "Shuffle".to_string(),
             OwnedValue::from(value.shuffle.unwrap_or(false)),
@@ -702,23 +1132,309 @@ impl From<Capabilities> for HashMap<String, OwnedValue> {
     }
 }
 
-#[derive(Debug)]
+/// properties of the root `org.mpris.MediaPlayer2` interface
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RootProperties {
+    pub identity: Option<String>,
+    pub desktop_entry: Option<String>,
+    pub can_quit: bool,
+    pub can_raise: bool,
+    pub has_track_list: bool,
+    pub supported_uri_schemes: Vec<String>,
+    pub supported_mime_types: Vec<String>,
+}
+
+impl<'a> TryFrom<HashMap<&str, Value<'a>>> for RootProperties {
+    type Error = anyhow::Error;
+
+    fn try_from(value: HashMap<&str, Value<'a>>) -> anyhow::Result<Self> {
+        let mut root = Self::default();
+        root.update(&value)?;
+        Ok(root)
+    }
+}
+
+impl RootProperties {
+    /// takes over the properties in `changed`, leaving the rest as they are
+    pub fn update(&mut self, changed: &HashMap<&str, Value<'_>>) -> anyhow::Result<()> {
+        let string = |key| -> anyhow::Result<Option<Option<String>>> {
+            match changed.get(key).map(unwrap_variant) {
+                Some(Value::Str(s)) if !s.is_empty() => Ok(Some(Some(s.to_string()))),
+                Some(Value::Str(_)) => Ok(Some(None)),
+                None => Ok(None),
+                Some(v) => bail!("incorrect type for {key}: {v:?}"),
+            }
+        };
+        let boolean = |key| -> anyhow::Result<Option<bool>> {
+            Ok(changed
+                .get(key)
+                .map(|v| bool::try_from(unwrap_variant(v)))
+                .transpose()?)
+        };
+        let strings = |key| -> anyhow::Result<Option<Vec<String>>> {
+            match changed.get(key) {
+                Some(v) => Ok(Some(unwrap_variant(v).try_clone()?.try_into()?)),
+                None => Ok(None),
+            }
+        };
+
+        if let Some(identity) = string("Identity")? {
+            self.identity = identity;
+        }
+        if let Some(desktop_entry) = string("DesktopEntry")? {
+            self.desktop_entry = desktop_entry;
+        }
+        if let Some(can_quit) = boolean("CanQuit")? {
+            self.can_quit = can_quit;
+        }
+        if let Some(can_raise) = boolean("CanRaise")? {
+            self.can_raise = can_raise;
+        }
+        if let Some(has_track_list) = boolean("HasTrackList")? {
+            self.has_track_list = has_track_list;
+        }
+        if let Some(schemes) = strings("SupportedUriSchemes")? {
+            self.supported_uri_schemes = schemes;
+        }
+        if let Some(mime_types) = strings("SupportedMimeTypes")? {
+            self.supported_mime_types = mime_types;
+        }
+        Ok(())
+    }
+}
+
+// names for players whose bus name doesn't capitalize well
+const KNOWN_NAMES: &[(&str, &str)] = &[
+    ("vlc", "VLC"),
+    ("mpv", "mpv"),
+    ("kdeconnect", "KDE Connect"),
+    ("plasma-browser-integration", "Plasma Browser Integration"),
+];
+
+/// a bus name split into the player and which of its instances it is
+///
+/// `org.mpris.MediaPlayer2.chromium.instance1234` is `chromium` with instance `1234`,
+/// `org.mpris.MediaPlayer2.firefox.instance_1_84` is `firefox` with `1_84` and
+/// `org.mpris.MediaPlayer2.kdeconnect.mpris_000001` is `kdeconnect` with `mpris_000001`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PlayerName {
+    pub base: String,
+    pub instance: Option<String>,
+}
+
+impl PlayerName {
+    /// takes the full bus name or the part after `org.mpris.MediaPlayer2.`
+    pub fn parse(bus_name: &str) -> Self {
+        let short = bus_name
+            .strip_prefix(MPRIS_PREFIX)
+            .and_then(|n| n.strip_prefix('.'))
+            .unwrap_or(bus_name);
+        let (base, suffix) = short.split_once('.').unwrap_or((short, ""));
+
+        // `instance1234`, `instance_1_23` (chromium) or `instance-2`
+        let instance = suffix
+            .strip_prefix("instance")
+            .map(|i| i.trim_start_matches(['_', '-']))
+            .unwrap_or(suffix);
+
+        Self {
+            base: base.to_string(),
+            instance: Some(instance.to_string()).filter(|i| !i.is_empty()),
+        }
+    }
+}
+
+/// a name for `bus_name` suitable for showing to users
+///
+/// uses `identity`, then the last component of `desktop_entry`, then the bus name itself without
+/// its instance suffix, see [`Player::display_name`] for telling instances apart.
+pub fn display_name(bus_name: &str, identity: Option<&str>, desktop_entry: Option<&str>) -> String {
+    identity
+        .map(str::trim)
+        .filter(|i| !i.is_empty())
+        .map(str::to_string)
+        .or_else(|| {
+            desktop_entry
+                .map(|e| e.trim_end_matches(".desktop"))
+                .and_then(|e| e.rsplit('.').next())
+                .filter(|e| !e.is_empty())
+                .map(friendly)
+        })
+        .unwrap_or_else(|| friendly(&PlayerName::parse(bus_name).base))
+}
+
+fn friendly(name: &str) -> String {
+    let lower = name.to_lowercase();
+    if let Some((_, known)) = KNOWN_NAMES.iter().find(|(n, _)| *n == lower) {
+        return known.to_string();
+    }
+
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum PlayerUpdated {
     PlaybackStatus(PlaybackStatus),
     Metadata(Box<Metadata>),
+    CanControl(bool),
+    CanGoNext(bool),
     CanGoPrevious(bool),
+    CanPause(bool),
+    CanPlay(bool),
+    CanSeek(bool),
+    LoopStatus(LoopStatus),
+    Rate(f64),
+    Shuffle(bool),
+    Volume(f64),
 }
 
-#[derive(Debug)]
+impl PlayerUpdated {
+    /// the name of the MPRIS property that changed
+    pub fn property(&self) -> &'static str {
+        match self {
+            Self::PlaybackStatus(_) => "PlaybackStatus",
+            Self::Metadata(_) => "Metadata",
+            Self::CanControl(_) => "CanControl",
+            Self::CanGoNext(_) => "CanGoNext",
+            Self::CanGoPrevious(_) => "CanGoPrevious",
+            Self::CanPause(_) => "CanPause",
+            Self::CanPlay(_) => "CanPlay",
+            Self::CanSeek(_) => "CanSeek",
+            Self::LoopStatus(_) => "LoopStatus",
+            Self::Rate(_) => "Rate",
+            Self::Shuffle(_) => "Shuffle",
+            Self::Volume(_) => "Volume",
+        }
+    }
+}
+
+/// one of the `Can*` variants of [`PlayerUpdated`]
+type FlagUpdate = fn(bool) -> PlayerUpdated;
+
+#[derive(Debug, Clone)]
 pub enum MprisEvent {
-    PlayerAdded,
-    PlayerRemoved,
-    PlayerUpdated(PlayerUpdated),
+    PlayerAdded(String),
+    PlayerRemoved(String),
+    /// another process took over the player's name, its state was read again
+    PlayerRestarted(String),
+    /// the client's bus connection went away, nothing changes until it is back
+    ConnectionLost,
+    /// the client has a connection again, what changed meanwhile comes with this
+    ConnectionRestored,
+    PlayerUpdated {
+        player: String,
+        update: PlayerUpdated,
+    },
+    /// the player moved on to a different track, as opposed to sending new metadata for the
+    /// same one (browsers fill in the length late for example)
+    TrackChanged {
+        player: String,
+        metadata: Box<Metadata>,
+    },
+    /// the player unloaded its track and has nothing to play, see [`Metadata::is_none`]
+    TrackCleared {
+        player: String,
+    },
+    /// the whole tracklist changed, only the ids are known, see [`Player::refresh_tracklist`]
+    TrackListReplaced {
+        player: String,
+        tracks: Vec<TrackId>,
+        current: TrackId,
+    },
+    TrackAdded {
+        player: String,
+        metadata: Box<Metadata>,
+        after: TrackId,
+    },
+    TrackRemoved {
+        player: String,
+        track: TrackId,
+    },
+    TrackMetadataChanged {
+        player: String,
+        track: TrackId,
+        metadata: Box<Metadata>,
+    },
+    /// the player jumped to `position` (microseconds) on its own or because it was asked to
+    Seeked {
+        player: String,
+        position: u64,
+    },
+    /// the playing track ends in `remaining`, see
+    /// [`MprisClient::set_track_ending_soon`](crate::MprisClient::set_track_ending_soon)
+    TrackEndingSoon {
+        player: String,
+        remaining: Duration,
+    },
+    /// a playlist was renamed or got a new icon
+    PlaylistUpdated {
+        player: String,
+        playlist: Playlist,
+    },
+    /// a different player became [`MprisClient::active_player`](crate::MprisClient::active_player),
+    /// `None` when the last one went away
+    ActivePlayerChanged {
+        player: Option<String>,
+    },
+    /// spotify started playing an ad, see [`Player::is_ad`]
+    AdStarted {
+        player: String,
+    },
+    /// the ads are over and spotify plays music again
+    AdEnded {
+        player: String,
+    },
+    /// a signal from the player couldn't be understood and was skipped, only sent with
+    /// [`EventLoopConfig::emit_parse_errors`](crate::EventLoopConfig::emit_parse_errors)
+    ParseError {
+        player: String,
+        error: String,
+    },
+}
+
+/// identifies a player within one [`MprisClient`](crate::MprisClient), unlike an index it stays
+/// valid when other players come and go and isn't reused for a later player
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PlayerId(usize);
+
+impl PlayerId {
+    pub(crate) fn new(id: usize) -> Self {
+        Self(id)
+    }
+}
+
+impl fmt::Display for PlayerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
 }
 
 pub struct Player {
+    id: PlayerId,
     pub(crate) capabilities: Capabilities,
+    root: RootProperties,
+    tracklist: Vec<Metadata>,
+    can_edit_tracks: bool,
     name: String,
+    // which concurrent instance of the same player this is, part of the stable id
+    instance: u32,
+    language: Option<String>,
+    last_updated: Option<Instant>,
+    limits: MetadataLimits,
+    // from the desktop entry, resolved along with the root properties
+    icon: Option<String>,
+    position: PositionTracker,
+    // when the playback status last changed, or when the player was found
+    status_since: Instant,
+    bluetooth: bool,
+    quirks: Quirks,
+    // whether spotify is playing an ad, see `Metadata::is_ad`
+    ad: bool,
+    call_policy: CallPolicy,
 }
 
 impl std::fmt::Debug for Player {
@@ -728,27 +1444,432 @@ impl std::fmt::Debug for Player {
 }
 
 impl Player {
+    /// reads the current state of `name`
+    ///
+    /// the player holds no signal stream, so this is fine for one-shot queries. it is only kept
+    /// up to date once added to an [`MprisClient`](crate::MprisClient).
     // #[tracing::instrument(skip(conn), ret, err)]
     pub async fn new(conn: &Connection, name: String) -> anyhow::Result<Self> {
-        let properties = conn
-            .call_method(
-                Some(&*name),
-                MPRIS_PATH,
-                Some(DBUS_PROPERTIES),
-                DbusMethods::GetAll,
-                &("org.mpris.MediaPlayer2.Player"),
-            )
-            .await?;
+        Self::new_with_quirks(conn, name, &QuirkRegistry::default(), CallPolicy::DEFAULT).await
+    }
+
+    /// [`Player::new`], correcting the player's state with the rules of `registry` that match it
+    /// and calling it with `policy` from the start
+    pub async fn new_with_quirks(
+        conn: &Connection,
+        name: String,
+        registry: &QuirkRegistry,
+        policy: CallPolicy,
+    ) -> anyhow::Result<Self> {
+        // not every player implements the root interface properly, it only adds niceties
+        let root = Self::fetch_root(conn, &policy, &name)
+            .await
+            .inspect_err(|e| warn!(player = name, "failed to get root properties: {e:?}"))
+            .unwrap_or_default();
+        let quirks = registry.lookup(&name, root.identity.as_deref());
+        let bluetooth = is_bluez_name(&name) || is_bluez_proxy(conn, &name).await;
+        let relaxed = bluetooth || quirks.is_relaxed();
+        let properties = Self::fetch_registered(conn, &policy, &name, relaxed).await?;
+
+        let mut player = Self::from_capabilities(name, properties)
+            .with_root(root)
+            .with_quirks(quirks);
+        player.call_policy = policy;
+        player.bluetooth |= bluetooth;
+        if let Err(e) = player.refresh_tracklist(conn).await {
+            warn!(player = player.name, "failed to get tracklist: {e:?}");
+        }
+
+        Ok(player)
+    }
+
+    /// re-reads both interfaces and replaces the cached state, for when it may have drifted
+    /// because signals got lost, like over a suspend and resume
+    pub async fn refresh(&mut self, conn: &Connection) -> anyhow::Result<()> {
+        self.refresh_at(conn, Instant::now(), &mut Vec::new()).await
+    }
+
+    /// [`Player::refresh`] for clients that use their own [`Clock`](crate::clock::Clock),
+    /// pushing the events what changed causes onto `events`
+    pub async fn refresh_at(
+        &mut self,
+        conn: &Connection,
+        now: Instant,
+        events: &mut Vec<MprisEvent>,
+    ) -> anyhow::Result<()> {
+        let capabilities =
+            Self::fetch_capabilities_as(conn, &self.call_policy, &self.name, self.is_relaxed())
+                .await?;
+        match Self::fetch_root(conn, &self.call_policy, &self.name).await {
+            Ok(root) => {
+                self.icon = root.desktop_entry.as_deref().and_then(desktop::icon);
+                self.root = root;
+            }
+            Err(e) => warn!(player = self.name, "failed to get root properties: {e:?}"),
+        }
+        if let Err(e) = self.refresh_tracklist(conn).await {
+            warn!(player = self.name, "failed to get tracklist: {e:?}");
+        }
+
+        // what signals would have told us goes through `apply` for the events, what no signal
+        // carries is replaced as is
+        let Capabilities {
+            max_rate,
+            min_rate,
+            position,
+            ..
+        } = capabilities;
+        for update in self.updates_to(capabilities) {
+            self.apply(update, now, events);
+        }
+        self.capabilities.max_rate = max_rate;
+        self.capabilities.min_rate = min_rate;
+        self.capabilities.position = position;
+        self.position.set_position(position, now);
+
+        Ok(())
+    }
+
+    /// [`Player::fetch_capabilities_as`] for a player that may still be setting up
+    ///
+    /// some players take their bus name a moment before they export the player interface, so
+    /// one that doesn't have it yet is asked again with backoff instead of failing right away.
+    async fn fetch_registered(
+        conn: &Connection,
+        policy: &CallPolicy,
+        name: &str,
+        relaxed: bool,
+    ) -> anyhow::Result<Capabilities> {
+        let mut backoff = REGISTRATION_BACKOFF.iter();
+        loop {
+            // players that can't be introspected get the benefit of the doubt
+            let result = match Self::has_player_interface(conn, policy, name).await {
+                Some(false) => Err(anyhow!("no {MPRIS_PLAYER_PREFIX} interface yet")),
+                _ => match Self::fetch_capabilities_as(conn, policy, name, relaxed).await {
+                    Err(e) if is_unregistered(&e) => Err(e),
+                    result => return result,
+                },
+            };
+            let Some(delay) = backoff.next() else {
+                return result;
+            };
+            debug!(player = name, ?delay, "player interface missing, retrying");
+            tokio::time::sleep(*delay).await;
+        }
+    }
+
+    /// whether `name` exports the player interface, going by its introspection data. `None`
+    /// when it can't be introspected
+    pub async fn has_player_interface(
+        conn: &Connection,
+        policy: &CallPolicy,
+        name: &str,
+    ) -> Option<bool> {
+        let reply = call_method(
+            conn,
+            policy,
+            name,
+            MPRIS_PATH,
+            "org.freedesktop.DBus.Introspectable",
+            "Introspect",
+            &(),
+        )
+        .await
+        .inspect_err(|e| debug!(player = name, "can't introspect: {e}"))
+        .ok()?;
+        let xml: String = reply.body().deserialize().ok()?;
+        Some(xml.contains(&format!("<interface name=\"{MPRIS_PLAYER_PREFIX}\"")))
+    }
+
+    /// runs `GetAll` on the root `org.mpris.MediaPlayer2` interface of `name`
+    pub async fn fetch_root(
+        conn: &Connection,
+        policy: &CallPolicy,
+        name: &str,
+    ) -> anyhow::Result<RootProperties> {
+        let properties = call_method(
+            conn,
+            policy,
+            name,
+            MPRIS_PATH,
+            DBUS_PROPERTIES,
+            DbusMethods::GetAll,
+            &(MPRIS_PREFIX),
+        )
+        .await?;
 
         let body = properties.body();
-        let properties: Capabilities = body.deserialize::<HashMap<&str, Value>>()?.try_into()?;
+        body.deserialize::<HashMap<&str, Value>>()?.try_into()
+    }
 
-        Ok(Self {
-            capabilities: properties,
+    /// runs `GetAll` on the player interface of `name`
+    pub async fn fetch_capabilities(
+        conn: &Connection,
+        policy: &CallPolicy,
+        name: &str,
+    ) -> anyhow::Result<Capabilities> {
+        Self::fetch_capabilities_as(conn, policy, name, false).await
+    }
+
+    /// [`Player::fetch_capabilities`], parsing like [`Capabilities::from_properties`]
+    pub async fn fetch_capabilities_as(
+        conn: &Connection,
+        policy: &CallPolicy,
+        name: &str,
+        relaxed: bool,
+    ) -> anyhow::Result<Capabilities> {
+        let properties = call_method(
+            conn,
+            policy,
+            name,
+            MPRIS_PATH,
+            DBUS_PROPERTIES,
+            DbusMethods::GetAll,
+            &("org.mpris.MediaPlayer2.Player"),
+        )
+        .await?;
+
+        let body = properties.body();
+        Capabilities::from_properties(body.deserialize()?, relaxed)
+    }
+
+    /// creates a player from already known state without talking to the bus
+    pub fn from_capabilities(name: String, capabilities: Capabilities) -> Self {
+        let now = Instant::now();
+        let position = PositionTracker::new(
+            capabilities.position,
+            capabilities.rate,
+            capabilities.playback_status,
+            now,
+        );
+        let bluetooth = is_bluez_name(&name) || is_bluez_track(&capabilities.metadata);
+        Self {
+            id: PlayerId::default(),
+            capabilities,
+            root: RootProperties::default(),
+            tracklist: Vec::new(),
+            can_edit_tracks: false,
             name,
+            instance: 0,
+            language: None,
+            last_updated: None,
+            limits: MetadataLimits::default(),
+            icon: None,
+            position,
+            status_since: now,
+            bluetooth,
+            quirks: Quirks::default(),
+            ad: false,
+            call_policy: CallPolicy::DEFAULT,
+        }
+    }
+
+    /// applies an update to the cached state, pushing the events it causes onto `events`
+    pub(crate) fn apply(
+        &mut self,
+        mut update: PlayerUpdated,
+        now: Instant,
+        events: &mut Vec<MprisEvent>,
+    ) {
+        debug!(
+            player = self.name,
+            property = update.property(),
+            "property changed"
+        );
+        self.last_updated = Some(now);
+        match &mut update {
+            PlayerUpdated::PlaybackStatus(playback_status) => {
+                // some players repeat the status with every other change
+                if self.capabilities.playback_status != *playback_status {
+                    self.status_since = now;
+                }
+                self.capabilities.playback_status = *playback_status;
+                self.position.set_status(*playback_status, now);
+            }
+            PlayerUpdated::Metadata(metadata) => {
+                self.bluetooth |= is_bluez_track(metadata);
+                metadata.apply_quirks(&self.quirks);
+                metadata.limit(&self.limits);
+                let cleared = metadata.is_none();
+                let changed = !self.capabilities.metadata.same_track(metadata);
+                let had_track = !self.capabilities.metadata.is_none();
+                self.capabilities.metadata = (**metadata).clone();
+                if cleared {
+                    if had_track {
+                        self.position.set_position(0, now);
+                        events.push(MprisEvent::TrackCleared {
+                            player: self.name.clone(),
+                        });
+                    }
+                } else if changed {
+                    // players seldom send `Seeked` for starting the next track
+                    self.position.set_position(0, now);
+                    events.push(MprisEvent::TrackChanged {
+                        player: self.name.clone(),
+                        metadata: metadata.clone(),
+                    });
+                }
+                let ad = self.is_spotify() && metadata.is_ad();
+                if ad != self.ad {
+                    self.ad = ad;
+                    let player = self.name.clone();
+                    events.push(if ad {
+                        MprisEvent::AdStarted { player }
+                    } else {
+                        MprisEvent::AdEnded { player }
+                    });
+                }
+            }
+            PlayerUpdated::CanControl(can_control) => {
+                *can_control = self.quirks.can_control.unwrap_or(*can_control);
+                self.capabilities.can_control = *can_control;
+            }
+            PlayerUpdated::CanGoNext(can_next) => {
+                *can_next = self.quirks.can_go_next.unwrap_or(*can_next);
+                self.capabilities.can_next = *can_next;
+            }
+            PlayerUpdated::CanGoPrevious(can_previous) => {
+                *can_previous = self.quirks.can_go_previous.unwrap_or(*can_previous);
+                self.capabilities.can_previous = *can_previous;
+            }
+            PlayerUpdated::CanPause(can_pause) => {
+                *can_pause = self.quirks.can_pause.unwrap_or(*can_pause);
+                self.capabilities.can_pause = *can_pause;
+            }
+            PlayerUpdated::CanPlay(can_play) => {
+                *can_play = self.quirks.can_play.unwrap_or(*can_play);
+                self.capabilities.can_play = *can_play;
+            }
+            PlayerUpdated::CanSeek(can_seek) => {
+                *can_seek = self.quirks.can_seek.unwrap_or(*can_seek);
+                self.capabilities.can_seek = *can_seek;
+            }
+            PlayerUpdated::LoopStatus(loop_status) => {
+                self.capabilities.loop_status = Some(*loop_status);
+            }
+            PlayerUpdated::Rate(rate) => {
+                self.capabilities.rate = *rate;
+                self.position.set_rate(*rate, now);
+            }
+            PlayerUpdated::Shuffle(shuffle) => {
+                self.capabilities.shuffle = Some(*shuffle);
+            }
+            PlayerUpdated::Volume(volume) => {
+                self.capabilities.volume = Some(*volume);
+            }
+        }
+
+        events.push(MprisEvent::PlayerUpdated {
+            player: self.name.clone(),
+            update,
+        });
+    }
+
+    /// the updates that bring the cached state to `caps`, freshly read from the player. the
+    /// metadata is always among them, [`Player::apply`] tells whether the track changed
+    pub(crate) fn updates_to(&self, mut caps: Capabilities) -> Vec<PlayerUpdated> {
+        caps.apply_quirks(&self.quirks);
+        let current = &self.capabilities;
+        let mut updates = Vec::new();
+        if caps.playback_status != current.playback_status {
+            updates.push(PlayerUpdated::PlaybackStatus(caps.playback_status));
+        }
+        let capabilities: [(bool, bool, FlagUpdate); 6] = [
+            (
+                caps.can_control,
+                current.can_control,
+                PlayerUpdated::CanControl,
+            ),
+            (caps.can_next, current.can_next, PlayerUpdated::CanGoNext),
+            (
+                caps.can_previous,
+                current.can_previous,
+                PlayerUpdated::CanGoPrevious,
+            ),
+            (caps.can_pause, current.can_pause, PlayerUpdated::CanPause),
+            (caps.can_play, current.can_play, PlayerUpdated::CanPlay),
+            (caps.can_seek, current.can_seek, PlayerUpdated::CanSeek),
+        ];
+        for (new, old, update) in capabilities {
+            if new != old {
+                updates.push(update(new));
+            }
+        }
+        if let Some(loop_status) = caps.loop_status.filter(|s| Some(*s) != current.loop_status) {
+            updates.push(PlayerUpdated::LoopStatus(loop_status));
+        }
+        if caps.rate != current.rate {
+            updates.push(PlayerUpdated::Rate(caps.rate));
+        }
+        if let Some(shuffle) = caps.shuffle.filter(|s| Some(*s) != current.shuffle) {
+            updates.push(PlayerUpdated::Shuffle(shuffle));
+        }
+        if let Some(volume) = caps.volume.filter(|v| Some(*v) != current.volume) {
+            updates.push(PlayerUpdated::Volume(volume));
+        }
+        updates.push(PlayerUpdated::Metadata(Box::new(caps.metadata)));
+        updates
+    }
+
+    /// handles a `Seeked` signal
+    pub(crate) fn seeked(&mut self, position: u64, now: Instant, events: &mut Vec<MprisEvent>) {
+        self.last_updated = Some(now);
+        self.capabilities.position = position;
+        self.position.set_position(position, now);
+        events.push(MprisEvent::Seeked {
+            player: self.name.clone(),
+            position,
+        });
+    }
+
+    /// where the player should be by now in microseconds, interpolated from the last known
+    /// position, see [`PositionTracker`]
+    ///
+    /// the [`Capabilities::position`] snapshot is only as fresh as the last
+    /// [`Player::fetch_position`] or `Seeked`.
+    pub fn estimated_position(&self) -> u64 {
+        self.estimated_position_at(Instant::now())
+    }
+
+    /// [`Player::estimated_position`] for clients that use their own [`Clock`](crate::clock::Clock)
+    pub fn estimated_position_at(&self, now: Instant) -> u64 {
+        position::within(
+            self.position.estimate(now),
+            self.capabilities.metadata.length(),
+        )
+    }
+
+    /// what [`MprisClient::position_ticks`](crate::MprisClient::position_ticks) needs to keep
+    /// estimating, `None` unless it is playing
+    pub(crate) fn playing(&self) -> Option<Playing> {
+        (self.capabilities.playback_status == PlaybackStatus::Playing).then(|| Playing {
+            player: self.name.clone(),
+            tracker: self.position,
+            length: self.capabilities.metadata.length(),
         })
     }
 
+    /// since when the player is playing, `None` while it isn't
+    ///
+    /// for players that were already playing when they were found this is when they were found.
+    pub fn playing_since(&self) -> Option<Instant> {
+        self.since(PlaybackStatus::Playing)
+    }
+
+    /// since when the player is paused, `None` while it isn't. like
+    /// [`Player::playing_since`] this starts when the player was found at the earliest
+    pub fn paused_since(&self) -> Option<Instant> {
+        self.since(PlaybackStatus::Paused)
+    }
+
+    fn since(&self, status: PlaybackStatus) -> Option<Instant> {
+        (self.capabilities.playback_status == status).then_some(self.status_since)
+    }
+
+    pub fn position_tracker(&self) -> &PositionTracker {
+        &self.position
+    }
+
     #[must_use]
     pub fn capabilities(&self) -> &Capabilities {
         &self.capabilities
@@ -758,204 +1879,757 @@ impl Player {
         &mut self.capabilities
     }
 
+    pub fn with_root(mut self, root: RootProperties) -> Self {
+        self.icon = root.desktop_entry.as_deref().and_then(desktop::icon);
+        self.root = root;
+        self
+    }
+
+    /// what the player calls itself, like `Spotify` or `VLC media player`
+    pub fn identity(&self) -> Option<&str> {
+        self.root.identity.as_deref()
+    }
+
+    /// the icon of the player's desktop entry, an icon theme name or an absolute path
+    ///
+    /// bluetooth devices have no desktop entry and get the `bluetooth` theme icon
+    pub fn icon(&self) -> Option<&str> {
+        self.icon
+            .as_deref()
+            .or_else(|| self.bluetooth.then_some(BLUETOOTH_ICON))
+    }
+
+    /// whether `name` is the player's identity or display name, ignoring case
+    pub fn is_called(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        self.identity()
+            .is_some_and(|identity| identity.to_lowercase() == name)
+            || self.display_name().to_lowercase() == name
+    }
+
+    pub fn root(&self) -> &RootProperties {
+        &self.root
+    }
+
+    /// whether the player runs on another device, like the phone players kdeconnect exposes
+    ///
+    /// those show up as `org.mpris.MediaPlayer2.kdeconnect.mpris_<n>`, their positions lag
+    /// behind and they claim capabilities the phone's player may not have.
+    pub fn is_remote(&self) -> bool {
+        self.short_name().starts_with("kdeconnect")
+            || self
+                .root
+                .desktop_entry
+                .as_deref()
+                .is_some_and(|entry| entry.contains("kdeconnect"))
+    }
+
+    /// whether spotify is playing an ad right now, see [`Metadata::is_ad`]
+    pub fn is_ad(&self) -> bool {
+        self.ad
+    }
+
+    fn is_spotify(&self) -> bool {
+        self.short_name().starts_with("spotify")
+    }
+
+    /// the bus name without `org.mpris.MediaPlayer2.`
+    fn short_name(&self) -> &str {
+        self.name
+            .strip_prefix(MPRIS_PREFIX)
+            .map(|name| name.trim_start_matches('.'))
+            .unwrap_or(&self.name)
+    }
+
+    /// whether the player is a bluetooth device, like a phone connected for its audio
+    ///
+    /// bluez exposes those through `mpris-proxy`. they only have part of the metadata, can't
+    /// seek and are parsed leniently, see [`Capabilities::from_properties`]. told apart by the
+    /// bus name, the process owning it and `mpris:trackid`s under `/org/bluez`.
+    pub fn is_bluetooth(&self) -> bool {
+        self.bluetooth
+    }
+
+    /// parses a signal of this player, see [`parse_properties_changed`]
+    pub fn parse_properties_changed(&self, msg: &Message) -> anyhow::Result<Vec<PlayerUpdated>> {
+        parse_properties_changed_as(msg, self.is_relaxed())
+    }
+
+    /// applies a `PropertiesChanged` signal of this player. changes to the root interface are
+    /// taken over silently, see [`Player::root`]
+    pub(crate) fn properties_changed(
+        &mut self,
+        msg: &Message,
+        now: Instant,
+        events: &mut Vec<MprisEvent>,
+    ) -> anyhow::Result<()> {
+        let updates = self.parse_properties_changed(msg)?;
+        if updates.is_empty() {
+            return self.root_changed(msg);
+        }
+        for update in updates {
+            self.apply(update, now, events);
+        }
+        Ok(())
+    }
+
+    fn root_changed(&mut self, msg: &Message) -> anyhow::Result<()> {
+        let body = msg.body();
+        let PropertiesChanged {
+            interface, changed, ..
+        } = body.deserialize()?;
+        if interface == MPRIS_PREFIX {
+            self.root.update(&changed)?;
+            self.icon = self.root.desktop_entry.as_deref().and_then(desktop::icon);
+        }
+        Ok(())
+    }
+
+    /// whether the player's properties are parsed leniently, see
+    /// [`Capabilities::from_properties`]
+    pub(crate) fn is_relaxed(&self) -> bool {
+        self.bluetooth || self.quirks.is_relaxed()
+    }
+
+    /// the corrections applied to the player, see [`crate::quirks`]
+    pub fn quirks(&self) -> &Quirks {
+        &self.quirks
+    }
+
+    /// applies `quirks` to the current state and everything parsed from now on
+    pub fn with_quirks(mut self, quirks: Quirks) -> Self {
+        self.capabilities.apply_quirks(&quirks);
+        self.capabilities.metadata.apply_quirks(&quirks);
+        self.quirks = quirks;
+        self
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
 
-    pub async fn play(&self, conn: &Connection) {
-        conn.call_method(
-            Some(&*self.name),
+    pub(crate) fn tracklist_mut(&mut self) -> &mut Vec<Metadata> {
+        &mut self.tracklist
+    }
+
+    /// the tracklist around the current track
+    pub fn queue(&self) -> Queue<'_> {
+        Queue::new(&self.tracklist, &self.capabilities.metadata)
+    }
+
+    /// whether the tracklist can be changed with [`Player::add_track`] and friends
+    pub fn can_edit_tracks(&self) -> bool {
+        self.can_edit_tracks
+    }
+
+    /// the tracks in the player's tracklist, empty unless it has one (`HasTrackList`)
+    pub fn tracklist(&self) -> &[Metadata] {
+        &self.tracklist
+    }
+
+    /// re-reads the `Tracks` of the tracklist interface and their metadata
+    pub async fn refresh_tracklist(&mut self, conn: &Connection) -> anyhow::Result<()> {
+        if !self.root.has_track_list {
+            self.tracklist.clear();
+            self.can_edit_tracks = false;
+            return Ok(());
+        }
+
+        let reply = call_method(
+            conn,
+            &self.call_policy,
+            &self.name,
+            MPRIS_PATH,
+            DBUS_PROPERTIES,
+            DbusMethods::GetAll,
+            &(MPRIS_TRACKLIST),
+        )
+        .await?;
+        let body = reply.body();
+        let properties = body.deserialize::<HashMap<&str, Value>>()?;
+        self.can_edit_tracks = properties
+            .get("CanEditTracks")
+            .map(bool::try_from)
+            .transpose()?
+            .unwrap_or(false);
+        let tracks: Vec<OwnedObjectPath> = match properties.get("Tracks") {
+            Some(tracks) => tracks.try_clone()?.try_into()?,
+            None => Vec::new(),
+        };
+
+        let mut tracklist = Vec::with_capacity(tracks.len());
+        if !tracks.is_empty() {
+            let reply = call_method(
+                conn,
+                &self.call_policy,
+                &self.name,
+                MPRIS_PATH,
+                MPRIS_TRACKLIST,
+                "GetTracksMetadata",
+                &(tracks),
+            )
+            .await?;
+
+            let body = reply.body();
+            for track in body.deserialize::<Vec<HashMap<String, Value>>>()? {
+                let mut metadata: Metadata = track.try_into()?;
+                metadata.limit(&self.limits);
+                tracklist.push(metadata);
+            }
+        }
+
+        self.tracklist = tracklist;
+        Ok(())
+    }
+
+    /// how well this player fits `uri`, `None` when it doesn't support it, see [`mime::score`]
+    pub fn uri_score(&self, uri: &str) -> Option<u32> {
+        mime::score(
+            uri,
+            &self.root.supported_uri_schemes,
+            &self.root.supported_mime_types,
+        )
+    }
+
+    /// which controls a frontend should offer for this player
+    pub fn ui_model(&self) -> UiModel {
+        UiModel::new(self)
+    }
+
+    pub fn id(&self) -> PlayerId {
+        self.id
+    }
+
+    pub(crate) fn set_id(&mut self, id: PlayerId) {
+        self.id = id;
+    }
+
+    /// an id that, unlike the bus name, stays the same across restarts of the player
+    pub fn stable_id(&self) -> StableId {
+        StableId::new(&self.stable_key(), self.instance)
+    }
+
+    pub(crate) fn stable_key(&self) -> String {
+        stable_id::stable_key(&self.name, self.root.desktop_entry.as_deref())
+    }
+
+    pub(crate) fn instance(&self) -> u32 {
+        self.instance
+    }
+
+    pub(crate) fn set_instance(&mut self, instance: u32) {
+        self.instance = instance;
+    }
+
+    /// a friendly name like `Firefox`, see [`display_name`]
+    ///
+    /// concurrent instances of the same player are numbered in the order they appeared, the
+    /// second chromium is `Chromium (2)`, rather than showing the pid from the bus name.
+    pub fn display_name(&self) -> String {
+        let name = display_name(
+            &self.name,
+            self.root.identity.as_deref(),
+            self.root.desktop_entry.as_deref(),
+        );
+
+        match self.instance {
+            0 => name,
+            n => format!("{name} ({})", n + 1),
+        }
+    }
+
+    pub fn player_name(&self) -> PlayerName {
+        PlayerName::parse(&self.name)
+    }
+
+    pub fn metadata_limits(&self) -> &MetadataLimits {
+        &self.limits
+    }
+
+    /// sets the limits for incoming metadata and applies them to the current one
+    pub fn set_metadata_limits(&mut self, limits: MetadataLimits) {
+        self.limits = limits;
+        self.capabilities.metadata.limit(&limits);
+    }
+
+    /// how calls to the player time out and retry, see [`CallPolicy`]
+    pub fn call_policy(&self) -> &CallPolicy {
+        &self.call_policy
+    }
+
+    pub fn set_call_policy(&mut self, policy: CallPolicy) {
+        self.call_policy = policy;
+    }
+
+    /// starts estimating the position from the cached one at `now`, it was taken with the
+    /// system clock when the player was created
+    pub(crate) fn anchor_position(&mut self, now: Instant) {
+        self.position.set_position(self.capabilities.position, now);
+    }
+
+    /// when the last signal from this player was handled, `None` if there wasn't one yet
+    pub fn last_updated(&self) -> Option<Instant> {
+        self.last_updated
+    }
+
+    /// the preferred language for localized metadata, see [`Metadata::title_in`]
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    pub fn set_language(&mut self, lang: Option<String>) {
+        self.language = lang;
+    }
+
+    /// template value for `key`, on top of the [`Metadata::field`] keys this knows about
+    ///
+    /// - `status`, `status_icon`: the playback status as text or a glyph
+    /// - `player`, `player_icon`: the bus name and its icon
+    /// - `playerName`: the bus name without `org.mpris.MediaPlayer2.`
+    /// - `volume`: between 0 and 1
+    /// - `display_name`: see [`Player::display_name`]
+    /// - `identity`, `icon`: see [`Player::identity`] and [`Player::icon`]
+    /// - `position`, `remaining`: formatted like `length`, see [`Player::estimated_position`]
+    /// - `progress`: position and length, like `1:23 / 4:56`
+    /// - `position_pct`: how far into the track the player is, `0` to `100`
+    pub fn field(&self, key: &str) -> Option<String> {
+        let caps = &self.capabilities;
+        let length = caps.metadata.length();
+        let position = self.estimated_position();
+        match key {
+            "title" | "xesam:title" => self.title().map(str::to_string),
+            "album" | "xesam:album" => self.album().map(str::to_string),
+            "status" => Some(caps.playback_status.to_string()),
+            "status_icon" => Some(icons::status_icon(caps.playback_status).to_string()),
+            "player" => Some(self.name.clone()),
+            "playerName" => Some(
+                self.name
+                    .strip_prefix(MPRIS_PREFIX)
+                    .map(|n| n.trim_start_matches('.'))
+                    .unwrap_or(&self.name)
+                    .to_string(),
+            ),
+            "volume" => caps.volume.map(|v| format!("{v:.2}")),
+            "display_name" => Some(self.display_name()),
+            "identity" => self.identity().map(str::to_string),
+            "icon" => self.icon().map(str::to_string),
+            "player_icon" if self.bluetooth => Some(icons::BLUETOOTH.to_string()),
+            "player_icon" => Some(icons::player_icon(&self.name).to_string()),
+            "position" => Some(progress::format_length(position)),
+            "remaining" => length.map(|len| progress::format_length(len.saturating_sub(position))),
+            "progress" => Some(progress::format_progress(position, length)),
+            "position_pct" => {
+                progress::percent(position, length).map(|pct| (pct as u64).to_string())
+            }
+            _ => caps.metadata.field(key),
+        }
+    }
+
+    /// formats the player state using a template, see [`Player::field`] for the keys
+    pub fn format(&self, template: &str) -> anyhow::Result<String> {
+        Ok(Template::parse(template)?.render(|key| self.field(key)))
+    }
+
+    /// the current title, localized to [`Player::language`] when the player provides it
+    pub fn title(&self) -> Option<&str> {
+        self.capabilities.metadata.title_in(self.language())
+    }
+
+    /// the current album, localized to [`Player::language`] when the player provides it
+    pub fn album(&self) -> Option<&str> {
+        self.capabilities.metadata.album_in(self.language())
+    }
+
+    pub async fn play(&self, conn: &Connection) -> anyhow::Result<()> {
+        call_method(
+            conn,
+            &self.call_policy,
+            &self.name,
             "/org/mpris/MediaPlayer2",
-            Some("org.mpris.MediaPlayer2.Player"),
+            "org.mpris.MediaPlayer2.Player",
             "Play",
             &(),
         )
-        .await
-        .unwrap();
+        .await?;
+
+        Ok(())
     }
 
-    pub async fn stop(&self, conn: &Connection) {
-        conn.call_method(
-            Some(&*self.name),
+    pub async fn stop(&self, conn: &Connection) -> anyhow::Result<()> {
+        call_method(
+            conn,
+            &self.call_policy,
+            &self.name,
             "/org/mpris/MediaPlayer2",
-            Some("org.mpris.MediaPlayer2.Player"),
+            "org.mpris.MediaPlayer2.Player",
             "Stop",
             &(),
         )
-        .await
-        .unwrap();
+        .await?;
+
+        Ok(())
     }
 
-    pub async fn next(&self, conn: &Connection) {
-        conn.call_method(
-            Some(&*self.name),
+    pub async fn next(&self, conn: &Connection) -> anyhow::Result<()> {
+        call_method(
+            conn,
+            &self.call_policy,
+            &self.name,
             "/org/mpris/MediaPlayer2",
-            Some("org.mpris.MediaPlayer2.Player"),
+            "org.mpris.MediaPlayer2.Player",
             "Next",
             &(),
         )
-        .await
-        .unwrap();
+        .await?;
+
+        Ok(())
     }
 
-    pub async fn prev(&self, conn: &Connection) {
-        conn.call_method(
-            Some(&*self.name),
+    pub async fn prev(&self, conn: &Connection) -> anyhow::Result<()> {
+        call_method(
+            conn,
+            &self.call_policy,
+            &self.name,
             "/org/mpris/MediaPlayer2",
-            Some("org.mpris.MediaPlayer2.Player"),
+            "org.mpris.MediaPlayer2.Player",
             "Previous",
             &(),
         )
-        .await
-        .unwrap();
+        .await?;
+
+        Ok(())
     }
 
-    pub async fn pause(&self, conn: &Connection) {
-        conn.call_method(
-            Some(&*self.name),
+    pub async fn pause(&self, conn: &Connection) -> anyhow::Result<()> {
+        call_method(
+            conn,
+            &self.call_policy,
+            &self.name,
             "/org/mpris/MediaPlayer2",
-            Some("org.mpris.MediaPlayer2.Player"),
+            "org.mpris.MediaPlayer2.Player",
             "Pause",
             &(),
         )
-        .await
-        .unwrap();
+        .await?;
+
+        Ok(())
     }
 
     pub async fn pause_play(&self, conn: &Connection) -> zbus::Result<Message> {
-        conn.call_method(
-            Some(&*self.name),
+        call_method(
+            conn,
+            &self.call_policy,
+            &self.name,
             "/org/mpris/MediaPlayer2",
-            Some("org.mpris.MediaPlayer2.Player"),
+            "org.mpris.MediaPlayer2.Player",
             "PausePlay",
             &(),
         )
         .await
     }
 
-    pub async fn seek(&self, conn: &Connection, nanos: u64) {
-        conn.call_method(
-            Some(&*self.name),
-            "/org/mpris/MediaPlayer2",
-            Some("org.mpris.MediaPlayer2.Player"),
-            "SetPosition",
-            &(nanos),
+    /// moves the position by `offset` microseconds, negative to go back
+    pub async fn seek(&self, conn: &Connection, offset: i64) -> anyhow::Result<()> {
+        call_method(
+            conn,
+            &self.call_policy,
+            self.name(),
+            MPRIS_PATH,
+            MPRIS_PLAYER_PREFIX,
+            "Seek",
+            &(offset),
         )
-        .await
-        .unwrap();
+        .await?;
+
+        Ok(())
     }
 
-    pub async fn set_position(&self, conn: &Connection, track_id: ObjectPath<'_>, nanos: u64) {
-        conn.call_method(
-            Some(&*self.name),
-            "/org/mpris/MediaPlayer2",
-            Some("org.mpris.MediaPlayer2.Player"),
+    /// seeks `by` ahead, players go to the next track when that is past the end
+    pub async fn seek_forward(&self, conn: &Connection, by: Duration) -> anyhow::Result<()> {
+        self.ensure_can_seek()?;
+        self.seek(conn, i64::try_from(by.as_micros())?).await
+    }
+
+    /// seeks `by` back, to the start of the track at most
+    pub async fn seek_backward(&self, conn: &Connection, by: Duration) -> anyhow::Result<()> {
+        self.ensure_can_seek()?;
+        self.seek(conn, -i64::try_from(by.as_micros())?).await
+    }
+
+    fn ensure_can_seek(&self) -> anyhow::Result<()> {
+        let caps = &self.capabilities;
+        anyhow::ensure!(
+            caps.can_control && caps.can_seek,
+            "{} can't seek",
+            self.name
+        );
+        Ok(())
+    }
+
+    /// jumps to `position` microseconds into `track_id`, players ignore this when `track_id`
+    /// isn't the current track
+    pub async fn set_position(
+        &self,
+        conn: &Connection,
+        track_id: ObjectPath<'_>,
+        position: u64,
+    ) -> anyhow::Result<()> {
+        call_method(
+            conn,
+            &self.call_policy,
+            self.name(),
+            MPRIS_PATH,
+            MPRIS_PLAYER_PREFIX,
             "SetPosition",
-            &(track_id, nanos),
+            &(track_id, position.cast_signed()),
         )
-        .await
-        .unwrap();
+        .await?;
+
+        Ok(())
     }
 
-    pub async fn open_uri(&self, conn: &Connection, uri: &str) {
-        conn.call_method(
-            Some(&*self.name),
-            "/org/mpris/MediaPlayer2",
-            Some("org.mpris.MediaPlayer2.Player"),
+    /// reads the position from the player, it isn't part of `PropertiesChanged` so the cached
+    /// [`Capabilities::position`] is only as recent as the last `GetAll`
+    pub async fn fetch_position(&mut self, conn: &Connection) -> anyhow::Result<u64> {
+        self.fetch_position_at(conn, Instant::now()).await
+    }
+
+    /// [`Player::fetch_position`] for clients that use their own [`Clock`](crate::clock::Clock)
+    pub async fn fetch_position_at(
+        &mut self,
+        conn: &Connection,
+        now: Instant,
+    ) -> anyhow::Result<u64> {
+        let msg = call_method(
+            conn,
+            &self.call_policy,
+            self.name(),
+            MPRIS_PATH,
+            DBUS_PROPERTIES,
+            DbusMethods::Get,
+            &(MPRIS_PLAYER_PREFIX, "Position"),
+        )
+        .await?;
+
+        let value: OwnedValue = msg.body().deserialize()?;
+        let position = match &*value {
+            Value::I64(p) => (*p).max(0).cast_unsigned(),
+            Value::U64(p) => *p,
+            value => bail!("Position has the wrong type: {value}"),
+        };
+        self.capabilities.position = position;
+        self.position.set_position(position, now);
+
+        Ok(position)
+    }
+
+    /// asks the player to open `uri`, see [`Player::uri_score`] for whether it claims to support it
+    pub async fn open_uri(&self, conn: &Connection, uri: &str) -> anyhow::Result<()> {
+        call_method(
+            conn,
+            &self.call_policy,
+            self.name(),
+            MPRIS_PATH,
+            MPRIS_PLAYER_PREFIX,
             "OpenUri",
             &(uri),
         )
-        .await
-        .unwrap();
-    }
+        .await?;
 
-    pub fn volume(&self) -> Option<f64> {
-        self.capabilities.volume
+        Ok(())
     }
 
-    pub async fn set_volume(&mut self, conn: &Connection, volume: f64) {
-        conn.call_method(
-            Some(self.name()),
+    /// brings the player's window to the front, players without one report
+    /// [`RootProperties::can_raise`] false
+    pub async fn raise(&self, conn: &Connection) -> anyhow::Result<()> {
+        call_method(
+            conn,
+            &self.call_policy,
+            self.name(),
             MPRIS_PATH,
-            Some("org.freedesktop.DBus.Properties"),
-            "Set",
-            &(MPRIS_PLAYER_PREFIX, "Volume", &Value::F64(volume)),
+            MPRIS_PREFIX,
+            "Raise",
+            &(),
         )
-        .await
-        .unwrap();
+        .await?;
 
-        self.capabilities.volume = Some(volume);
+        Ok(())
     }
 
-    pub async fn toggle_shuffle(&self, conn: &Connection, shuffle: bool) {
-        conn.call_method(
-            Some(self.name()),
+    /// asks the player to exit, see [`RootProperties::can_quit`]
+    pub async fn quit(&self, conn: &Connection) -> anyhow::Result<()> {
+        call_method(
+            conn,
+            &self.call_policy,
+            self.name(),
             MPRIS_PATH,
-            Some("org.freedesktop.DBus.Properties"),
-            "Set",
-            &(MPRIS_PLAYER_PREFIX, "Shuffle", &Value::from(shuffle)),
+            MPRIS_PREFIX,
+            "Quit",
+            &(),
         )
-        .await
-        .unwrap();
+        .await?;
+
+        Ok(())
     }
 
-    pub async fn set_loop_status(
+    pub fn volume(&self) -> Option<f64> {
+        self.capabilities.volume
+    }
+
+    /// sets a property of the player interface, failing without asking the player when it
+    /// reports `CanControl` false
+    pub async fn set_property(
         &self,
         conn: &Connection,
-        status: LoopStatus,
+        property: &str,
+        value: Value<'_>,
     ) -> anyhow::Result<()> {
-        conn.call_method(
-            Some(self.name()),
+        if !self.capabilities.can_control {
+            bail!("{} can not be controlled", self.name);
+        }
+
+        call_method(
+            conn,
+            &self.call_policy,
+            self.name(),
             MPRIS_PATH,
-            Some("org.freedesktop.DBus.Properties"),
+            DBUS_PROPERTIES,
             "Set",
-            &(MPRIS_PLAYER_PREFIX, "LoopStatus", Value::from(status)),
+            &(MPRIS_PLAYER_PREFIX, property, value),
         )
         .await?;
 
         Ok(())
     }
+
+    /// sets the volume, negative values are treated as 0 and values above 1 are allowed
+    pub async fn set_volume(&mut self, conn: &Connection, volume: f64) -> anyhow::Result<()> {
+        let volume = volume.max(0.0);
+        self.set_property(conn, "Volume", Value::F64(volume))
+            .await?;
+        self.capabilities.volume = Some(volume);
+
+        Ok(())
+    }
+
+    pub async fn toggle_shuffle(&self, conn: &Connection, shuffle: bool) -> anyhow::Result<()> {
+        self.set_property(conn, "Shuffle", Value::from(shuffle))
+            .await
+    }
+
+    pub async fn set_loop_status(
+        &self,
+        conn: &Connection,
+        status: LoopStatus,
+    ) -> anyhow::Result<()> {
+        self.set_property(conn, "LoopStatus", Value::from(status))
+            .await
+    }
 }
 
 #[instrument]
-pub fn poll_player<'a>(stream: &mut SignalStream<'a>) -> Poll<PlayerUpdated> {
+/// `Ready(None)` once the stream is closed, `Pending` when there was nothing (of interest) to read
+///
+/// a signal that doesn't parse is handed out as an error rather than ending the stream
+pub fn poll_player<'a>(
+    stream: &mut SignalStream<'a>,
+) -> Poll<Option<anyhow::Result<Vec<PlayerUpdated>>>> {
+    match poll_message(stream) {
+        Poll::Ready(Some(msg)) => match parse_properties_changed(&msg) {
+            Ok(updates) if updates.is_empty() => Poll::Pending,
+            Ok(updates) => Poll::Ready(Some(Ok(updates))),
+            Err(e) => Poll::Ready(Some(Err(e))),
+        },
+        Poll::Ready(None) => Poll::Ready(None),
+        Poll::Pending => Poll::Pending,
+    }
+}
+
+/// the next signal on `stream` as it arrived, [`poll_player`] parses it too
+pub fn poll_message(stream: &mut SignalStream<'_>) -> Poll<Option<Message>> {
     let waker = WAKER;
     let mut cx = Context::from_waker(&waker);
-    if let Poll::Ready(Some(msg)) = stream.poll_next_unpin(&mut cx) {
-        let body = msg.body();
-        // returns interface (str), changed (vec), invalidated (vec), invalidated seems to always
-        // be empty
-        let structure: zbus::zvariant::Structure = body.deserialize().unwrap();
+    stream.poll_next_unpin(&mut cx)
+}
 
-        // let iface: zbus::zvariant::Str = structure.fields()[0].clone().try_into()?;
-        let changed: HashMap<String, zbus::zvariant::OwnedValue> =
-            structure.fields()[1].clone().try_into().unwrap();
+/// the body of a `PropertiesChanged` signal, borrowing from the message
+#[derive(Debug, Deserialize, Type)]
+pub struct PropertiesChanged<'a> {
+    #[serde(borrow)]
+    pub interface: &'a str,
+    #[serde(borrow)]
+    pub changed: HashMap<&'a str, Value<'a>>,
+    /// seems to always be empty
+    #[serde(borrow)]
+    pub invalidated: Vec<&'a str>,
+}
 
-        if let Some(status) = changed.get("PlaybackStatus") {
-            let val = &**status;
+/// the updates a `PropertiesChanged` signal of the player interface describes, one for each
+/// changed property the client keeps track of
+pub fn parse_properties_changed(msg: &Message) -> anyhow::Result<Vec<PlayerUpdated>> {
+    parse_properties_changed_as(msg, false)
+}
 
-            let val = match val {
-                Value::Str(s) => PlaybackStatus::try_from(s),
-                _ => panic!("incorrect type {val}"),
-            }
-            .unwrap();
+/// [`parse_properties_changed`], parsing the metadata like [`Capabilities::from_properties`]
+/// does when `relaxed`
+pub fn parse_properties_changed_as(
+    msg: &Message,
+    relaxed: bool,
+) -> anyhow::Result<Vec<PlayerUpdated>> {
+    let body = msg.body();
+    let PropertiesChanged {
+        interface, changed, ..
+    } = body.deserialize()?;
+    if interface != MPRIS_PLAYER_PREFIX {
+        return Ok(Vec::new());
+    }
 
-            return Poll::Ready(PlayerUpdated::PlaybackStatus(val));
-        }
-        if let Some(status) = changed.get("Metadata") {
-            let val = &**status;
-            if let Value::Dict(dict) = val {
-                let map: HashMap<String, Value> = dict.try_clone().unwrap().try_into().unwrap();
-                let metadata: Metadata = map.try_into().unwrap();
-                return Poll::Ready(PlayerUpdated::Metadata(Box::new(metadata)));
-            }
-        }
-        if let Some(status) = changed.get("CanGoPrevious") {
-            return Poll::Ready(PlayerUpdated::CanGoPrevious(
-                bool::try_from(status).unwrap(),
-            ));
+    let mut updates = Vec::new();
+    if let Some(status) = changed.get("PlaybackStatus") {
+        let status = match unwrap_variant(status) {
+            Value::Str(s) => PlaybackStatus::try_from(s)?,
+            val => bail!("PlaybackStatus has the wrong type: {val}"),
+        };
+        updates.push(PlayerUpdated::PlaybackStatus(status));
+    }
+    if let Some(metadata) = changed.get("Metadata") {
+        let metadata = match unwrap_variant(metadata) {
+            Value::Dict(_) | Value::Array(_) => parse_metadata(metadata, relaxed)?,
+            _ => bail!("Metadata has the wrong type: {metadata}"),
+        };
+        updates.push(PlayerUpdated::Metadata(Box::new(metadata)));
+    }
+    if let Some(rate) = changed.get("Rate") {
+        updates.push(PlayerUpdated::Rate(f64::try_from(unwrap_variant(rate))?));
+    }
+    if let Some(volume) = changed.get("Volume") {
+        updates.push(PlayerUpdated::Volume(f64::try_from(unwrap_variant(
+            volume,
+        ))?));
+    }
+    if let Some(shuffle) = changed.get("Shuffle") {
+        updates.push(PlayerUpdated::Shuffle(bool::try_from(unwrap_variant(
+            shuffle,
+        ))?));
+    }
+    if let Some(loop_status) = changed.get("LoopStatus") {
+        updates.push(PlayerUpdated::LoopStatus(LoopStatus::try_from(
+            unwrap_variant(loop_status),
+        )?));
+    }
+    let capabilities: [(&str, FlagUpdate); 6] = [
+        ("CanControl", PlayerUpdated::CanControl),
+        ("CanGoNext", PlayerUpdated::CanGoNext),
+        ("CanGoPrevious", PlayerUpdated::CanGoPrevious),
+        ("CanPause", PlayerUpdated::CanPause),
+        ("CanPlay", PlayerUpdated::CanPlay),
+        ("CanSeek", PlayerUpdated::CanSeek),
+    ];
+    for (property, update) in capabilities {
+        if let Some(value) = changed.get(property) {
+            updates.push(update(bool::try_from(unwrap_variant(value))?));
         }
     }
 
-    Poll::Pending
+    Ok(updates)
 }
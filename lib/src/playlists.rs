@@ -0,0 +1,99 @@
+//! the `org.mpris.MediaPlayer2.Playlists` interface
+
+use std::{
+    fmt,
+    str::FromStr,
+    task::{Context, Poll},
+};
+
+use anyhow::anyhow;
+use futures::StreamExt;
+use serde::Serialize;
+use tracing::warn;
+use zbus::{proxy::SignalStream, zvariant::OwnedObjectPath};
+
+use crate::WAKER;
+
+/// a playlist as the player describes it, `(oss)` on the bus
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Playlist {
+    pub id: String,
+    pub name: String,
+    /// an icon uri, empty when the playlist has none
+    pub icon: String,
+}
+
+impl From<(OwnedObjectPath, String, String)> for Playlist {
+    fn from((id, name, icon): (OwnedObjectPath, String, String)) -> Self {
+        Self {
+            id: id.to_string(),
+            name,
+            icon,
+        }
+    }
+}
+
+/// an order `GetPlaylists` can sort by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum PlaylistOrdering {
+    Alphabetical,
+    CreationDate,
+    ModifiedDate,
+    LastPlayDate,
+    UserDefined,
+}
+
+impl PlaylistOrdering {
+    /// the name MPRIS uses for the ordering
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Alphabetical => "Alphabetical",
+            Self::CreationDate => "Created",
+            Self::ModifiedDate => "Modified",
+            Self::LastPlayDate => "Played",
+            Self::UserDefined => "User",
+        }
+    }
+}
+
+impl fmt::Display for PlaylistOrdering {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PlaylistOrdering {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Alphabetical" => Ok(Self::Alphabetical),
+            "Created" => Ok(Self::CreationDate),
+            "Modified" => Ok(Self::ModifiedDate),
+            "Played" => Ok(Self::LastPlayDate),
+            "User" => Ok(Self::UserDefined),
+            _ => Err(anyhow!("invalid playlist ordering {s}")),
+        }
+    }
+}
+
+/// polls a stream of `PlaylistChanged` signals, signals that fail to parse are logged and skipped
+pub fn poll_playlists(stream: &mut SignalStream<'_>) -> Poll<Option<Playlist>> {
+    let waker = WAKER;
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        let msg = match stream.poll_next_unpin(&mut cx) {
+            Poll::Ready(Some(msg)) => msg,
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => return Poll::Pending,
+        };
+
+        match msg
+            .body()
+            .deserialize::<(OwnedObjectPath, String, String)>()
+        {
+            Ok(playlist) => return Poll::Ready(Some(playlist.into())),
+            Err(e) => warn!("failed to parse PlaylistChanged: {e:?}"),
+        }
+    }
+}
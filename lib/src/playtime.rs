@@ -0,0 +1,66 @@
+//! how long a track was actually listened to, for scrobbling and the history
+//!
+//! the position is sampled whenever the caller looks, the time between two samples counts as
+//! played unless the position moved faster than double speed, which is a seek.
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::player::Metadata;
+
+/// tells tracks apart: the trackid, the title and the artists
+pub type TrackKey = (Option<String>, Option<String>, Option<Vec<String>>);
+
+pub fn track_key(metadata: &Metadata) -> TrackKey {
+    (
+        metadata.track_id().map(|id| id.to_string()),
+        metadata.title().map(str::to_string),
+        metadata.artists().map(<[_]>::to_vec),
+    )
+}
+
+/// adds up the time one track played, seeking doesn't count
+#[derive(Debug, Clone, Default)]
+pub struct PlayTime {
+    played: Duration,
+    // the position and when it was seen, while playing
+    last: Option<(u64, Instant)>,
+}
+
+impl PlayTime {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the track is at `position` at `now`, and `playing` or not
+    pub fn observe(&mut self, playing: bool, position: u64, now: Instant) {
+        if !playing {
+            self.last = None;
+            return;
+        }
+        if let Some((last, at)) = self.last {
+            let moved = position.saturating_sub(last);
+            // anything faster than double speed is a seek
+            let plausible = 2 * now.duration_since(at).as_micros() as u64 + 1_000_000;
+            if position >= last && moved <= plausible {
+                self.played += Duration::from_micros(moved);
+            }
+        }
+        self.last = Some((position, now));
+    }
+
+    pub fn played(&self) -> Duration {
+        self.played
+    }
+
+    /// the position at the last observation, `None` unless it was playing
+    pub fn last_position(&self) -> Option<u64> {
+        self.last.map(|(position, _)| position)
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,157 @@
+//! estimating where a player is in the track between updates
+//!
+//! players don't signal `Position` changes, only `Seeked` when it jumps. what they do signal is
+//! enough to work it out: the last known position, when it was known, whether the player is
+//! playing and how fast.
+
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::{stream::BoxStream, StreamExt};
+use tokio::{sync::watch, time::MissedTickBehavior};
+use tracing::warn;
+use zbus::proxy::SignalStream;
+
+use crate::{clock::Clock, player::PlaybackStatus, WAKER};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionTracker {
+    /// microseconds at `at`
+    position: u64,
+    at: Instant,
+    rate: f64,
+    playing: bool,
+}
+
+impl PositionTracker {
+    pub fn new(position: u64, rate: f64, status: PlaybackStatus, at: Instant) -> Self {
+        Self {
+            position,
+            at,
+            rate,
+            playing: status == PlaybackStatus::Playing,
+        }
+    }
+
+    /// the position in microseconds at `now`, not clamped to the track's length
+    pub fn estimate(&self, now: Instant) -> u64 {
+        if !self.playing {
+            return self.position;
+        }
+
+        let elapsed = now.saturating_duration_since(self.at).as_micros() as f64;
+        (self.position as f64 + elapsed * self.rate).max(0.0) as u64
+    }
+
+    /// a fresh snapshot, from `Seeked` or asking for `Position`
+    pub fn set_position(&mut self, position: u64, now: Instant) {
+        self.position = position;
+        self.at = now;
+    }
+
+    pub fn set_status(&mut self, status: PlaybackStatus, now: Instant) {
+        self.rebase(now);
+        self.playing = status == PlaybackStatus::Playing;
+        if status == PlaybackStatus::Stopped {
+            self.position = 0;
+        }
+    }
+
+    pub fn set_rate(&mut self, rate: f64, now: Instant) {
+        self.rebase(now);
+        self.rate = rate;
+    }
+
+    // everything up to `now` happened at the old status and rate
+    fn rebase(&mut self, now: Instant) {
+        self.position = self.estimate(now);
+        self.at = now;
+    }
+}
+
+/// `position` cut off at the end of the track, a length of 0 is as good as none
+pub(crate) fn within(position: u64, length: Option<u64>) -> u64 {
+    match length.filter(|len| *len > 0) {
+        Some(length) => position.min(length),
+        None => position,
+    }
+}
+
+/// where a playing player is, see [`MprisClient::position_ticks`](crate::MprisClient::position_ticks)
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionTick {
+    pub player: String,
+    /// microseconds, estimated
+    pub position: u64,
+    pub length: Option<u64>,
+}
+
+/// a playing player as of the client's last look, enough to estimate where it is later
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Playing {
+    pub player: String,
+    pub tracker: PositionTracker,
+    pub length: Option<u64>,
+}
+
+impl Playing {
+    fn tick(&self, now: Instant) -> PositionTick {
+        PositionTick {
+            player: self.player.clone(),
+            position: within(self.tracker.estimate(now), self.length),
+            length: self.length,
+        }
+    }
+}
+
+/// the positions of whatever is in `playing` every `interval`, ticks where nothing plays are
+/// skipped. ends once the client sending `playing` is gone
+pub(crate) fn ticks(
+    playing: watch::Receiver<Vec<Playing>>,
+    clock: Arc<dyn Clock>,
+    interval: Duration,
+) -> BoxStream<'static, Vec<PositionTick>> {
+    let mut timer = tokio::time::interval(interval);
+    // a consumer that fell behind wants the position now, not the ones it missed
+    timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    futures::stream::unfold(
+        (timer, playing, clock),
+        |(mut timer, playing, clock)| async move {
+            loop {
+                timer.tick().await;
+                if playing.has_changed().is_err() {
+                    return None;
+                }
+                let now = clock.now();
+                let ticks: Vec<PositionTick> =
+                    playing.borrow().iter().map(|p| p.tick(now)).collect();
+                if !ticks.is_empty() {
+                    return Some((ticks, (timer, playing, clock)));
+                }
+            }
+        },
+    )
+    .boxed()
+}
+
+/// polls a stream of `Seeked` signals for the new position in microseconds, signals that fail to
+/// parse are logged and skipped
+pub fn poll_seeked(stream: &mut SignalStream<'_>) -> Poll<Option<u64>> {
+    let waker = WAKER;
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        let msg = match stream.poll_next_unpin(&mut cx) {
+            Poll::Ready(Some(msg)) => msg,
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => return Poll::Pending,
+        };
+
+        match msg.body().deserialize::<i64>() {
+            Ok(position) => return Poll::Ready(Some(position.max(0).cast_unsigned())),
+            Err(e) => warn!("failed to parse Seeked: {e:?}"),
+        }
+    }
+}
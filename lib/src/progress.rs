@@ -0,0 +1,45 @@
+//! showing how far into a track a player is, the same way in every frontend
+//!
+//! positions and lengths are in microseconds like MPRIS has them, see
+//! [`Player::estimated_position`](crate::player::Player::estimated_position) for a position that
+//! doesn't need asking the player.
+
+/// formats a length in microseconds (the unit MPRIS uses) as `m:ss`, or `h:mm:ss` once it is
+/// longer than an hour
+pub fn format_length(micros: u64) -> String {
+    let total_secs = micros / 1_000_000;
+    let hours = total_secs / 3600;
+    let minutes = (total_secs / 60) % 60;
+    let secs = total_secs % 60;
+
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{secs:02}")
+    } else {
+        format!("{minutes}:{secs:02}")
+    }
+}
+
+/// `1:23 / 4:56`, or only the position when the length is unknown
+pub fn format_progress(position: u64, length: Option<u64>) -> String {
+    match length.filter(|len| *len > 0) {
+        Some(length) => format!(
+            "{} / {}",
+            format_length(position.min(length)),
+            format_length(length)
+        ),
+        None => format_length(position),
+    }
+}
+
+/// how far `position` is into a track of `length`, from `0` to `100`. `None` when the length is
+/// unknown
+pub fn percent(position: u64, length: Option<u64>) -> Option<f64> {
+    let length = length.filter(|len| *len > 0)?;
+    Some(position.min(length) as f64 * 100.0 / length as f64)
+}
+
+/// a bar `width` characters wide filled `percent` of the way, like `━━━━━━────`
+pub fn bar(percent: f64, width: usize) -> String {
+    let filled = (percent.clamp(0.0, 100.0) / 100.0 * width as f64).round() as usize;
+    "━".repeat(filled) + &"─".repeat(width - filled)
+}
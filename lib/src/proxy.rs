@@ -0,0 +1,306 @@
+//! a player that stands in for whichever player is active, like `playerctld`
+//!
+//! the proxy is served as `org.mpris.MediaPlayer2.mpris_controller`, mirrors the state of the
+//! [active player](MprisClient::active_player) and hands calls on to it, so clients that only
+//! know one bus name (media keys, bars) always reach the right player.
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use lib::{proxy::Proxy, MprisClient};
+//!
+//! let mut client = MprisClient::connect().await?;
+//! client.get_all().await?;
+//! let mut proxy = Proxy::start(client).await?;
+//! loop {
+//!     proxy.step().await;
+//!     // sleep a little
+//! }
+//! # }
+//! ```
+
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tracing::{debug, warn};
+use zbus::{
+    connection,
+    zvariant::{ObjectPath, Value},
+};
+
+use crate::{
+    call::call_method,
+    player::{Capabilities, LoopStatus, MprisEvent, PlayerId, TrackId},
+    service::{MprisService, PlayerHandler},
+    MprisClient, MPRIS_PATH, MPRIS_PLAYER_PREFIX, MPRIS_PREFIX,
+};
+
+/// what comes after `org.mpris.MediaPlayer2.` in the proxy's bus name
+pub const PROXY_NAME: &str = "mpris_controller";
+
+/// a call made to the proxy, to be run on the active player
+#[derive(Debug, Clone, PartialEq)]
+enum Call {
+    Method(&'static str),
+    Root(&'static str),
+    Seek(i64),
+    SetPosition(TrackId, u64),
+    OpenUri(String),
+    Property(&'static str, PropertyValue),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PropertyValue {
+    F64(f64),
+    Bool(bool),
+    LoopStatus(LoopStatus),
+}
+
+impl PropertyValue {
+    fn to_value(&self) -> Value<'static> {
+        match self {
+            Self::F64(v) => Value::F64(*v),
+            Self::Bool(v) => Value::Bool(*v),
+            Self::LoopStatus(v) => Value::from(v.to_string()),
+        }
+    }
+}
+
+// the handler only queues calls, `Proxy::step` runs them where the client is
+struct Forward(UnboundedSender<Call>);
+
+impl Forward {
+    fn send(&self, call: Call) -> anyhow::Result<()> {
+        self.0
+            .send(call)
+            .map_err(|_| anyhow::anyhow!("the proxy stopped"))
+    }
+}
+
+impl PlayerHandler for Forward {
+    fn play(&mut self, _: &mut Capabilities) -> anyhow::Result<()> {
+        self.send(Call::Method("Play"))
+    }
+
+    fn pause(&mut self, _: &mut Capabilities) -> anyhow::Result<()> {
+        self.send(Call::Method("Pause"))
+    }
+
+    fn play_pause(&mut self, _: &mut Capabilities) -> anyhow::Result<()> {
+        self.send(Call::Method("PlayPause"))
+    }
+
+    fn stop(&mut self, _: &mut Capabilities) -> anyhow::Result<()> {
+        self.send(Call::Method("Stop"))
+    }
+
+    fn next(&mut self, _: &mut Capabilities) -> anyhow::Result<()> {
+        self.send(Call::Method("Next"))
+    }
+
+    fn previous(&mut self, _: &mut Capabilities) -> anyhow::Result<()> {
+        self.send(Call::Method("Previous"))
+    }
+
+    fn seek(&mut self, _: &mut Capabilities, offset: i64) -> anyhow::Result<()> {
+        self.send(Call::Seek(offset))
+    }
+
+    fn set_position(
+        &mut self,
+        _: &mut Capabilities,
+        track: &TrackId,
+        position: u64,
+    ) -> anyhow::Result<()> {
+        self.send(Call::SetPosition(track.clone(), position))
+    }
+
+    fn open_uri(&mut self, _: &mut Capabilities, uri: &str) -> anyhow::Result<()> {
+        self.send(Call::OpenUri(uri.to_string()))
+    }
+
+    fn set_volume(&mut self, _: &mut Capabilities, volume: f64) -> anyhow::Result<()> {
+        self.send(Call::Property("Volume", PropertyValue::F64(volume)))
+    }
+
+    fn set_rate(&mut self, _: &mut Capabilities, rate: f64) -> anyhow::Result<()> {
+        self.send(Call::Property("Rate", PropertyValue::F64(rate)))
+    }
+
+    fn set_loop_status(&mut self, _: &mut Capabilities, status: LoopStatus) -> anyhow::Result<()> {
+        self.send(Call::Property(
+            "LoopStatus",
+            PropertyValue::LoopStatus(status),
+        ))
+    }
+
+    fn set_shuffle(&mut self, _: &mut Capabilities, shuffle: bool) -> anyhow::Result<()> {
+        self.send(Call::Property("Shuffle", PropertyValue::Bool(shuffle)))
+    }
+
+    fn raise(&mut self) -> anyhow::Result<()> {
+        self.send(Call::Root("Raise"))
+    }
+
+    fn quit(&mut self) -> anyhow::Result<()> {
+        self.send(Call::Root("Quit"))
+    }
+}
+
+#[derive(Debug)]
+pub struct Proxy {
+    client: MprisClient,
+    service: MprisService,
+    calls: UnboundedReceiver<Call>,
+    // the player whose state is mirrored
+    mirrored: Option<PlayerId>,
+}
+
+impl Proxy {
+    /// serves the proxy in front of the players `client` knows, on the bus the client was
+    /// connected to with [`MprisClient::connect_to`]
+    pub async fn start(client: MprisClient) -> anyhow::Result<Self> {
+        let bus = client
+            .bus()
+            .ok_or_else(|| anyhow::anyhow!("the client's bus is unknown, use Proxy::start_on"))?;
+        let builder = bus.builder()?;
+        Self::start_on(client, builder).await
+    }
+
+    /// serves the proxy on the bus `builder` connects to, which should be the bus of `client`
+    pub async fn start_on(
+        mut client: MprisClient,
+        builder: connection::Builder<'_>,
+    ) -> anyhow::Result<Self> {
+        // never stand in for ourselves
+        client.ignore([format!("{MPRIS_PREFIX}.{PROXY_NAME}")]);
+        let (tx, calls) = mpsc::unbounded_channel();
+        let service = MprisService::builder(PROXY_NAME, Forward(tx))
+            .serve_on(builder)
+            .await?;
+
+        let mut proxy = Self {
+            client,
+            service,
+            calls,
+            mirrored: None,
+        };
+        proxy.mirror(&[]).await;
+        Ok(proxy)
+    }
+
+    pub fn client(&self) -> &MprisClient {
+        &self.client
+    }
+
+    pub fn client_mut(&mut self) -> &mut MprisClient {
+        &mut self.client
+    }
+
+    pub fn service(&self) -> &MprisService {
+        &self.service
+    }
+
+    /// hands the calls made to the proxy on and mirrors what changed, returning the events of
+    /// [`MprisClient::event`]
+    pub async fn step(&mut self) -> Vec<MprisEvent> {
+        while let Ok(call) = self.calls.try_recv() {
+            if let Err(e) = self.forward(call).await {
+                warn!("failed to forward a call: {e:?}");
+            }
+        }
+
+        let events = self.client.event().await;
+        self.mirror(&events).await;
+        events
+    }
+
+    async fn forward(&self, call: Call) -> anyhow::Result<()> {
+        let Some(player) = self.client.active_player() else {
+            debug!(?call, "no active player to forward to");
+            return Ok(());
+        };
+        let conn = self
+            .client
+            .connection()
+            .ok_or_else(|| anyhow::anyhow!("client is not connected to a bus"))?;
+        debug!(player = player.name(), ?call, "forwarding");
+
+        let name = player.name();
+        match call {
+            Call::Method(method) => {
+                call_method(
+                    conn,
+                    player.call_policy(),
+                    name,
+                    MPRIS_PATH,
+                    MPRIS_PLAYER_PREFIX,
+                    method,
+                    &(),
+                )
+                .await?;
+            }
+            Call::Root(method) => {
+                call_method(
+                    conn,
+                    player.call_policy(),
+                    name,
+                    MPRIS_PATH,
+                    MPRIS_PREFIX,
+                    method,
+                    &(),
+                )
+                .await?;
+            }
+            Call::Seek(offset) => player.seek(conn, offset).await?,
+            Call::SetPosition(track, position) => {
+                let track = ObjectPath::try_from(track.as_str())?;
+                player.set_position(conn, track, position).await?;
+            }
+            Call::OpenUri(uri) => player.open_uri(conn, &uri).await?,
+            Call::Property(property, value) => {
+                player
+                    .set_property(conn, property, value.to_value())
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// copies the state of the active player, clients of the proxy see a switch to another
+    /// player like a track change
+    async fn mirror(&mut self, events: &[MprisEvent]) {
+        let active = self.client.active_player();
+        let id = active.map(|p| p.id());
+        let capabilities = active
+            .map(|p| Capabilities {
+                position: p.estimated_position(),
+                ..p.capabilities().clone()
+            })
+            .unwrap_or_else(|| Capabilities {
+                rate: 1.0,
+                ..Default::default()
+            });
+        let root = active.map(|p| p.root().clone()).unwrap_or_default();
+        let seeked = active.and_then(|active| {
+            events.iter().rev().find_map(|e| match e {
+                MprisEvent::Seeked { player, position } if player == active.name() => {
+                    Some(*position)
+                }
+                _ => None,
+            })
+        });
+
+        let mirrored = async {
+            // both only announce what changed
+            self.service.update(|state| *state = capabilities).await?;
+            self.service.update_root(|state| *state = root).await?;
+            if let Some(position) = seeked.filter(|_| self.mirrored == id) {
+                self.service.seeked(position).await?;
+            }
+            anyhow::Ok(())
+        };
+        if let Err(e) = mirrored.await {
+            warn!("failed to mirror the active player: {e:?}");
+        }
+        self.mirrored = id;
+    }
+}
@@ -0,0 +1,192 @@
+//! the PulseAudio (or PipeWire through `pipewire-pulse`) streams of a player
+//!
+//! some players, mostly browsers, show a volume over MPRIS but ignore changes to it or don't
+//! have one at all. their audio still goes through a sink input, which is found by the process
+//! that owns the player's bus name (or one of its children, browsers play from a helper
+//! process) and failing that by the desktop entry. this talks to `pactl`, which has to be
+//! installed.
+
+use anyhow::{bail, Context};
+use serde_json::Value;
+use tokio::process::Command;
+use tracing::debug;
+use zbus::Connection;
+
+pub use crate::bus_name_pid;
+use crate::player::Player;
+
+/// `PA_VOLUME_NORM`, 100%
+const VOLUME_NORM: f64 = 65536.0;
+
+/// one stream playing into a sink
+#[derive(Debug, Clone, PartialEq)]
+pub struct SinkInput {
+    pub index: u32,
+    /// `application.process.id`
+    pub pid: Option<u32>,
+    /// `application.process.binary`, like `firefox`
+    pub binary: Option<String>,
+    /// `application.name`, like `Firefox`
+    pub app_name: Option<String>,
+    /// `application.id` or the flatpak app id, like `org.mozilla.firefox`
+    pub app_id: Option<String>,
+    /// the average over the channels, 1 is 100%
+    pub volume: f64,
+    pub muted: bool,
+}
+
+/// parses the output of `pactl -f json list sink-inputs`
+pub fn parse_sink_inputs(json: &str) -> anyhow::Result<Vec<SinkInput>> {
+    let inputs: Vec<Value> = serde_json::from_str(json).context("parsing pactl output")?;
+    inputs
+        .iter()
+        .map(|input| {
+            let index = input["index"]
+                .as_u64()
+                .context("a sink input without an index")?;
+            let property = |key: &str| input["properties"][key].as_str().map(str::to_string);
+            let volumes: Vec<f64> = input["volume"]
+                .as_object()
+                .into_iter()
+                .flat_map(|channels| channels.values())
+                .filter_map(|channel| channel["value"].as_f64())
+                .collect();
+            let volume = match volumes.len() {
+                0 => 1.0,
+                n => volumes.iter().sum::<f64>() / n as f64 / VOLUME_NORM,
+            };
+
+            Ok(SinkInput {
+                index: index as u32,
+                pid: property("application.process.id").and_then(|pid| pid.parse().ok()),
+                binary: property("application.process.binary"),
+                app_name: property("application.name"),
+                app_id: property("application.id")
+                    .or_else(|| property("pipewire.access.portal.app_id")),
+                volume,
+                muted: input["mute"].as_bool().unwrap_or(false),
+            })
+        })
+        .collect()
+}
+
+/// every sink input there is
+pub async fn sink_inputs() -> anyhow::Result<Vec<SinkInput>> {
+    let output = pactl(&["-f", "json", "list", "sink-inputs"]).await?;
+    parse_sink_inputs(&output)
+}
+
+/// sets the volume of one sink input, 1 is 100%
+pub async fn set_sink_input_volume(index: u32, volume: f64) -> anyhow::Result<()> {
+    let percent = format!("{:.0}%", volume.max(0.0) * 100.0);
+    pactl(&["set-sink-input-volume", &index.to_string(), &percent]).await?;
+    Ok(())
+}
+
+async fn pactl(args: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new("pactl")
+        .args(args)
+        .output()
+        .await
+        .context("running pactl")?;
+    if !output.status.success() {
+        bail!(
+            "pactl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// the streams out of `inputs` that belong to a player with the process `pid` and
+/// `desktop_entry`. streams of the process or its children win over ones matched by name
+pub fn player_streams<'a>(
+    inputs: &'a [SinkInput],
+    pid: Option<u32>,
+    desktop_entry: Option<&str>,
+) -> Vec<&'a SinkInput> {
+    if let Some(pid) = pid {
+        let by_pid: Vec<_> = inputs
+            .iter()
+            .filter(|input| input.pid.is_some_and(|child| is_descendant(child, pid)))
+            .collect();
+        if !by_pid.is_empty() {
+            return by_pid;
+        }
+    }
+
+    // `org.mozilla.firefox` and `firefox` both name firefox
+    let Some(entry) = desktop_entry.map(str::to_lowercase) else {
+        return Vec::new();
+    };
+    let short = entry.rsplit('.').next().unwrap_or(&entry).to_string();
+    inputs
+        .iter()
+        .filter(|input| {
+            input.app_id.as_deref().map(str::to_lowercase) == Some(entry.clone())
+                || [&input.binary, &input.app_name]
+                    .into_iter()
+                    .flatten()
+                    .any(|name| name.to_lowercase() == short)
+        })
+        .collect()
+}
+
+/// whether `pid` is `ancestor` or one of its descendants, going by `/proc`
+fn is_descendant(mut pid: u32, ancestor: u32) -> bool {
+    // init is everyone's ancestor, and the chain can't be longer than this in practice
+    for _ in 0..64 {
+        if pid == ancestor {
+            return true;
+        }
+        if pid <= 1 {
+            return false;
+        }
+        match parent(pid) {
+            Some(parent) => pid = parent,
+            None => return false,
+        }
+    }
+    false
+}
+
+fn parent(pid: u32) -> Option<u32> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // the command in parentheses can contain spaces and parentheses itself
+    let rest = &stat[stat.rfind(')')? + 1..];
+    rest.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// the streams `player` is playing to
+pub async fn streams_of(conn: &Connection, player: &Player) -> anyhow::Result<Vec<SinkInput>> {
+    let pid = match bus_name_pid(conn, player.name()).await {
+        Ok(pid) => Some(pid),
+        Err(e) => {
+            debug!(player = player.name(), "no pid: {e:#}");
+            None
+        }
+    };
+    let inputs = sink_inputs().await?;
+    let streams = player_streams(&inputs, pid, player.root().desktop_entry.as_deref());
+    Ok(streams.into_iter().cloned().collect())
+}
+
+/// sets the volume of every stream of `player`, returns how many there were
+pub async fn set_player_volume(
+    conn: &Connection,
+    player: &Player,
+    volume: f64,
+) -> anyhow::Result<usize> {
+    let streams = streams_of(conn, player).await?;
+    for stream in &streams {
+        debug!(
+            player = player.name(),
+            stream = stream.index,
+            volume,
+            "stream volume"
+        );
+        set_sink_input_volume(stream.index, volume).await?;
+    }
+    Ok(streams.len())
+}
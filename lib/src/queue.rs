@@ -0,0 +1,76 @@
+//! "what's next" on top of the tracklist and the current `mpris:trackid`
+
+use crate::player::Metadata;
+
+/// a view of a player's tracklist around the current track, see [`Player::queue`](crate::player::Player::queue)
+#[derive(Debug, Clone, Copy)]
+pub struct Queue<'a> {
+    tracks: &'a [Metadata],
+    current: Option<usize>,
+}
+
+impl<'a> Queue<'a> {
+    /// `current` is looked up by trackid, tracks without one can't be the current track
+    pub fn new(tracks: &'a [Metadata], current: &Metadata) -> Self {
+        let current = current
+            .track_id()
+            .filter(|id| !id.is_no_track())
+            .and_then(|id| tracks.iter().position(|t| t.track_id() == Some(id)));
+
+        Self { tracks, current }
+    }
+
+    pub fn tracks(&self) -> &'a [Metadata] {
+        self.tracks
+    }
+
+    pub fn len(&self) -> usize {
+        self.tracks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    /// the index of the current track, `None` when it isn't in the tracklist
+    pub fn position(&self) -> Option<usize> {
+        self.current
+    }
+
+    pub fn current(&self) -> Option<&'a Metadata> {
+        self.tracks.get(self.current?)
+    }
+
+    pub fn next(&self) -> Option<&'a Metadata> {
+        self.upcoming(1).first()
+    }
+
+    pub fn previous(&self) -> Option<&'a Metadata> {
+        self.history(1).last()
+    }
+
+    /// up to `n` tracks after the current one
+    pub fn upcoming(&self, n: usize) -> &'a [Metadata] {
+        match self.current {
+            Some(i) => {
+                let start = i + 1;
+                &self.tracks[start..(start + n).min(self.tracks.len())]
+            }
+            None => &[],
+        }
+    }
+
+    /// up to `n` tracks before the current one, oldest first
+    pub fn history(&self, n: usize) -> &'a [Metadata] {
+        match self.current {
+            Some(i) => &self.tracks[i.saturating_sub(n)..i],
+            None => &[],
+        }
+    }
+
+    /// tracks left after the current one
+    pub fn remaining(&self) -> usize {
+        self.current
+            .map_or(0, |i| self.tracks.len().saturating_sub(i + 1))
+    }
+}
@@ -0,0 +1,210 @@
+//! known ways players deviate from the spec, and what to do about them
+//!
+//! a [`QuirkRegistry`] is a list of [`QuirkRule`]s matched by bus name or `Identity`. the rules
+//! matching a player are merged into its [`Quirks`], later ones winning, which are applied
+//! whenever its properties are parsed, so the capability checks see the corrected values too.
+//! [`QuirkRegistry::default`] has the rules for players known to misbehave, more can be added
+//! like in the client's config:
+//!
+//! ```toml
+//! [[quirks]]
+//! player = "vlc"
+//! length-unit = "milliseconds"
+//!
+//! [[quirks]]
+//! identity = "Some Player"
+//! can-seek = false
+//! ```
+
+use serde::Deserialize;
+
+use crate::pattern;
+
+/// the unit a player really sends `mpris:length` in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LengthUnit {
+    /// what the spec says
+    #[default]
+    Microseconds,
+    Milliseconds,
+    /// milliseconds when the length would be under a second in microseconds, no track is that
+    /// short. for players that only get it wrong sometimes
+    Guess,
+}
+
+impl LengthUnit {
+    pub fn to_micros(self, length: u64) -> u64 {
+        match self {
+            Self::Milliseconds => length.saturating_mul(1000),
+            Self::Guess if length > 0 && length < 1_000_000 => length.saturating_mul(1000),
+            Self::Microseconds | Self::Guess => length,
+        }
+    }
+}
+
+/// the corrections for one player, `None` leaves things as the player sends them
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Quirks {
+    pub length_unit: Option<LengthUnit>,
+    /// takes the artist from an `Artist - Title` title when there are no artists, browsers
+    /// often only have the page title
+    pub artist_from_title: Option<bool>,
+    /// overrides for capabilities the player misreports
+    pub can_control: Option<bool>,
+    pub can_go_next: Option<bool>,
+    pub can_go_previous: Option<bool>,
+    pub can_pause: Option<bool>,
+    pub can_play: Option<bool>,
+    pub can_seek: Option<bool>,
+    /// parses like for bluetooth players, see
+    /// [`Capabilities::from_properties`](crate::player::Capabilities::from_properties)
+    pub relaxed: Option<bool>,
+}
+
+impl Quirks {
+    /// `other` on top of `self`
+    pub fn merge(&mut self, other: &Quirks) {
+        self.length_unit = other.length_unit.or(self.length_unit);
+        self.artist_from_title = other.artist_from_title.or(self.artist_from_title);
+        self.can_control = other.can_control.or(self.can_control);
+        self.can_go_next = other.can_go_next.or(self.can_go_next);
+        self.can_go_previous = other.can_go_previous.or(self.can_go_previous);
+        self.can_pause = other.can_pause.or(self.can_pause);
+        self.can_play = other.can_play.or(self.can_play);
+        self.can_seek = other.can_seek.or(self.can_seek);
+        self.relaxed = other.relaxed.or(self.relaxed);
+    }
+
+    pub fn is_relaxed(&self) -> bool {
+        self.relaxed.unwrap_or(false)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// the quirks of the players matching `player` or `identity`, a rule with neither matches none
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct QuirkRule {
+    /// a bus name pattern, see [`pattern`]
+    pub player: Option<String>,
+    /// the player's `Identity`, ignoring case
+    pub identity: Option<String>,
+    #[serde(flatten)]
+    pub quirks: Quirks,
+}
+
+impl QuirkRule {
+    pub fn matches(&self, name: &str, identity: Option<&str>) -> bool {
+        self.player
+            .as_deref()
+            .is_some_and(|pattern| pattern::matches(pattern, name))
+            || self
+                .identity
+                .as_deref()
+                .zip(identity)
+                .is_some_and(|(rule, identity)| rule.eq_ignore_ascii_case(identity))
+    }
+}
+
+/// the bus names of browsers, chromium based ones mostly show up as `chromium.instance<pid>`
+const BROWSERS: &[&str] = &[
+    "firefox*",
+    "librewolf*",
+    "chromium*",
+    "chrome*",
+    "brave*",
+    "vivaldi*",
+    "plasma-browser-integration*",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuirkRegistry {
+    rules: Vec<QuirkRule>,
+}
+
+impl Default for QuirkRegistry {
+    /// the built in rules
+    fn default() -> Self {
+        let rule = |player: &str, quirks: Quirks| QuirkRule {
+            player: Some(player.to_string()),
+            identity: None,
+            quirks,
+        };
+        // browsers leave out fields or send odd types for them while a page navigates, what
+        // did arrive is still worth having
+        let browsers = BROWSERS.iter().map(|browser| {
+            rule(
+                browser,
+                Quirks {
+                    relaxed: Some(true),
+                    ..Default::default()
+                },
+            )
+        });
+        Self {
+            rules: browsers
+                .chain([
+                    // some versions and streams report the length in milliseconds
+                    rule(
+                        "vlc",
+                        Quirks {
+                            length_unit: Some(LengthUnit::Guess),
+                            ..Default::default()
+                        },
+                    ),
+                    // only the page title, youtube and the like put the artist in front
+                    rule(
+                        "firefox*",
+                        Quirks {
+                            artist_from_title: Some(true),
+                            ..Default::default()
+                        },
+                    ),
+                    // says it can seek for every stream, but a phone's player often can't
+                    rule(
+                        "kdeconnect*",
+                        Quirks {
+                            can_seek: Some(false),
+                            ..Default::default()
+                        },
+                    ),
+                ])
+                .collect(),
+        }
+    }
+}
+
+impl QuirkRegistry {
+    /// a registry without any rules
+    pub fn empty() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    /// adds `rules` after the others, so they win over the built in ones
+    pub fn with_rules(mut self, rules: impl IntoIterator<Item = QuirkRule>) -> Self {
+        self.rules.extend(rules);
+        self
+    }
+
+    pub fn rules(&self) -> &[QuirkRule] {
+        &self.rules
+    }
+
+    /// the merged quirks of every rule matching the player
+    pub fn lookup(&self, name: &str, identity: Option<&str>) -> Quirks {
+        let mut quirks = Quirks::default();
+        for rule in self
+            .rules
+            .iter()
+            .filter(|rule| rule.matches(name, identity))
+        {
+            quirks.merge(&rule.quirks);
+        }
+        quirks
+    }
+}
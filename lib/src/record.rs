@@ -0,0 +1,202 @@
+//! recording the signals a client receives and replaying them later
+//!
+//! a recording is JSON lines: every player as it was when it showed up, the `PropertiesChanged`
+//! and `Seeked` signals it sent and when it went away. values are in the format of
+//! [`variant`](crate::variant), so a recording attached to a bug report can be read and trimmed
+//! by hand.
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use lib::{record::{self, Recorder}, MprisClient};
+//!
+//! let mut client = MprisClient::connect().await?;
+//! client.get_all().await?;
+//! client.record_to(Some(Recorder::create("signals.jsonl")?));
+//! // ... later, somewhere else
+//! let mut replayed = MprisClient::replaying();
+//! for recorded in record::load("signals.jsonl")? {
+//!     let events = replayed.replay(&recorded)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! players whose signal stream fell back to polling aren't recorded past their first state.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use zbus::{
+    zvariant::{OwnedValue, Structure, Value},
+    Message,
+};
+
+use crate::{player::Player, variant};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Recorded {
+    /// a player and the properties it had when it was added
+    Player {
+        at_ms: u64,
+        player: String,
+        properties: serde_json::Value,
+    },
+    /// a signal from `player`, the body as a typed structure
+    Signal {
+        at_ms: u64,
+        player: String,
+        member: String,
+        body: serde_json::Value,
+    },
+    Removed {
+        at_ms: u64,
+        player: String,
+    },
+}
+
+impl Recorded {
+    /// how long after the recording started this happened
+    pub fn at(&self) -> Duration {
+        let (Self::Player { at_ms, .. } | Self::Signal { at_ms, .. } | Self::Removed { at_ms, .. }) =
+            self;
+        Duration::from_millis(*at_ms)
+    }
+
+    pub fn player(&self) -> &str {
+        let (Self::Player { player, .. }
+        | Self::Signal { player, .. }
+        | Self::Removed { player, .. }) = self;
+        player
+    }
+
+    /// the signal as a message again, as if `player` had just sent it
+    pub fn message(&self) -> anyhow::Result<Option<Message>> {
+        let Self::Signal { member, body, .. } = self else {
+            return Ok(None);
+        };
+        let interface = match member.as_str() {
+            "PropertiesChanged" => crate::DBUS_PROPERTIES,
+            _ => crate::MPRIS_PLAYER_PREFIX,
+        };
+        let body = Structure::try_from(Value::from(variant::from_json(body)?))?;
+
+        Ok(Some(
+            Message::signal(crate::MPRIS_PATH, interface, member.as_str())?.build(&body)?,
+        ))
+    }
+}
+
+/// everything in the recording at `path`
+pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Vec<Recorded>> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.as_ref().map_or(true, |l| !l.trim().is_empty()))
+        .map(|(i, line)| {
+            serde_json::from_str(&line?).with_context(|| format!("{}:{}", path.display(), i + 1))
+        })
+        .collect()
+}
+
+/// writes what a client receives to a file, see [`MprisClient::record_to`]
+///
+/// failing to write is logged, a recording never gets in the way of the client.
+///
+/// [`MprisClient::record_to`]: crate::MprisClient::record_to
+#[derive(Debug)]
+pub struct Recorder {
+    out: BufWriter<File>,
+    start: Instant,
+}
+
+impl Recorder {
+    /// starts a recording at `path`, replacing what was there
+    pub fn create(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+
+        Ok(Self {
+            out: BufWriter::new(file),
+            start: Instant::now(),
+        })
+    }
+
+    fn at_ms(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+
+    fn write(&mut self, recorded: &Recorded) {
+        let written = serde_json::to_writer(&mut self.out, recorded)
+            .map_err(anyhow::Error::from)
+            .and_then(|()| Ok(self.out.write_all(b"\n")?))
+            .and_then(|()| Ok(self.out.flush()?));
+        if let Err(e) = written {
+            warn!("failed to record: {e:?}");
+        }
+    }
+
+    pub(crate) fn player(&mut self, player: &Player) {
+        let properties: HashMap<String, OwnedValue> = player.capabilities().clone().into();
+        let properties = properties
+            .iter()
+            .map(|(key, value)| (key.clone(), variant::to_json(value)))
+            .collect();
+        let recorded = Recorded::Player {
+            at_ms: self.at_ms(),
+            player: player.name().to_string(),
+            properties,
+        };
+        self.write(&recorded);
+    }
+
+    pub(crate) fn signal(&mut self, player: &str, msg: &Message) {
+        let header = msg.header();
+        let Some(member) = header.member() else {
+            return;
+        };
+        let body = match msg.body().deserialize::<Structure>() {
+            Ok(body) => variant::to_json(&Value::Structure(body)),
+            Err(e) => {
+                warn!(player, "failed to record {member}: {e:?}");
+                return;
+            }
+        };
+        let recorded = Recorded::Signal {
+            at_ms: self.at_ms(),
+            player: player.to_string(),
+            member: member.to_string(),
+            body,
+        };
+        self.write(&recorded);
+    }
+
+    pub(crate) fn seeked(&mut self, player: &str, position: u64) {
+        let body = Structure::from((position.cast_signed(),));
+        let recorded = Recorded::Signal {
+            at_ms: self.at_ms(),
+            player: player.to_string(),
+            member: "Seeked".to_string(),
+            body: variant::to_json(&Value::Structure(body)),
+        };
+        self.write(&recorded);
+    }
+
+    pub(crate) fn removed(&mut self, player: &str) {
+        let recorded = Recorded::Removed {
+            at_ms: self.at_ms(),
+            player: player.to_string(),
+        };
+        self.write(&recorded);
+    }
+}
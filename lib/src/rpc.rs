@@ -0,0 +1,406 @@
+//! JSON-RPC 2.0 over a unix socket, for controlling players without talking D-Bus
+//!
+//! every line sent to the socket is a request and every request with an `id` gets a line back.
+//! the socket is only accessible to its owner, over ssh it can be reached with
+//! `ssh host socat - UNIX-CONNECT:/run/user/1000/mpris-controller.sock`.
+//!
+//! ```text
+//! > {"jsonrpc": "2.0", "id": 1, "method": "play_pause", "params": {"player": "spotify"}}
+//! < {"jsonrpc":"2.0","id":1,"result":null}
+//! ```
+//!
+//! methods, `player` is a pattern like `--player` and defaults to the active player:
+//!
+//! - `players`: every player, like `player` returns them
+//! - `player {player}`: the name, state and interpolated position of one player
+//! - `play`, `pause`, `play_pause`, `stop`, `next`, `previous`, `raise`, `quit` `{player}`
+//! - `seek {player, offset}`: `offset` in microseconds, negative to go back
+//! - `set_position {player, position}`: microseconds into the current track
+//! - `set_volume {player, volume}`
+//! - `open_uri {player, uri}`
+
+use std::{
+    io,
+    os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+use anyhow::bail;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+};
+use tracing::{debug, warn};
+use zbus::zvariant::ObjectPath;
+
+use crate::{
+    call::call_method,
+    player::{MprisEvent, Player},
+    selector, MprisClient, MPRIS_PATH, MPRIS_PLAYER_PREFIX, MPRIS_PREFIX,
+};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// no player matched, or there is no active one
+pub const NO_PLAYER: i64 = -32001;
+/// the player returned an error
+pub const PLAYER_ERROR: i64 = -32002;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::new(PLAYER_ERROR, format!("{e:#}"))
+    }
+}
+
+impl From<zbus::Error> for RpcError {
+    fn from(e: zbus::Error) -> Self {
+        Self::new(PLAYER_ERROR, e.to_string())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    jsonrpc: String,
+    /// `None` for notifications, which get no reply
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// a request waiting for the server loop, which owns the client
+pub(crate) struct Pending {
+    pub(crate) method: String,
+    pub(crate) params: Value,
+    pub(crate) reply: oneshot::Sender<Result<Value, RpcError>>,
+}
+
+pub struct RpcServer {
+    client: MprisClient,
+    path: PathBuf,
+    requests: mpsc::UnboundedReceiver<Pending>,
+    accept: JoinHandle<()>,
+}
+
+impl RpcServer {
+    /// listens on `path`, replacing a socket left behind by a server that is gone
+    pub async fn bind(client: MprisClient, path: &Path) -> anyhow::Result<Self> {
+        match std::fs::symlink_metadata(path) {
+            Ok(meta) if !meta.file_type().is_socket() => {
+                bail!("{} exists and isn't a socket", path.display());
+            }
+            Ok(_) => {
+                if UnixStream::connect(path).await.is_ok() {
+                    bail!("another server is listening on {}", path.display());
+                }
+                std::fs::remove_file(path)?;
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let listener = bind_private(path)?;
+
+        let (send, requests) = mpsc::unbounded_channel();
+        let accept = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        tokio::spawn(serve(stream, send.clone()));
+                    }
+                    Err(e) => warn!("failed to accept a connection: {e}"),
+                }
+            }
+        });
+
+        Ok(Self {
+            client,
+            path: path.to_path_buf(),
+            requests,
+            accept,
+        })
+    }
+
+    /// `$XDG_RUNTIME_DIR/mpris-controller.sock`
+    pub fn default_path() -> Option<PathBuf> {
+        let runtime = std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty())?;
+        Some(PathBuf::from(runtime).join("mpris-controller.sock"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn client(&self) -> &MprisClient {
+        &self.client
+    }
+
+    pub fn client_mut(&mut self) -> &mut MprisClient {
+        &mut self.client
+    }
+
+    /// answers the requests that came in since the last call, then picks up the client's
+    /// events. call it in a loop like [`MprisClient::event`]
+    pub async fn step(&mut self) -> Vec<MprisEvent> {
+        while let Ok(pending) = self.requests.try_recv() {
+            let result = self.call(&pending.method, &pending.params).await;
+            _ = pending.reply.send(result);
+        }
+
+        self.client.event().await
+    }
+
+    /// runs one method, as if it came in over the socket
+    pub async fn call(&mut self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        call(&mut self.client, method, params).await
+    }
+}
+
+/// runs one method on `client`, the same way for every transport
+pub async fn call(
+    client: &mut MprisClient,
+    method: &str,
+    params: &Value,
+) -> Result<Value, RpcError> {
+    debug!(method, %params, "rpc call");
+    match method {
+        "players" => Ok(Value::Array(
+            client.players().iter().map(player_json).collect(),
+        )),
+        "player" => Ok(player_json(player(client, params)?)),
+        "set_volume" => {
+            let volume = param::<f64>(params, "volume")?;
+            let name = player(client, params)?.name().to_string();
+            let conn = connection(client)?;
+            let player = client
+                .get_mut(&name)
+                .ok_or_else(|| RpcError::new(NO_PLAYER, format!("{name} went away")))?;
+            player.set_volume(&conn, volume).await?;
+            Ok(Value::Null)
+        }
+        "play" | "pause" | "play_pause" | "stop" | "next" | "previous" | "raise" | "quit"
+        | "seek" | "set_position" | "open_uri" => {
+            let player = player(client, params)?;
+            let conn = connection(client)?;
+            let name = player.name();
+            let call = |method| {
+                call_method(
+                    &conn,
+                    player.call_policy(),
+                    name,
+                    MPRIS_PATH,
+                    MPRIS_PLAYER_PREFIX,
+                    method,
+                    &(),
+                )
+            };
+            match method {
+                "play" => call("Play").await?,
+                "pause" => call("Pause").await?,
+                "play_pause" => call("PlayPause").await?,
+                "stop" => call("Stop").await?,
+                "next" => call("Next").await?,
+                "previous" => call("Previous").await?,
+                "raise" | "quit" => {
+                    let method = if method == "raise" { "Raise" } else { "Quit" };
+                    call_method(
+                        &conn,
+                        player.call_policy(),
+                        name,
+                        MPRIS_PATH,
+                        MPRIS_PREFIX,
+                        method,
+                        &(),
+                    )
+                    .await?
+                }
+                "seek" => {
+                    player.seek(&conn, param(params, "offset")?).await?;
+                    return Ok(Value::Null);
+                }
+                "set_position" => {
+                    let position = param(params, "position")?;
+                    let track = player
+                        .capabilities()
+                        .metadata
+                        .track_id()
+                        .ok_or_else(|| RpcError::new(PLAYER_ERROR, "the track has no trackid"))?
+                        .to_string();
+                    let track = ObjectPath::try_from(track.as_str())
+                        .map_err(|e| RpcError::new(PLAYER_ERROR, e.to_string()))?;
+                    player.set_position(&conn, track, position).await?;
+                    return Ok(Value::Null);
+                }
+                "open_uri" => {
+                    player
+                        .open_uri(&conn, &param::<String>(params, "uri")?)
+                        .await?;
+                    return Ok(Value::Null);
+                }
+                _ => unreachable!("matched above"),
+            };
+            Ok(Value::Null)
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("no method {method}"),
+        )),
+    }
+}
+
+/// the player `params.player` picks, or the active one
+fn player<'a>(client: &'a MprisClient, params: &Value) -> Result<&'a Player, RpcError> {
+    match params.get("player") {
+        None | Some(Value::Null) => client
+            .active_player()
+            .ok_or_else(|| RpcError::new(NO_PLAYER, "no active player")),
+        Some(Value::String(pattern)) => client
+            .players()
+            .iter()
+            .find(|player| selector::matches(pattern, player))
+            .ok_or_else(|| RpcError::new(NO_PLAYER, format!("no player matches {pattern}"))),
+        Some(_) => Err(RpcError::new(INVALID_PARAMS, "player must be a string")),
+    }
+}
+
+fn connection(client: &MprisClient) -> Result<zbus::Connection, RpcError> {
+    client
+        .connection()
+        .cloned()
+        .ok_or_else(|| RpcError::new(PLAYER_ERROR, "not connected to a bus"))
+}
+
+impl Drop for RpcServer {
+    fn drop(&mut self) {
+        self.accept.abort();
+        _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn param<T: serde::de::DeserializeOwned>(params: &Value, key: &str) -> Result<T, RpcError> {
+    let value = params
+        .get(key)
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("missing {key}")))?;
+    serde_json::from_value(value.clone())
+        .map_err(|e| RpcError::new(INVALID_PARAMS, format!("{key}: {e}")))
+}
+
+pub(crate) fn player_json(player: &Player) -> Value {
+    json!({
+        "player": player.name(),
+        "id": player.stable_id(),
+        "display_name": player.display_name(),
+        "remote": player.is_remote(),
+        "bluetooth": player.is_bluetooth(),
+        "position": player.estimated_position(),
+        "root": player.root(),
+        "capabilities": player.capabilities(),
+    })
+}
+
+/// binds `path` so that only its owner can connect: the socket is made in a directory nobody
+/// else can enter, made private there and then moved into place
+fn bind_private(path: &Path) -> io::Result<UnixListener> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let dir = path.with_file_name(format!(".{name}.{}", std::process::id()));
+    std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+    let socket = dir.join("socket");
+    let bound = UnixListener::bind(&socket).and_then(|listener| {
+        std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&socket, path)?;
+        Ok(listener)
+    });
+    if bound.is_err() {
+        _ = std::fs::remove_file(&socket);
+    }
+    _ = std::fs::remove_dir(&dir);
+    bound
+}
+
+/// reads requests off one connection until it closes
+async fn serve(stream: UnixStream, requests: mpsc::UnboundedSender<Pending>) {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some(reply) = answer(&line, &requests).await else {
+            continue;
+        };
+        let mut reply = reply.to_string();
+        reply.push('\n');
+        if write.write_all(reply.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// the reply to one line, `None` for notifications
+pub(crate) async fn answer(line: &str, requests: &mpsc::UnboundedSender<Pending>) -> Option<Value> {
+    let reply = |id: Value, result: Result<Value, RpcError>| {
+        let mut reply = json!({"jsonrpc": "2.0", "id": id});
+        match result {
+            Ok(result) => reply["result"] = result,
+            Err(e) => reply["error"] = json!({"code": e.code, "message": e.message}),
+        }
+        reply
+    };
+
+    let request: Request = match serde_json::from_str::<Value>(line) {
+        Err(e) => {
+            return Some(reply(
+                Value::Null,
+                Err(RpcError::new(PARSE_ERROR, e.to_string())),
+            ))
+        }
+        Ok(value) => match serde_json::from_value(value) {
+            Ok(request) => request,
+            Err(e) => {
+                let e = RpcError::new(INVALID_REQUEST, e.to_string());
+                return Some(reply(Value::Null, Err(e)));
+            }
+        },
+    };
+    if request.jsonrpc != "2.0" {
+        let e = RpcError::new(INVALID_REQUEST, "only jsonrpc 2.0 is spoken");
+        return Some(reply(request.id.unwrap_or_default(), Err(e)));
+    }
+
+    let (send, receive) = oneshot::channel();
+    let pending = Pending {
+        method: request.method,
+        params: request.params,
+        reply: send,
+    };
+    let result = match requests.send(pending) {
+        Ok(()) => receive
+            .await
+            .unwrap_or_else(|_| Err(RpcError::new(PLAYER_ERROR, "the server shut down"))),
+        Err(_) => Err(RpcError::new(PLAYER_ERROR, "the server shut down")),
+    };
+    request.id.map(|id| reply(id, result))
+}
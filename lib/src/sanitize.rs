@@ -0,0 +1,89 @@
+//! optional normalizers for metadata that is really just a web page title, mostly useful when the
+//! "player" is a browser tab
+
+/// words that mark a bracketed suffix as noise, `(Official Video)`, `[HD]`, ...
+const NOISE: &[&str] = &[
+    "official",
+    "music",
+    "video",
+    "audio",
+    "lyric",
+    "lyrics",
+    "visualizer",
+    "visualiser",
+    "hd",
+    "hq",
+    "4k",
+    "mv",
+    "m/v",
+];
+
+/// suffixes browsers append to the tab title
+const SITE_SUFFIXES: &[&str] = &[" - YouTube", " - YouTube Music", " | YouTube"];
+
+/// collapses runs of whitespace into a single space and trims both ends
+pub fn collapse_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// strips youtube style noise like `(Official Video)`, `[HD]` or a trailing ` - YouTube`
+///
+/// bracketed parts that carry meaning (`(Live)`, `(feat. someone)`, `(Remix)`) are kept.
+///
+/// ```
+/// use lib::sanitize::clean_title;
+///
+/// assert_eq!(clean_title("Song (Official Music Video) [HD] - YouTube"), "Song");
+/// assert_eq!(clean_title("Song  (Live)"), "Song (Live)");
+/// ```
+pub fn clean_title(title: &str) -> String {
+    let mut title = collapse_whitespace(title);
+
+    for suffix in SITE_SUFFIXES {
+        if let Some(stripped) = title.strip_suffix(suffix) {
+            title = stripped.to_string();
+        }
+    }
+
+    let mut out = String::with_capacity(title.len());
+    let mut rest = title.as_str();
+    while let Some(start) = rest.find(['(', '[']) {
+        let close = if rest.as_bytes()[start] == b'(' {
+            ')'
+        } else {
+            ']'
+        };
+        let Some(len) = rest[start..].find(close) else {
+            break;
+        };
+
+        let group = &rest[start + 1..start + len];
+        out.push_str(&rest[..start]);
+        if !is_noise(group) {
+            out.push_str(&rest[start..=start + len]);
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+
+    collapse_whitespace(&out)
+}
+
+/// strips the ` - Topic` suffix of youtube's auto generated channels and a trailing `VEVO`
+pub fn clean_artist(artist: &str) -> String {
+    let artist = collapse_whitespace(artist);
+    let artist = artist.strip_suffix(" - Topic").unwrap_or(&artist);
+    let artist = match artist.strip_suffix("VEVO") {
+        Some(stripped) if !stripped.is_empty() => stripped,
+        _ => artist,
+    };
+
+    artist.trim().to_string()
+}
+
+fn is_noise(group: &str) -> bool {
+    group
+        .split(|c: char| c.is_whitespace() || c == '-' || c == ',')
+        .filter(|w| !w.is_empty())
+        .all(|w| NOISE.contains(&w.to_lowercase().as_str()))
+}
@@ -0,0 +1,526 @@
+//! scrobbling what was listened to to Last.fm and ListenBrainz
+//!
+//! a track counts as a listen once it played for half its length or four minutes, whichever
+//! comes first. tracks shorter than 30 seconds never count, and seeking forward doesn't count as
+//! playing. listens are queued on disk per service and submitted when the service is reachable,
+//! so nothing is lost while offline.
+//!
+//! ```toml
+//! [scrobble.lastfm]
+//! api-key = "..."
+//! secret = "..."
+//! # from `LastFm::authenticate`
+//! session-key = "..."
+//!
+//! [scrobble.listenbrainz]
+//! token = "..."
+//! ```
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Context};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::{debug, warn};
+
+use crate::{
+    player::{Metadata, MprisEvent, PlaybackStatus},
+    playtime::{track_key, unix_now, PlayTime, TrackKey},
+    MprisClient,
+};
+
+pub const LASTFM_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+pub const LISTENBRAINZ_URL: &str = "https://api.listenbrainz.org";
+/// tracks shorter than this are never scrobbled
+pub const MIN_LENGTH: Duration = Duration::from_secs(30);
+/// a track counts after half its length or this, whichever is shorter
+pub const MAX_THRESHOLD: Duration = Duration::from_secs(240);
+/// how long to wait before trying a service again after it failed
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// the time that has to be played before `length` counts as a listen, `None` when it never does
+pub fn threshold(length: Option<Duration>) -> Option<Duration> {
+    match length {
+        Some(length) if length < MIN_LENGTH => None,
+        Some(length) => Some((length / 2).min(MAX_THRESHOLD)),
+        None => Some(MAX_THRESHOLD),
+    }
+}
+
+/// a track that was listened to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Listen {
+    pub artist: String,
+    pub title: String,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    /// seconds
+    pub length: Option<u64>,
+    /// unix time the track started playing, in seconds
+    pub listened_at: u64,
+    /// the bus name of the player, minus `org.mpris.MediaPlayer2.`
+    pub player: String,
+}
+
+impl Listen {
+    /// `None` without an artist and a title, which both services need
+    pub fn new(metadata: &Metadata, player: &str, listened_at: u64) -> Option<Self> {
+        let artist = metadata.artists()?.first()?.clone();
+        let title = metadata.title()?.to_string();
+        Some(Self {
+            artist,
+            title,
+            album: metadata.album().map(str::to_string),
+            album_artist: metadata
+                .album_artists()
+                .and_then(|artists| artists.first().cloned()),
+            length: metadata.length().map(|length| length / 1_000_000),
+            listened_at,
+            player: player
+                .strip_prefix(crate::MPRIS_PREFIX)
+                .map(|name| name.trim_start_matches('.'))
+                .unwrap_or(player)
+                .to_string(),
+        })
+    }
+}
+
+/// what is being played on one player
+#[derive(Debug)]
+struct Tracked {
+    track: TrackKey,
+    listen: Option<Listen>,
+    threshold: Option<Duration>,
+    played: PlayTime,
+    counted: bool,
+}
+
+/// follows what each player plays and hands out a [`Listen`] once a track played long enough
+#[derive(Debug, Default)]
+pub struct ListenTracker {
+    players: HashMap<String, Tracked>,
+}
+
+impl ListenTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// looks at every player of `client` after [`MprisClient::event`] returned `events`, using
+    /// the interpolated position
+    pub fn handle_events(&mut self, client: &MprisClient, events: &[MprisEvent]) -> Vec<Listen> {
+        for event in events {
+            if let MprisEvent::PlayerRemoved(name) = event {
+                self.players.remove(name);
+            }
+        }
+
+        let now = Instant::now();
+        let unix = unix_now();
+        client
+            .players()
+            .iter()
+            .filter_map(|player| {
+                let caps = player.capabilities();
+                self.observe(
+                    player.name(),
+                    &caps.metadata,
+                    caps.playback_status == PlaybackStatus::Playing,
+                    player.estimated_position_at(now),
+                    now,
+                    unix,
+                )
+            })
+            .collect()
+    }
+
+    /// records that `player` is at `position` of the track in `metadata` at `now`, `unix` is
+    /// the same moment as unix time
+    pub fn observe(
+        &mut self,
+        player: &str,
+        metadata: &Metadata,
+        playing: bool,
+        position: u64,
+        now: Instant,
+        unix: u64,
+    ) -> Option<Listen> {
+        let track = track_key(metadata);
+        let started = || unix.saturating_sub(position / 1_000_000);
+        let new = || Tracked {
+            track: track.clone(),
+            listen: Listen::new(metadata, player, started()),
+            threshold: threshold(metadata.length().map(Duration::from_micros)),
+            played: PlayTime::new(),
+            counted: false,
+        };
+
+        let tracked = self.players.entry(player.to_string()).or_insert_with(new);
+        // a new track, or the same one again from the start
+        let restarted = tracked.counted
+            && tracked
+                .played
+                .last_position()
+                .is_some_and(|last| position < last && position < 5_000_000);
+        if tracked.track != track || restarted {
+            *tracked = new();
+        }
+
+        tracked.played.observe(playing, position, now);
+        if !playing {
+            return None;
+        }
+        let threshold = tracked.threshold?;
+        if tracked.counted || tracked.played.played() < threshold {
+            return None;
+        }
+        tracked.counted = true;
+        tracked.listen.clone()
+    }
+}
+
+/// Last.fm credentials, see <https://www.last.fm/api/authentication>
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct LastFm {
+    pub api_key: String,
+    pub secret: String,
+    /// empty until [`LastFm::authenticate`] got one
+    #[serde(default)]
+    pub session_key: String,
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+impl LastFm {
+    /// at most this many listens go into one request
+    pub const BATCH: usize = 50;
+
+    /// trades a username and password for the session key scrobbling needs
+    pub async fn authenticate(
+        api_key: &str,
+        secret: &str,
+        username: &str,
+        password: &str,
+    ) -> anyhow::Result<String> {
+        let mut params = vec![
+            ("method".to_string(), "auth.getMobileSession".to_string()),
+            ("api_key".to_string(), api_key.to_string()),
+            ("username".to_string(), username.to_string()),
+            ("password".to_string(), password.to_string()),
+        ];
+        let reply = Self::call(&reqwest::Client::new(), LASTFM_URL, secret, &mut params).await?;
+        reply["session"]["key"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Last.fm sent no session key: {reply}"))
+    }
+
+    pub async fn scrobble(&self, http: &reqwest::Client, listens: &[Listen]) -> anyhow::Result<()> {
+        let mut params = vec![
+            ("method".to_string(), "track.scrobble".to_string()),
+            ("api_key".to_string(), self.api_key.clone()),
+            ("sk".to_string(), self.session_key.clone()),
+        ];
+        for (i, listen) in listens.iter().enumerate() {
+            let mut param = |key: &str, value: String| params.push((format!("{key}[{i}]"), value));
+            param("artist", listen.artist.clone());
+            param("track", listen.title.clone());
+            param("timestamp", listen.listened_at.to_string());
+            if let Some(album) = &listen.album {
+                param("album", album.clone());
+            }
+            if let Some(album_artist) = &listen.album_artist {
+                param("albumArtist", album_artist.clone());
+            }
+            if let Some(length) = listen.length {
+                param("duration", length.to_string());
+            }
+        }
+
+        let url = self.url.as_deref().unwrap_or(LASTFM_URL);
+        Self::call(http, url, &self.secret, &mut params).await?;
+        Ok(())
+    }
+
+    /// signs and posts a call, Last.fm answers errors with a 200 and an `error` field too
+    async fn call(
+        http: &reqwest::Client,
+        url: &str,
+        secret: &str,
+        params: &mut Vec<(String, String)>,
+    ) -> anyhow::Result<Value> {
+        params.sort();
+        let mut signed = String::new();
+        for (key, value) in params.iter() {
+            signed.push_str(key);
+            signed.push_str(value);
+        }
+        signed.push_str(secret);
+        params.push(("api_sig".to_string(), format!("{:x}", md5::compute(signed))));
+        params.push(("format".to_string(), "json".to_string()));
+
+        let bytes = http.post(url).form(params).send().await?.bytes().await?;
+        let reply: Value = serde_json::from_slice(&bytes)?;
+        if let Some(error) = reply.get("error") {
+            bail!("Last.fm error {error}: {}", reply["message"]);
+        }
+        Ok(reply)
+    }
+}
+
+/// ListenBrainz credentials, the token is on <https://listenbrainz.org/settings/>
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ListenBrainz {
+    pub token: String,
+    /// for other instances, like a self hosted one
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+impl ListenBrainz {
+    /// at most this many listens go into one request
+    pub const BATCH: usize = 100;
+
+    pub async fn submit(&self, http: &reqwest::Client, listens: &[Listen]) -> anyhow::Result<()> {
+        let payload: Vec<_> = listens
+            .iter()
+            .map(|listen| {
+                let mut info = json!({
+                    "media_player": listen.player,
+                    "submission_client": "mpris-controller",
+                });
+                if let Some(length) = listen.length {
+                    info["duration_ms"] = json!(length * 1000);
+                }
+                json!({
+                    "listened_at": listen.listened_at,
+                    "track_metadata": {
+                        "artist_name": listen.artist,
+                        "track_name": listen.title,
+                        "release_name": listen.album,
+                        "additional_info": info,
+                    },
+                })
+            })
+            .collect();
+        let body = json!({
+            "listen_type": if listens.len() == 1 { "single" } else { "import" },
+            "payload": payload,
+        });
+
+        let url = self.url.as_deref().unwrap_or(LISTENBRAINZ_URL);
+        let response = http
+            .post(format!("{}/1/submit-listens", url.trim_end_matches('/')))
+            .header("Authorization", format!("Token {}", self.token))
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_vec(&body)?)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            bail!("ListenBrainz answered {status}: {}", response.text().await?);
+        }
+        Ok(())
+    }
+}
+
+/// the `[scrobble]` section of the config, services without credentials are skipped
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScrobbleConfig {
+    pub lastfm: Option<LastFm>,
+    pub listenbrainz: Option<ListenBrainz>,
+}
+
+#[derive(Debug)]
+enum Service {
+    LastFm(LastFm),
+    ListenBrainz(ListenBrainz),
+}
+
+impl Service {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::LastFm(_) => "lastfm",
+            Self::ListenBrainz(_) => "listenbrainz",
+        }
+    }
+
+    fn batch(&self) -> usize {
+        match self {
+            Self::LastFm(_) => LastFm::BATCH,
+            Self::ListenBrainz(_) => ListenBrainz::BATCH,
+        }
+    }
+
+    async fn submit(&self, http: &reqwest::Client, listens: &[Listen]) -> anyhow::Result<()> {
+        match self {
+            Self::LastFm(lastfm) => lastfm.scrobble(http, listens).await,
+            Self::ListenBrainz(listenbrainz) => listenbrainz.submit(http, listens).await,
+        }
+    }
+}
+
+/// listens waiting to be submitted to one service, kept in a json file
+#[derive(Debug)]
+pub struct ScrobbleQueue {
+    path: PathBuf,
+    listens: Vec<Listen>,
+}
+
+impl ScrobbleQueue {
+    /// an empty queue when `path` doesn't exist yet
+    pub fn load(path: PathBuf) -> anyhow::Result<Self> {
+        let listens = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("parsing {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+        };
+        Ok(Self { path, listens })
+    }
+
+    pub fn listens(&self) -> &[Listen] {
+        &self.listens
+    }
+
+    pub fn push(&mut self, listen: Listen) -> anyhow::Result<()> {
+        self.listens.push(listen);
+        self.save()
+    }
+
+    /// drops the first `n` listens, once they were submitted
+    pub fn remove(&mut self, n: usize) -> anyhow::Result<()> {
+        self.listens.drain(..n.min(self.listens.len()));
+        self.save()
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // write then rename, a crash mid-write must not lose the queue
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&self.listens)?)?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("writing {}", self.path.display()))
+    }
+}
+
+#[derive(Debug)]
+struct Target {
+    service: Service,
+    queue: ScrobbleQueue,
+    retry_at: Option<Instant>,
+}
+
+/// tracks listens and submits them to every configured service
+#[derive(Debug)]
+pub struct Scrobbler {
+    http: reqwest::Client,
+    tracker: ListenTracker,
+    targets: Vec<Target>,
+}
+
+impl Scrobbler {
+    /// keeps a queue per service in `dir`, see [`Scrobbler::default_dir`]
+    pub fn new(config: &ScrobbleConfig, dir: &Path) -> anyhow::Result<Self> {
+        let services = config
+            .lastfm
+            .clone()
+            .map(Service::LastFm)
+            .into_iter()
+            .chain(config.listenbrainz.clone().map(Service::ListenBrainz));
+        let targets = services
+            .map(|service| {
+                let path = dir.join(format!("scrobbles-{}.json", service.name()));
+                Ok(Target {
+                    queue: ScrobbleQueue::load(path)?,
+                    service,
+                    retry_at: None,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if targets.is_empty() {
+            bail!("no scrobbling service is configured");
+        }
+        if config
+            .lastfm
+            .as_ref()
+            .is_some_and(|lastfm| lastfm.session_key.is_empty())
+        {
+            bail!("Last.fm has no session key yet, see `LastFm::authenticate`");
+        }
+
+        Ok(Self {
+            http: reqwest::Client::new(),
+            tracker: ListenTracker::new(),
+            targets,
+        })
+    }
+
+    /// `$XDG_STATE_HOME/mpris-controller`, falling back to `~/.local/state`
+    pub fn default_dir() -> Option<PathBuf> {
+        crate::persist::default_path()?
+            .parent()
+            .map(Path::to_path_buf)
+    }
+
+    /// listens not submitted yet, per service
+    pub fn pending(&self) -> Vec<(&'static str, usize)> {
+        self.targets
+            .iter()
+            .map(|target| (target.service.name(), target.queue.listens().len()))
+            .collect()
+    }
+
+    /// queues the listens that `events` completed and submits what is queued
+    pub async fn handle_events(
+        &mut self,
+        client: &MprisClient,
+        events: &[MprisEvent],
+    ) -> anyhow::Result<()> {
+        let listens = self.tracker.handle_events(client, events);
+        for listen in listens {
+            debug!(artist = listen.artist, title = listen.title, "listened");
+            for target in &mut self.targets {
+                target.queue.push(listen.clone())?;
+            }
+        }
+
+        self.flush().await
+    }
+
+    /// submits what is queued, services that fail are tried again after [`RETRY_INTERVAL`]
+    pub async fn flush(&mut self) -> anyhow::Result<()> {
+        let now = Instant::now();
+        for target in &mut self.targets {
+            if target.retry_at.is_some_and(|at| now < at) {
+                continue;
+            }
+            target.retry_at = None;
+
+            while !target.queue.listens().is_empty() {
+                let batch = target.queue.listens().len().min(target.service.batch());
+                let listens = &target.queue.listens()[..batch];
+                if let Err(e) = target.service.submit(&self.http, listens).await {
+                    warn!(
+                        service = target.service.name(),
+                        queued = target.queue.listens().len(),
+                        "failed to scrobble: {e:#}"
+                    );
+                    target.retry_at = Some(now + RETRY_INTERVAL);
+                    break;
+                }
+                target.queue.remove(batch)?;
+            }
+        }
+        Ok(())
+    }
+}
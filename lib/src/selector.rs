@@ -0,0 +1,126 @@
+//! choosing players out of an [`MprisClient`]
+//!
+//! ```ignore
+//! let player = Selector::playing().prefer("spotify").exclude("chromium").select(&client);
+//! ```
+//!
+//! patterns are the ones from [`pattern`], `chromium` also covers
+//! `chromium.instance1234`. a [`StableId`](crate::stable_id::StableId) matches its player and so
+//! does a friendly name like `VLC media player`, see [`Player::is_called`].
+
+use crate::{
+    pattern,
+    player::{PlaybackStatus, Player},
+    MprisClient,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct Selector {
+    include: Vec<String>,
+    exclude: Vec<String>,
+    prefer: Vec<String>,
+    status: Option<PlaybackStatus>,
+    remote: Option<bool>,
+}
+
+/// whether `pattern` picks out `player`
+pub fn matches(pattern: &str, player: &Player) -> bool {
+    let name = player.name();
+    pattern::matches(pattern, name)
+        || pattern::matches(&format!("{pattern}.*"), name)
+        || player.stable_id().to_string() == pattern
+        || player.is_called(pattern)
+}
+
+impl Selector {
+    /// every player
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// players that are playing right now
+    pub fn playing() -> Self {
+        Self::new().status(PlaybackStatus::Playing)
+    }
+
+    pub fn status(mut self, status: PlaybackStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// only players on other devices, or only local ones, see [`Player::is_remote`]
+    pub fn remote(mut self, remote: bool) -> Self {
+        self.remote = Some(remote);
+        self
+    }
+
+    /// only players matching `pattern` or one of the other included patterns
+    pub fn include(mut self, pattern: impl Into<String>) -> Self {
+        self.include.push(pattern.into());
+        self
+    }
+
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.exclude.push(pattern.into());
+        self
+    }
+
+    /// puts players matching `pattern` first, after the ones preferred before
+    pub fn prefer(mut self, pattern: impl Into<String>) -> Self {
+        self.prefer.push(pattern.into());
+        self
+    }
+
+    pub fn prefer_all<I, S>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.prefer.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn matches(&self, player: &Player) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| matches(p, player)))
+            && !self.exclude.iter().any(|p| matches(p, player))
+            && self
+                .status
+                .is_none_or(|status| player.capabilities().playback_status == status)
+            && self
+                .remote
+                .is_none_or(|remote| player.is_remote() == remote)
+    }
+
+    /// every matching player, the preferred ones first in the order they were preferred in
+    pub fn select_all<'a>(&self, client: &'a MprisClient) -> Vec<&'a Player> {
+        let mut players: Vec<&Player> = client
+            .players()
+            .iter()
+            .filter(|p| self.matches(p))
+            .collect();
+        // stable, so players matching the same pattern keep the client's order
+        players.sort_by_key(|p| {
+            self.prefer
+                .iter()
+                .position(|pattern| matches(pattern, p))
+                .unwrap_or(self.prefer.len())
+        });
+
+        players
+    }
+
+    /// "the" matching player: one matching the earliest preferred pattern that matches any, the
+    /// [active player](MprisClient::active_player) otherwise, then the one found first
+    ///
+    /// among several players matching the same preferred pattern the active one wins too.
+    pub fn select<'a>(&self, client: &'a MprisClient) -> Option<&'a Player> {
+        let players = self.select_all(client);
+        let first = *players.first()?;
+        let rank = |player: &Player| self.prefer.iter().position(|p| matches(p, player));
+        let active = client
+            .active_player()
+            .filter(|active| self.matches(active) && rank(active) == rank(first));
+
+        Some(active.unwrap_or(first))
+    }
+}
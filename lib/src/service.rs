@@ -0,0 +1,1303 @@
+//! the other side of the protocol: exposing an application as an MPRIS player
+//!
+//! the service keeps the state clients read and announces changes to it, the application only
+//! says what happens when it is asked to do something.
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use lib::{
+//!     player::{Capabilities, MetadataBuilder, PlaybackStatus},
+//!     service::{MprisService, PlayerHandler},
+//! };
+//!
+//! struct Radio;
+//!
+//! impl PlayerHandler for Radio {
+//!     fn play(&mut self, state: &mut Capabilities) -> anyhow::Result<()> {
+//!         // start the stream, then say so
+//!         state.playback_status = PlaybackStatus::Playing;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let service = MprisService::builder("radio", Radio)
+//!     .capabilities(Capabilities { can_control: true, can_play: true, rate: 1.0, ..Default::default() })
+//!     .serve()
+//!     .await?;
+//! service
+//!     .set_metadata(MetadataBuilder::default().title("news".to_string()).finish())
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use zbus::{
+    connection, fdo,
+    object_server::{InterfaceRef, SignalEmitter},
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value},
+    Connection, ObjectServer,
+};
+
+use crate::{
+    player::{
+        Capabilities, LoopStatus, Metadata, PlaybackStatus, RootProperties, TrackId, NO_TRACK,
+    },
+    playlists::{Playlist, PlaylistOrdering},
+    tracklist::TrackListUpdate,
+    MPRIS_PATH, MPRIS_PREFIX,
+};
+
+/// what the application does when a client asks it to, the defaults refuse with
+/// `org.freedesktop.DBus.Error.NotSupported`
+///
+/// `state` is what clients see, changes made to it are announced once the method returns. an
+/// error is handed to the client that made the call.
+pub trait PlayerHandler: Send + 'static {
+    fn play(&mut self, state: &mut Capabilities) -> anyhow::Result<()> {
+        let _ = state;
+        Err(unsupported("Play"))
+    }
+
+    fn pause(&mut self, state: &mut Capabilities) -> anyhow::Result<()> {
+        let _ = state;
+        Err(unsupported("Pause"))
+    }
+
+    /// pauses when playing and plays otherwise
+    fn play_pause(&mut self, state: &mut Capabilities) -> anyhow::Result<()> {
+        match state.playback_status {
+            PlaybackStatus::Playing => self.pause(state),
+            _ => self.play(state),
+        }
+    }
+
+    fn stop(&mut self, state: &mut Capabilities) -> anyhow::Result<()> {
+        let _ = state;
+        Err(unsupported("Stop"))
+    }
+
+    fn next(&mut self, state: &mut Capabilities) -> anyhow::Result<()> {
+        let _ = state;
+        Err(unsupported("Next"))
+    }
+
+    fn previous(&mut self, state: &mut Capabilities) -> anyhow::Result<()> {
+        let _ = state;
+        Err(unsupported("Previous"))
+    }
+
+    /// moves by `offset` microseconds, changing `state.position` sends `Seeked`
+    fn seek(&mut self, state: &mut Capabilities, offset: i64) -> anyhow::Result<()> {
+        let _ = (state, offset);
+        Err(unsupported("Seek"))
+    }
+
+    /// jumps to `position` in microseconds if `track` is still the current track, changing
+    /// `state.position` sends `Seeked`
+    fn set_position(
+        &mut self,
+        state: &mut Capabilities,
+        track: &TrackId,
+        position: u64,
+    ) -> anyhow::Result<()> {
+        let _ = (state, track, position);
+        Err(unsupported("SetPosition"))
+    }
+
+    fn open_uri(&mut self, state: &mut Capabilities, uri: &str) -> anyhow::Result<()> {
+        let _ = (state, uri);
+        Err(unsupported("OpenUri"))
+    }
+
+    fn set_volume(&mut self, state: &mut Capabilities, volume: f64) -> anyhow::Result<()> {
+        let _ = (state, volume);
+        Err(unsupported("setting Volume"))
+    }
+
+    fn set_rate(&mut self, state: &mut Capabilities, rate: f64) -> anyhow::Result<()> {
+        let _ = (state, rate);
+        Err(unsupported("setting Rate"))
+    }
+
+    fn set_loop_status(
+        &mut self,
+        state: &mut Capabilities,
+        status: LoopStatus,
+    ) -> anyhow::Result<()> {
+        let _ = (state, status);
+        Err(unsupported("setting LoopStatus"))
+    }
+
+    fn set_shuffle(&mut self, state: &mut Capabilities, shuffle: bool) -> anyhow::Result<()> {
+        let _ = (state, shuffle);
+        Err(unsupported("setting Shuffle"))
+    }
+
+    fn raise(&mut self) -> anyhow::Result<()> {
+        Err(unsupported("Raise"))
+    }
+
+    fn quit(&mut self) -> anyhow::Result<()> {
+        Err(unsupported("Quit"))
+    }
+}
+
+/// the tracklist clients see, changes to it are announced with the tracklist signals
+///
+/// tracks without an `mpris:trackid` are left out. the player's metadata follows `current`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrackListState {
+    pub tracks: Vec<Metadata>,
+    pub current: Option<TrackId>,
+    pub can_edit_tracks: bool,
+}
+
+impl TrackListState {
+    pub fn track(&self, id: &TrackId) -> Option<&Metadata> {
+        self.tracks.iter().find(|m| m.track_id() == Some(id))
+    }
+
+    fn ids(&self) -> impl Iterator<Item = &TrackId> {
+        self.tracks.iter().filter_map(Metadata::track_id)
+    }
+
+    fn current_metadata(&self) -> Option<&Metadata> {
+        self.current.as_ref().and_then(|id| self.track(id))
+    }
+}
+
+/// what the application does with `org.mpris.MediaPlayer2.TrackList` calls, like
+/// [`PlayerHandler`]
+///
+/// `AddTrack` and `RemoveTrack` don't reach the handler while `can_edit_tracks` is false.
+pub trait TrackListHandler: Send + 'static {
+    /// adds `uri` after `after`, or at the start when `None`
+    fn add_track(
+        &mut self,
+        state: &mut TrackListState,
+        uri: &str,
+        after: Option<&TrackId>,
+        set_current: bool,
+    ) -> anyhow::Result<()> {
+        let _ = (state, uri, after, set_current);
+        Err(unsupported("AddTrack"))
+    }
+
+    fn remove_track(&mut self, state: &mut TrackListState, track: &TrackId) -> anyhow::Result<()> {
+        let _ = (state, track);
+        Err(unsupported("RemoveTrack"))
+    }
+
+    /// skips to `track`, by default only by making it the current track
+    fn go_to(&mut self, state: &mut TrackListState, track: &TrackId) -> anyhow::Result<()> {
+        if state.track(track).is_some() {
+            state.current = Some(track.clone());
+        }
+        Ok(())
+    }
+}
+
+/// the playlists clients see
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlaylistsState {
+    pub playlists: Vec<Playlist>,
+    pub orderings: Vec<PlaylistOrdering>,
+    pub active: Option<Playlist>,
+}
+
+/// what the application does with `org.mpris.MediaPlayer2.Playlists` calls, like
+/// [`PlayerHandler`]
+pub trait PlaylistsHandler: Send + 'static {
+    /// starts playing the playlist with the id `id`
+    fn activate_playlist(&mut self, state: &mut PlaylistsState, id: &str) -> anyhow::Result<()> {
+        let _ = (state, id);
+        Err(unsupported("ActivatePlaylist"))
+    }
+
+    /// a page of playlists, by default `state.playlists` in the order they're in for every
+    /// ordering but `Alphabetical`
+    fn get_playlists(
+        &mut self,
+        state: &PlaylistsState,
+        index: u32,
+        max_count: u32,
+        order: PlaylistOrdering,
+        reverse: bool,
+    ) -> anyhow::Result<Vec<Playlist>> {
+        let mut playlists = state.playlists.clone();
+        if order == PlaylistOrdering::Alphabetical {
+            playlists.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+        if reverse {
+            playlists.reverse();
+        }
+        Ok(playlists
+            .into_iter()
+            .skip(index as usize)
+            .take(max_count as usize)
+            .collect())
+    }
+}
+
+/// the error the defaults of [`PlayerHandler`] return, for handlers that only support some of a
+/// call
+pub fn unsupported(what: &str) -> anyhow::Error {
+    fdo::Error::NotSupported(format!("{what} is not supported")).into()
+}
+
+fn to_fdo(e: anyhow::Error) -> fdo::Error {
+    e.downcast::<fdo::Error>()
+        .unwrap_or_else(|e| fdo::Error::Failed(format!("{e:#}")))
+}
+
+/// runs `f` on the handler behind `handler`
+fn call<H: ?Sized, T>(
+    handler: &Mutex<Box<H>>,
+    f: impl FnOnce(&mut H) -> anyhow::Result<T>,
+) -> fdo::Result<anyhow::Result<T>> {
+    let mut handler = handler
+        .lock()
+        .map_err(|_| fdo::Error::Failed("the player handler panicked".to_string()))?;
+    Ok(f(handler.as_mut()))
+}
+
+fn metadata_dict(metadata: &Metadata) -> fdo::Result<HashMap<String, OwnedValue>> {
+    HashMap::<String, Value>::from(metadata.clone())
+        .into_iter()
+        .map(|(key, value)| Ok((key, value.try_to_owned().map_err(zbus::Error::from)?)))
+        .collect()
+}
+
+fn object_path(id: &str) -> fdo::Result<ObjectPath<'_>> {
+    ObjectPath::try_from(id).map_err(|e| fdo::Error::Failed(format!("invalid id {id}: {e}")))
+}
+
+fn playlist_struct(playlist: &Playlist) -> fdo::Result<(OwnedObjectPath, String, String)> {
+    Ok((
+        object_path(&playlist.id)?.into(),
+        playlist.name.clone(),
+        playlist.icon.clone(),
+    ))
+}
+
+type Handler = Arc<Mutex<Box<dyn PlayerHandler>>>;
+
+pub struct MprisServiceBuilder {
+    name: String,
+    capabilities: Capabilities,
+    root: RootProperties,
+    handler: Box<dyn PlayerHandler>,
+    tracklist: Option<(TrackListState, Box<dyn TrackListHandler>)>,
+    playlists: Option<(PlaylistsState, Box<dyn PlaylistsHandler>)>,
+}
+
+impl MprisServiceBuilder {
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub fn root(mut self, root: RootProperties) -> Self {
+        self.root = root;
+        self
+    }
+
+    /// also serves `org.mpris.MediaPlayer2.TrackList`, which sets `HasTrackList`
+    pub fn tracklist(mut self, state: TrackListState, handler: impl TrackListHandler) -> Self {
+        self.tracklist = Some((state, Box::new(handler)));
+        self
+    }
+
+    /// also serves `org.mpris.MediaPlayer2.Playlists`
+    pub fn playlists(mut self, state: PlaylistsState, handler: impl PlaylistsHandler) -> Self {
+        self.playlists = Some((state, Box::new(handler)));
+        self
+    }
+
+    /// serves the player on the session bus
+    pub async fn serve(self) -> anyhow::Result<MprisService> {
+        self.serve_on(connection::Builder::session()?).await
+    }
+
+    /// serves the player on the bus `builder` connects to
+    pub async fn serve_on(
+        mut self,
+        builder: connection::Builder<'_>,
+    ) -> anyhow::Result<MprisService> {
+        let handler: Handler = Arc::new(Mutex::new(self.handler));
+        let mut capabilities = self.capabilities;
+        if let Some((state, _)) = &self.tracklist {
+            self.root.has_track_list = true;
+            if let Some(metadata) = state.current_metadata() {
+                capabilities.metadata = metadata.clone();
+            }
+        }
+
+        let mut builder = builder
+            .name(self.name.as_str())?
+            .serve_at(
+                MPRIS_PATH,
+                PlayerIface {
+                    capabilities,
+                    handler: handler.clone(),
+                },
+            )?
+            .serve_at(
+                MPRIS_PATH,
+                RootIface {
+                    root: self.root,
+                    handler,
+                },
+            )?;
+        if let Some((state, handler)) = self.tracklist {
+            builder = builder.serve_at(
+                MPRIS_PATH,
+                TrackListIface {
+                    state,
+                    handler: Mutex::new(handler),
+                },
+            )?;
+        }
+        if let Some((state, handler)) = self.playlists {
+            builder = builder.serve_at(
+                MPRIS_PATH,
+                PlaylistsIface {
+                    state,
+                    handler: Mutex::new(handler),
+                },
+            )?;
+        }
+        let conn = builder.build().await?;
+
+        Ok(MprisService {
+            name: self.name,
+            conn,
+        })
+    }
+}
+
+/// an application served as `org.mpris.MediaPlayer2.<name>` until this is dropped
+#[derive(Debug)]
+pub struct MprisService {
+    name: String,
+    conn: Connection,
+}
+
+impl MprisService {
+    /// `name` is what comes after `org.mpris.MediaPlayer2.`, like `vlc` or
+    /// `chromium.instance1234`
+    pub fn builder(name: &str, handler: impl PlayerHandler) -> MprisServiceBuilder {
+        MprisServiceBuilder {
+            name: format!("{MPRIS_PREFIX}.{name}"),
+            capabilities: Capabilities {
+                rate: 1.0,
+                ..Default::default()
+            },
+            root: RootProperties::default(),
+            handler: Box::new(handler),
+            tracklist: None,
+            playlists: None,
+        }
+    }
+
+    /// the full bus name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    async fn player(&self) -> anyhow::Result<InterfaceRef<PlayerIface>> {
+        Ok(self
+            .conn
+            .object_server()
+            .interface::<_, PlayerIface>(MPRIS_PATH)
+            .await?)
+    }
+
+    async fn root_iface(&self) -> anyhow::Result<InterfaceRef<RootIface>> {
+        Ok(self
+            .conn
+            .object_server()
+            .interface::<_, RootIface>(MPRIS_PATH)
+            .await?)
+    }
+
+    async fn tracklist_iface(&self) -> anyhow::Result<InterfaceRef<TrackListIface>> {
+        Ok(self
+            .conn
+            .object_server()
+            .interface::<_, TrackListIface>(MPRIS_PATH)
+            .await?)
+    }
+
+    async fn playlists_iface(&self) -> anyhow::Result<InterfaceRef<PlaylistsIface>> {
+        Ok(self
+            .conn
+            .object_server()
+            .interface::<_, PlaylistsIface>(MPRIS_PATH)
+            .await?)
+    }
+
+    pub async fn capabilities(&self) -> anyhow::Result<Capabilities> {
+        Ok(self.player().await?.get().await.capabilities.clone())
+    }
+
+    pub async fn root(&self) -> anyhow::Result<RootProperties> {
+        Ok(self.root_iface().await?.get().await.root.clone())
+    }
+
+    /// changes the state clients see and announces what changed
+    ///
+    /// the position changes silently like the spec wants, see [`MprisService::seeked`] for
+    /// jumps.
+    pub async fn update(&self, f: impl FnOnce(&mut Capabilities)) -> anyhow::Result<()> {
+        let player = self.player().await?;
+        let mut iface = player.get_mut().await;
+        let before = iface.capabilities.clone();
+        f(&mut iface.capabilities);
+        iface.announce(&before, player.signal_emitter()).await?;
+        Ok(())
+    }
+
+    pub async fn set_playback_status(&self, status: PlaybackStatus) -> anyhow::Result<()> {
+        self.update(|state| state.playback_status = status).await
+    }
+
+    /// a new track usually starts at 0, set the position along with it
+    pub async fn set_metadata(&self, metadata: Metadata) -> anyhow::Result<()> {
+        self.update(|state| state.metadata = metadata).await
+    }
+
+    pub async fn set_volume(&self, volume: f64) -> anyhow::Result<()> {
+        self.update(|state| state.volume = Some(volume)).await
+    }
+
+    pub async fn set_rate(&self, rate: f64) -> anyhow::Result<()> {
+        self.update(|state| state.rate = rate).await
+    }
+
+    pub async fn set_loop_status(&self, status: LoopStatus) -> anyhow::Result<()> {
+        self.update(|state| state.loop_status = Some(status)).await
+    }
+
+    pub async fn set_shuffle(&self, shuffle: bool) -> anyhow::Result<()> {
+        self.update(|state| state.shuffle = Some(shuffle)).await
+    }
+
+    /// playback moved on by itself, clients read the position when they need it
+    pub async fn set_position(&self, position: u64) -> anyhow::Result<()> {
+        self.update(|state| state.position = position).await
+    }
+
+    /// jumps to `position` and sends `Seeked`, for jumps the application made on its own
+    pub async fn seeked(&self, position: u64) -> anyhow::Result<()> {
+        let player = self.player().await?;
+        player.get_mut().await.capabilities.position = position;
+        PlayerIface::seeked(player.signal_emitter(), position.cast_signed()).await?;
+        Ok(())
+    }
+
+    /// changes the root properties and announces what changed
+    pub async fn update_root(&self, f: impl FnOnce(&mut RootProperties)) -> anyhow::Result<()> {
+        let root = self.root_iface().await?;
+        let mut iface = root.get_mut().await;
+        let before = iface.root.clone();
+        f(&mut iface.root);
+        iface.announce(&before, root.signal_emitter()).await?;
+        Ok(())
+    }
+
+    /// fails unless the service was built with [`MprisServiceBuilder::tracklist`]
+    pub async fn tracklist(&self) -> anyhow::Result<TrackListState> {
+        Ok(self.tracklist_iface().await?.get().await.state.clone())
+    }
+
+    /// changes the tracklist and sends the signals that describe the change
+    pub async fn update_tracklist(
+        &self,
+        f: impl FnOnce(&mut TrackListState),
+    ) -> anyhow::Result<()> {
+        let tracklist = self.tracklist_iface().await?;
+        let mut iface = tracklist.get_mut().await;
+        let before = iface.state.clone();
+        f(&mut iface.state);
+        iface
+            .announce(
+                &before,
+                tracklist.signal_emitter(),
+                self.conn.object_server(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// fails unless the service was built with [`MprisServiceBuilder::playlists`]
+    pub async fn playlists(&self) -> anyhow::Result<PlaylistsState> {
+        Ok(self.playlists_iface().await?.get().await.state.clone())
+    }
+
+    /// changes the playlists and announces what changed
+    pub async fn update_playlists(
+        &self,
+        f: impl FnOnce(&mut PlaylistsState),
+    ) -> anyhow::Result<()> {
+        let playlists = self.playlists_iface().await?;
+        let mut iface = playlists.get_mut().await;
+        let before = iface.state.clone();
+        f(&mut iface.state);
+        iface.announce(&before, playlists.signal_emitter()).await?;
+        Ok(())
+    }
+}
+
+struct PlayerIface {
+    capabilities: Capabilities,
+    handler: Handler,
+}
+
+impl PlayerIface {
+    /// sends `PropertiesChanged` for what differs from `before`
+    async fn announce(
+        &self,
+        before: &Capabilities,
+        emitter: &SignalEmitter<'_>,
+    ) -> zbus::Result<()> {
+        let now = &self.capabilities;
+        if now.playback_status != before.playback_status {
+            self.playback_status_changed(emitter).await?;
+        }
+        if now.loop_status != before.loop_status {
+            self.loop_status_changed(emitter).await?;
+        }
+        if now.rate != before.rate {
+            self.rate_changed(emitter).await?;
+        }
+        if now.shuffle != before.shuffle {
+            self.shuffle_changed(emitter).await?;
+        }
+        if now.metadata != before.metadata {
+            self.metadata_changed(emitter).await?;
+        }
+        if now.volume != before.volume {
+            self.volume_changed(emitter).await?;
+        }
+        if now.min_rate != before.min_rate {
+            self.minimum_rate_changed(emitter).await?;
+        }
+        if now.max_rate != before.max_rate {
+            self.maximum_rate_changed(emitter).await?;
+        }
+        if now.can_next != before.can_next {
+            self.can_go_next_changed(emitter).await?;
+        }
+        if now.can_previous != before.can_previous {
+            self.can_go_previous_changed(emitter).await?;
+        }
+        if now.can_play != before.can_play {
+            self.can_play_changed(emitter).await?;
+        }
+        if now.can_pause != before.can_pause {
+            self.can_pause_changed(emitter).await?;
+        }
+        if now.can_seek != before.can_seek {
+            self.can_seek_changed(emitter).await?;
+        }
+        if now.can_control != before.can_control {
+            self.can_control_changed(emitter).await?;
+        }
+        Ok(())
+    }
+
+    /// runs a handler method on the state and announces what it changed, jumps in the position
+    /// with `Seeked`
+    async fn handle(
+        &mut self,
+        emitter: &SignalEmitter<'_>,
+        f: impl FnOnce(&mut dyn PlayerHandler, &mut Capabilities) -> anyhow::Result<()>,
+    ) -> fdo::Result<()> {
+        self.handle_setter(emitter, |_, _| {}, f).await
+    }
+
+    /// [`PlayerIface::handle`] for property setters, zbus announces the property that was set
+    /// itself so `skip` copies it into the state from before
+    async fn handle_setter(
+        &mut self,
+        emitter: &SignalEmitter<'_>,
+        skip: impl FnOnce(&mut Capabilities, &Capabilities),
+        f: impl FnOnce(&mut dyn PlayerHandler, &mut Capabilities) -> anyhow::Result<()>,
+    ) -> fdo::Result<()> {
+        let mut before = self.capabilities.clone();
+        let result = {
+            let mut handler = self
+                .handler
+                .lock()
+                .map_err(|_| fdo::Error::Failed("the player handler panicked".to_string()))?;
+            f(handler.as_mut(), &mut self.capabilities)
+        };
+        skip(&mut before, &self.capabilities);
+        self.announce(&before, emitter).await?;
+        if self.capabilities.position != before.position {
+            Self::seeked(emitter, self.capabilities.position.cast_signed()).await?;
+        }
+        result.map_err(to_fdo)
+    }
+}
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+impl PlayerIface {
+    async fn play(
+        &mut self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.handle(&emitter, |h, state| h.play(state)).await
+    }
+
+    async fn pause(
+        &mut self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.handle(&emitter, |h, state| h.pause(state)).await
+    }
+
+    async fn play_pause(
+        &mut self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.handle(&emitter, |h, state| h.play_pause(state)).await
+    }
+
+    async fn stop(
+        &mut self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.handle(&emitter, |h, state| h.stop(state)).await
+    }
+
+    async fn next(
+        &mut self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.handle(&emitter, |h, state| h.next(state)).await
+    }
+
+    async fn previous(
+        &mut self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.handle(&emitter, |h, state| h.previous(state)).await
+    }
+
+    async fn seek(
+        &mut self,
+        offset: i64,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.handle(&emitter, |h, state| h.seek(state, offset))
+            .await
+    }
+
+    async fn set_position(
+        &mut self,
+        track_id: ObjectPath<'_>,
+        position: i64,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        // the spec says to ignore positions outside the track
+        if position < 0 {
+            return Ok(());
+        }
+        let track = TrackId::new(track_id.as_str());
+        self.handle(&emitter, |h, state| {
+            h.set_position(state, &track, position.cast_unsigned())
+        })
+        .await
+    }
+
+    async fn open_uri(
+        &mut self,
+        uri: String,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.handle(&emitter, |h, state| h.open_uri(state, &uri))
+            .await
+    }
+
+    #[zbus(signal)]
+    async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        self.capabilities.playback_status.to_string()
+    }
+
+    #[zbus(property)]
+    fn loop_status(&self) -> String {
+        self.capabilities
+            .loop_status
+            .unwrap_or(LoopStatus::None)
+            .to_string()
+    }
+
+    #[zbus(property)]
+    async fn set_loop_status(
+        &mut self,
+        status: String,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        let status = LoopStatus::try_from(status.as_str())
+            .map_err(|e| fdo::Error::InvalidArgs(format!("{e:#}")))?;
+        self.handle_setter(
+            &emitter,
+            |before, now| before.loop_status = now.loop_status,
+            |h, state| h.set_loop_status(state, status),
+        )
+        .await
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        self.capabilities.rate
+    }
+
+    #[zbus(property)]
+    async fn set_rate(
+        &mut self,
+        rate: f64,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.handle_setter(
+            &emitter,
+            |before, now| before.rate = now.rate,
+            |h, state| h.set_rate(state, rate),
+        )
+        .await
+    }
+
+    #[zbus(property)]
+    fn shuffle(&self) -> bool {
+        self.capabilities.shuffle.unwrap_or(false)
+    }
+
+    #[zbus(property)]
+    async fn set_shuffle(
+        &mut self,
+        shuffle: bool,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.handle_setter(
+            &emitter,
+            |before, now| before.shuffle = now.shuffle,
+            |h, state| h.set_shuffle(state, shuffle),
+        )
+        .await
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> fdo::Result<HashMap<String, OwnedValue>> {
+        metadata_dict(&self.capabilities.metadata)
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.capabilities.volume.unwrap_or(0.0)
+    }
+
+    #[zbus(property)]
+    async fn set_volume(
+        &mut self,
+        volume: f64,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.handle_setter(
+            &emitter,
+            |before, now| before.volume = now.volume,
+            |h, state| h.set_volume(state, volume),
+        )
+        .await
+    }
+
+    // changes all the time, so it is read when needed rather than announced
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        self.capabilities.position.cast_signed()
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        self.capabilities.min_rate.unwrap_or(1.0)
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        self.capabilities.max_rate.unwrap_or(1.0)
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        self.capabilities.can_next
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        self.capabilities.can_previous
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        self.capabilities.can_play
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        self.capabilities.can_pause
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        self.capabilities.can_seek
+    }
+
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        self.capabilities.can_control
+    }
+}
+
+struct RootIface {
+    root: RootProperties,
+    handler: Handler,
+}
+
+impl RootIface {
+    async fn announce(
+        &self,
+        before: &RootProperties,
+        emitter: &SignalEmitter<'_>,
+    ) -> zbus::Result<()> {
+        let now = &self.root;
+        if now.identity != before.identity {
+            self.identity_changed(emitter).await?;
+        }
+        if now.desktop_entry != before.desktop_entry {
+            self.desktop_entry_changed(emitter).await?;
+        }
+        if now.can_quit != before.can_quit {
+            self.can_quit_changed(emitter).await?;
+        }
+        if now.can_raise != before.can_raise {
+            self.can_raise_changed(emitter).await?;
+        }
+        if now.has_track_list != before.has_track_list {
+            self.has_track_list_changed(emitter).await?;
+        }
+        if now.supported_uri_schemes != before.supported_uri_schemes {
+            self.supported_uri_schemes_changed(emitter).await?;
+        }
+        if now.supported_mime_types != before.supported_mime_types {
+            self.supported_mime_types_changed(emitter).await?;
+        }
+        Ok(())
+    }
+
+    fn call(
+        &self,
+        f: impl FnOnce(&mut dyn PlayerHandler) -> anyhow::Result<()>,
+    ) -> fdo::Result<()> {
+        let mut handler = self
+            .handler
+            .lock()
+            .map_err(|_| fdo::Error::Failed("the player handler panicked".to_string()))?;
+        f(handler.as_mut()).map_err(to_fdo)
+    }
+}
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2")]
+impl RootIface {
+    fn raise(&self) -> fdo::Result<()> {
+        self.call(|h| h.raise())
+    }
+
+    fn quit(&self) -> fdo::Result<()> {
+        self.call(|h| h.quit())
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        self.root.identity.clone().unwrap_or_default()
+    }
+
+    #[zbus(property)]
+    fn desktop_entry(&self) -> String {
+        self.root.desktop_entry.clone().unwrap_or_default()
+    }
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        self.root.can_quit
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        self.root.can_raise
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        self.root.has_track_list
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        self.root.supported_uri_schemes.clone()
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        self.root.supported_mime_types.clone()
+    }
+}
+
+/// the signals that take the tracklist from `before` to `now`, a reordering or a whole new
+/// list is sent as `TrackListReplaced`
+fn tracklist_changes(before: &TrackListState, now: &TrackListState) -> Vec<TrackListUpdate> {
+    let kept_before: Vec<_> = before.ids().filter(|id| now.track(id).is_some()).collect();
+    let kept_now: Vec<_> = now.ids().filter(|id| before.track(id).is_some()).collect();
+    let everything_new = kept_now.is_empty() && before.ids().next().is_some();
+    if kept_before != kept_now || (everything_new && now.ids().next().is_some()) {
+        return vec![TrackListUpdate::Replaced {
+            tracks: now.ids().cloned().collect(),
+            current: now
+                .current
+                .clone()
+                .unwrap_or_else(|| TrackId::new(NO_TRACK)),
+        }];
+    }
+
+    let mut changes: Vec<_> = before
+        .ids()
+        .filter(|id| now.track(id).is_none())
+        .map(|id| TrackListUpdate::Removed(id.clone()))
+        .collect();
+    let mut after = TrackId::new(NO_TRACK);
+    for metadata in &now.tracks {
+        let Some(id) = metadata.track_id() else {
+            continue;
+        };
+        match before.track(id) {
+            None => changes.push(TrackListUpdate::Added {
+                metadata: Box::new(metadata.clone()),
+                after: after.clone(),
+            }),
+            Some(old) if old != metadata => changes.push(TrackListUpdate::MetadataChanged {
+                track: id.clone(),
+                metadata: Box::new(metadata.clone()),
+            }),
+            Some(_) => {}
+        }
+        after = id.clone();
+    }
+
+    changes
+}
+
+struct TrackListIface {
+    state: TrackListState,
+    handler: Mutex<Box<dyn TrackListHandler>>,
+}
+
+impl TrackListIface {
+    /// sends the tracklist signals for what differs from `before` and moves the player to the
+    /// current track
+    async fn announce(
+        &self,
+        before: &TrackListState,
+        emitter: &SignalEmitter<'_>,
+        server: &ObjectServer,
+    ) -> zbus::Result<()> {
+        let now = &self.state;
+        if now.tracks != before.tracks {
+            for change in tracklist_changes(before, now) {
+                match change {
+                    TrackListUpdate::Replaced { tracks, current } => {
+                        let tracks = tracks
+                            .iter()
+                            .map(|id| object_path(id))
+                            .collect::<fdo::Result<Vec<_>>>()?;
+                        Self::track_list_replaced(emitter, tracks, object_path(&current)?).await?;
+                    }
+                    TrackListUpdate::Added { metadata, after } => {
+                        Self::track_added(emitter, metadata_dict(&metadata)?, object_path(&after)?)
+                            .await?;
+                    }
+                    TrackListUpdate::Removed(track) => {
+                        Self::track_removed(emitter, object_path(&track)?).await?;
+                    }
+                    TrackListUpdate::MetadataChanged { track, metadata } => {
+                        Self::track_metadata_changed(
+                            emitter,
+                            object_path(&track)?,
+                            metadata_dict(&metadata)?,
+                        )
+                        .await?;
+                    }
+                }
+            }
+            self.tracks_invalidate(emitter).await?;
+        }
+        if now.can_edit_tracks != before.can_edit_tracks {
+            self.can_edit_tracks_changed(emitter).await?;
+        }
+
+        let current = now.current_metadata();
+        if current != before.current_metadata() {
+            if let Some(metadata) = current {
+                let player = server.interface::<_, PlayerIface>(MPRIS_PATH).await?;
+                let mut iface = player.get_mut().await;
+                let before = iface.capabilities.clone();
+                iface.capabilities.metadata = metadata.clone();
+                iface.announce(&before, player.signal_emitter()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle(
+        &mut self,
+        emitter: &SignalEmitter<'_>,
+        server: &ObjectServer,
+        f: impl FnOnce(&mut dyn TrackListHandler, &mut TrackListState) -> anyhow::Result<()>,
+    ) -> fdo::Result<()> {
+        let before = self.state.clone();
+        let result = call(&self.handler, |h| f(h, &mut self.state))?;
+        self.announce(&before, emitter, server).await?;
+        result.map_err(to_fdo)
+    }
+}
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2.TrackList")]
+impl TrackListIface {
+    /// tracks that aren't in the tracklist are left out
+    fn get_tracks_metadata(
+        &self,
+        track_ids: Vec<ObjectPath<'_>>,
+    ) -> fdo::Result<Vec<HashMap<String, OwnedValue>>> {
+        track_ids
+            .iter()
+            .filter_map(|id| self.state.track(&TrackId::new(id.as_str())))
+            .map(metadata_dict)
+            .collect()
+    }
+
+    async fn add_track(
+        &mut self,
+        uri: String,
+        after_track: ObjectPath<'_>,
+        set_as_current: bool,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(object_server)] server: &ObjectServer,
+    ) -> fdo::Result<()> {
+        if !self.state.can_edit_tracks {
+            return Ok(());
+        }
+        let after = Some(TrackId::new(after_track.as_str())).filter(|id| !id.is_no_track());
+        self.handle(&emitter, server, |h, state| {
+            h.add_track(state, &uri, after.as_ref(), set_as_current)
+        })
+        .await
+    }
+
+    async fn remove_track(
+        &mut self,
+        track_id: ObjectPath<'_>,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(object_server)] server: &ObjectServer,
+    ) -> fdo::Result<()> {
+        if !self.state.can_edit_tracks {
+            return Ok(());
+        }
+        let track = TrackId::new(track_id.as_str());
+        self.handle(&emitter, server, |h, state| h.remove_track(state, &track))
+            .await
+    }
+
+    async fn go_to(
+        &mut self,
+        track_id: ObjectPath<'_>,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(object_server)] server: &ObjectServer,
+    ) -> fdo::Result<()> {
+        let track = TrackId::new(track_id.as_str());
+        self.handle(&emitter, server, |h, state| h.go_to(state, &track))
+            .await
+    }
+
+    #[zbus(signal)]
+    async fn track_list_replaced(
+        emitter: &SignalEmitter<'_>,
+        tracks: Vec<ObjectPath<'_>>,
+        current_track: ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn track_added(
+        emitter: &SignalEmitter<'_>,
+        metadata: HashMap<String, OwnedValue>,
+        after_track: ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn track_removed(
+        emitter: &SignalEmitter<'_>,
+        track_id: ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn track_metadata_changed(
+        emitter: &SignalEmitter<'_>,
+        track_id: ObjectPath<'_>,
+        metadata: HashMap<String, OwnedValue>,
+    ) -> zbus::Result<()>;
+
+    // the signals above say how it changed
+    #[zbus(property(emits_changed_signal = "invalidates"))]
+    fn tracks(&self) -> fdo::Result<Vec<OwnedObjectPath>> {
+        self.state
+            .ids()
+            .map(|id| Ok(object_path(id)?.into()))
+            .collect()
+    }
+
+    #[zbus(property)]
+    fn can_edit_tracks(&self) -> bool {
+        self.state.can_edit_tracks
+    }
+}
+
+struct PlaylistsIface {
+    state: PlaylistsState,
+    handler: Mutex<Box<dyn PlaylistsHandler>>,
+}
+
+impl PlaylistsIface {
+    /// sends `PlaylistChanged` for playlists that kept their id and `PropertiesChanged` for
+    /// the rest
+    async fn announce(
+        &self,
+        before: &PlaylistsState,
+        emitter: &SignalEmitter<'_>,
+    ) -> zbus::Result<()> {
+        let now = &self.state;
+        for playlist in &now.playlists {
+            let changed = before
+                .playlists
+                .iter()
+                .any(|old| old.id == playlist.id && old != playlist);
+            if changed {
+                Self::playlist_changed(emitter, playlist_struct(playlist)?).await?;
+            }
+        }
+        if now.playlists.len() != before.playlists.len() {
+            self.playlist_count_changed(emitter).await?;
+        }
+        if now.orderings != before.orderings {
+            self.orderings_changed(emitter).await?;
+        }
+        if now.active != before.active {
+            self.active_playlist_changed(emitter).await?;
+        }
+        Ok(())
+    }
+}
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2.Playlists")]
+impl PlaylistsIface {
+    async fn activate_playlist(
+        &mut self,
+        playlist_id: ObjectPath<'_>,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        let before = self.state.clone();
+        let result = call(&self.handler, |h| {
+            h.activate_playlist(&mut self.state, playlist_id.as_str())
+        })?;
+        self.announce(&before, &emitter).await?;
+        result.map_err(to_fdo)
+    }
+
+    fn get_playlists(
+        &self,
+        index: u32,
+        max_count: u32,
+        order: String,
+        reverse_order: bool,
+    ) -> fdo::Result<Vec<(OwnedObjectPath, String, String)>> {
+        let order = order
+            .parse::<PlaylistOrdering>()
+            .map_err(|e| fdo::Error::InvalidArgs(format!("{e:#}")))?;
+        call(&self.handler, |h| {
+            h.get_playlists(&self.state, index, max_count, order, reverse_order)
+        })?
+        .map_err(to_fdo)?
+        .iter()
+        .map(playlist_struct)
+        .collect()
+    }
+
+    #[zbus(signal)]
+    async fn playlist_changed(
+        emitter: &SignalEmitter<'_>,
+        playlist: (OwnedObjectPath, String, String),
+    ) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playlist_count(&self) -> u32 {
+        self.state.playlists.len().try_into().unwrap_or(u32::MAX)
+    }
+
+    #[zbus(property)]
+    fn orderings(&self) -> Vec<String> {
+        self.state
+            .orderings
+            .iter()
+            .map(|ordering| ordering.to_string())
+            .collect()
+    }
+
+    /// `(false, ("/", "", ""))` while no playlist is active
+    #[zbus(property)]
+    fn active_playlist(&self) -> fdo::Result<(bool, (OwnedObjectPath, String, String))> {
+        match &self.state.active {
+            Some(playlist) => Ok((true, playlist_struct(playlist)?)),
+            None => Ok((
+                false,
+                (object_path("/")?.into(), String::new(), String::new()),
+            )),
+        }
+    }
+}
+
+impl std::fmt::Debug for MprisServiceBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MprisServiceBuilder")
+            .field("name", &self.name)
+            .field("capabilities", &self.capabilities)
+            .field("root", &self.root)
+            .field(
+                "tracklist",
+                &self.tracklist.as_ref().map(|(state, _)| state),
+            )
+            .field(
+                "playlists",
+                &self.playlists.as_ref().map(|(state, _)| state),
+            )
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,58 @@
+//! identifiers for players that survive restarts
+//!
+//! bus names often carry a pid (`org.mpris.MediaPlayer2.firefox.instance_1_84`), so they make for
+//! bad keys in configuration. a [`StableId`] is derived from the `DesktopEntry` when the player
+//! has one and from the bus name without its instance suffix otherwise. concurrent instances of
+//! the same player are told apart by the order they appeared in.
+
+use std::{fmt, str::FromStr};
+
+use serde::Serialize;
+
+use crate::{fnv1a, player::PlayerName};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StableId(u64);
+
+impl StableId {
+    /// the id of the `instance`th (counting from 0) concurrent player with this key, see
+    /// [`stable_key`]
+    pub fn new(key: &str, instance: u32) -> Self {
+        match instance {
+            0 => Self(fnv1a(key.as_bytes())),
+            n => Self(fnv1a(format!("{key}#{n}").as_bytes())),
+        }
+    }
+}
+
+/// what identifies a player independently of its bus name, lowercased so `VLC` and `vlc` agree
+pub fn stable_key(bus_name: &str, desktop_entry: Option<&str>) -> String {
+    if let Some(entry) = desktop_entry
+        .map(|e| e.trim_end_matches(".desktop"))
+        .filter(|e| !e.is_empty())
+    {
+        return entry.to_lowercase();
+    }
+
+    PlayerName::parse(bus_name).base.to_lowercase()
+}
+
+impl fmt::Display for StableId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for StableId {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        u64::from_str_radix(s, 16).map(Self)
+    }
+}
+
+impl Serialize for StableId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
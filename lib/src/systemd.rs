@@ -0,0 +1,94 @@
+//! running as a systemd service: readiness, the watchdog and a unit to install
+//!
+//! this speaks the `sd_notify` protocol directly, it's one datagram to `$NOTIFY_SOCKET`. outside
+//! of systemd the socket isn't set and every call does nothing.
+
+use std::{
+    io,
+    os::unix::net::{SocketAddr, UnixDatagram},
+    path::Path,
+    time::Duration,
+};
+
+/// sends `state` like `READY=1` to the service manager, `false` when not running under one
+pub fn notify(state: &str) -> io::Result<bool> {
+    let Some(socket) = std::env::var_os("NOTIFY_SOCKET").filter(|socket| !socket.is_empty()) else {
+        return Ok(false);
+    };
+    let address = match socket.as_encoded_bytes().strip_prefix(b"@") {
+        Some(name) => abstract_address(name)?,
+        None => SocketAddr::from_pathname(&socket)?,
+    };
+
+    UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &address)?;
+    Ok(true)
+}
+
+#[cfg(target_os = "linux")]
+fn abstract_address(name: &[u8]) -> io::Result<SocketAddr> {
+    use std::os::linux::net::SocketAddrExt;
+    SocketAddr::from_abstract_name(name)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn abstract_address(_: &[u8]) -> io::Result<SocketAddr> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "abstract sockets only exist on linux",
+    ))
+}
+
+/// startup is done, `Type=notify` units count as started from here
+pub fn ready() -> io::Result<bool> {
+    notify("READY=1")
+}
+
+/// shutting down, so a slow exit isn't mistaken for a hang
+pub fn stopping() -> io::Result<bool> {
+    notify("STOPPING=1")
+}
+
+/// one line for `systemctl status`
+pub fn status(status: &str) -> io::Result<bool> {
+    notify(&format!("STATUS={}", status.replace('\n', " ")))
+}
+
+/// tells the watchdog the service is still alive
+pub fn watchdog() -> io::Result<bool> {
+    notify("WATCHDOG=1")
+}
+
+/// how often to call [`watchdog`], half of `WatchdogSec=`. `None` without a watchdog or when it
+/// is meant for another process
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Some(pid) = std::env::var_os("WATCHDOG_PID") {
+        if pid.to_str()?.parse::<u32>().ok()? != std::process::id() {
+            return None;
+        }
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec / 2))
+}
+
+/// a user unit running `exe`, for `~/.config/systemd/user/<name>.service`
+pub fn user_unit(description: &str, exe: &Path) -> String {
+    format!(
+        "[Unit]
+Description={description}
+After=dbus.socket
+
+[Service]
+Type=notify
+ExecStart={exe}
+WatchdogSec=30
+Restart=on-failure
+
+[Install]
+WantedBy=default.target
+",
+        exe = match exe.display().to_string() {
+            exe if exe.contains(char::is_whitespace) => format!("\"{exe}\""),
+            exe => exe,
+        },
+    )
+}
@@ -0,0 +1,162 @@
+use anyhow::bail;
+
+use crate::{
+    icons,
+    player::PlaybackStatus,
+    progress::{self, format_length},
+};
+
+/// A single piece of a parsed template, either literal text or a `{key|fallback}` placeholder.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Text(String),
+    Field {
+        key: String,
+        fallback: Option<String>,
+    },
+}
+
+/// A parsed format string like `{artist} — {title} [{album|no album}]`.
+///
+/// `{{` and `}}` produce literal braces, a missing field renders as its fallback (or nothing when
+/// there isn't one). A key can be wrapped in a helper, `{duration(mpris:length)}`, see [`helper`]
+/// for the ones there are.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> anyhow::Result<Self> {
+        let mut segments = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut inner = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => inner.push(c),
+                            None => bail!("unterminated placeholder in {template:?}"),
+                        }
+                    }
+
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+
+                    let (key, fallback) = match inner.split_once('|') {
+                        Some((key, fallback)) => (key, Some(fallback.to_string())),
+                        None => (inner.as_str(), None),
+                    };
+                    segments.push(Segment::Field {
+                        key: key.trim().to_string(),
+                        fallback,
+                    });
+                }
+                '}' => bail!("unmatched '}}' in {template:?}"),
+                c => text.push(c),
+            }
+        }
+
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+
+        Ok(Self { segments })
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// renders the template, `lookup` resolves a placeholder key to its value
+    pub fn render<F>(&self, mut lookup: F) -> String
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        let mut out = String::new();
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => out.push_str(text),
+                Segment::Field { key, fallback } => match lookup_with_helpers(key, &mut lookup) {
+                    Some(value) if !value.is_empty() => out.push_str(&value),
+                    _ => {
+                        if let Some(fallback) = fallback {
+                            out.push_str(fallback);
+                        }
+                    }
+                },
+            }
+        }
+
+        out
+    }
+}
+
+/// resolves `key`, applying the helper if it has the form `helper(inner)`
+fn lookup_with_helpers<F>(key: &str, lookup: &mut F) -> Option<String>
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let call = key
+        .strip_suffix(')')
+        .and_then(|k| k.split_once('('))
+        .filter(|(name, _)| !name.is_empty());
+
+    match call {
+        Some((name, inner)) => helper(name.trim(), &lookup_with_helpers(inner.trim(), lookup)?),
+        None => lookup(key),
+    }
+}
+
+/// how many characters wide `{bar(position_pct)}` is
+const BAR_WIDTH: usize = 10;
+
+/// applies a template helper to an already resolved value, `None` for unknown helpers
+///
+/// - `bar`: a percentage like `{position_pct}` as a progress bar, see [`progress::bar`]
+/// - `duration`: microseconds as `m:ss`, see [`format_length`]
+/// - `emoji`: an icon for a playback status (`Playing`) or a volume between 0 and 1
+/// - `lc`, `uc`: lower and upper case
+/// - `markup_escape`: escapes `&`, `<` and `>` for pango markup
+pub fn helper(name: &str, value: &str) -> Option<String> {
+    let value = match name {
+        "bar" => progress::bar(value.parse().ok()?, BAR_WIDTH),
+        "duration" => match value.parse::<u64>() {
+            Ok(micros) => format_length(micros),
+            // already formatted, like `{position}`
+            Err(_) => value.to_string(),
+        },
+        "emoji" => {
+            if let Ok(status) = value.parse::<PlaybackStatus>() {
+                icons::status_icon(status).to_string()
+            } else if let Ok(volume) = value.parse::<f64>() {
+                icons::volume_icon(volume).to_string()
+            } else {
+                value.to_string()
+            }
+        }
+        "lc" => value.to_lowercase(),
+        "uc" => value.to_uppercase(),
+        "markup_escape" => value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;"),
+        _ => return None,
+    };
+
+    Some(value)
+}
@@ -0,0 +1,162 @@
+//! helpers for testing code built on top of [`MprisClient`] without a session bus
+//!
+//! ```
+//! use std::time::Duration;
+//! use lib::{player::{Capabilities, MetadataBuilder, MprisEvent}, test_util::TestHarness};
+//!
+//! let mut harness = TestHarness::new();
+//! harness.add_player("org.mpris.MediaPlayer2.mock", Capabilities::default());
+//!
+//! harness.advance(Duration::from_secs(5));
+//! let events = harness.emit_metadata(
+//!     "org.mpris.MediaPlayer2.mock",
+//!     MetadataBuilder::default().title("sailor".to_string()).finish(),
+//! );
+//! assert!(events.iter().any(|e| matches!(e, MprisEvent::TrackChanged { .. })));
+//! ```
+
+pub mod bus;
+pub mod mock;
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::MockClock,
+    player::{
+        Capabilities, Metadata, MetadataBuilder, MprisEvent, PlaybackStatus, Player, PlayerUpdated,
+    },
+    service::PlayerHandler,
+    MprisClient,
+};
+
+/// a handler for services whose calls don't matter to a test
+pub struct Silent;
+
+impl PlayerHandler for Silent {}
+
+/// calls `step` every 10ms until it returns something, `None` when it still hasn't after about
+/// three seconds
+pub async fn wait_for<T>(mut step: impl AsyncFnMut() -> Option<T>) -> Option<T> {
+    for _ in 0..300 {
+        if let Some(value) = step().await {
+            return Some(value);
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    None
+}
+
+/// steps `client` until one of its events passes `done`, returning every event seen on the way
+///
+/// panics after about three seconds
+pub async fn events_until<F>(client: &mut MprisClient, mut done: F) -> Vec<MprisEvent>
+where
+    F: FnMut(&MprisEvent) -> bool,
+{
+    let mut events = Vec::new();
+    let found = wait_for(async || {
+        events.extend(client.event().await);
+        events.iter().any(&mut done).then_some(())
+    })
+    .await;
+    if found.is_none() {
+        panic!("gave up waiting, got {events:#?}");
+    }
+
+    events
+}
+
+/// an [`MprisClient`] driven by a [`MockClock`] and fake players, updates are fed in by hand
+/// instead of arriving over D-Bus
+#[derive(Debug)]
+pub struct TestHarness {
+    client: MprisClient,
+    clock: MockClock,
+}
+
+impl Default for TestHarness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestHarness {
+    pub fn new() -> Self {
+        let clock = MockClock::new();
+        let client = MprisClient::disconnected().with_clock(Arc::new(clock.clone()));
+
+        Self { client, clock }
+    }
+
+    pub fn client(&self) -> &MprisClient {
+        &self.client
+    }
+
+    pub fn client_mut(&mut self) -> &mut MprisClient {
+        &mut self.client
+    }
+
+    pub fn clock(&self) -> &MockClock {
+        &self.clock
+    }
+
+    /// adds a fake player with the given starting state
+    pub fn add_player(&mut self, name: &str, capabilities: Capabilities) -> &mut Player {
+        self.client
+            .insert(Player::from_capabilities(name.to_string(), capabilities));
+        self.client.get_mut(name).unwrap()
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.clock.advance(by);
+    }
+
+    /// feeds `update` to the client as if `player` had sent it, returning the resulting events
+    pub fn emit(&mut self, player: &str, update: PlayerUpdated) -> Vec<MprisEvent> {
+        self.client.apply(player, update)
+    }
+
+    pub fn emit_metadata(&mut self, player: &str, metadata: Metadata) -> Vec<MprisEvent> {
+        self.emit(player, PlayerUpdated::Metadata(Box::new(metadata)))
+    }
+
+    pub fn emit_playback_status(
+        &mut self,
+        player: &str,
+        status: PlaybackStatus,
+    ) -> Vec<MprisEvent> {
+        self.emit(player, PlayerUpdated::PlaybackStatus(status))
+    }
+}
+
+/// a track called `title` that is `seconds` long, by `band` and `singer` on `album`
+pub fn track(title: &str, seconds: u64) -> Metadata {
+    MetadataBuilder::default()
+        .trackid(format!("/org/mpris/MediaPlayer2/track/{title}"))
+        .title(title.to_string())
+        .artists(vec!["band".to_string(), "singer".to_string()])
+        .album("album".to_string())
+        .length(seconds * 1_000_000)
+        .finish()
+}
+
+/// playing from `from` to `to` seconds into a track that started at `start`, `unix` in unix
+/// time: the position, the instant and the unix time of one observation a second
+pub fn play(
+    start: Instant,
+    unix: u64,
+    from: u64,
+    to: u64,
+) -> impl Iterator<Item = (u64, Instant, u64)> {
+    (from..=to).map(move |second| {
+        (
+            second * 1_000_000,
+            start + Duration::from_secs(second),
+            unix + second,
+        )
+    })
+}
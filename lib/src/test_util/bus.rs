@@ -0,0 +1,126 @@
+//! a private bus per test, so tests don't see the developer's players and players from one test
+//! don't show up in another
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use lib::test_util::{bus::TestBus, mock::MockPlayer};
+//!
+//! let bus = TestBus::start()?;
+//! let mock = bus.serve(MockPlayer::builder()).await?;
+//! let mut client = bus.client().await?;
+//! client.get_all().await?;
+//! assert_eq!(client.player_names(), [mock.name()]);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! `dbus-daemon` has to be installed.
+
+use std::{
+    io::{BufRead, BufReader},
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::Context;
+use zbus::{connection, Connection};
+
+use crate::{
+    test_util::mock::{MockPlayer, MockPlayerBuilder},
+    Bus, MprisClient,
+};
+
+// only a socket, no service activation so nothing from the host gets started
+const CONFIG: &str = r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <type>session</type>
+  <listen>unix:dir=DIR</listen>
+  <policy context="default">
+    <allow send_destination="*" eavesdrop="true"/>
+    <allow eavesdrop="true"/>
+    <allow own="*"/>
+  </policy>
+</busconfig>
+"#;
+
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+/// a `dbus-daemon` of its own, stopped when this is dropped
+#[derive(Debug)]
+pub struct TestBus {
+    daemon: Child,
+    address: String,
+    dir: PathBuf,
+}
+
+impl TestBus {
+    pub fn start() -> anyhow::Result<Self> {
+        let dir = std::env::temp_dir().join(format!(
+            "mpris-controller-bus-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir)?;
+        let config = dir.join("bus.conf");
+        std::fs::write(&config, CONFIG.replace("DIR", &dir.to_string_lossy()))?;
+
+        let mut daemon = Command::new("dbus-daemon")
+            .arg("--nofork")
+            .arg("--print-address=1")
+            .arg(format!("--config-file={}", config.display()))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("starting dbus-daemon")?;
+
+        let mut address = String::new();
+        let stdout = daemon.stdout.take().expect("stdout is piped");
+        BufReader::new(stdout).read_line(&mut address)?;
+        let address = address.trim().to_string();
+        if address.is_empty() {
+            let _ = daemon.kill();
+            anyhow::bail!("dbus-daemon didn't print its address");
+        }
+
+        Ok(Self {
+            daemon,
+            address,
+            dir,
+        })
+    }
+
+    /// like `unix:path=/tmp/...`, what `DBUS_SESSION_BUS_ADDRESS` would be set to
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// for connections that need more than [`TestBus::connect`] does, like serving objects
+    pub fn builder(&self) -> anyhow::Result<connection::Builder<'static>> {
+        Ok(connection::Builder::address(self.address.as_str())?)
+    }
+
+    pub async fn connect(&self) -> anyhow::Result<Connection> {
+        Ok(self.builder()?.build().await?)
+    }
+
+    /// a client on this bus, with no players added yet
+    pub async fn client(&self) -> anyhow::Result<MprisClient> {
+        MprisClient::connect_to(Bus::Address(self.address.clone())).await
+    }
+
+    /// serves a mock player on this bus
+    pub async fn serve(&self, player: MockPlayerBuilder) -> anyhow::Result<MockPlayer> {
+        player.serve_on(self.builder()?).await
+    }
+}
+
+impl Drop for TestBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
@@ -0,0 +1,491 @@
+//! a fake player on a real bus, for testing discovery, signals and control calls end to end
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use lib::{player::{Capabilities, MetadataBuilder, PlaybackStatus}, test_util::mock::{MockCall, MockPlayer}, MprisClient};
+//!
+//! let mock = MockPlayer::builder()
+//!     .capabilities(Capabilities { can_control: true, can_play: true, ..Default::default() })
+//!     .serve()
+//!     .await?;
+//! let mut client = MprisClient::connect().await?;
+//! client.add(mock.name().to_string()).await?;
+//!
+//! mock.set_metadata(MetadataBuilder::default().title("sailor".to_string()).finish()).await?;
+//! client.get(mock.name()).unwrap().play(client.connection().unwrap()).await?;
+//! assert_eq!(mock.calls().await?, [MockCall::Play]);
+//! assert_eq!(mock.capabilities().await?.playback_status, PlaybackStatus::Playing);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+
+use zbus::{
+    connection, fdo,
+    object_server::{InterfaceRef, SignalEmitter},
+    zvariant::{ObjectPath, OwnedValue, Value},
+    Connection, ObjectServer,
+};
+
+use crate::{
+    player::{Capabilities, LoopStatus, Metadata, PlaybackStatus, RootProperties},
+    MPRIS_PATH, MPRIS_PREFIX,
+};
+
+/// a method the player was asked to run, in the order they came in
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockCall {
+    Play,
+    Pause,
+    PlayPause,
+    Stop,
+    Next,
+    Previous,
+    Seek(i64),
+    SetPosition { track_id: String, position: i64 },
+    OpenUri(String),
+    SetVolume(f64),
+    Raise,
+    Quit,
+}
+
+#[derive(Debug)]
+pub struct MockPlayerBuilder {
+    name: String,
+    capabilities: Capabilities,
+    root: RootProperties,
+}
+
+impl Default for MockPlayerBuilder {
+    fn default() -> Self {
+        Self {
+            name: format!("{MPRIS_PREFIX}.mock"),
+            capabilities: Capabilities::default(),
+            root: RootProperties::default(),
+        }
+    }
+}
+
+impl MockPlayerBuilder {
+    /// the bus name, `org.mpris.MediaPlayer2.mock` by default
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub fn root(mut self, root: RootProperties) -> Self {
+        self.root = root;
+        self
+    }
+
+    /// serves the player on the session bus
+    pub async fn serve(self) -> anyhow::Result<MockPlayer> {
+        self.serve_on(connection::Builder::session()?).await
+    }
+
+    /// serves the player on the bus `builder` connects to, see
+    /// [`TestBus::serve`](super::bus::TestBus::serve)
+    pub async fn serve_on(self, builder: connection::Builder<'_>) -> anyhow::Result<MockPlayer> {
+        let conn = builder
+            .name(self.name.as_str())?
+            .serve_at(
+                MPRIS_PATH,
+                PlayerIface {
+                    capabilities: self.capabilities,
+                    calls: Vec::new(),
+                },
+            )?
+            .serve_at(MPRIS_PATH, RootIface { root: self.root })?
+            .build()
+            .await?;
+
+        Ok(MockPlayer {
+            name: self.name,
+            conn,
+        })
+    }
+}
+
+/// a player served on a bus until it is dropped
+///
+/// control calls are recorded (see [`MockPlayer::calls`]) and act like a simple player would:
+/// `Play` starts playing, `Seek` moves the position and sends `Seeked`. everything else is
+/// scripted with the setters, which send `PropertiesChanged` like a real player.
+#[derive(Debug)]
+pub struct MockPlayer {
+    name: String,
+    conn: Connection,
+}
+
+impl MockPlayer {
+    pub fn builder() -> MockPlayerBuilder {
+        MockPlayerBuilder::default()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// the connection the player is served on
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    async fn player(&self) -> anyhow::Result<InterfaceRef<PlayerIface>> {
+        Ok(self
+            .conn
+            .object_server()
+            .interface::<_, PlayerIface>(MPRIS_PATH)
+            .await?)
+    }
+
+    pub async fn capabilities(&self) -> anyhow::Result<Capabilities> {
+        Ok(self.player().await?.get().await.capabilities.clone())
+    }
+
+    pub async fn calls(&self) -> anyhow::Result<Vec<MockCall>> {
+        Ok(self.player().await?.get().await.calls.clone())
+    }
+
+    pub async fn clear_calls(&self) -> anyhow::Result<()> {
+        self.player().await?.get_mut().await.calls.clear();
+        Ok(())
+    }
+
+    pub async fn set_metadata(&self, metadata: Metadata) -> anyhow::Result<()> {
+        let player = self.player().await?;
+        player.get_mut().await.capabilities.metadata = metadata;
+        player
+            .get()
+            .await
+            .metadata_changed(player.signal_emitter())
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_playback_status(&self, status: PlaybackStatus) -> anyhow::Result<()> {
+        let player = self.player().await?;
+        player.get_mut().await.capabilities.playback_status = status;
+        player
+            .get()
+            .await
+            .playback_status_changed(player.signal_emitter())
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_volume(&self, volume: f64) -> anyhow::Result<()> {
+        let player = self.player().await?;
+        player.get_mut().await.capabilities.volume = Some(volume);
+        player
+            .get()
+            .await
+            .volume_changed(player.signal_emitter())
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_rate(&self, rate: f64) -> anyhow::Result<()> {
+        let player = self.player().await?;
+        player.get_mut().await.capabilities.rate = rate;
+        player
+            .get()
+            .await
+            .rate_changed(player.signal_emitter())
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_can_go_next(&self, can_go_next: bool) -> anyhow::Result<()> {
+        let player = self.player().await?;
+        player.get_mut().await.capabilities.can_next = can_go_next;
+        player
+            .get()
+            .await
+            .can_go_next_changed(player.signal_emitter())
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_identity(&self, identity: &str) -> anyhow::Result<()> {
+        let root = self
+            .conn
+            .object_server()
+            .interface::<_, RootIface>(MPRIS_PATH)
+            .await?;
+        root.get_mut().await.root.identity = Some(identity.to_string());
+        root.get()
+            .await
+            .identity_changed(root.signal_emitter())
+            .await?;
+        Ok(())
+    }
+
+    /// jumps to `position` and sends `Seeked`, like a user dragging the player's seek bar
+    pub async fn seek_to(&self, position: u64) -> anyhow::Result<()> {
+        let player = self.player().await?;
+        player.get_mut().await.capabilities.position = position;
+        PlayerIface::seeked(player.signal_emitter(), position.cast_signed()).await?;
+        Ok(())
+    }
+}
+
+struct PlayerIface {
+    capabilities: Capabilities,
+    calls: Vec<MockCall>,
+}
+
+impl PlayerIface {
+    async fn set_status(
+        &mut self,
+        status: PlaybackStatus,
+        emitter: &SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        if self.capabilities.playback_status != status {
+            self.capabilities.playback_status = status;
+            self.playback_status_changed(emitter).await?;
+        }
+        Ok(())
+    }
+}
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+impl PlayerIface {
+    async fn play(
+        &mut self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.calls.push(MockCall::Play);
+        self.set_status(PlaybackStatus::Playing, &emitter).await
+    }
+
+    async fn pause(
+        &mut self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.calls.push(MockCall::Pause);
+        self.set_status(PlaybackStatus::Paused, &emitter).await
+    }
+
+    async fn play_pause(
+        &mut self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.calls.push(MockCall::PlayPause);
+        let status = match self.capabilities.playback_status {
+            PlaybackStatus::Playing => PlaybackStatus::Paused,
+            _ => PlaybackStatus::Playing,
+        };
+        self.set_status(status, &emitter).await
+    }
+
+    async fn stop(
+        &mut self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.calls.push(MockCall::Stop);
+        self.capabilities.position = 0;
+        self.set_status(PlaybackStatus::Stopped, &emitter).await
+    }
+
+    fn next(&mut self) {
+        self.calls.push(MockCall::Next);
+    }
+
+    fn previous(&mut self) {
+        self.calls.push(MockCall::Previous);
+    }
+
+    async fn seek(
+        &mut self,
+        offset: i64,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.calls.push(MockCall::Seek(offset));
+        let position = self
+            .capabilities
+            .position
+            .cast_signed()
+            .saturating_add(offset)
+            .max(0);
+        self.capabilities.position = position.cast_unsigned();
+        Self::seeked(&emitter, position).await?;
+        Ok(())
+    }
+
+    async fn set_position(
+        &mut self,
+        track_id: ObjectPath<'_>,
+        position: i64,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.calls.push(MockCall::SetPosition {
+            track_id: track_id.to_string(),
+            position,
+        });
+        // like the spec says, a stale track id is ignored
+        let current = self.capabilities.metadata.track_id();
+        if position >= 0 && current.is_some_and(|id| id.as_str() == track_id.as_str()) {
+            self.capabilities.position = position.cast_unsigned();
+            Self::seeked(&emitter, position).await?;
+        }
+        Ok(())
+    }
+
+    fn open_uri(&mut self, uri: String) {
+        self.calls.push(MockCall::OpenUri(uri));
+    }
+
+    #[zbus(signal)]
+    async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        self.capabilities.playback_status.to_string()
+    }
+
+    #[zbus(property)]
+    fn loop_status(&self) -> String {
+        self.capabilities
+            .loop_status
+            .unwrap_or(LoopStatus::None)
+            .to_string()
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        self.capabilities.rate
+    }
+
+    #[zbus(property)]
+    fn shuffle(&self) -> bool {
+        self.capabilities.shuffle.unwrap_or(false)
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> fdo::Result<HashMap<String, OwnedValue>> {
+        HashMap::<String, Value>::from(self.capabilities.metadata.clone())
+            .into_iter()
+            .map(|(key, value)| Ok((key, value.try_to_owned().map_err(zbus::Error::from)?)))
+            .collect()
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.capabilities.volume.unwrap_or(0.0)
+    }
+
+    #[zbus(property)]
+    fn set_volume(&mut self, volume: f64) {
+        self.calls.push(MockCall::SetVolume(volume));
+        self.capabilities.volume = Some(volume);
+    }
+
+    #[zbus(property)]
+    fn position(&self) -> i64 {
+        self.capabilities.position.cast_signed()
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        self.capabilities.min_rate.unwrap_or(1.0)
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        self.capabilities.max_rate.unwrap_or(1.0)
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        self.capabilities.can_next
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        self.capabilities.can_previous
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        self.capabilities.can_play
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        self.capabilities.can_pause
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        self.capabilities.can_seek
+    }
+
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        self.capabilities.can_control
+    }
+}
+
+// the calls are kept with the player interface
+async fn record(server: &ObjectServer, call: MockCall) -> fdo::Result<()> {
+    let player = server.interface::<_, PlayerIface>(MPRIS_PATH).await?;
+    player.get_mut().await.calls.push(call);
+    Ok(())
+}
+
+struct RootIface {
+    root: RootProperties,
+}
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2")]
+impl RootIface {
+    async fn raise(&self, #[zbus(object_server)] server: &ObjectServer) -> fdo::Result<()> {
+        record(server, MockCall::Raise).await
+    }
+
+    async fn quit(&self, #[zbus(object_server)] server: &ObjectServer) -> fdo::Result<()> {
+        record(server, MockCall::Quit).await
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        self.root.identity.clone().unwrap_or_default()
+    }
+
+    #[zbus(property)]
+    fn desktop_entry(&self) -> String {
+        self.root.desktop_entry.clone().unwrap_or_default()
+    }
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        self.root.can_quit
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        self.root.can_raise
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        self.root.has_track_list
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        self.root.supported_uri_schemes.clone()
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        self.root.supported_mime_types.clone()
+    }
+}
@@ -0,0 +1,292 @@
+//! editing the `org.mpris.MediaPlayer2.TrackList` of a player and following its signals
+
+use std::{
+    collections::HashMap,
+    fmt,
+    task::{Context, Poll},
+};
+
+use futures::StreamExt;
+use tracing::warn;
+use zbus::{
+    proxy::SignalStream,
+    zvariant::{ObjectPath, OwnedObjectPath, Value},
+    Connection, Message,
+};
+
+use crate::{
+    call::call_method,
+    player::{Metadata, MetadataBuilder, MprisEvent, Player, TrackId, NO_TRACK},
+    MPRIS_PATH, MPRIS_TRACKLIST, WAKER,
+};
+
+/// a signal from the tracklist interface
+#[derive(Debug, Clone)]
+pub enum TrackListUpdate {
+    Replaced {
+        tracks: Vec<TrackId>,
+        current: TrackId,
+    },
+    Added {
+        metadata: Box<Metadata>,
+        /// `NO_TRACK` when added at the start
+        after: TrackId,
+    },
+    Removed(TrackId),
+    MetadataChanged {
+        track: TrackId,
+        metadata: Box<Metadata>,
+    },
+}
+
+#[derive(Debug)]
+pub enum TrackListError {
+    /// the player doesn't implement the tracklist interface (`HasTrackList` is false)
+    NoTrackList,
+    /// the tracklist is read only (`CanEditTracks` is false)
+    ReadOnly,
+    /// the trackid isn't a valid object path
+    InvalidTrackId(String),
+    /// the player refused or the call failed
+    Dbus(zbus::Error),
+}
+
+impl fmt::Display for TrackListError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoTrackList => write!(f, "player has no tracklist"),
+            Self::ReadOnly => write!(f, "player's tracklist can not be edited"),
+            Self::InvalidTrackId(id) => write!(f, "invalid trackid {id}"),
+            Self::Dbus(e) => write!(f, "tracklist call failed: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for TrackListError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Dbus(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<zbus::Error> for TrackListError {
+    fn from(value: zbus::Error) -> Self {
+        Self::Dbus(value)
+    }
+}
+
+fn object_path(id: &str) -> Result<ObjectPath<'_>, TrackListError> {
+    ObjectPath::try_from(id).map_err(|_| TrackListError::InvalidTrackId(id.to_string()))
+}
+
+impl Player {
+    fn check_tracklist(&self, edit: bool) -> Result<(), TrackListError> {
+        if !self.root().has_track_list {
+            return Err(TrackListError::NoTrackList);
+        }
+        if edit && !self.can_edit_tracks() {
+            return Err(TrackListError::ReadOnly);
+        }
+
+        Ok(())
+    }
+
+    async fn call_tracklist<B>(
+        &self,
+        conn: &Connection,
+        method: &str,
+        body: &B,
+    ) -> Result<(), TrackListError>
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+    {
+        call_method(
+            conn,
+            self.call_policy(),
+            self.name(),
+            MPRIS_PATH,
+            MPRIS_TRACKLIST,
+            method,
+            body,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// adds `uri` after the track `after`, or at the start when `None`
+    pub async fn add_track(
+        &self,
+        conn: &Connection,
+        uri: &str,
+        after: Option<&TrackId>,
+        set_current: bool,
+    ) -> Result<(), TrackListError> {
+        self.check_tracklist(true)?;
+        let after = object_path(after.map(TrackId::as_str).unwrap_or(NO_TRACK))?;
+
+        self.call_tracklist(conn, "AddTrack", &(uri, after, set_current))
+            .await
+    }
+
+    pub async fn remove_track(
+        &self,
+        conn: &Connection,
+        track: &TrackId,
+    ) -> Result<(), TrackListError> {
+        self.check_tracklist(true)?;
+        let track = object_path(track.as_str())?;
+
+        self.call_tracklist(conn, "RemoveTrack", &(track,)).await
+    }
+
+    /// skips to `track`, this only needs a tracklist, not an editable one
+    pub async fn go_to(&self, conn: &Connection, track: &TrackId) -> Result<(), TrackListError> {
+        self.check_tracklist(false)?;
+        let track = object_path(track.as_str())?;
+
+        self.call_tracklist(conn, "GoTo", &(track,)).await
+    }
+}
+
+impl Player {
+    /// updates the cached [`Player::tracklist`] and pushes the matching event onto `events`
+    pub(crate) fn apply_tracklist(
+        &mut self,
+        update: TrackListUpdate,
+        events: &mut Vec<MprisEvent>,
+    ) {
+        let player = self.name().to_string();
+        let limits = *self.metadata_limits();
+        let tracklist = self.tracklist_mut();
+        let position = |tracklist: &[Metadata], id: &TrackId| {
+            tracklist.iter().position(|m| m.track_id() == Some(id))
+        };
+
+        let event = match update {
+            TrackListUpdate::Replaced { tracks, current } => {
+                // keep what we know about tracks that are still there
+                let mut old = std::mem::take(tracklist);
+                for id in &tracks {
+                    let metadata = match position(&old, id) {
+                        Some(i) => old.swap_remove(i),
+                        None => MetadataBuilder::default().trackid(id.to_string()).finish(),
+                    };
+                    tracklist.push(metadata);
+                }
+
+                MprisEvent::TrackListReplaced {
+                    player,
+                    tracks,
+                    current,
+                }
+            }
+            TrackListUpdate::Added {
+                mut metadata,
+                after,
+            } => {
+                metadata.limit(&limits);
+                let index = if after.is_no_track() {
+                    0
+                } else {
+                    position(tracklist, &after).map_or(tracklist.len(), |i| i + 1)
+                };
+                tracklist.insert(index, (*metadata).clone());
+
+                MprisEvent::TrackAdded {
+                    player,
+                    metadata,
+                    after,
+                }
+            }
+            TrackListUpdate::Removed(track) => {
+                if let Some(i) = position(tracklist, &track) {
+                    tracklist.remove(i);
+                }
+
+                MprisEvent::TrackRemoved { player, track }
+            }
+            TrackListUpdate::MetadataChanged {
+                track,
+                mut metadata,
+            } => {
+                metadata.limit(&limits);
+                match position(tracklist, &track) {
+                    Some(i) => tracklist[i] = (*metadata).clone(),
+                    None => tracklist.push((*metadata).clone()),
+                }
+
+                MprisEvent::TrackMetadataChanged {
+                    player,
+                    track,
+                    metadata,
+                }
+            }
+        };
+
+        events.push(event);
+    }
+}
+
+/// like [`poll_player`](crate::player::poll_player) for a stream of tracklist signals, signals
+/// that fail to parse are logged and skipped
+pub fn poll_tracklist(stream: &mut SignalStream<'_>) -> Poll<Option<TrackListUpdate>> {
+    let waker = WAKER;
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        let msg = match stream.poll_next_unpin(&mut cx) {
+            Poll::Ready(Some(msg)) => msg,
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => return Poll::Pending,
+        };
+
+        match parse_signal(&msg) {
+            Ok(Some(update)) => return Poll::Ready(Some(update)),
+            Ok(None) => {}
+            Err(e) => warn!("failed to parse tracklist signal: {e:?}"),
+        }
+    }
+}
+
+fn parse_signal(msg: &Message) -> anyhow::Result<Option<TrackListUpdate>> {
+    let header = msg.header();
+    let Some(member) = header.member() else {
+        return Ok(None);
+    };
+    let body = msg.body();
+
+    let update = match member.as_str() {
+        "TrackListReplaced" => {
+            let (tracks, current): (Vec<OwnedObjectPath>, OwnedObjectPath) = body.deserialize()?;
+            TrackListUpdate::Replaced {
+                tracks: tracks.iter().map(|t| TrackId::new(t.as_str())).collect(),
+                current: TrackId::new(current.as_str()),
+            }
+        }
+        "TrackAdded" => {
+            let (metadata, after): (HashMap<String, Value>, OwnedObjectPath) =
+                body.deserialize()?;
+            TrackListUpdate::Added {
+                metadata: Box::new(metadata.try_into()?),
+                after: TrackId::new(after.as_str()),
+            }
+        }
+        "TrackRemoved" => {
+            let track: OwnedObjectPath = body.deserialize()?;
+            TrackListUpdate::Removed(TrackId::new(track.as_str()))
+        }
+        "TrackMetadataChanged" => {
+            let (track, metadata): (OwnedObjectPath, HashMap<String, Value>) =
+                body.deserialize()?;
+            TrackListUpdate::MetadataChanged {
+                track: TrackId::new(track.as_str()),
+                metadata: Box::new(metadata.try_into()?),
+            }
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(update))
+}
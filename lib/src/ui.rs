@@ -0,0 +1,85 @@
+//! what a frontend should show for a player, so every frontend doesn't have to interpret the
+//! `Can*` properties itself
+
+use serde::Serialize;
+
+use crate::player::{LoopStatus, PlaybackStatus, Player};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Slider {
+    pub min: f64,
+    pub max: f64,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UiModel {
+    pub play: bool,
+    pub pause: bool,
+    /// whether the play/pause toggle should currently show pause
+    pub playing: bool,
+    pub stop: bool,
+    pub next: bool,
+    pub previous: bool,
+    /// position in microseconds, `None` when the player can't seek or the length is unknown
+    pub seek: Option<Slider>,
+    pub volume: Option<Slider>,
+    /// `None` when the player only supports one rate
+    pub rate: Option<Slider>,
+    /// `None` when the player doesn't support shuffle
+    pub shuffle: Option<bool>,
+    pub loop_status: Option<LoopStatus>,
+    pub raise: bool,
+    pub quit: bool,
+    pub tracklist: bool,
+}
+
+impl UiModel {
+    pub fn new(player: &Player) -> Self {
+        let caps = player.capabilities();
+        let root = player.root();
+        // with `CanControl` false the other `Can*` properties aren't meaningful, and nothing
+        // that changes playback should be offered
+        let control = caps.can_control;
+
+        let seek = caps
+            .metadata
+            .length()
+            .filter(|len| control && caps.can_seek && *len > 0)
+            .map(|len| Slider {
+                min: 0.0,
+                max: len as f64,
+                value: caps.position.min(len) as f64,
+            });
+        let volume = caps.volume.filter(|_| control).map(|volume| Slider {
+            min: 0.0,
+            max: 1.0,
+            value: volume.clamp(0.0, 1.0),
+        });
+        let rate = match (caps.min_rate, caps.max_rate) {
+            (Some(min), Some(max)) if control && min < max => Some(Slider {
+                min,
+                max,
+                value: caps.rate.clamp(min, max),
+            }),
+            _ => None,
+        };
+
+        Self {
+            play: control && caps.can_play,
+            pause: control && caps.can_pause,
+            playing: caps.playback_status == PlaybackStatus::Playing,
+            stop: control,
+            next: control && caps.can_next,
+            previous: control && caps.can_previous,
+            seek,
+            volume,
+            rate,
+            shuffle: caps.shuffle.filter(|_| control),
+            loop_status: caps.loop_status.filter(|_| control),
+            raise: root.can_raise,
+            quit: root.can_quit,
+            tracklist: root.has_track_list,
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! D-Bus values as JSON that keeps their types, for fixtures and recordings people can read and
+//! edit by hand
+//!
+//! every value is an object with its signature as the only key, `{"x": 215000000}`, so a player
+//! sending `mpris:length` as `t` instead of `x` survives the round trip. containers hold plain
+//! JSON except for variants, which are typed again:
+//!
+//! ```json
+//! {"a{sv}": {"xesam:title": {"s": "sailor"}, "xesam:artist": {"as": ["someone"]}}}
+//! ```
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Context};
+use serde_json::{Map, Number, Value as Json};
+use zbus::zvariant::{
+    Array, Dict, ObjectPath, OwnedValue, Signature, Str, StructureBuilder, Value,
+};
+
+/// `value` with its signature
+pub fn to_json(value: &Value) -> Json {
+    let mut map = Map::new();
+    map.insert(value.value_signature().to_string(), plain(value));
+    Json::Object(map)
+}
+
+fn plain(value: &Value) -> Json {
+    match value {
+        Value::U8(n) => (*n).into(),
+        Value::Bool(b) => (*b).into(),
+        Value::I16(n) => (*n).into(),
+        Value::U16(n) => (*n).into(),
+        Value::I32(n) => (*n).into(),
+        Value::U32(n) => (*n).into(),
+        Value::I64(n) => (*n).into(),
+        Value::U64(n) => (*n).into(),
+        // NaN and the infinities aren't JSON, a player sending them is broken anyway
+        Value::F64(n) => Number::from_f64(*n).map_or(Json::Null, Json::Number),
+        Value::Str(s) => s.as_str().into(),
+        Value::Signature(s) => s.to_string().into(),
+        Value::ObjectPath(p) => p.as_str().into(),
+        Value::Value(inner) => to_json(inner),
+        Value::Array(array) => array.iter().map(plain).collect(),
+        Value::Dict(dict) => Json::Object(
+            dict.iter()
+                .map(|(k, v)| {
+                    let key = match plain(k) {
+                        Json::String(s) => s,
+                        other => other.to_string(),
+                    };
+                    (key, plain(v))
+                })
+                .collect(),
+        ),
+        Value::Structure(s) => s.fields().iter().map(plain).collect(),
+        #[cfg(unix)]
+        Value::Fd(_) => Json::Null,
+    }
+}
+
+/// the value [`to_json`] turned into `json`
+pub fn from_json(json: &Json) -> anyhow::Result<OwnedValue> {
+    Ok(typed(json)?.try_to_owned()?)
+}
+
+/// an object of typed values, like the properties `GetAll` returns
+pub fn properties_from_json(json: &Json) -> anyhow::Result<HashMap<String, OwnedValue>> {
+    json.as_object()
+        .ok_or_else(|| anyhow!("expected an object of properties, got {json}"))?
+        .iter()
+        .map(|(key, value)| Ok((key.clone(), from_json(value).context(key.clone())?)))
+        .collect()
+}
+
+fn typed(json: &Json) -> anyhow::Result<Value<'static>> {
+    let (signature, value) = match json.as_object() {
+        Some(map) if map.len() == 1 => map.iter().next().unwrap(),
+        _ => bail!("expected {{\"<signature>\": value}}, got {json}"),
+    };
+    with_signature(&parse_signature(signature)?, value)
+}
+
+fn parse_signature(signature: &str) -> anyhow::Result<Signature> {
+    Signature::try_from(signature).map_err(|e| anyhow!("invalid signature {signature}: {e}"))
+}
+
+fn with_signature(signature: &Signature, json: &Json) -> anyhow::Result<Value<'static>> {
+    let wrong = || anyhow!("{json} isn't a {signature}");
+    let int = || json.as_i64().ok_or_else(wrong);
+    let uint = || json.as_u64().ok_or_else(wrong);
+
+    let value = match signature {
+        Signature::U8 => Value::U8(uint()?.try_into()?),
+        Signature::Bool => Value::Bool(json.as_bool().ok_or_else(wrong)?),
+        Signature::I16 => Value::I16(int()?.try_into()?),
+        Signature::U16 => Value::U16(uint()?.try_into()?),
+        Signature::I32 => Value::I32(int()?.try_into()?),
+        Signature::U32 => Value::U32(uint()?.try_into()?),
+        Signature::I64 => Value::I64(int()?),
+        Signature::U64 => Value::U64(uint()?),
+        Signature::F64 => Value::F64(json.as_f64().ok_or_else(wrong)?),
+        Signature::Str => Value::Str(Str::from(json.as_str().ok_or_else(wrong)?.to_string())),
+        Signature::ObjectPath => Value::ObjectPath(ObjectPath::try_from(
+            json.as_str().ok_or_else(wrong)?.to_string(),
+        )?),
+        Signature::Signature => {
+            Value::Signature(parse_signature(json.as_str().ok_or_else(wrong)?)?)
+        }
+        Signature::Variant => Value::new(typed(json)?),
+        Signature::Array(element) => {
+            let mut array = Array::new(element);
+            for item in json.as_array().ok_or_else(wrong)? {
+                array.append(with_signature(element, item)?)?;
+            }
+            Value::Array(array)
+        }
+        Signature::Dict { key, value } => {
+            let mut dict = Dict::new(key, value);
+            for (k, v) in json.as_object().ok_or_else(wrong)? {
+                // keys are always strings in JSON
+                let k = match &**key {
+                    Signature::Str | Signature::ObjectPath | Signature::Signature => {
+                        Json::String(k.clone())
+                    }
+                    _ => serde_json::from_str(k)?,
+                };
+                dict.append(with_signature(key, &k)?, with_signature(value, v)?)?;
+            }
+            Value::Dict(dict)
+        }
+        Signature::Structure(fields) => {
+            let items = json.as_array().ok_or_else(wrong)?;
+            let fields: Vec<_> = fields.iter().collect();
+            if items.len() != fields.len() {
+                return Err(wrong());
+            }
+            let mut builder = StructureBuilder::new();
+            for (field, item) in fields.into_iter().zip(items) {
+                builder.push_value(with_signature(field, item)?);
+            }
+            Value::Structure(builder.build()?)
+        }
+        _ => bail!("{signature} can't be read from JSON"),
+    };
+
+    Ok(value)
+}
@@ -0,0 +1,344 @@
+//! a local WebSocket server streaming events as json, for browser overlays
+//!
+//! every connection gets a `snapshot` of the players first and then one message per
+//! [`MprisEvent`], see [`event_json`]. messages sent to the server are JSON-RPC requests like on
+//! the [`rpc`](crate::rpc) socket, but only with [`WsServer::allow_control`], any web page the
+//! browser has open can reach a local server. without it only `players` and `player` are
+//! answered. `snapshot` sends the snapshot again.
+//!
+//! browsers say which page opened a connection in `Origin`, only pages on localhost and the ones
+//! given to [`WsServer::allow_origins`] are let in. local files like OBS browser sources send
+//! `null`, which sandboxed pages can send too, so it has to be allowed like any other origin.
+//! connections without an `Origin` don't come from a browser and are let in.
+//!
+//! ```text
+//! < {"event":"snapshot","players":[...]}
+//! < {"event":"track_changed","player":"org.mpris.MediaPlayer2.spotify","metadata":{...}}
+//! > {"jsonrpc":"2.0","id":1,"method":"next"}
+//! < {"jsonrpc":"2.0","id":1,"result":null}
+//! ```
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{broadcast, mpsc, oneshot},
+    task::JoinHandle,
+};
+use tokio_tungstenite::tungstenite::{
+    handshake::server::{ErrorResponse, Request, Response},
+    http::{header::ORIGIN, StatusCode},
+    Message,
+};
+use tracing::{debug, warn};
+
+use crate::{
+    player::{MprisEvent, PlayerUpdated},
+    rpc::{self, Pending, RpcError},
+    MprisClient,
+};
+
+/// `127.0.0.1:6790`
+pub const DEFAULT_ADDRESS: SocketAddr =
+    SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), 6790);
+/// methods answered without [`WsServer::allow_control`]
+const READ_ONLY: [&str; 2] = ["players", "player"];
+
+/// an event as sent to websocket clients, `event` says which one it is
+pub fn event_json(event: &MprisEvent) -> Value {
+    match event {
+        MprisEvent::PlayerAdded(player) => json!({"event": "player_added", "player": player}),
+        MprisEvent::PlayerRemoved(player) => json!({"event": "player_removed", "player": player}),
+        MprisEvent::PlayerRestarted(player) => {
+            json!({"event": "player_restarted", "player": player})
+        }
+        MprisEvent::ConnectionLost => json!({"event": "connection_lost"}),
+        MprisEvent::ConnectionRestored => json!({"event": "connection_restored"}),
+        MprisEvent::PlayerUpdated { player, update } => {
+            let value = match update {
+                PlayerUpdated::PlaybackStatus(status) => json!(status),
+                PlayerUpdated::Metadata(metadata) => json!(metadata),
+                PlayerUpdated::CanControl(can)
+                | PlayerUpdated::CanGoNext(can)
+                | PlayerUpdated::CanGoPrevious(can)
+                | PlayerUpdated::CanPause(can)
+                | PlayerUpdated::CanPlay(can)
+                | PlayerUpdated::CanSeek(can) => json!(can),
+                PlayerUpdated::LoopStatus(status) => json!(status),
+                PlayerUpdated::Rate(rate) | PlayerUpdated::Volume(rate) => json!(rate),
+                PlayerUpdated::Shuffle(shuffle) => json!(shuffle),
+            };
+            json!({
+                "event": "player_updated",
+                "player": player,
+                "property": update.property(),
+                "value": value,
+            })
+        }
+        MprisEvent::TrackChanged { player, metadata } => {
+            json!({"event": "track_changed", "player": player, "metadata": metadata})
+        }
+        MprisEvent::TrackCleared { player } => {
+            json!({"event": "track_cleared", "player": player})
+        }
+        MprisEvent::TrackListReplaced {
+            player,
+            tracks,
+            current,
+        } => json!({
+            "event": "tracklist_replaced",
+            "player": player,
+            "tracks": tracks,
+            "current": current,
+        }),
+        MprisEvent::TrackAdded {
+            player,
+            metadata,
+            after,
+        } => json!({
+            "event": "track_added",
+            "player": player,
+            "metadata": metadata,
+            "after": after,
+        }),
+        MprisEvent::TrackRemoved { player, track } => {
+            json!({"event": "track_removed", "player": player, "track": track})
+        }
+        MprisEvent::TrackMetadataChanged {
+            player,
+            track,
+            metadata,
+        } => json!({
+            "event": "track_metadata_changed",
+            "player": player,
+            "track": track,
+            "metadata": metadata,
+        }),
+        MprisEvent::Seeked { player, position } => {
+            json!({"event": "seeked", "player": player, "position": position})
+        }
+        MprisEvent::TrackEndingSoon { player, remaining } => json!({
+            "event": "track_ending_soon",
+            "player": player,
+            "remaining": remaining.as_micros() as u64,
+        }),
+        MprisEvent::PlaylistUpdated { player, playlist } => {
+            json!({"event": "playlist_updated", "player": player, "playlist": playlist})
+        }
+        MprisEvent::ActivePlayerChanged { player } => {
+            json!({"event": "active_player_changed", "player": player})
+        }
+        MprisEvent::AdStarted { player } => json!({"event": "ad_started", "player": player}),
+        MprisEvent::AdEnded { player } => json!({"event": "ad_ended", "player": player}),
+        MprisEvent::ParseError { player, error } => {
+            json!({"event": "parse_error", "player": player, "error": error})
+        }
+    }
+}
+
+/// whether a page from `origin` may connect, see the module docs
+pub fn origin_allowed(origin: Option<&str>, allowed: &[String]) -> bool {
+    let Some(origin) = origin else {
+        return true;
+    };
+    if allowed.iter().any(|allowed| allowed == origin) {
+        return true;
+    }
+    let Some(host) = origin
+        .strip_prefix("http://")
+        .or_else(|| origin.strip_prefix("https://"))
+    else {
+        return false;
+    };
+    let host = match host.strip_prefix('[') {
+        Some(v6) => v6.split_once(']').map_or(v6, |(host, _)| host),
+        None => host.split_once(':').map_or(host, |(host, _)| host),
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "::1")
+}
+
+/// what a connection needs from the server loop
+#[derive(Clone)]
+struct Shared {
+    events: broadcast::Sender<String>,
+    requests: mpsc::UnboundedSender<Pending>,
+    origins: Arc<Mutex<Vec<String>>>,
+}
+
+pub struct WsServer {
+    client: MprisClient,
+    address: SocketAddr,
+    allow_control: bool,
+    origins: Arc<Mutex<Vec<String>>>,
+    events: broadcast::Sender<String>,
+    requests: mpsc::UnboundedReceiver<Pending>,
+    accept: JoinHandle<()>,
+}
+
+impl WsServer {
+    /// listens on `address`, port 0 picks a free one, see [`WsServer::address`]
+    pub async fn bind(client: MprisClient, address: SocketAddr) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind(address).await?;
+        let address = listener.local_addr()?;
+        if !address.ip().is_loopback() {
+            warn!(%address, "the websocket server is reachable from other machines");
+        }
+
+        let (events, _) = broadcast::channel(256);
+        let (send, requests) = mpsc::unbounded_channel();
+        let origins = Arc::default();
+        let shared = Shared {
+            events: events.clone(),
+            requests: send,
+            origins: Arc::clone(&origins),
+        };
+        let accept = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        let shared = shared.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = serve(stream, shared).await {
+                                debug!(%peer, "websocket closed: {e:#}");
+                            }
+                        });
+                    }
+                    Err(e) => warn!("failed to accept a connection: {e}"),
+                }
+            }
+        });
+
+        Ok(Self {
+            client,
+            address,
+            allow_control: false,
+            origins,
+            events,
+            requests,
+            accept,
+        })
+    }
+
+    /// answers every JSON-RPC method instead of only reading the state
+    pub fn allow_control(mut self, allow: bool) -> Self {
+        self.allow_control = allow;
+        self
+    }
+
+    /// lets pages from `origins` connect besides the ones on localhost, like
+    /// `https://example.com`, or `null` for local files
+    pub fn allow_origins(self, origins: impl IntoIterator<Item = String>) -> Self {
+        *self.origins.lock().unwrap() = origins.into_iter().collect();
+        self
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn client(&self) -> &MprisClient {
+        &self.client
+    }
+
+    pub fn client_mut(&mut self) -> &mut MprisClient {
+        &mut self.client
+    }
+
+    /// answers the requests that came in since the last call, then sends the client's events
+    /// to every connection. call it in a loop like [`MprisClient::event`]
+    pub async fn step(&mut self) -> Vec<MprisEvent> {
+        while let Ok(pending) = self.requests.try_recv() {
+            let result = if pending.method == "snapshot" {
+                Ok(snapshot(&self.client))
+            } else if self.allow_control || READ_ONLY.contains(&pending.method.as_str()) {
+                rpc::call(&mut self.client, &pending.method, &pending.params).await
+            } else {
+                Err(RpcError::new(
+                    rpc::METHOD_NOT_FOUND,
+                    format!("{} needs control to be allowed", pending.method),
+                ))
+            };
+            _ = pending.reply.send(result);
+        }
+
+        let events = self.client.event().await;
+        // nobody listening is fine
+        for event in &events {
+            _ = self.events.send(event_json(event).to_string());
+        }
+        events
+    }
+}
+
+impl Drop for WsServer {
+    fn drop(&mut self) {
+        self.accept.abort();
+    }
+}
+
+fn snapshot(client: &MprisClient) -> Value {
+    json!({
+        "event": "snapshot",
+        "players": client.players().iter().map(rpc::player_json).collect::<Vec<_>>(),
+        "active": client.active_player().map(|player| player.name()),
+    })
+}
+
+async fn serve(stream: TcpStream, shared: Shared) -> anyhow::Result<()> {
+    // the error type is tungstenite's
+    #[allow(clippy::result_large_err)]
+    let check = |request: &Request, response: Response| {
+        let origin = request
+            .headers()
+            .get(ORIGIN)
+            .map(|origin| origin.to_str().unwrap_or_default());
+        if origin_allowed(origin, &shared.origins.lock().unwrap()) {
+            return Ok(response);
+        }
+        debug!(?origin, "refused a websocket connection");
+        let mut response = ErrorResponse::new(Some("origin not allowed".to_string()));
+        *response.status_mut() = StatusCode::FORBIDDEN;
+        Err(response)
+    };
+    let mut socket = tokio_tungstenite::accept_hdr_async(stream, check).await?;
+    let mut events = shared.events.subscribe();
+
+    // the snapshot goes through the server loop like a request, it owns the client
+    let (reply, snapshot) = oneshot::channel();
+    shared.requests.send(Pending {
+        method: "snapshot".to_string(),
+        params: Value::Null,
+        reply,
+    })?;
+    if let Ok(snapshot) = snapshot.await? {
+        socket.send(Message::text(snapshot.to_string())).await?;
+    }
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => socket.send(Message::text(event)).await?,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!(missed, "a websocket client fell behind, events were dropped");
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+            message = socket.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    if let Some(reply) = rpc::answer(&text, &shared.requests).await {
+                        socket.send(Message::text(reply.to_string())).await?;
+                    }
+                }
+                Some(Ok(Message::Close(_))) | None => return Ok(()),
+                // pings are answered by tungstenite
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e.into()),
+            },
+        }
+    }
+}
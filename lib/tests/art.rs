@@ -0,0 +1,26 @@
+//! the art cache, run with `--features art`
+
+use lib::{art::ArtCache, player::MetadataBuilder};
+
+#[tokio::test]
+async fn tracks_sharing_a_cover_share_the_file() -> anyhow::Result<()> {
+    let dir = std::env::temp_dir().join(format!("art-test-{}", std::process::id()));
+    let cache = ArtCache::new(dir.clone(), lib::art::DEFAULT_MAX_BYTES)?;
+    let track = |id: &str, art: &str| {
+        MetadataBuilder::default()
+            .trackid(format!("/org/mpris/MediaPlayer2/track/{id}"))
+            .art_url(art.to_string())
+            .finish()
+    };
+
+    let first = cache.resolve(&track("1", "data:,cover")).await?;
+    let second = cache.resolve(&track("2", "data:,cover")).await?;
+    let other = cache.resolve(&track("1", "data:,other")).await?;
+    assert!(first.is_some());
+    assert_eq!(first, second);
+    assert_ne!(first, other);
+    assert_eq!(std::fs::read(other.unwrap())?, b"other");
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
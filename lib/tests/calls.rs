@@ -0,0 +1,136 @@
+//! timeouts and retries of calls to players that don't answer, run with `--features test-util`
+
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use lib::{
+    call::{self, CallPolicy},
+    test_util::{bus::TestBus, mock::MockPlayer},
+    MPRIS_PATH, MPRIS_PLAYER_PREFIX,
+};
+
+/// never answers `Play`, like a suspended browser tab. `Pause` fails with `NoReply` until
+/// it has been called `flaky` times
+struct Stuck {
+    pauses: Arc<AtomicU32>,
+    flaky: u32,
+}
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Stuck {
+    async fn play(&self) {
+        std::future::pending::<()>().await;
+    }
+
+    fn pause(&self) -> zbus::fdo::Result<()> {
+        if self.pauses.fetch_add(1, Ordering::SeqCst) < self.flaky {
+            return Err(zbus::fdo::Error::NoReply("asleep".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn gives_up_on_players_that_dont_answer() -> anyhow::Result<()> {
+    let policy = CallPolicy {
+        timeout: Duration::from_millis(200),
+        retries: 2,
+        backoff: Duration::from_millis(10),
+    };
+    let bus = TestBus::start()?;
+    let pauses = Arc::new(AtomicU32::new(0));
+    let stuck = Stuck {
+        pauses: pauses.clone(),
+        flaky: 2,
+    };
+    let _player = bus
+        .builder()?
+        .name("org.mpris.MediaPlayer2.stuck")?
+        .serve_at(MPRIS_PATH, stuck)?
+        .build()
+        .await?;
+    let client = bus.client().await?;
+    let conn = client.connection().unwrap();
+    let name = "org.mpris.MediaPlayer2.stuck";
+
+    let start = Instant::now();
+    let e = call::call_method(
+        conn,
+        &policy,
+        name,
+        MPRIS_PATH,
+        MPRIS_PLAYER_PREFIX,
+        "Play",
+        &(),
+    )
+    .await
+    .unwrap_err();
+    assert!(call::is_timeout(&e), "{e}");
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    // two `NoReply`s are retried, the third call goes through
+    call::call_method(
+        conn,
+        &policy,
+        name,
+        MPRIS_PATH,
+        MPRIS_PLAYER_PREFIX,
+        "Pause",
+        &(),
+    )
+    .await?;
+    assert_eq!(pauses.load(Ordering::SeqCst), 3);
+
+    // one more than there are retries for is handed back
+    pauses.store(0, Ordering::SeqCst);
+    let policy = CallPolicy {
+        retries: 1,
+        ..policy
+    };
+    let e = call::call_method(
+        conn,
+        &policy,
+        name,
+        MPRIS_PATH,
+        MPRIS_PLAYER_PREFIX,
+        "Pause",
+        &(),
+    )
+    .await
+    .unwrap_err();
+    assert!(call::is_no_reply(&e), "{e}");
+    assert_eq!(pauses.load(Ordering::SeqCst), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn players_follow_the_policy_of_their_client() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let mock = bus.serve(MockPlayer::builder()).await?;
+    let policy = CallPolicy {
+        timeout: Duration::from_millis(200),
+        ..CallPolicy::DEFAULT
+    };
+
+    let mut client = bus.client().await?;
+    client.set_call_policy(policy);
+    client.add(mock.name().to_string()).await?;
+    assert_eq!(client.get(mock.name()).unwrap().call_policy(), &policy);
+
+    // every client has its own
+    let other = bus.client().await?;
+    assert_eq!(other.call_policy(), &CallPolicy::DEFAULT);
+
+    let fewer = CallPolicy {
+        retries: 0,
+        ..policy
+    };
+    client.set_call_policy(fewer);
+    assert_eq!(client.get(mock.name()).unwrap().call_policy(), &fewer);
+    Ok(())
+}
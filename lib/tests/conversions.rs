@@ -0,0 +1,219 @@
+//! whatever a player sends, parsing it may fail but never panics
+
+use std::collections::HashMap;
+
+use lib::{
+    player::{
+        parse_properties_changed, parse_properties_changed_as, Capabilities, Metadata,
+        PlayerUpdated,
+    },
+    DBUS_PROPERTIES, MPRIS_PATH, MPRIS_PLAYER_PREFIX,
+};
+use proptest::{collection, prelude::*};
+use zbus::{
+    zvariant::{Array, Dict, ObjectPath, Signature, Value},
+    Message,
+};
+
+const METADATA_KEYS: &[&str] = &[
+    "mpris:artUrl",
+    "mpris:length",
+    "mpris:trackid",
+    "xesam:album",
+    "xesam:albumArtist",
+    "xesam:artist",
+    "xesam:autoRating",
+    "xesam:discNumber",
+    "xesam:title",
+    "xesam:title@de",
+    "xesam:trackNumber",
+    "xesam:url",
+];
+
+const PLAYER_KEYS: &[&str] = &[
+    "CanControl",
+    "CanGoNext",
+    "CanGoPrevious",
+    "CanPause",
+    "CanPlay",
+    "CanSeek",
+    "LoopStatus",
+    "MaximumRate",
+    "Metadata",
+    "MinimumRate",
+    "PlaybackStatus",
+    "Position",
+    "Rate",
+    "Shuffle",
+    "Volume",
+];
+
+fn object_path() -> impl Strategy<Value = ObjectPath<'static>> {
+    "(/[a-zA-Z0-9_]{1,8}){0,3}"
+        .prop_map(|p| ObjectPath::try_from(if p.is_empty() { "/".to_string() } else { p }).unwrap())
+}
+
+fn leaf() -> impl Strategy<Value = Value<'static>> {
+    prop_oneof![
+        any::<bool>().prop_map(Value::Bool),
+        any::<u8>().prop_map(Value::U8),
+        any::<i16>().prop_map(Value::I16),
+        any::<u16>().prop_map(Value::U16),
+        any::<i32>().prop_map(Value::I32),
+        any::<u32>().prop_map(Value::U32),
+        any::<i64>().prop_map(Value::I64),
+        any::<u64>().prop_map(Value::U64),
+        any::<f64>().prop_map(Value::F64),
+        ".{0,12}".prop_map(Value::from),
+        prop_oneof![
+            Just("Playing"),
+            Just("Paused"),
+            Just("Stopped"),
+            Just("Track")
+        ]
+        .prop_map(Value::from),
+        object_path().prop_map(Value::ObjectPath),
+        collection::vec(".{0,8}", 0..3).prop_map(Value::from),
+        collection::vec(any::<i32>(), 0..3).prop_map(Value::from),
+        Just(Value::Array(Array::new(&Signature::Bool))),
+    ]
+}
+
+/// a dict of `a{sv}`, keys from `keys` or made up
+fn dict(
+    keys: &'static [&'static str],
+    value: impl Strategy<Value = Value<'static>>,
+) -> impl Strategy<Value = Value<'static>> {
+    let key =
+        prop_oneof![3 => proptest::sample::select(keys).prop_map(String::from), 1 => ".{0,8}"];
+    collection::hash_map(key, value, 0..keys.len()).prop_map(|map| {
+        let mut dict = Dict::new(&Signature::Str, &Signature::Variant);
+        for (k, v) in map {
+            dict.append(Value::from(k), Value::new(v)).unwrap();
+        }
+        Value::Dict(dict)
+    })
+}
+
+fn value() -> impl Strategy<Value = Value<'static>> {
+    leaf().prop_recursive(3, 16, 4, |inner| {
+        prop_oneof![
+            inner.clone().prop_map(Value::new),
+            dict(METADATA_KEYS, inner),
+        ]
+    })
+}
+
+fn properties(
+    keys: &'static [&'static str],
+) -> impl Strategy<Value = HashMap<String, Value<'static>>> {
+    let key =
+        prop_oneof![3 => proptest::sample::select(keys).prop_map(String::from), 1 => ".{0,8}"];
+    let value = prop_oneof![3 => value(), 1 => dict(METADATA_KEYS, value())];
+    collection::hash_map(key, value, 0..keys.len())
+}
+
+proptest! {
+    #[test]
+    fn metadata_never_panics(properties in properties(METADATA_KEYS)) {
+        let as_value = Value::from(properties.clone());
+        let _ = Metadata::try_from(properties);
+        let _ = Metadata::try_from(&as_value);
+    }
+
+    #[test]
+    fn capabilities_never_panic(properties in properties(PLAYER_KEYS)) {
+        let properties: HashMap<&str, Value> = properties
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.try_clone().unwrap()))
+            .collect();
+        let _ = Capabilities::try_from(properties);
+    }
+
+    #[test]
+    fn properties_changed_never_panics(properties in properties(PLAYER_KEYS)) {
+        let changed: HashMap<String, Value> = properties
+            .into_iter()
+            .map(|(k, v)| (k, Value::new(v)))
+            .collect();
+        let msg = Message::signal(MPRIS_PATH, DBUS_PROPERTIES, "PropertiesChanged")
+            .unwrap()
+            .build(&(MPRIS_PLAYER_PREFIX, changed, Vec::<String>::new()))
+            .unwrap();
+        let _ = parse_properties_changed(&msg);
+    }
+}
+
+#[test]
+fn relaxed_parsing_fills_in_what_bluez_leaves_out() {
+    let mut metadata: HashMap<String, Value> = HashMap::new();
+    metadata.insert("xesam:title".into(), Value::from("Song"));
+    metadata.insert("xesam:artist".into(), Value::from("Artist"));
+    metadata.insert("xesam:trackNumber".into(), Value::from(3u32));
+    let mut properties: HashMap<&str, Value> = HashMap::new();
+    properties.insert("PlaybackStatus", Value::from("Playing"));
+    properties.insert("Metadata", Value::from(metadata));
+
+    let strict: HashMap<&str, Value> = properties
+        .iter()
+        .map(|(k, v)| (*k, v.try_clone().unwrap()))
+        .collect();
+    assert!(Capabilities::try_from(strict).is_err());
+
+    let caps = Capabilities::from_properties(properties, true).unwrap();
+    assert_eq!(caps.rate, 1.0);
+    assert_eq!(caps.position, 0);
+    assert!(!caps.can_seek);
+    assert_eq!(caps.metadata.title(), Some("Song"));
+    assert_eq!(caps.metadata.artists(), Some(&["Artist".to_string()][..]));
+    assert_eq!(caps.metadata.track_number(), None);
+}
+
+#[test]
+fn nothing_loaded_parses_as_no_metadata() {
+    let mut metadata: HashMap<String, Value> = HashMap::new();
+    metadata.insert(
+        "mpris:trackid".into(),
+        Value::from(ObjectPath::try_from(lib::player::NO_TRACK).unwrap()),
+    );
+    // a zero length of the wrong type, like some players leave behind
+    metadata.insert("mpris:length".into(), Value::from(0i32));
+    let metadata = Metadata::try_from(metadata).unwrap();
+    assert_eq!(metadata, Metadata::none());
+    assert!(metadata.is_none());
+
+    let mut properties: HashMap<&str, Value> = HashMap::new();
+    properties.insert("PlaybackStatus", Value::from("Stopped"));
+    properties.insert("Rate", Value::from(1.0));
+    properties.insert("Position", Value::from(0i64));
+    properties.insert("Metadata", Value::from(Vec::<String>::new()));
+    let caps = Capabilities::try_from(properties).unwrap();
+    assert!(caps.track().is_none());
+}
+
+#[test]
+fn relaxed_signals_keep_what_browsers_send() {
+    let mut metadata: HashMap<String, Value> = HashMap::new();
+    // mid navigation: a title that isn't a string and artists that aren't all strings
+    metadata.insert("xesam:title".into(), Value::from(0u32));
+    metadata.insert(
+        "xesam:artist".into(),
+        Value::from(Array::from(vec![Value::from("Artist"), Value::from(1u32)])),
+    );
+    metadata.insert("xesam:url".into(), Value::from("https://example.com/watch"));
+    let changed: HashMap<String, Value> =
+        HashMap::from([("Metadata".to_string(), Value::new(Value::from(metadata)))]);
+    let msg = Message::signal(MPRIS_PATH, DBUS_PROPERTIES, "PropertiesChanged")
+        .unwrap()
+        .build(&(MPRIS_PLAYER_PREFIX, changed, Vec::<String>::new()))
+        .unwrap();
+
+    assert!(parse_properties_changed(&msg).is_err());
+    let updates = parse_properties_changed_as(&msg, true).unwrap();
+    let [PlayerUpdated::Metadata(metadata)] = &updates[..] else {
+        panic!("no metadata update");
+    };
+    assert_eq!(metadata.title(), None);
+    assert_eq!(metadata.artists(), Some(&["Artist".to_string()][..]));
+    assert_eq!(metadata.url(), Some("https://example.com/watch"));
+}
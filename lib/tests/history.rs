@@ -0,0 +1,139 @@
+//! recording plays and querying them, run with `--features test-util,history`
+
+use std::time::{Duration, Instant};
+
+use lib::{
+    history::{History, HistoryRecorder},
+    player::{Metadata, PlaybackStatus},
+    test_util::{self, track},
+};
+
+const PLAYER: &str = "org.mpris.MediaPlayer2.test";
+const START: u64 = 1_700_000_000;
+
+/// plays `metadata` from `from` to `to` seconds, one observation a second
+fn play(
+    recorder: &mut HistoryRecorder,
+    metadata: &Metadata,
+    start: Instant,
+    from: u64,
+    to: u64,
+) -> anyhow::Result<()> {
+    for (position, now, unix) in test_util::play(start, START, from, to) {
+        recorder.observe(
+            PLAYER,
+            metadata,
+            PlaybackStatus::Playing,
+            position,
+            now,
+            unix,
+        )?;
+    }
+    Ok(())
+}
+
+#[test]
+fn records_plays() -> anyhow::Result<()> {
+    let mut recorder = HistoryRecorder::new(History::open_in_memory()?);
+    let start = Instant::now();
+    let first = track("first", 100);
+    play(&mut recorder, &first, start, 0, 50)?;
+
+    let playing = recorder.history().recent(10)?;
+    assert_eq!(playing.len(), 1);
+    assert_eq!(playing[0].player, "test");
+    assert_eq!(playing[0].artist.as_deref(), Some("band, singer"));
+    assert_eq!(playing[0].started_at, START);
+    assert_eq!(playing[0].ended_at, None);
+
+    // the next track ends the first one
+    let second = track("second", 100);
+    recorder.observe(
+        PLAYER,
+        &second,
+        PlaybackStatus::Playing,
+        0,
+        start + Duration::from_secs(51),
+        START + 51,
+    )?;
+    let plays = recorder.history().recent(10)?;
+    assert_eq!(plays.len(), 2);
+    assert_eq!(plays[0].title.as_deref(), Some("second"));
+    assert_eq!(plays[1].title.as_deref(), Some("first"));
+    assert_eq!(plays[1].ended_at, Some(START + 51));
+    assert_eq!(plays[1].played, 50_000_000);
+    assert_eq!(plays[1].completion, Some(0.5));
+
+    recorder.finish_all()?;
+    assert!(recorder.history().recent(10)?[0].ended_at.is_some());
+    Ok(())
+}
+
+#[test]
+fn seeking_and_pausing_are_not_playing() -> anyhow::Result<()> {
+    let mut recorder = HistoryRecorder::new(History::open_in_memory()?);
+    let start = Instant::now();
+    let metadata = track("song", 100);
+    play(&mut recorder, &metadata, start, 0, 10)?;
+    // jumps to 80s, then pauses there
+    for (second, status) in [(11, PlaybackStatus::Playing), (12, PlaybackStatus::Paused)] {
+        recorder.observe(
+            PLAYER,
+            &metadata,
+            status,
+            80_000_000,
+            start + Duration::from_secs(second),
+            START + second,
+        )?;
+    }
+    recorder.observe(
+        PLAYER,
+        &metadata,
+        PlaybackStatus::Stopped,
+        0,
+        start + Duration::from_secs(60),
+        START + 60,
+    )?;
+
+    let plays = recorder.history().recent(10)?;
+    assert_eq!(plays.len(), 1);
+    assert_eq!(plays[0].played, 10_000_000);
+    assert_eq!(plays[0].ended_at, Some(START + 60));
+    Ok(())
+}
+
+#[test]
+fn queries() -> anyhow::Result<()> {
+    let history = History::open_in_memory()?;
+    let old = history.start(PLAYER, &track("morning", 200), START)?;
+    history.finish(old, START + 200, 200_000_000)?;
+    let new = history.start(
+        "org.mpris.MediaPlayer2.radio",
+        &track("100%_evening", 200),
+        START + 3600,
+    )?;
+
+    let titles = |plays: Vec<lib::history::Play>| {
+        plays
+            .into_iter()
+            .map(|play| play.title.unwrap_or_default())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(titles(history.recent(10)?), ["100%_evening", "morning"]);
+    assert_eq!(titles(history.recent(1)?), ["100%_evening"]);
+    assert_eq!(
+        titles(history.around(START + 100, Duration::from_secs(60))?),
+        ["morning"]
+    );
+    // the evening play hasn't ended, it counts as still going
+    assert_eq!(
+        titles(history.between(START + 7200, START + 7300)?),
+        ["100%_evening"]
+    );
+    assert_eq!(titles(history.search("MORN", 10)?), ["morning"]);
+    assert_eq!(titles(history.search("%_", 10)?), ["100%_evening"]);
+    assert_eq!(titles(history.search("singer", 10)?).len(), 2);
+    assert_eq!(history.get(new)?.unwrap().player, "radio");
+    assert_eq!(history.get(old)?.unwrap().completion, Some(1.0));
+    Ok(())
+}
@@ -0,0 +1,108 @@
+//! running hooks on player events, run with `--features test-util,hooks`
+
+use std::path::Path;
+
+use lib::{
+    hooks::{Hook, HookEvent, Hooks},
+    player::{Capabilities, MetadataBuilder, PlaybackStatus},
+    service::MprisService,
+    test_util::{bus::TestBus, wait_for, Silent},
+    MprisClient,
+};
+
+/// appends what the hook saw to `out`, one line per run
+fn recorder(out: &Path, on: Vec<HookEvent>, player: Vec<String>) -> Hook {
+    Hook {
+        on,
+        command: vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            format!(
+                r#"echo "$MPRIS_EVENT $MPRIS_PLAYER_NAME $MPRIS_STATUS $1" >> {}"#,
+                out.display()
+            ),
+            "sh".to_string(),
+            "{title|-}".to_string(),
+        ],
+        player,
+    }
+}
+
+async fn lines_until(client: &mut MprisClient, hooks: &Hooks, out: &Path, n: usize) -> Vec<String> {
+    wait_for(async || {
+        let events = client.event().await;
+        hooks.handle_events(client, &events);
+        let lines: Vec<String> = std::fs::read_to_string(out)
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        (lines.len() >= n).then_some(lines)
+    })
+    .await
+    .unwrap_or_else(|| panic!("gave up waiting for {n} hook runs"))
+}
+
+#[tokio::test]
+async fn runs_hooks_on_events() -> anyhow::Result<()> {
+    let out = std::env::temp_dir().join(format!("hooks-test-{}", std::process::id()));
+    _ = std::fs::remove_file(&out);
+
+    let bus = TestBus::start()?;
+    let service = MprisService::builder("hooked", Silent)
+        .capabilities(Capabilities {
+            rate: 1.0,
+            ..Default::default()
+        })
+        .serve_on(bus.builder()?)
+        .await?;
+    let mut client = bus.client().await?;
+    client.add(service.name().to_string()).await?;
+    let hooks = Hooks::new(vec![
+        recorder(
+            &out,
+            vec![HookEvent::TrackChanged, HookEvent::StatusChanged],
+            vec!["hooked".to_string()],
+        ),
+        // never runs, the player doesn't match
+        recorder(
+            &out,
+            vec![HookEvent::TrackChanged],
+            vec!["other".to_string()],
+        ),
+    ])?;
+
+    service
+        .update(|state| {
+            state.metadata = MetadataBuilder::default()
+                .trackid("/org/mpris/MediaPlayer2/track/1".to_string())
+                .title("first song".to_string())
+                .finish();
+        })
+        .await?;
+    let lines = lines_until(&mut client, &hooks, &out, 1).await;
+    assert_eq!(lines, ["track-changed hooked Stopped first song"]);
+
+    service.set_playback_status(PlaybackStatus::Playing).await?;
+    let lines = lines_until(&mut client, &hooks, &out, 2).await;
+    assert_eq!(lines[1], "status-changed hooked Playing first song");
+
+    std::fs::remove_file(out)?;
+    Ok(())
+}
+
+#[test]
+fn rejects_broken_hooks() {
+    let hook = |command: &[&str]| Hook {
+        on: vec![HookEvent::PlayerAdded],
+        command: command.iter().map(|s| s.to_string()).collect(),
+        player: Vec::new(),
+    };
+    assert!(Hooks::new(vec![hook(&[])]).is_err());
+    assert!(Hooks::new(vec![hook(&["echo", "{title"])]).is_err());
+    assert!(Hooks::new(vec![hook(&["echo", "{title}"])]).is_ok());
+    assert_eq!(
+        "player-removed".parse::<HookEvent>().unwrap(),
+        HookEvent::PlayerRemoved
+    );
+}
@@ -0,0 +1,79 @@
+//! idle inhibition against a fake screensaver, run with `--features test-util,inhibit`
+
+use std::sync::{Arc, Mutex};
+
+use lib::{
+    inhibit::IdleInhibitor,
+    player::{Capabilities, PlaybackStatus},
+    test_util::{bus::TestBus, mock::MockPlayer, wait_for},
+    MprisClient,
+};
+
+/// the cookies of the inhibitors being held
+#[derive(Default, Clone)]
+struct ScreenSaver {
+    held: Arc<Mutex<Vec<u32>>>,
+}
+
+#[zbus::interface(name = "org.freedesktop.ScreenSaver")]
+impl ScreenSaver {
+    fn inhibit(&self, _application: String, _reason: String) -> u32 {
+        let mut held = self.held.lock().unwrap();
+        let cookie = held.iter().max().map_or(1, |cookie| cookie + 1);
+        held.push(cookie);
+        cookie
+    }
+
+    fn un_inhibit(&self, cookie: u32) {
+        self.held.lock().unwrap().retain(|held| *held != cookie);
+    }
+}
+
+async fn wait_for_status(client: &mut MprisClient, status: PlaybackStatus) {
+    wait_for(async || {
+        client.event().await;
+        (client.players()[0].capabilities().playback_status == status).then_some(())
+    })
+    .await
+    .unwrap_or_else(|| panic!("gave up waiting for {status}"));
+}
+
+#[tokio::test]
+async fn inhibits_while_playing() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let screensaver = ScreenSaver::default();
+    let _screensaver = bus
+        .builder()?
+        .name("org.freedesktop.ScreenSaver")?
+        .serve_at("/org/freedesktop/ScreenSaver", screensaver.clone())?
+        .build()
+        .await?;
+    let player = bus
+        .serve(MockPlayer::builder().capabilities(Capabilities {
+            playback_status: PlaybackStatus::Paused,
+            rate: 1.0,
+            ..Default::default()
+        }))
+        .await?;
+    let mut client = bus.client().await?;
+    client.get_all().await?;
+
+    // the test bus has no portal
+    let mut inhibitor = IdleInhibitor::new(bus.connect().await?);
+    inhibitor.update(&client).await?;
+    assert!(!inhibitor.is_inhibiting());
+
+    player.set_playback_status(PlaybackStatus::Playing).await?;
+    wait_for_status(&mut client, PlaybackStatus::Playing).await;
+    inhibitor.update(&client).await?;
+    inhibitor.update(&client).await?;
+    assert!(inhibitor.is_inhibiting());
+    assert_eq!(*screensaver.held.lock().unwrap(), [1]);
+
+    player.set_playback_status(PlaybackStatus::Stopped).await?;
+    wait_for_status(&mut client, PlaybackStatus::Stopped).await;
+    inhibitor.update(&client).await?;
+    assert!(!inhibitor.is_inhibiting());
+    assert!(screensaver.held.lock().unwrap().is_empty());
+    Ok(())
+}
@@ -0,0 +1,117 @@
+//! lrc parsing and following a player's lyrics, run with `--features test-util,lyrics`
+
+use std::time::Duration;
+
+use lib::{
+    lyrics::{parse_lrc, Lrclib, LyricsFollower, TimedLine},
+    player::{Capabilities, Metadata, MetadataBuilder, PlaybackStatus},
+    service::MprisService,
+    test_util::{bus::TestBus, wait_for, Silent},
+};
+
+fn line(seconds: f64, text: &str) -> TimedLine {
+    TimedLine {
+        start: (seconds * 1_000_000.0) as u64,
+        text: text.to_string(),
+    }
+}
+
+fn metadata() -> Metadata {
+    MetadataBuilder::default()
+        .trackid("/org/mpris/MediaPlayer2/track/1".to_string())
+        .title("song".to_string())
+        .artists(vec!["band".to_string()])
+        .length(200_000_000)
+        .finish()
+}
+
+fn cache_dir(test: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("lyrics-test-{test}-{}", std::process::id()))
+}
+
+#[test]
+fn parses_lrc() {
+    let lrc = "[ar:band]\n\
+               [offset:+500]\n\
+               [00:10.00][01:10.00]chorus\n\
+               [00:05.50] <00:05.50>first <00:06.00>line\n\
+               [00:01:25]\n";
+    assert_eq!(
+        parse_lrc(lrc),
+        [
+            line(0.75, ""),
+            line(5.0, "first line"),
+            line(9.5, "chorus"),
+            line(69.5, "chorus"),
+        ]
+    );
+}
+
+#[test]
+fn finds_the_current_line() {
+    let lyrics = lib::lyrics::Lyrics {
+        synced: vec![line(1.0, "one"), line(2.0, "two"), line(3.0, "three")],
+        ..Default::default()
+    };
+    assert_eq!(lyrics.line_at(500_000), None);
+    assert_eq!(lyrics.line_at(1_000_000), Some(0));
+    assert_eq!(lyrics.line_at(2_500_000), Some(1));
+    assert_eq!(lyrics.line_at(60_000_000), Some(2));
+    assert_eq!(lyrics.next_start(2_500_000), Some(3_000_000));
+    assert_eq!(lyrics.next_start(3_000_000), None);
+}
+
+#[tokio::test]
+async fn follows_the_position() -> anyhow::Result<()> {
+    let dir = cache_dir("follow");
+    // nothing listens there, the lyrics have to come from the cache
+    let lrclib = Lrclib::new(dir.clone())?.with_url("http://127.0.0.1:9");
+    std::fs::write(
+        lrclib.cache_path(&metadata()).unwrap(),
+        r#"{"instrumental":false,"plainLyrics":"one\ntwo","syncedLyrics":"[00:01.00]one\n[00:20.00]two"}"#,
+    )?;
+    assert_eq!(
+        lrclib.lyrics(&metadata()).await?.unwrap().plain.as_deref(),
+        Some("one\ntwo")
+    );
+
+    let bus = TestBus::start()?;
+    let service = MprisService::builder("singer", Silent)
+        .capabilities(Capabilities {
+            metadata: metadata(),
+            playback_status: PlaybackStatus::Playing,
+            position: 5_000_000,
+            rate: 1.0,
+            ..Default::default()
+        })
+        .serve_on(bus.builder()?)
+        .await?;
+    let mut client = bus.client().await?;
+    client.add(service.name().to_string()).await?;
+    let mut follower = LyricsFollower::new(lrclib);
+
+    let mut lines = Vec::new();
+    wait_for(async || {
+        let events = client.event().await;
+        lines.extend(follower.handle_events(&client, &events));
+        (!lines.is_empty()).then_some(())
+    })
+    .await;
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0].player, service.name());
+    assert_eq!((lines[0].index, lines[0].text.as_str()), (0, "one"));
+    let until = follower.until_next(&client).unwrap();
+    assert!(until > Duration::from_secs(10) && until <= Duration::from_secs(15));
+
+    service.seeked(30_000_000).await?;
+    wait_for(async || {
+        let events = client.event().await;
+        lines.extend(follower.handle_events(&client, &events));
+        (lines.len() > 1).then_some(())
+    })
+    .await;
+    assert_eq!((lines[1].index, lines[1].text.as_str()), (1, "two"));
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
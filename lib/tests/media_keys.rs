@@ -0,0 +1,115 @@
+//! media keys from a fake settings daemon, run with `--features test-util,media-keys`
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use lib::{
+    media_keys::{Backend, MediaKey, MediaKeys},
+    player::{Capabilities, PlaybackStatus},
+    test_util::{
+        bus::TestBus,
+        mock::{MockCall, MockPlayer},
+    },
+};
+use zbus::object_server::SignalEmitter;
+
+const DAEMON: &str = "org.gnome.SettingsDaemon.MediaKeys";
+const PATH: &str = "/org/gnome/SettingsDaemon/MediaKeys";
+
+/// remembers who grabbed the keys
+#[derive(Default, Clone)]
+struct SettingsDaemon {
+    grabbed: Arc<Mutex<Vec<String>>>,
+}
+
+#[zbus::interface(name = "org.gnome.SettingsDaemon.MediaKeys")]
+impl SettingsDaemon {
+    fn grab_media_player_keys(&self, application: String, _time: u32) {
+        self.grabbed.lock().unwrap().push(application);
+    }
+
+    fn release_media_player_keys(&self, application: String) {
+        self.grabbed
+            .lock()
+            .unwrap()
+            .retain(|app| *app != application);
+    }
+
+    #[zbus(signal)]
+    async fn media_player_key_pressed(
+        emitter: &SignalEmitter<'_>,
+        application: &str,
+        key: &str,
+    ) -> zbus::Result<()>;
+}
+
+#[tokio::test]
+async fn falls_back_to_the_settings_daemon() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let daemon = SettingsDaemon::default();
+    let daemon_conn = bus
+        .builder()?
+        .name(DAEMON)?
+        .serve_at(PATH, daemon.clone())?
+        .build()
+        .await?;
+    let player = bus
+        .serve(MockPlayer::builder().capabilities(Capabilities {
+            can_control: true,
+            can_play: true,
+            can_pause: true,
+            can_next: true,
+            playback_status: PlaybackStatus::Playing,
+            rate: 1.0,
+            ..Default::default()
+        }))
+        .await?;
+    let mut client = bus.client().await?;
+    client.get_all().await?;
+
+    // the test bus has no portal
+    let conn = bus.connect().await?;
+    let mut keys = MediaKeys::grab(&conn, "mpris-controller-test").await?;
+    assert_eq!(keys.backend(), &Backend::SettingsDaemon(DAEMON.to_string()));
+    assert_eq!(*daemon.grabbed.lock().unwrap(), ["mpris-controller-test"]);
+
+    let emitter = SignalEmitter::new(&daemon_conn, PATH)?;
+    // keys for other applications and ones without an action are skipped
+    SettingsDaemon::media_player_key_pressed(&emitter, "someone-else", "Next").await?;
+    SettingsDaemon::media_player_key_pressed(&emitter, "mpris-controller-test", "Eject").await?;
+    SettingsDaemon::media_player_key_pressed(&emitter, "mpris-controller-test", "Play").await?;
+    let key = tokio::time::timeout(Duration::from_secs(5), keys.next()).await?;
+    assert_eq!(key, Some(MediaKey::Play));
+
+    key.unwrap().press(&client).await?;
+    MediaKey::Next.press(&client).await?;
+    assert_eq!(player.calls().await?, [MockCall::PlayPause, MockCall::Next]);
+
+    keys.release().await?;
+    assert!(daemon.grabbed.lock().unwrap().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn says_when_there_is_nothing_to_grab_from() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    // neither a portal nor a daemon on the test bus, and no X server either
+    std::env::remove_var("DISPLAY");
+    let conn = bus.connect().await?;
+    let Err(e) = MediaKeys::grab(&conn, "mpris-controller-test").await else {
+        panic!("grabbed the keys from nowhere");
+    };
+    assert!(format!("{e:#}").contains("DISPLAY isn't set"), "{e:#}");
+    Ok(())
+}
+
+#[test]
+fn parses_shortcut_ids() {
+    for key in MediaKey::ALL {
+        assert_eq!(key.as_str().parse::<MediaKey>().unwrap(), key);
+    }
+    assert!("eject".parse::<MediaKey>().is_err());
+    assert_eq!(MediaKey::Previous.keysym(), "XF86AudioPrev");
+}
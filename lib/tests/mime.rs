@@ -0,0 +1,55 @@
+//! matching uris against what players say they open
+
+use lib::mime::{guess, scheme, score};
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+#[test]
+fn reads_schemes_and_extensions() {
+    assert_eq!(scheme("https://example.com/a.mp3"), "https");
+    assert_eq!(scheme("/home/me/a.flac"), "file");
+    assert_eq!(scheme("spotify:track:1234"), "spotify");
+    assert_eq!(scheme("song.ogg"), "file");
+
+    assert_eq!(
+        guess("https://example.com/a.MP3?token=1#t=5"),
+        Some("audio/mpeg")
+    );
+    assert_eq!(guess("/music/b.opus"), Some("audio/ogg"));
+    assert_eq!(guess("https://example.com/watch?v=1"), None);
+    assert_eq!(guess("/music/no_extension"), None);
+}
+
+#[test]
+fn exact_types_beat_kinds_beat_schemes() {
+    let schemes = strings(&["file", "http"]);
+    let uri = "/music/a.flac";
+
+    assert_eq!(score(uri, &schemes, &strings(&["audio/flac"])), Some(3));
+    assert_eq!(score(uri, &schemes, &strings(&["audio/mpeg"])), Some(2));
+    assert_eq!(score(uri, &schemes, &[]), Some(1));
+    assert_eq!(score(uri, &[], &[]), Some(0));
+    // a scheme the player didn't list, or a kind it can't play
+    assert_eq!(score("https://example.com/a.flac", &schemes, &[]), None);
+    assert_eq!(score(uri, &schemes, &strings(&["video/mp4"])), None);
+    // nothing to guess from, the scheme decides
+    assert_eq!(
+        score("http://radio/stream", &schemes, &strings(&["audio/mpeg"])),
+        Some(1)
+    );
+}
+
+#[test]
+fn ignores_case() {
+    let schemes = strings(&["HTTP"]);
+    assert_eq!(
+        score(
+            "http://example.com/a.mp3",
+            &schemes,
+            &strings(&["Audio/MPEG"])
+        ),
+        Some(3)
+    );
+}
@@ -0,0 +1,665 @@
+//! the client against mock players on a private bus, run with `--features test-util`
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures::StreamExt;
+use lib::{
+    ads::AdMuter,
+    clock::{Clock, MockClock},
+    player::{Capabilities, Metadata, MetadataBuilder, MprisEvent, PlaybackStatus, PlayerUpdated},
+    position::PositionTick,
+    selector::Selector,
+    test_util::{
+        bus::TestBus,
+        events_until,
+        mock::{MockCall, MockPlayer},
+    },
+    Bus, DiscoveryMode, MprisClient, MPRIS_PATH,
+};
+use zbus::zvariant::OwnedValue;
+
+fn controllable() -> Capabilities {
+    Capabilities {
+        can_control: true,
+        can_play: true,
+        can_pause: true,
+        can_seek: true,
+        rate: 1.0,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn discovers_players_on_the_bus() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let first = bus.serve(MockPlayer::builder()).await?;
+    let second = bus
+        .serve(MockPlayer::builder().name("org.mpris.MediaPlayer2.mock.instance2"))
+        .await?;
+
+    let mut client = bus.client().await?;
+    let discovered = client.get_all().await?;
+
+    assert!(discovered.failed.is_empty());
+    let mut names = client.player_names();
+    names.sort();
+    assert_eq!(names, [first.name(), second.name()]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn rediscovering_keeps_the_active_player() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let picked = bus.serve(MockPlayer::builder()).await?;
+    let playing = bus
+        .serve(
+            MockPlayer::builder()
+                .name("org.mpris.MediaPlayer2.mock.instance2")
+                .capabilities(Capabilities {
+                    playback_status: PlaybackStatus::Playing,
+                    ..controllable()
+                }),
+        )
+        .await?;
+    let mut client = bus.client().await?;
+    client.get_all().await?;
+    assert_eq!(client.active_player().unwrap().name(), playing.name());
+
+    let id = client.get(picked.name()).unwrap().id();
+    assert!(client.set_active_player(id));
+    // the players come back with new ids
+    client.get_all().await?;
+    assert_eq!(client.active_player().unwrap().name(), picked.name());
+    Ok(())
+}
+
+#[tokio::test]
+async fn tags_kdeconnect_players_as_remote() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let local = bus.serve(MockPlayer::builder()).await?;
+    let phone = bus
+        .serve(MockPlayer::builder().name("org.mpris.MediaPlayer2.kdeconnect.mpris_000001"))
+        .await?;
+    let mut client = bus.client().await?;
+    client.get_all().await?;
+
+    assert!(!client.get(local.name()).unwrap().is_remote());
+    assert!(client.get(phone.name()).unwrap().is_remote());
+    let names = |players: Vec<&lib::player::Player>| -> Vec<String> {
+        players.iter().map(|p| p.name().to_string()).collect()
+    };
+    assert_eq!(
+        names(Selector::new().remote(false).select_all(&client)),
+        [local.name()]
+    );
+    assert_eq!(
+        names(Selector::new().remote(true).select_all(&client)),
+        [phone.name()]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn tags_bluez_players_as_bluetooth() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let local = bus.serve(MockPlayer::builder()).await?;
+    let track = MetadataBuilder::default()
+        .trackid("/org/bluez/hci0/dev_00_11_22_33_44_55/player0/Track".to_string())
+        .finish();
+    let phone = bus
+        .serve(
+            MockPlayer::builder()
+                .name("org.mpris.MediaPlayer2.Pixel_7")
+                .capabilities(Capabilities {
+                    metadata: track,
+                    ..controllable()
+                }),
+        )
+        .await?;
+    let mut client = bus.client().await?;
+    client.get_all().await?;
+
+    let local = client.get(local.name()).unwrap();
+    assert!(!local.is_bluetooth());
+    assert_eq!(local.icon(), None);
+    let phone = client.get(phone.name()).unwrap();
+    assert!(phone.is_bluetooth());
+    assert_eq!(phone.icon(), Some("bluetooth"));
+    assert_eq!(phone.field("player_icon").as_deref(), Some("\u{f00af}"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn mutes_spotify_ads() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let mock = bus
+        .serve(
+            MockPlayer::builder()
+                .name("org.mpris.MediaPlayer2.spotify")
+                .capabilities(Capabilities {
+                    volume: Some(0.8),
+                    ..controllable()
+                }),
+        )
+        .await?;
+    let mut client = bus.client().await?;
+    client.add(mock.name().to_string()).await?;
+    let mut muter = AdMuter::new(client.connection().unwrap().clone());
+
+    mock.set_metadata(
+        MetadataBuilder::default()
+            .trackid("/com/spotify/ad/1234".to_string())
+            .title("Advertisement".to_string())
+            .finish(),
+    )
+    .await?;
+    let events = events_until(&mut client, |e| matches!(e, MprisEvent::AdStarted { .. })).await;
+    assert!(client.get(mock.name()).unwrap().is_ad());
+    muter.handle_events(&mut client, &events).await?;
+    assert!(muter.is_muted(mock.name()));
+
+    mock.set_metadata(
+        MetadataBuilder::default()
+            .trackid("/com/spotify/track/5678".to_string())
+            .title("sailor".to_string())
+            .art_url("https://i.scdn.co/image/cover".to_string())
+            .finish(),
+    )
+    .await?;
+    let events = events_until(&mut client, |e| matches!(e, MprisEvent::AdEnded { .. })).await;
+    muter.handle_events(&mut client, &events).await?;
+    assert!(!muter.is_muted(mock.name()));
+    assert_eq!(
+        mock.calls().await?,
+        [MockCall::SetVolume(0.0), MockCall::SetVolume(0.8)]
+    );
+    Ok(())
+}
+
+#[test]
+fn tracks_without_art_are_not_ads() {
+    let track = |trackid: &str| MetadataBuilder::default().trackid(trackid.to_string());
+    // a local file, no art and no album
+    let local = track("spotify:local:band::sailor:180")
+        .title("sailor".to_string())
+        .artists(vec!["band".to_string()])
+        .finish();
+    assert!(!local.is_ad());
+    assert!(!track("/com/spotify/track/5678").finish().is_ad());
+
+    assert!(track("spotify:ad:1234").finish().is_ad());
+    assert!(track("/com/spotify/ad/1234").finish().is_ad());
+    assert!(track("/com/spotify/track/5678")
+        .title("Advertisement".to_string())
+        .finish()
+        .is_ad());
+}
+
+#[tokio::test]
+async fn clears_the_track_when_nothing_is_loaded() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let mock = bus
+        .serve(MockPlayer::builder().capabilities(controllable()))
+        .await?;
+    let mut client = bus.client().await?;
+    client.add(mock.name().to_string()).await?;
+
+    mock.set_metadata(
+        MetadataBuilder::default()
+            .title("sailor".to_string())
+            .finish(),
+    )
+    .await?;
+    events_until(&mut client, |e| {
+        matches!(e, MprisEvent::TrackChanged { .. })
+    })
+    .await;
+    assert!(client
+        .get(mock.name())
+        .unwrap()
+        .capabilities()
+        .track()
+        .is_some());
+
+    mock.set_metadata(Metadata::none()).await?;
+    let events = events_until(&mut client, |e| {
+        matches!(e, MprisEvent::TrackCleared { .. })
+    })
+    .await;
+    assert!(!events
+        .iter()
+        .any(|e| matches!(e, MprisEvent::TrackChanged { .. })));
+    assert!(client
+        .get(mock.name())
+        .unwrap()
+        .capabilities()
+        .track()
+        .is_none());
+    Ok(())
+}
+
+/// only the properties `GetAll` has to have
+struct SlowPlayer;
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+impl SlowPlayer {
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        "Paused".to_string()
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn position(&self) -> i64 {
+        0
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        HashMap::new()
+    }
+}
+
+#[tokio::test]
+async fn waits_for_players_still_registering() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    // the name is taken before the player interface is exported
+    let slow = bus
+        .builder()?
+        .name("org.mpris.MediaPlayer2.slow")?
+        .build()
+        .await?;
+    // answering calls (with errors for now) only starts with the object server
+    slow.object_server();
+    let server = slow.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        server.object_server().at(MPRIS_PATH, SlowPlayer).await
+    });
+
+    let mut client = bus.client().await?;
+    let discovered = client.get_all().await?;
+    assert!(discovered.failed.is_empty(), "{:?}", discovered.failed);
+    assert_eq!(discovered.added, ["org.mpris.MediaPlayer2.slow"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn follows_scripted_changes() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let mock = bus
+        .serve(MockPlayer::builder().capabilities(controllable()))
+        .await?;
+    let mut client = bus.client().await?;
+    client.add(mock.name().to_string()).await?;
+
+    mock.set_metadata(
+        MetadataBuilder::default()
+            .title("sailor".to_string())
+            .finish(),
+    )
+    .await?;
+    mock.set_playback_status(PlaybackStatus::Playing).await?;
+    events_until(&mut client, |e| {
+        matches!(
+            e,
+            MprisEvent::PlayerUpdated {
+                update: PlayerUpdated::PlaybackStatus(PlaybackStatus::Playing),
+                ..
+            }
+        )
+    })
+    .await;
+
+    let player = client.get(mock.name()).unwrap();
+    assert_eq!(player.title(), Some("sailor"));
+    assert_eq!(
+        player.capabilities().playback_status,
+        PlaybackStatus::Playing
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn control_calls_reach_the_player() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let mock = bus
+        .serve(MockPlayer::builder().capabilities(controllable()))
+        .await?;
+    let mut client = bus.client().await?;
+    client.add(mock.name().to_string()).await?;
+    let conn = client.connection().unwrap().clone();
+
+    let player = client.get(mock.name()).unwrap();
+    player.play(&conn).await?;
+    player.seek_forward(&conn, Duration::from_secs(10)).await?;
+    events_until(&mut client, |e| {
+        matches!(
+            e,
+            MprisEvent::Seeked {
+                position: 10_000_000,
+                ..
+            }
+        )
+    })
+    .await;
+
+    assert_eq!(
+        mock.calls().await?,
+        [MockCall::Play, MockCall::Seek(10_000_000)]
+    );
+    assert_eq!(
+        client
+            .get(mock.name())
+            .unwrap()
+            .capabilities()
+            .playback_status,
+        PlaybackStatus::Playing
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn lazy_discovery_loads_players_when_needed() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let playing = bus
+        .serve(MockPlayer::builder().capabilities(controllable()))
+        .await?;
+    let queried = bus
+        .serve(MockPlayer::builder().name("org.mpris.MediaPlayer2.mock.instance2"))
+        .await?;
+    let mut client = bus.client().await?;
+    client.set_discovery_mode(DiscoveryMode::Lazy);
+
+    let discovered = client.get_all().await?;
+    assert!(discovered.added.is_empty());
+    assert_eq!(discovered.deferred.len(), 2);
+    assert!(client.players().is_empty());
+
+    playing.set_playback_status(PlaybackStatus::Playing).await?;
+    events_until(
+        &mut client,
+        |e| matches!(e, MprisEvent::PlayerAdded(name) if name == playing.name()),
+    )
+    .await;
+    assert_eq!(client.player_names(), [playing.name()]);
+
+    assert_eq!(client.load(queried.name()).await?.name(), queried.name());
+    assert_eq!(client.deferred().count(), 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn refresh_reads_what_signals_missed() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let mock = bus
+        .serve(MockPlayer::builder().capabilities(controllable()))
+        .await?;
+    let mut client = bus.client().await?;
+    client.add(mock.name().to_string()).await?;
+
+    // the signals are never looked at, as if they were lost
+    mock.set_metadata(
+        MetadataBuilder::default()
+            .title("sailor".to_string())
+            .finish(),
+    )
+    .await?;
+    mock.set_playback_status(PlaybackStatus::Playing).await?;
+    mock.set_volume(0.25).await?;
+    mock.set_can_go_next(true).await?;
+    assert!(client.refresh_all().await?.is_empty());
+
+    let player = client.get(mock.name()).unwrap();
+    assert_eq!(player.title(), Some("sailor"));
+    assert_eq!(
+        player.capabilities().playback_status,
+        PlaybackStatus::Playing
+    );
+    assert_eq!(player.capabilities().volume, Some(0.25));
+    assert!(player.capabilities().can_next);
+    let events = client.event().await;
+    let track_changed = events
+        .iter()
+        .position(|e| {
+            matches!(
+                e,
+                MprisEvent::TrackChanged { player, .. } if player == mock.name()
+            )
+        })
+        .unwrap();
+    // every property that changed is told about, not only the status and the track. the
+    // refresh's events come first, the signals read after them only repeat what it found
+    for property in ["Volume", "CanGoNext"] {
+        assert!(
+            events[..track_changed].iter().any(|e| matches!(
+                e,
+                MprisEvent::PlayerUpdated { update, .. } if update.property() == property
+            )),
+            "{property}"
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn positions_follow_the_client_clock() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let mock = bus
+        .serve(MockPlayer::builder().capabilities(Capabilities {
+            playback_status: PlaybackStatus::Playing,
+            position: 10_000_000,
+            ..controllable()
+        }))
+        .await?;
+    let clock = MockClock::new();
+    let mut client = bus.client().await?.with_clock(Arc::new(clock.clone()));
+    client.add(mock.name().to_string()).await?;
+
+    clock.advance(Duration::from_secs(5));
+    let player = client.get(mock.name()).unwrap();
+    assert_eq!(player.estimated_position_at(clock.now()), 15_000_000);
+
+    mock.seek_to(42_000_000).await?;
+    let conn = client.connection().unwrap().clone();
+    let now = clock.now();
+    let player = client.get_mut(mock.name()).unwrap();
+    assert_eq!(player.fetch_position_at(&conn, now).await?, 42_000_000);
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(player.estimated_position_at(clock.now()), 43_000_000);
+    Ok(())
+}
+
+#[tokio::test]
+async fn ticks_while_playing() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let mock = bus
+        .serve(MockPlayer::builder().capabilities(Capabilities {
+            playback_status: PlaybackStatus::Playing,
+            position: 10_000_000,
+            ..controllable()
+        }))
+        .await?;
+    let clock = MockClock::new();
+    let mut client = bus.client().await?.with_clock(Arc::new(clock.clone()));
+    client.add(mock.name().to_string()).await?;
+    let mut ticks = client.position_ticks(Duration::from_millis(10));
+
+    clock.advance(Duration::from_secs(5));
+    let tick = ticks.next().await.unwrap();
+    assert_eq!(
+        tick,
+        [PositionTick {
+            player: mock.name().to_string(),
+            position: 15_000_000,
+            length: None,
+        }]
+    );
+
+    // nothing plays, nothing ticks
+    mock.set_playback_status(PlaybackStatus::Paused).await?;
+    events_until(&mut client, |e| {
+        matches!(
+            e,
+            MprisEvent::PlayerUpdated {
+                update: PlayerUpdated::PlaybackStatus(PlaybackStatus::Paused),
+                ..
+            }
+        )
+    })
+    .await;
+    let paused = tokio::time::timeout(Duration::from_millis(100), ticks.next()).await;
+    assert!(paused.is_err());
+
+    drop(client);
+    assert_eq!(ticks.next().await, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn reconnects_after_the_bus_restarts() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let mock = bus.serve(MockPlayer::builder()).await?;
+    let mut client = bus.client().await?;
+    client.add(mock.name().to_string()).await?;
+    let id = client.get(mock.name()).unwrap().id();
+    // the restarted bus listens somewhere else
+    let address = Arc::new(Mutex::new(bus.address().to_string()));
+    let current = address.clone();
+    client.reconnect_with(move || {
+        let address = current.lock().unwrap().clone();
+        async move {
+            zbus::connection::Builder::address(address.as_str())?
+                .build()
+                .await
+        }
+    });
+    client.event().await;
+
+    drop(mock);
+    drop(bus);
+    events_until(&mut client, |e| matches!(e, MprisEvent::ConnectionLost)).await;
+    assert!(client.is_connection_lost());
+
+    let bus = TestBus::start()?;
+    let mock = bus.serve(MockPlayer::builder()).await?;
+    *address.lock().unwrap() = bus.address().to_string();
+    events_until(&mut client, |e| matches!(e, MprisEvent::ConnectionRestored)).await;
+    assert_eq!(client.get(mock.name()).map(|p| p.id()), Some(id));
+
+    // the player's signals come through the new connection
+    mock.set_playback_status(PlaybackStatus::Playing).await?;
+    events_until(&mut client, |e| {
+        matches!(
+            e,
+            MprisEvent::PlayerUpdated {
+                update: PlayerUpdated::PlaybackStatus(PlaybackStatus::Playing),
+                ..
+            }
+        )
+    })
+    .await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn connects_to_a_bus_by_address() -> anyhow::Result<()> {
+    assert_eq!("session".parse::<Bus>()?, Bus::Session);
+    assert_eq!("system".parse::<Bus>()?, Bus::System);
+    assert!("sessoin".parse::<Bus>().is_err());
+
+    let bus = TestBus::start()?;
+    let mock = bus.serve(MockPlayer::builder()).await?;
+    let address: Bus = bus.address().parse()?;
+    assert_eq!(address, Bus::Address(bus.address().to_string()));
+
+    let mut client = MprisClient::connect_to(address).await?;
+    let discovered = client.get_all().await?;
+    assert_eq!(discovered.added, [mock.name()]);
+    Ok(())
+}
+
+#[cfg(feature = "owner_changed")]
+#[tokio::test]
+async fn follows_players_coming_and_going() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let mut client = bus.client().await?;
+    client.follow_owner_changes().await?;
+
+    let mock = bus.serve(MockPlayer::builder()).await?;
+    let name = mock.name().to_string();
+    events_until(
+        &mut client,
+        |e| matches!(e, MprisEvent::PlayerAdded(added) if *added == name),
+    )
+    .await;
+    assert!(client.get(&name).is_some());
+
+    drop(mock);
+    events_until(
+        &mut client,
+        |e| matches!(e, MprisEvent::PlayerRemoved(removed) if *removed == name),
+    )
+    .await;
+    assert!(client.get(&name).is_none());
+    Ok(())
+}
+
+#[cfg(feature = "owner_changed")]
+#[tokio::test]
+async fn reads_a_player_again_when_another_process_takes_its_name() -> anyhow::Result<()> {
+    use zbus::fdo::RequestNameFlags;
+
+    let bus = TestBus::start()?;
+    let old = bus.serve(MockPlayer::builder()).await?;
+    // let the next process with the name take it over, like a restarted player does
+    old.connection()
+        .request_name_with_flags(old.name(), RequestNameFlags::AllowReplacement.into())
+        .await?;
+    let mut client = bus.client().await?;
+    client.add(old.name().to_string()).await?;
+    client.follow_owner_changes().await?;
+    let id = client.get(old.name()).unwrap().id();
+
+    let new = bus
+        .serve(MockPlayer::builder().capabilities(Capabilities {
+            playback_status: PlaybackStatus::Playing,
+            ..controllable()
+        }))
+        .await?;
+    let name = new.name().to_string();
+    events_until(
+        &mut client,
+        |e| matches!(e, MprisEvent::PlayerRestarted(restarted) if *restarted == name),
+    )
+    .await;
+    let player = client.get(&name).unwrap();
+    assert_eq!(player.id(), id);
+    assert_eq!(
+        player.capabilities().playback_status,
+        PlaybackStatus::Playing
+    );
+
+    // the signals of the new process reach the player
+    new.set_volume(0.25).await?;
+    events_until(&mut client, |e| {
+        matches!(
+            e,
+            MprisEvent::PlayerUpdated {
+                update: PlayerUpdated::Volume(_),
+                ..
+            }
+        )
+    })
+    .await;
+    drop(old);
+    Ok(())
+}
@@ -0,0 +1,226 @@
+//! the MPD bridge against a scripted MPD, run with `--features test-util,mpd-bridge`
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use lib::{
+    mpd::MpdBridge,
+    player::{MprisEvent, PlaybackStatus, PlayerUpdated},
+    test_util::{bus::TestBus, events_until, wait_for},
+    MPRIS_PATH, MPRIS_PLAYER_PREFIX,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::watch,
+};
+
+#[derive(Debug)]
+struct State {
+    playing: bool,
+    volume: u32,
+    commands: Vec<String>,
+    connections: usize,
+}
+
+/// just enough of MPD for the bridge, `changed` wakes the connections in `idle` and `timeout`
+/// closes the ones waiting for a command, like MPD's `connection_timeout`
+#[derive(Clone)]
+struct FakeMpd {
+    state: Arc<Mutex<State>>,
+    changed: watch::Sender<u64>,
+    timeout: watch::Sender<u64>,
+}
+
+impl FakeMpd {
+    async fn start() -> anyhow::Result<(Self, String)> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?.to_string();
+        let mpd = Self {
+            state: Arc::new(Mutex::new(State {
+                playing: false,
+                volume: 50,
+                commands: Vec::new(),
+                connections: 0,
+            })),
+            changed: watch::Sender::new(0),
+            timeout: watch::Sender::new(0),
+        };
+
+        let server = mpd.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(server.clone().serve(stream));
+            }
+        });
+        Ok((mpd, address))
+    }
+
+    fn commands(&self) -> Vec<String> {
+        self.state.lock().unwrap().commands.clone()
+    }
+
+    fn connections(&self) -> usize {
+        self.state.lock().unwrap().connections
+    }
+
+    fn time_out(&self) {
+        self.timeout.send_modify(|n| *n += 1);
+    }
+
+    async fn serve(self, stream: TcpStream) -> anyhow::Result<()> {
+        self.state.lock().unwrap().connections += 1;
+        let (read, mut write) = stream.into_split();
+        let mut lines = BufReader::new(read).lines();
+        let mut timeout = self.timeout.subscribe();
+        write.write_all(b"OK MPD 0.23.5\n").await?;
+
+        loop {
+            let command = tokio::select! {
+                command = lines.next_line() => command?,
+                _ = timeout.changed() => return Ok(()),
+            };
+            let Some(command) = command else {
+                return Ok(());
+            };
+            let reply = match command.as_str() {
+                "status" => {
+                    let state = self.state.lock().unwrap();
+                    format!(
+                        "volume: {}\nrepeat: 1\nrandom: 0\nsingle: 0\nplaylistlength: 2\n\
+                         state: {}\nsongid: 7\nelapsed: 12.000\nduration: 200.000\n",
+                        state.volume,
+                        if state.playing { "play" } else { "pause" },
+                    )
+                }
+                "currentsong" => {
+                    "file: a.flac\nTitle: song\nArtist: band\nduration: 200.000\nId: 7\n"
+                        .to_string()
+                }
+                "urlhandlers" => "handler: http://\n".to_string(),
+                idle if idle.starts_with("idle") => {
+                    let mut changed = self.changed.subscribe();
+                    changed.changed().await?;
+                    // only connections waiting for a command time out
+                    timeout.mark_unchanged();
+                    "changed: player\n".to_string()
+                }
+                command => {
+                    {
+                        let mut state = self.state.lock().unwrap();
+                        match command.split_once(' ') {
+                            None if command == "play" => state.playing = true,
+                            Some(("pause", "1")) => state.playing = false,
+                            Some(("setvol", volume)) => state.volume = volume.parse()?,
+                            _ => {}
+                        }
+                        state.commands.push(command.to_string());
+                    }
+                    self.changed.send_modify(|n| *n += 1);
+                    String::new()
+                }
+            };
+            write.write_all(format!("{reply}OK\n").as_bytes()).await?;
+        }
+    }
+}
+
+#[tokio::test]
+async fn bridges_status_and_commands() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let (mpd, address) = FakeMpd::start().await?;
+    let bridge = MpdBridge::start_on(&address, bus.builder()?).await?;
+    let name = bridge.service().name().to_string();
+    let state = bridge.service().capabilities().await?;
+    assert_eq!(state.playback_status, PlaybackStatus::Paused);
+    assert_eq!(state.metadata.title(), Some("song"));
+    assert_eq!(state.metadata.length(), Some(200_000_000));
+    assert_eq!(state.position, 12_000_000);
+    assert_eq!(state.volume, Some(0.5));
+    assert_eq!(
+        bridge.service().root().await?.supported_uri_schemes,
+        ["http"]
+    );
+    tokio::spawn(bridge.run());
+
+    let mut client = bus.client().await?;
+    client.add(name.clone()).await?;
+    let conn = client.connection().unwrap().clone();
+    conn.call_method(
+        Some(name.as_str()),
+        MPRIS_PATH,
+        Some(MPRIS_PLAYER_PREFIX),
+        "Play",
+        &(),
+    )
+    .await?;
+    events_until(&mut client, |e| {
+        matches!(
+            e,
+            MprisEvent::PlayerUpdated {
+                update: PlayerUpdated::PlaybackStatus(PlaybackStatus::Playing),
+                ..
+            }
+        )
+    })
+    .await;
+
+    client
+        .get_mut(&name)
+        .unwrap()
+        .set_volume(&conn, 0.8)
+        .await?;
+    conn.call_method(
+        Some(name.as_str()),
+        MPRIS_PATH,
+        Some(MPRIS_PLAYER_PREFIX),
+        "Seek",
+        &(-5_000_000i64,),
+    )
+    .await?;
+    wait_for(async || (mpd.commands().len() == 3).then_some(())).await;
+    assert_eq!(mpd.commands(), ["play", "setvol 80", "seekcur -5"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn reconnects_after_mpd_times_out() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let (mpd, address) = FakeMpd::start().await?;
+    let bridge = MpdBridge::start_on(&address, bus.builder()?).await?;
+    let name = bridge.service().name().to_string();
+    tokio::spawn(bridge.run());
+    assert_eq!(mpd.connections(), 2);
+
+    // the idle connection is left alone once it waits for a change
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    mpd.time_out();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let mut client = bus.client().await?;
+    client.add(name.clone()).await?;
+    let conn = client.connection().unwrap().clone();
+    conn.call_method(
+        Some(name.as_str()),
+        MPRIS_PATH,
+        Some(MPRIS_PLAYER_PREFIX),
+        "Play",
+        &(),
+    )
+    .await?;
+    events_until(&mut client, |e| {
+        matches!(
+            e,
+            MprisEvent::PlayerUpdated {
+                update: PlayerUpdated::PlaybackStatus(PlaybackStatus::Playing),
+                ..
+            }
+        )
+    })
+    .await;
+    assert_eq!(mpd.commands(), ["play"]);
+    assert_eq!(mpd.connections(), 3);
+    Ok(())
+}
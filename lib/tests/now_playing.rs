@@ -0,0 +1,77 @@
+//! the now playing file writer, run with `--features test-util`
+
+use lib::{
+    now_playing::NowPlayingWriter,
+    player::{Capabilities, MetadataBuilder},
+    service::MprisService,
+    test_util::{bus::TestBus, wait_for, Silent},
+    MprisClient,
+};
+
+async fn wait_for_title(client: &mut MprisClient, name: &str, title: &str) {
+    wait_for(async || {
+        client.event().await;
+        (client.get(name).and_then(|player| player.title()) == Some(title)).then_some(())
+    })
+    .await
+    .unwrap_or_else(|| panic!("gave up waiting for {title}"));
+}
+
+#[tokio::test]
+async fn writes_text_and_art() -> anyhow::Result<()> {
+    let dir = std::env::temp_dir().join(format!("now-playing-test-{}", std::process::id()));
+    _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir)?;
+    let cover = dir.join("cover one.png");
+    std::fs::write(&cover, b"first cover")?;
+
+    let bus = TestBus::start()?;
+    let service = MprisService::builder("streamed", Silent)
+        .capabilities(Capabilities {
+            metadata: MetadataBuilder::default()
+                .trackid("/org/mpris/MediaPlayer2/track/1".to_string())
+                .title("first song".to_string())
+                .artists(vec!["someone".to_string()])
+                .art_url(format!("file://{}", cover.display()).replace(' ', "%20"))
+                .finish(),
+            ..Default::default()
+        })
+        .serve_on(bus.builder()?)
+        .await?;
+    let mut client = bus.client().await?;
+    client.add(service.name().to_string()).await?;
+
+    let text = dir.join("obs/now-playing.txt");
+    let art = dir.join("obs/art");
+    let mut writer = NowPlayingWriter::new(&text, "{artist} - {title}")?
+        .idle("nothing")
+        .art(&art);
+
+    writer.update(client.get(service.name()))?;
+    assert_eq!(std::fs::read_to_string(&text)?, "someone - first song");
+    assert_eq!(std::fs::read(&art)?, b"first cover");
+    // nothing changed, nothing is written
+    assert!(!writer.write_text(client.get(service.name()))?);
+    assert!(!writer.write_art(Some(&cover))?);
+
+    service
+        .update(|state| {
+            state.metadata = MetadataBuilder::default()
+                .trackid("/org/mpris/MediaPlayer2/track/2".to_string())
+                .title("second song".to_string())
+                .finish();
+        })
+        .await?;
+    wait_for_title(&mut client, service.name(), "second song").await;
+    writer.update(client.get(service.name()))?;
+    assert_eq!(std::fs::read_to_string(&text)?, " - second song");
+    assert!(!art.exists());
+
+    writer.update(None)?;
+    assert_eq!(std::fs::read_to_string(&text)?, "nothing");
+    // only the files themselves, no temporary ones left behind
+    assert_eq!(std::fs::read_dir(dir.join("obs"))?.count(), 1);
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
@@ -0,0 +1,22 @@
+//! matching player names against patterns
+
+use lib::pattern::matches;
+
+const SPOTIFY: &str = "org.mpris.MediaPlayer2.spotify";
+const CHROMIUM: &str = "org.mpris.MediaPlayer2.chromium.instance1234";
+const VLC: &str = "org.mpris.MediaPlayer2.vlc";
+
+#[test]
+fn matches_full_and_short_names() {
+    assert!(matches("spotify", SPOTIFY));
+    assert!(matches(SPOTIFY, SPOTIFY));
+    assert!(matches("chromium.*", CHROMIUM));
+    assert!(matches("*1234", CHROMIUM));
+    assert!(matches("c*i*4", CHROMIUM));
+    assert!(matches("*", VLC));
+
+    assert!(!matches("chromium", CHROMIUM));
+    assert!(!matches("spot", SPOTIFY));
+    assert!(!matches("*fy.x", SPOTIFY));
+    assert!(!matches("MediaPlayer2.vlc", VLC));
+}
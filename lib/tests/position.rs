@@ -0,0 +1,76 @@
+//! interpolating the position between updates
+
+use std::time::Duration;
+
+use lib::{
+    clock::{Clock, MockClock},
+    player::PlaybackStatus,
+    position::PositionTracker,
+};
+
+const SECOND: u64 = 1_000_000;
+
+#[test]
+fn moves_while_playing() {
+    let clock = MockClock::new();
+    let tracker = PositionTracker::new(10 * SECOND, 1.0, PlaybackStatus::Playing, clock.now());
+
+    clock.advance(Duration::from_secs(5));
+    assert_eq!(tracker.estimate(clock.now()), 15 * SECOND);
+
+    let paused = PositionTracker::new(10 * SECOND, 1.0, PlaybackStatus::Paused, clock.now());
+    clock.advance(Duration::from_secs(5));
+    assert_eq!(paused.estimate(clock.now()), 10 * SECOND);
+}
+
+#[test]
+fn follows_the_rate() {
+    let clock = MockClock::new();
+    let mut tracker = PositionTracker::new(0, 2.0, PlaybackStatus::Playing, clock.now());
+
+    clock.advance(Duration::from_secs(5));
+    assert_eq!(tracker.estimate(clock.now()), 10 * SECOND);
+
+    // the time before the change still counts at the old rate
+    tracker.set_rate(0.5, clock.now());
+    clock.advance(Duration::from_secs(4));
+    assert_eq!(tracker.estimate(clock.now()), 12 * SECOND);
+
+    // going backwards stops at the start
+    tracker.set_rate(-1.0, clock.now());
+    clock.advance(Duration::from_secs(60));
+    assert_eq!(tracker.estimate(clock.now()), 0);
+}
+
+#[test]
+fn rebases_on_status_changes() {
+    let clock = MockClock::new();
+    let mut tracker = PositionTracker::new(0, 1.0, PlaybackStatus::Playing, clock.now());
+
+    clock.advance(Duration::from_secs(3));
+    tracker.set_status(PlaybackStatus::Paused, clock.now());
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(tracker.estimate(clock.now()), 3 * SECOND);
+
+    tracker.set_status(PlaybackStatus::Playing, clock.now());
+    clock.advance(Duration::from_secs(2));
+    assert_eq!(tracker.estimate(clock.now()), 5 * SECOND);
+
+    tracker.set_position(60 * SECOND, clock.now());
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(tracker.estimate(clock.now()), 61 * SECOND);
+}
+
+#[test]
+fn stopping_goes_back_to_the_start() {
+    let clock = MockClock::new();
+    let mut tracker = PositionTracker::new(42 * SECOND, 1.0, PlaybackStatus::Playing, clock.now());
+
+    tracker.set_status(PlaybackStatus::Stopped, clock.now());
+    clock.advance(Duration::from_secs(5));
+    assert_eq!(tracker.estimate(clock.now()), 0);
+
+    tracker.set_status(PlaybackStatus::Playing, clock.now());
+    clock.advance(Duration::from_secs(5));
+    assert_eq!(tracker.estimate(clock.now()), 5 * SECOND);
+}
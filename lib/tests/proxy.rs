@@ -0,0 +1,158 @@
+//! the `mpris_controller` proxy in front of mock players, run with `--features test-util`
+
+use lib::{
+    player::{Capabilities, MetadataBuilder, PlaybackStatus},
+    proxy::{Proxy, PROXY_NAME},
+    test_util::{
+        bus::TestBus,
+        mock::{MockCall, MockPlayer},
+        wait_for,
+    },
+    MprisClient, MPRIS_PATH, MPRIS_PLAYER_PREFIX, MPRIS_PREFIX,
+};
+
+fn capabilities(playback_status: PlaybackStatus) -> Capabilities {
+    Capabilities {
+        can_control: true,
+        can_play: true,
+        can_pause: true,
+        can_next: true,
+        can_seek: true,
+        playback_status,
+        rate: 1.0,
+        ..Default::default()
+    }
+}
+
+async fn step_until<F>(proxy: &mut Proxy, mut done: F)
+where
+    F: AsyncFnMut(&mut Proxy) -> bool,
+{
+    wait_for(async || {
+        proxy.step().await;
+        done(proxy).await.then_some(())
+    })
+    .await
+    .expect("gave up waiting");
+}
+
+#[tokio::test]
+async fn forwards_to_the_active_player() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let playing = bus
+        .serve(
+            MockPlayer::builder()
+                .name(format!("{MPRIS_PREFIX}.playing"))
+                .capabilities(capabilities(PlaybackStatus::Playing)),
+        )
+        .await?;
+    let paused = bus
+        .serve(
+            MockPlayer::builder()
+                .name(format!("{MPRIS_PREFIX}.paused"))
+                .capabilities(capabilities(PlaybackStatus::Paused)),
+        )
+        .await?;
+    let mut client = bus.client().await?;
+    client.get_all().await?;
+    let mut proxy = Proxy::start_on(client, bus.builder()?).await?;
+    assert!(proxy
+        .client()
+        .get(&format!("{MPRIS_PREFIX}.{PROXY_NAME}"))
+        .is_none());
+
+    let conn = bus.connect().await?;
+    let name = format!("{MPRIS_PREFIX}.{PROXY_NAME}");
+    conn.call_method(
+        Some(name.as_str()),
+        MPRIS_PATH,
+        Some(MPRIS_PLAYER_PREFIX),
+        "Next",
+        &(),
+    )
+    .await?;
+    conn.call_method(
+        Some(name.as_str()),
+        MPRIS_PATH,
+        Some(MPRIS_PLAYER_PREFIX),
+        "Seek",
+        &(5i64,),
+    )
+    .await?;
+    step_until(&mut proxy, async |_| {
+        playing.calls().await.unwrap().len() == 2
+    })
+    .await;
+
+    assert_eq!(playing.calls().await?, [MockCall::Next, MockCall::Seek(5)]);
+    assert!(paused.calls().await?.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn mirrors_the_active_player() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let player = bus
+        .serve(MockPlayer::builder().capabilities(capabilities(PlaybackStatus::Playing)))
+        .await?;
+    let mut client = bus.client().await?;
+    client.get_all().await?;
+    let mut proxy = Proxy::start_on(client, bus.builder()?).await?;
+    assert_eq!(
+        proxy.service().capabilities().await?.playback_status,
+        PlaybackStatus::Playing
+    );
+
+    player
+        .set_metadata(
+            MetadataBuilder::default()
+                .title("mirrored".to_string())
+                .finish(),
+        )
+        .await?;
+    step_until(&mut proxy, async |proxy| {
+        let state = proxy.service().capabilities().await.unwrap();
+        state.metadata.title() == Some("mirrored")
+    })
+    .await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn mirrors_changes_of_the_same_player() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let player = bus
+        .serve(MockPlayer::builder().capabilities(capabilities(PlaybackStatus::Playing)))
+        .await?;
+    let mut client = bus.client().await?;
+    client.get_all().await?;
+    let mut proxy = Proxy::start_on(client, bus.builder()?).await?;
+    assert!(proxy.service().capabilities().await?.can_next);
+
+    player.set_can_go_next(false).await?;
+    player.set_volume(0.25).await?;
+    player.set_identity("Mock").await?;
+    step_until(&mut proxy, async |proxy| {
+        let state = proxy.service().capabilities().await.unwrap();
+        let root = proxy.service().root().await.unwrap();
+        !state.can_next && state.volume == Some(0.25) && root.identity.as_deref() == Some("Mock")
+    })
+    .await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn starts_on_the_bus_of_the_client() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let client = MprisClient::connect_to(bus.address().parse()?).await?;
+    let _proxy = Proxy::start(client).await?;
+
+    let conn = bus.connect().await?;
+    let name = format!("{MPRIS_PREFIX}.{PROXY_NAME}");
+    let owned = zbus::fdo::DBusProxy::new(&conn)
+        .await?
+        .name_has_owner(name.as_str().try_into()?)
+        .await?;
+    assert!(owned);
+    Ok(())
+}
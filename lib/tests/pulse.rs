@@ -0,0 +1,83 @@
+//! finding a player's streams in `pactl` output, run with `--features pulse`
+
+use lib::pulse::{parse_sink_inputs, player_streams};
+
+/// trimmed down `pactl -f json list sink-inputs` from pipewire-pulse
+fn pactl_output(pid: u32) -> String {
+    format!(
+        r#"[
+  {{
+    "index": 71,
+    "driver": "PipeWire",
+    "mute": false,
+    "volume": {{
+      "front-left": {{"value": 32768, "value_percent": "50%", "db": "-18.06 dB"}},
+      "front-right": {{"value": 32768, "value_percent": "50%", "db": "-18.06 dB"}}
+    }},
+    "properties": {{
+      "application.name": "Firefox",
+      "application.process.id": "{pid}",
+      "application.process.binary": "firefox"
+    }}
+  }},
+  {{
+    "index": 80,
+    "mute": true,
+    "volume": {{"mono": {{"value": 65536, "value_percent": "100%", "db": "0.00 dB"}}}},
+    "properties": {{
+      "application.name": "Spotify",
+      "application.process.id": "1",
+      "pipewire.access.portal.app_id": "com.spotify.Client"
+    }}
+  }}
+]"#
+    )
+}
+
+#[test]
+fn parses_sink_inputs() -> anyhow::Result<()> {
+    let inputs = parse_sink_inputs(&pactl_output(1234))?;
+    assert_eq!(inputs.len(), 2);
+    assert_eq!(inputs[0].index, 71);
+    assert_eq!(inputs[0].pid, Some(1234));
+    assert_eq!(inputs[0].binary.as_deref(), Some("firefox"));
+    assert_eq!(inputs[0].volume, 0.5);
+    assert!(!inputs[0].muted);
+    assert_eq!(inputs[1].app_id.as_deref(), Some("com.spotify.Client"));
+    assert_eq!(inputs[1].volume, 1.0);
+    assert!(inputs[1].muted);
+
+    assert!(parse_sink_inputs("not json").is_err());
+    Ok(())
+}
+
+#[test]
+fn matches_streams_to_players() -> anyhow::Result<()> {
+    let inputs = parse_sink_inputs(&pactl_output(std::process::id()))?;
+    let index = |streams: Vec<&lib::pulse::SinkInput>| -> Vec<u32> {
+        streams.iter().map(|stream| stream.index).collect()
+    };
+
+    // the process itself, which beats the desktop entry
+    assert_eq!(
+        index(player_streams(
+            &inputs,
+            Some(std::process::id()),
+            Some("com.spotify.Client")
+        )),
+        [71]
+    );
+    // every process descends from init, so pid 1 owns everything
+    assert_eq!(index(player_streams(&inputs, Some(1), None)), [71, 80]);
+    assert_eq!(
+        index(player_streams(&inputs, None, Some("com.spotify.Client"))),
+        [80]
+    );
+    assert_eq!(
+        index(player_streams(&inputs, None, Some("org.mozilla.firefox"))),
+        [71]
+    );
+    assert!(player_streams(&inputs, None, Some("vlc")).is_empty());
+    assert!(player_streams(&inputs, None, None).is_empty());
+    Ok(())
+}
@@ -0,0 +1,107 @@
+//! quirk rules against mock players, run with `--features test-util`
+
+use std::time::Duration;
+
+use lib::{
+    player::{Capabilities, MetadataBuilder},
+    quirks::{LengthUnit, QuirkRegistry, QuirkRule},
+    test_util::{bus::TestBus, mock::MockPlayer},
+};
+
+#[test]
+fn later_rules_win() -> anyhow::Result<()> {
+    let rule: QuirkRule = serde_json::from_value(serde_json::json!({
+        "player": "vlc",
+        "length-unit": "milliseconds",
+        "can-seek": true,
+    }))?;
+    let registry = QuirkRegistry::default().with_rules([rule]);
+
+    let quirks = registry.lookup("org.mpris.MediaPlayer2.vlc", None);
+    assert_eq!(quirks.length_unit, Some(LengthUnit::Milliseconds));
+    assert_eq!(quirks.can_seek, Some(true));
+    assert!(registry
+        .lookup("org.mpris.MediaPlayer2.mpv", None)
+        .is_empty());
+    Ok(())
+}
+
+#[test]
+fn browsers_are_parsed_leniently() {
+    let registry = QuirkRegistry::default();
+    for name in [
+        "org.mpris.MediaPlayer2.firefox.instance_1_2",
+        "org.mpris.MediaPlayer2.chromium.instance1234",
+        "org.mpris.MediaPlayer2.brave.instance5678",
+    ] {
+        assert!(registry.lookup(name, None).is_relaxed(), "{name}");
+    }
+    assert!(!registry
+        .lookup("org.mpris.MediaPlayer2.vlc", None)
+        .is_relaxed());
+}
+
+#[test]
+fn matches_by_identity() {
+    let rule = QuirkRule {
+        player: None,
+        identity: Some("Some Player".to_string()),
+        quirks: Default::default(),
+    };
+    assert!(rule.matches("org.mpris.MediaPlayer2.other", Some("some player")));
+    assert!(!rule.matches("org.mpris.MediaPlayer2.other", None));
+}
+
+#[tokio::test]
+async fn corrects_players_while_parsing() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let metadata = MetadataBuilder::default()
+        .title("Artist - Song".to_string())
+        .length(240_000)
+        .finish();
+    let capabilities = Capabilities {
+        can_control: true,
+        can_seek: true,
+        rate: 1.0,
+        metadata,
+        ..Default::default()
+    };
+    let vlc = bus
+        .serve(
+            MockPlayer::builder()
+                .name("org.mpris.MediaPlayer2.vlc")
+                .capabilities(capabilities.clone()),
+        )
+        .await?;
+    let firefox = bus
+        .serve(
+            MockPlayer::builder()
+                .name("org.mpris.MediaPlayer2.firefox.instance_1_2")
+                .capabilities(capabilities),
+        )
+        .await?;
+
+    let mut client = bus.client().await?;
+    let rule: QuirkRule =
+        serde_json::from_value(serde_json::json!({"player": "firefox*", "can-seek": false}))?;
+    client.set_quirks(QuirkRegistry::default().with_rules([rule]));
+    client.get_all().await?;
+
+    let vlc = client.get(vlc.name()).unwrap();
+    let length = vlc.capabilities().metadata.length();
+    assert_eq!(length, Some(Duration::from_secs(240).as_micros() as u64));
+    assert_eq!(vlc.title(), Some("Artist - Song"));
+
+    let firefox = client.get(firefox.name()).unwrap();
+    assert_eq!(firefox.title(), Some("Song"));
+    assert_eq!(
+        firefox.capabilities().metadata.artists(),
+        Some(&["Artist".to_string()][..])
+    );
+    assert!(!firefox.capabilities().can_seek);
+    assert!(firefox
+        .seek_forward(client.connection().unwrap(), Duration::from_secs(5))
+        .await
+        .is_err());
+    Ok(())
+}
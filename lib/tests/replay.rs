@@ -0,0 +1,80 @@
+//! recording what a client receives from a mock player and replaying it, run with
+//! `--features test-util`
+
+use lib::{
+    player::{Capabilities, MetadataBuilder, MprisEvent, PlaybackStatus, PlayerUpdated},
+    record::{self, Recorded, Recorder},
+    test_util::{bus::TestBus, events_until, mock::MockPlayer},
+    MprisClient,
+};
+
+#[tokio::test]
+async fn replaying_a_recording_gives_the_same_state() -> anyhow::Result<()> {
+    let path = std::env::temp_dir().join(format!(
+        "mpris-controller-replay-{}.jsonl",
+        std::process::id()
+    ));
+    let bus = TestBus::start()?;
+    let mock = bus
+        .serve(MockPlayer::builder().capabilities(Capabilities {
+            can_control: true,
+            can_seek: true,
+            rate: 1.0,
+            ..Default::default()
+        }))
+        .await?;
+    let mut client = bus.client().await?;
+    client.record_to(Some(Recorder::create(&path)?));
+    client.add(mock.name().to_string()).await?;
+
+    mock.set_metadata(
+        MetadataBuilder::default()
+            .title("sailor".to_string())
+            .length(100_000)
+            .finish(),
+    )
+    .await?;
+    mock.set_playback_status(PlaybackStatus::Playing).await?;
+    mock.seek_to(42_000_000).await?;
+    events_until(&mut client, |e| matches!(e, MprisEvent::Seeked { .. })).await;
+    client.record_to(None);
+
+    let recorded = record::load(&path)?;
+    let _ = std::fs::remove_file(&path);
+    assert!(matches!(recorded[0], Recorded::Player { .. }));
+    assert!(recorded.windows(2).all(|w| w[0].at() <= w[1].at()));
+
+    let mut replayed = MprisClient::replaying();
+    let mut events = Vec::new();
+    for recorded in &recorded {
+        events.extend(replayed.replay(recorded)?);
+    }
+
+    assert!(matches!(&events[0], MprisEvent::PlayerAdded(name) if name == mock.name()));
+    assert!(events.iter().any(|e| matches!(
+        e,
+        MprisEvent::PlayerUpdated {
+            update: PlayerUpdated::PlaybackStatus(PlaybackStatus::Playing),
+            ..
+        }
+    )));
+    assert!(events.iter().any(|e| matches!(
+        e,
+        MprisEvent::Seeked {
+            position: 42_000_000,
+            ..
+        }
+    )));
+    let original = client.get(mock.name()).unwrap();
+    let player = replayed.get(mock.name()).unwrap();
+    assert_eq!(player.title(), Some("sailor"));
+    assert_eq!(
+        player.capabilities().metadata.length(),
+        original.capabilities().metadata.length()
+    );
+    assert_eq!(
+        player.capabilities().playback_status,
+        PlaybackStatus::Playing
+    );
+    Ok(())
+}
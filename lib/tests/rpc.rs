@@ -0,0 +1,132 @@
+//! the json-rpc socket against a served player, run with `--features test-util,rpc`
+
+use std::{
+    os::unix::fs::PermissionsExt,
+    sync::{Arc, Mutex},
+};
+
+use lib::{
+    player::{Capabilities, MetadataBuilder, PlaybackStatus},
+    rpc::{RpcServer, METHOD_NOT_FOUND, NO_PLAYER},
+    service::{MprisService, PlayerHandler},
+    test_util::{bus::TestBus, wait_for},
+};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::UnixStream,
+};
+
+#[derive(Clone, Default)]
+struct Recorder {
+    calls: Arc<Mutex<Vec<String>>>,
+}
+
+impl PlayerHandler for Recorder {
+    fn play(&mut self, state: &mut Capabilities) -> anyhow::Result<()> {
+        self.calls.lock().unwrap().push("play".to_string());
+        state.playback_status = PlaybackStatus::Playing;
+        Ok(())
+    }
+
+    fn seek(&mut self, state: &mut Capabilities, offset: i64) -> anyhow::Result<()> {
+        self.calls.lock().unwrap().push(format!("seek {offset}"));
+        state.position = state.position.saturating_add_signed(offset);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn answers_requests() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let recorder = Recorder::default();
+    let service = MprisService::builder("rpc_test", recorder.clone())
+        .capabilities(Capabilities {
+            can_control: true,
+            can_play: true,
+            can_seek: true,
+            rate: 1.0,
+            metadata: MetadataBuilder::default()
+                .trackid("/org/mpris/MediaPlayer2/track/1".to_string())
+                .title("song".to_string())
+                .finish(),
+            ..Default::default()
+        })
+        .serve_on(bus.builder()?)
+        .await?;
+    let mut client = bus.client().await?;
+    client.add(service.name().to_string()).await?;
+
+    let path = std::env::temp_dir().join(format!("rpc-test-{}.sock", std::process::id()));
+    let mut server = RpcServer::bind(client, &path).await?;
+    assert_eq!(
+        std::fs::metadata(&path)?.permissions().mode() & 0o777,
+        0o600
+    );
+    assert!(RpcServer::bind(bus.client().await?, &path).await.is_err());
+
+    let script = tokio::spawn({
+        let path = path.clone();
+        async move {
+            let stream = UnixStream::connect(&path).await?;
+            let (read, mut write) = stream.into_split();
+            let mut lines = BufReader::new(read).lines();
+            let mut replies = Vec::new();
+            for request in [
+                r#"{"jsonrpc":"2.0","id":1,"method":"player","params":{"player":"rpc_test"}}"#,
+                // a notification, no reply
+                r#"{"jsonrpc":"2.0","method":"play","params":{"player":"rpc_test"}}"#,
+                r#"{"jsonrpc":"2.0","id":2,"method":"seek","params":{"player":"rpc_test","offset":5000000}}"#,
+                r#"{"jsonrpc":"2.0","id":3,"method":"play","params":{"player":"nobody"}}"#,
+                r#"{"jsonrpc":"2.0","id":4,"method":"dance"}"#,
+                "not json",
+            ] {
+                write.write_all(format!("{request}\n").as_bytes()).await?;
+            }
+            for _ in 0..5 {
+                let line = lines.next_line().await?.expect("a reply");
+                replies.push(serde_json::from_str::<Value>(&line)?);
+            }
+            anyhow::Ok(replies)
+        }
+    });
+
+    wait_for(async || {
+        server.step().await;
+        script.is_finished().then_some(())
+    })
+    .await
+    .expect("gave up waiting for the replies");
+    let replies = script.await??;
+
+    assert_eq!(replies[0]["id"], 1);
+    assert_eq!(replies[0]["result"]["player"], service.name());
+    assert_eq!(
+        replies[0]["result"]["capabilities"]["metadata"]["title"],
+        "song"
+    );
+    assert_eq!(
+        replies[1],
+        json!({"jsonrpc": "2.0", "id": 2, "result": null})
+    );
+    assert_eq!(replies[2]["error"]["code"], NO_PLAYER);
+    assert_eq!(replies[3]["error"]["code"], METHOD_NOT_FOUND);
+    assert_eq!(replies[4]["error"]["code"], -32700);
+    assert_eq!(*recorder.calls.lock().unwrap(), ["play", "seek 5000000"]);
+
+    drop(server);
+    assert!(!path.exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn leaves_other_files_alone() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let path = std::env::temp_dir().join(format!("rpc-test-{}.txt", std::process::id()));
+    std::fs::write(&path, "keep me")?;
+
+    assert!(RpcServer::bind(bus.client().await?, &path).await.is_err());
+    assert_eq!(std::fs::read_to_string(&path)?, "keep me");
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
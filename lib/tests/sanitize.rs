@@ -0,0 +1,47 @@
+//! cleaning up titles and artists taken from web pages
+
+use lib::sanitize::{clean_artist, clean_title, collapse_whitespace};
+
+#[test]
+fn strips_noise_from_titles() {
+    for (title, clean) in [
+        ("Song (Official Video)", "Song"),
+        ("Song [Official Music Video] (HD)", "Song"),
+        ("Song (Lyric Video) - YouTube", "Song"),
+        ("Song (Official Audio) | YouTube", "Song"),
+        ("Song (4K, Official Visualizer)", "Song"),
+        ("Song [M/V]", "Song"),
+        ("Song - YouTube Music", "Song"),
+    ] {
+        assert_eq!(clean_title(title), clean, "{title:?}");
+    }
+}
+
+#[test]
+fn keeps_brackets_that_mean_something() {
+    for title in [
+        "Song (Live)",
+        "Song (feat. Someone)",
+        "Song [Remix]",
+        "Song (Live Video)",
+        // unclosed, left as is
+        "Song (Official",
+    ] {
+        assert_eq!(clean_title(title), title, "{title:?}");
+    }
+    assert_eq!(clean_title("Song (Remix) (Official Video)"), "Song (Remix)");
+}
+
+#[test]
+fn cleans_artists() {
+    assert_eq!(clean_artist("Band - Topic"), "Band");
+    assert_eq!(clean_artist("BandVEVO"), "Band");
+    assert_eq!(clean_artist("VEVO"), "VEVO");
+    assert_eq!(clean_artist("  Some   Band "), "Some Band");
+}
+
+#[test]
+fn collapses_whitespace() {
+    assert_eq!(collapse_whitespace("  a \t b\n\nc  "), "a b c");
+    assert_eq!(collapse_whitespace("   "), "");
+}
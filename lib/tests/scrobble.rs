@@ -0,0 +1,127 @@
+//! when a listen counts and the offline queue, run with `--features test-util,scrobble`
+
+use std::time::{Duration, Instant};
+
+use lib::{
+    player::Metadata,
+    scrobble::{Listen, ListenTracker, ScrobbleQueue},
+    test_util::{self, track},
+};
+
+const PLAYER: &str = "org.mpris.MediaPlayer2.test";
+
+/// plays `metadata` from `from` to `to` seconds, one observation a second
+fn play(
+    tracker: &mut ListenTracker,
+    metadata: &Metadata,
+    start: Instant,
+    from: u64,
+    to: u64,
+) -> Vec<Listen> {
+    test_util::play(start, 1_000_000, from, to)
+        .filter_map(|(position, now, unix)| {
+            tracker.observe(PLAYER, metadata, true, position, now, unix)
+        })
+        .collect()
+}
+
+#[test]
+fn counts_after_half_the_track() {
+    let mut tracker = ListenTracker::new();
+    let metadata = track("song", 100);
+    let start = Instant::now();
+
+    assert!(play(&mut tracker, &metadata, start, 0, 49).is_empty());
+    let listens = play(&mut tracker, &metadata, start, 50, 100);
+    assert_eq!(listens.len(), 1);
+    assert_eq!(listens[0].title, "song");
+    assert_eq!(listens[0].artist, "band");
+    assert_eq!(listens[0].length, Some(100));
+    assert_eq!(listens[0].listened_at, 1_000_000);
+    assert_eq!(listens[0].player, "test");
+}
+
+#[test]
+fn counts_after_four_minutes_of_long_tracks() {
+    let mut tracker = ListenTracker::new();
+    let metadata = track("long", 3600);
+    let start = Instant::now();
+
+    assert!(play(&mut tracker, &metadata, start, 0, 239).is_empty());
+    assert_eq!(play(&mut tracker, &metadata, start, 240, 241).len(), 1);
+}
+
+#[test]
+fn seeking_is_not_listening() {
+    let mut tracker = ListenTracker::new();
+    let metadata = track("song", 100);
+    let start = Instant::now();
+
+    assert!(play(&mut tracker, &metadata, start, 0, 10).is_empty());
+    // jumps to 90s one second later
+    assert!(tracker
+        .observe(
+            PLAYER,
+            &metadata,
+            true,
+            90_000_000,
+            start + Duration::from_secs(11),
+            1_000_011,
+        )
+        .is_none());
+    for second in 12..=21 {
+        let position = (second + 79) * 1_000_000;
+        let now = start + Duration::from_secs(second);
+        assert!(tracker
+            .observe(PLAYER, &metadata, true, position, now, 1_000_000 + second)
+            .is_none());
+    }
+}
+
+#[test]
+fn short_and_paused_tracks_never_count() {
+    let mut tracker = ListenTracker::new();
+    let start = Instant::now();
+    assert!(play(&mut tracker, &track("jingle", 20), start, 0, 20).is_empty());
+
+    let metadata = track("song", 100);
+    for second in 0..100 {
+        assert!(tracker
+            .observe(
+                PLAYER,
+                &metadata,
+                false,
+                0,
+                start + Duration::from_secs(second),
+                1_000_000 + second,
+            )
+            .is_none());
+    }
+}
+
+#[test]
+fn queue_survives_a_restart() -> anyhow::Result<()> {
+    let dir = std::env::temp_dir().join(format!("scrobble-test-{}", std::process::id()));
+    let path = dir.join("scrobbles-test.json");
+    let listen = |title: &str| Listen {
+        artist: "band".to_string(),
+        title: title.to_string(),
+        album: None,
+        album_artist: None,
+        length: Some(100),
+        listened_at: 1_000_000,
+        player: "test".to_string(),
+    };
+
+    let mut queue = ScrobbleQueue::load(path.clone())?;
+    assert!(queue.listens().is_empty());
+    queue.push(listen("one"))?;
+    queue.push(listen("two"))?;
+    queue.push(listen("three"))?;
+    queue.remove(1)?;
+
+    let queue = ScrobbleQueue::load(path)?;
+    assert_eq!(queue.listens(), [listen("two"), listen("three")]);
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
@@ -0,0 +1,94 @@
+//! picking players by pattern, status and preference, run with `--features test-util`
+
+use lib::{
+    player::{Capabilities, PlaybackStatus, Player},
+    selector::Selector,
+    test_util::TestHarness,
+};
+
+const SPOTIFY: &str = "org.mpris.MediaPlayer2.spotify";
+const CHROMIUM: &str = "org.mpris.MediaPlayer2.chromium.instance1234";
+const VLC: &str = "org.mpris.MediaPlayer2.vlc";
+const PHONE: &str = "org.mpris.MediaPlayer2.kdeconnect.mpris_000001";
+
+fn status(status: PlaybackStatus) -> Capabilities {
+    Capabilities {
+        playback_status: status,
+        ..Default::default()
+    }
+}
+
+fn harness() -> TestHarness {
+    let mut harness = TestHarness::new();
+    harness.add_player(SPOTIFY, status(PlaybackStatus::Paused));
+    harness.add_player(CHROMIUM, status(PlaybackStatus::Playing));
+    harness.add_player(VLC, status(PlaybackStatus::Playing));
+    harness.add_player(PHONE, status(PlaybackStatus::Stopped));
+    harness
+}
+
+fn names(players: Vec<&Player>) -> Vec<&str> {
+    players.into_iter().map(|player| player.name()).collect()
+}
+
+#[test]
+fn filters_players() {
+    let harness = harness();
+    let client = harness.client();
+
+    assert_eq!(names(Selector::new().select_all(client)).len(), 4);
+    assert_eq!(
+        names(Selector::playing().select_all(client)),
+        [CHROMIUM, VLC]
+    );
+    // `chromium` covers its instances
+    assert_eq!(
+        names(Selector::new().include("chromium").select_all(client)),
+        [CHROMIUM]
+    );
+    assert_eq!(
+        names(Selector::playing().exclude("chromium").select_all(client)),
+        [VLC]
+    );
+    assert_eq!(
+        names(Selector::new().remote(true).select_all(client)),
+        [PHONE]
+    );
+    assert_eq!(
+        names(Selector::new().remote(false).select_all(client)),
+        [SPOTIFY, CHROMIUM, VLC]
+    );
+}
+
+#[test]
+fn preferred_players_come_first() {
+    let harness = harness();
+    let client = harness.client();
+
+    let selector = Selector::new().prefer("vlc").prefer("spotify");
+    assert_eq!(
+        names(selector.select_all(client)),
+        [VLC, SPOTIFY, CHROMIUM, PHONE]
+    );
+    assert_eq!(selector.select(client).map(|p| p.name()), Some(VLC));
+    // a preference nothing matches changes nothing
+    let selector = Selector::playing().prefer("mpv");
+    assert_eq!(selector.select(client).map(|p| p.name()), Some(CHROMIUM));
+    assert!(Selector::new().include("mpv").select(client).is_none());
+}
+
+#[test]
+fn the_active_player_wins_a_tie() {
+    let mut harness = harness();
+    let vlc = harness.client().get(VLC).unwrap().id();
+    assert!(harness.client_mut().set_active_player(vlc));
+
+    let client = harness.client();
+    assert_eq!(
+        Selector::playing().select(client).map(|p| p.name()),
+        Some(VLC)
+    );
+    // but not over a player that is preferred more
+    let selector = Selector::playing().prefer("chromium");
+    assert_eq!(selector.select(client).map(|p| p.name()), Some(CHROMIUM));
+}
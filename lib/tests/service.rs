@@ -0,0 +1,463 @@
+//! a player served with `lib::service` as seen by the client, run with `--features test-util`
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use lib::{
+    player::{
+        Capabilities, Metadata, MetadataBuilder, MprisEvent, PlaybackStatus, PlayerUpdated, TrackId,
+    },
+    playlists::{Playlist, PlaylistOrdering},
+    service::{
+        MprisService, PlayerHandler, PlaylistsHandler, PlaylistsState, TrackListHandler,
+        TrackListState,
+    },
+    test_util::{bus::TestBus, events_until},
+    MprisClient, DBUS_PROPERTIES, MPRIS_PATH, MPRIS_PLAYER_PREFIX, MPRIS_PLAYLISTS,
+};
+use zbus::zvariant::{OwnedObjectPath, Value};
+
+#[derive(Clone, Default)]
+struct Radio {
+    volumes: Arc<Mutex<Vec<f64>>>,
+}
+
+impl PlayerHandler for Radio {
+    fn play(&mut self, state: &mut Capabilities) -> anyhow::Result<()> {
+        state.playback_status = PlaybackStatus::Playing;
+        Ok(())
+    }
+
+    fn pause(&mut self, state: &mut Capabilities) -> anyhow::Result<()> {
+        state.playback_status = PlaybackStatus::Paused;
+        Ok(())
+    }
+
+    fn seek(&mut self, state: &mut Capabilities, offset: i64) -> anyhow::Result<()> {
+        state.position = state.position.saturating_add_signed(offset);
+        Ok(())
+    }
+
+    fn set_volume(&mut self, state: &mut Capabilities, volume: f64) -> anyhow::Result<()> {
+        self.volumes.lock().unwrap().push(volume);
+        state.volume = Some(volume.clamp(0.0, 1.0));
+        Ok(())
+    }
+}
+
+fn capabilities() -> Capabilities {
+    Capabilities {
+        can_control: true,
+        can_play: true,
+        can_pause: true,
+        can_seek: true,
+        rate: 1.0,
+        volume: Some(0.5),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn clients_control_the_service() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let radio = Radio::default();
+    let service = MprisService::builder("radio", radio.clone())
+        .capabilities(capabilities())
+        .serve_on(bus.builder()?)
+        .await?;
+    let mut client = bus.client().await?;
+    client.add(service.name().to_string()).await?;
+    let conn = client.connection().unwrap().clone();
+
+    client.get(service.name()).unwrap().play(&conn).await?;
+    events_until(&mut client, |e| {
+        matches!(
+            e,
+            MprisEvent::PlayerUpdated {
+                update: PlayerUpdated::PlaybackStatus(PlaybackStatus::Playing),
+                ..
+            }
+        )
+    })
+    .await;
+    assert_eq!(
+        service.capabilities().await?.playback_status,
+        PlaybackStatus::Playing
+    );
+
+    let player = client.get(service.name()).unwrap();
+    player.seek_forward(&conn, Duration::from_secs(10)).await?;
+    events_until(&mut client, |e| {
+        matches!(
+            e,
+            MprisEvent::Seeked {
+                position: 10_000_000,
+                ..
+            }
+        )
+    })
+    .await;
+
+    client
+        .get_mut(service.name())
+        .unwrap()
+        .set_volume(&conn, 2.0)
+        .await?;
+    assert_eq!(*radio.volumes.lock().unwrap(), [2.0]);
+    assert_eq!(service.capabilities().await?.volume, Some(1.0));
+    Ok(())
+}
+
+#[tokio::test]
+async fn unsupported_calls_fail() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let service = MprisService::builder("radio", Radio::default())
+        .serve_on(bus.builder()?)
+        .await?;
+    let conn = bus.connect().await?;
+
+    let err = conn
+        .call_method(
+            Some(service.name()),
+            MPRIS_PATH,
+            Some("org.mpris.MediaPlayer2.Player"),
+            "Next",
+            &(),
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, zbus::Error::MethodError(name, _, _) if name.as_str() == "org.freedesktop.DBus.Error.NotSupported"),
+        "{err:?}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn setters_announce_changes() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let service = MprisService::builder("radio", Radio::default())
+        .capabilities(capabilities())
+        .serve_on(bus.builder()?)
+        .await?;
+    let mut client = bus.client().await?;
+    client.add(service.name().to_string()).await?;
+
+    service
+        .set_metadata(
+            MetadataBuilder::default()
+                .title("news".to_string())
+                .finish(),
+        )
+        .await?;
+    events_until(&mut client, |e| {
+        matches!(e, MprisEvent::TrackChanged { .. })
+    })
+    .await;
+    assert_eq!(client.get(service.name()).unwrap().title(), Some("news"));
+
+    service.seeked(42_000_000).await?;
+    events_until(&mut client, |e| {
+        matches!(
+            e,
+            MprisEvent::Seeked {
+                position: 42_000_000,
+                ..
+            }
+        )
+    })
+    .await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn every_property_of_a_signal_is_applied() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let service = MprisService::builder("radio", Radio::default())
+        .capabilities(capabilities())
+        .serve_on(bus.builder()?)
+        .await?;
+    let mut client = bus.client().await?;
+    client.add(service.name().to_string()).await?;
+
+    // the service announces one property per signal, players like vlc bundle them
+    let metadata = HashMap::from([("xesam:title", Value::from("news"))]);
+    let changed = HashMap::from([
+        ("PlaybackStatus", Value::from("Playing")),
+        ("Metadata", Value::from(metadata)),
+        ("CanGoNext", Value::from(true)),
+        ("Volume", Value::from(0.25)),
+    ]);
+    service
+        .connection()
+        .emit_signal(
+            None::<()>,
+            MPRIS_PATH,
+            DBUS_PROPERTIES,
+            "PropertiesChanged",
+            &(MPRIS_PLAYER_PREFIX, changed, Vec::<String>::new()),
+        )
+        .await?;
+
+    let events = events_until(&mut client, |e| {
+        matches!(
+            e,
+            MprisEvent::PlayerUpdated {
+                update: PlayerUpdated::Volume(_),
+                ..
+            }
+        )
+    })
+    .await;
+    let properties: Vec<&str> = events
+        .iter()
+        .filter_map(|e| match e {
+            MprisEvent::PlayerUpdated { update, .. } => Some(update.property()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        properties,
+        ["PlaybackStatus", "Metadata", "Volume", "CanGoNext"]
+    );
+    assert!(events
+        .iter()
+        .any(|e| matches!(e, MprisEvent::TrackChanged { .. })));
+
+    let player = client.get(service.name()).unwrap();
+    let caps = player.capabilities();
+    assert_eq!(caps.playback_status, PlaybackStatus::Playing);
+    assert_eq!(player.title(), Some("news"));
+    assert!(caps.can_next);
+    assert_eq!(caps.volume, Some(0.25));
+    Ok(())
+}
+
+#[tokio::test]
+async fn signals_of_no_interest_dont_hold_up_the_rest() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let service = MprisService::builder("radio", Radio::default())
+        .capabilities(capabilities())
+        .serve_on(bus.builder()?)
+        .await?;
+    let mut client = bus.client().await?;
+    client.add(service.name().to_string()).await?;
+
+    // MinimumRate isn't something the client follows
+    let changed = HashMap::from([("MinimumRate", Value::from(0.5))]);
+    service
+        .connection()
+        .emit_signal(
+            None::<()>,
+            MPRIS_PATH,
+            DBUS_PROPERTIES,
+            "PropertiesChanged",
+            &(MPRIS_PLAYER_PREFIX, changed, Vec::<String>::new()),
+        )
+        .await?;
+    service.set_playback_status(PlaybackStatus::Playing).await?;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let events = client.event().await;
+    assert!(
+        events.iter().any(|e| matches!(
+            e,
+            MprisEvent::PlayerUpdated {
+                update: PlayerUpdated::PlaybackStatus(PlaybackStatus::Playing),
+                ..
+            }
+        )),
+        "{events:#?}"
+    );
+    Ok(())
+}
+
+fn track(id: u32, title: &str) -> Metadata {
+    MetadataBuilder::default()
+        .trackid(format!("/tracks/{id}"))
+        .title(title.to_string())
+        .finish()
+}
+
+struct Queue;
+
+impl TrackListHandler for Queue {
+    fn add_track(
+        &mut self,
+        state: &mut TrackListState,
+        uri: &str,
+        after: Option<&TrackId>,
+        set_current: bool,
+    ) -> anyhow::Result<()> {
+        let index = after
+            .and_then(|after| {
+                state
+                    .tracks
+                    .iter()
+                    .position(|m| m.track_id() == Some(after))
+            })
+            .map_or(0, |i| i + 1);
+        let metadata = track(state.tracks.len() as u32 + 1, uri);
+        if set_current {
+            state.current = metadata.track_id().cloned();
+        }
+        state.tracks.insert(index, metadata);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn tracklist_changes_are_signalled() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let service = MprisService::builder("radio", Radio::default())
+        .capabilities(capabilities())
+        .tracklist(
+            TrackListState {
+                tracks: vec![track(1, "one"), track(2, "two")],
+                current: Some(TrackId::new("/tracks/1")),
+                can_edit_tracks: true,
+            },
+            Queue,
+        )
+        .serve_on(bus.builder()?)
+        .await?;
+    let mut client = bus.client().await?;
+    client.add(service.name().to_string()).await?;
+    let conn = client.connection().unwrap().clone();
+    let player = client.get(service.name()).unwrap();
+    assert_eq!(player.tracklist().len(), 2);
+    assert_eq!(player.title(), Some("one"));
+
+    player
+        .add_track(&conn, "three", Some(&TrackId::new("/tracks/2")), false)
+        .await?;
+    events_until(
+        &mut client,
+        |e| matches!(e, MprisEvent::TrackAdded { after, .. } if after.as_str() == "/tracks/2"),
+    )
+    .await;
+    let titles = |client: &MprisClient| {
+        client
+            .get(service.name())
+            .unwrap()
+            .tracklist()
+            .iter()
+            .map(|m| m.title().unwrap_or_default().to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(titles(&client), ["one", "two", "three"]);
+
+    client
+        .get(service.name())
+        .unwrap()
+        .go_to(&conn, &TrackId::new("/tracks/2"))
+        .await?;
+    events_until(&mut client, |e| {
+        matches!(e, MprisEvent::TrackChanged { .. })
+    })
+    .await;
+    assert_eq!(client.get(service.name()).unwrap().title(), Some("two"));
+
+    service
+        .update_tracklist(|state| {
+            state.tracks.remove(0);
+            state.tracks[0] = track(2, "two, renamed");
+        })
+        .await?;
+    let events = events_until(&mut client, |e| {
+        matches!(e, MprisEvent::TrackMetadataChanged { .. })
+    })
+    .await;
+    assert!(events.iter().any(
+        |e| matches!(e, MprisEvent::TrackRemoved { track, .. } if track.as_str() == "/tracks/1")
+    ));
+    assert_eq!(titles(&client), ["two, renamed", "three"]);
+
+    service
+        .update_tracklist(|state| state.tracks.reverse())
+        .await?;
+    events_until(&mut client, |e| {
+        matches!(e, MprisEvent::TrackListReplaced { tracks, .. } if tracks[0].as_str() == "/tracks/3")
+    })
+    .await;
+    Ok(())
+}
+
+struct Library;
+
+impl PlaylistsHandler for Library {
+    fn activate_playlist(&mut self, state: &mut PlaylistsState, id: &str) -> anyhow::Result<()> {
+        state.active = state.playlists.iter().find(|p| p.id == id).cloned();
+        Ok(())
+    }
+}
+
+fn playlist(id: u32, name: &str) -> Playlist {
+    Playlist {
+        id: format!("/playlists/{id}"),
+        name: name.to_string(),
+        icon: String::new(),
+    }
+}
+
+#[tokio::test]
+async fn playlists_are_served() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let service = MprisService::builder("radio", Radio::default())
+        .playlists(
+            PlaylistsState {
+                playlists: vec![playlist(1, "jazz"), playlist(2, "blues")],
+                orderings: vec![
+                    PlaylistOrdering::Alphabetical,
+                    PlaylistOrdering::UserDefined,
+                ],
+                active: None,
+            },
+            Library,
+        )
+        .serve_on(bus.builder()?)
+        .await?;
+    let mut client = bus.client().await?;
+    client.add(service.name().to_string()).await?;
+    let conn = client.connection().unwrap().clone();
+
+    let reply = conn
+        .call_method(
+            Some(service.name()),
+            MPRIS_PATH,
+            Some(MPRIS_PLAYLISTS),
+            "GetPlaylists",
+            &(0u32, 10u32, "Alphabetical", false),
+        )
+        .await?;
+    let playlists: Vec<(OwnedObjectPath, String, String)> = reply.body().deserialize()?;
+    let names: Vec<_> = playlists.iter().map(|(_, name, _)| name.as_str()).collect();
+    assert_eq!(names, ["blues", "jazz"]);
+
+    let path = OwnedObjectPath::try_from("/playlists/2")?;
+    conn.call_method(
+        Some(service.name()),
+        MPRIS_PATH,
+        Some(MPRIS_PLAYLISTS),
+        "ActivatePlaylist",
+        &(path,),
+    )
+    .await?;
+    assert_eq!(
+        service.playlists().await?.active,
+        Some(playlist(2, "blues"))
+    );
+
+    service
+        .update_playlists(|state| state.playlists[0].name = "bebop".to_string())
+        .await?;
+    events_until(
+        &mut client,
+        |e| matches!(e, MprisEvent::PlaylistUpdated { playlist, .. } if playlist.name == "bebop"),
+    )
+    .await;
+    Ok(())
+}
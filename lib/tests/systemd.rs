@@ -0,0 +1,47 @@
+//! the sd_notify protocol, one test since it goes through the environment
+
+use std::{os::unix::net::UnixDatagram, path::Path, time::Duration};
+
+use lib::systemd;
+
+#[test]
+fn notifies_the_service_manager() -> anyhow::Result<()> {
+    std::env::remove_var("NOTIFY_SOCKET");
+    assert!(!systemd::ready()?);
+
+    let path = std::env::temp_dir().join(format!("systemd-test-{}.sock", std::process::id()));
+    _ = std::fs::remove_file(&path);
+    let socket = UnixDatagram::bind(&path)?;
+    socket.set_read_timeout(Some(Duration::from_secs(5)))?;
+    std::env::set_var("NOTIFY_SOCKET", &path);
+
+    let mut buf = [0; 64];
+    for (sent, expected) in [
+        (systemd::ready()?, "READY=1"),
+        (systemd::status("two\nlines")?, "STATUS=two lines"),
+        (systemd::watchdog()?, "WATCHDOG=1"),
+        (systemd::stopping()?, "STOPPING=1"),
+    ] {
+        assert!(sent);
+        let n = socket.recv(&mut buf)?;
+        assert_eq!(std::str::from_utf8(&buf[..n])?, expected);
+    }
+
+    std::env::set_var("WATCHDOG_USEC", "30000000");
+    std::env::set_var("WATCHDOG_PID", std::process::id().to_string());
+    assert_eq!(systemd::watchdog_interval(), Some(Duration::from_secs(15)));
+    // meant for the process that started this one
+    std::env::set_var("WATCHDOG_PID", "1");
+    assert_eq!(systemd::watchdog_interval(), None);
+
+    std::env::remove_var("NOTIFY_SOCKET");
+    std::fs::remove_file(path)?;
+    Ok(())
+}
+
+#[test]
+fn quotes_paths_in_the_unit() {
+    let unit = systemd::user_unit("test", Path::new("/opt/my apps/server"));
+    assert!(unit.contains("ExecStart=\"/opt/my apps/server\"\n"));
+    assert!(unit.contains("Type=notify\n"));
+}
@@ -0,0 +1,52 @@
+//! parsing and rendering format templates
+
+use lib::template::{Segment, Template};
+
+fn render(template: &str, values: &[(&str, &str)]) -> String {
+    Template::parse(template).unwrap().render(|key| {
+        values
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v.to_string())
+    })
+}
+
+#[test]
+fn doubled_braces_are_literal() {
+    assert_eq!(render("{{title}}", &[("title", "sailor")]), "{title}");
+    assert_eq!(render("{{{title}}}", &[("title", "sailor")]), "{sailor}");
+    assert_eq!(
+        Template::parse("a {{b}} c").unwrap().segments(),
+        [Segment::Text("a {b} c".to_string())]
+    );
+}
+
+#[test]
+fn unknown_keys_render_as_nothing() {
+    assert_eq!(render("[{nope}]", &[]), "[]");
+    assert_eq!(render("{title} {nope}", &[("title", "sailor")]), "sailor ");
+    // an unknown helper hides the value instead of guessing
+    assert_eq!(render("{shout(title)}", &[("title", "sailor")]), "");
+}
+
+#[test]
+fn fallbacks_fill_in_missing_and_empty_values() {
+    assert_eq!(render("{album|no album}", &[]), "no album");
+    assert_eq!(render("{album|no album}", &[("album", "")]), "no album");
+    assert_eq!(render("{album|no album}", &[("album", "blue")]), "blue");
+    assert_eq!(render("{album|}", &[]), "");
+    assert_eq!(
+        Template::parse("{ album |a|b}").unwrap().segments(),
+        [Segment::Field {
+            key: "album".to_string(),
+            fallback: Some("a|b".to_string()),
+        }]
+    );
+}
+
+#[test]
+fn unbalanced_braces_are_errors() {
+    for template in ["{title", "title}", "{title}}", "{{title}", "}"] {
+        assert!(Template::parse(template).is_err(), "{template:?}");
+    }
+}
@@ -0,0 +1,151 @@
+//! the websocket server against a served player, run with `--features test-util,ws`
+
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use lib::{
+    player::{Capabilities, MetadataBuilder, PlaybackStatus},
+    service::{MprisService, PlayerHandler},
+    test_util::{bus::TestBus, wait_for},
+    ws::{origin_allowed, WsServer},
+};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Error, Message};
+
+struct Player;
+
+impl PlayerHandler for Player {
+    fn play(&mut self, state: &mut Capabilities) -> anyhow::Result<()> {
+        state.playback_status = PlaybackStatus::Playing;
+        Ok(())
+    }
+}
+
+type Socket = tokio_tungstenite::WebSocketStream<TcpStream>;
+
+/// steps the server until the next message, which has to be json
+async fn next(server: &mut WsServer, socket: &mut Socket) -> Value {
+    wait_for(async || {
+        server.step().await;
+        match tokio::time::timeout(Duration::from_millis(10), socket.next()).await {
+            Ok(Some(Ok(Message::Text(text)))) => Some(serde_json::from_str(&text).unwrap()),
+            Ok(other) => panic!("expected text, got {other:?}"),
+            Err(_) => None,
+        }
+    })
+    .await
+    .expect("gave up waiting for a message")
+}
+
+#[tokio::test]
+async fn streams_events_and_takes_commands() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let service = MprisService::builder("ws_test", Player)
+        .capabilities(Capabilities {
+            can_control: true,
+            can_play: true,
+            rate: 1.0,
+            ..Default::default()
+        })
+        .serve_on(bus.builder()?)
+        .await?;
+    let mut client = bus.client().await?;
+    client.add(service.name().to_string()).await?;
+
+    let mut server = WsServer::bind(client, "127.0.0.1:0".parse()?).await?;
+    let address = server.address();
+    let (mut socket, _) = tokio_tungstenite::client_async(
+        format!("ws://{address}/"),
+        TcpStream::connect(address).await?,
+    )
+    .await?;
+
+    let snapshot = next(&mut server, &mut socket).await;
+    assert_eq!(snapshot["event"], "snapshot");
+    assert_eq!(snapshot["players"][0]["player"], service.name());
+
+    service
+        .update(|state| {
+            state.metadata = MetadataBuilder::default()
+                .trackid("/org/mpris/MediaPlayer2/track/1".to_string())
+                .title("song".to_string())
+                .finish();
+        })
+        .await?;
+    let mut event = next(&mut server, &mut socket).await;
+    while event["event"] != "track_changed" {
+        event = next(&mut server, &mut socket).await;
+    }
+    assert_eq!(event["metadata"]["title"], "song");
+
+    // read-only until control is allowed
+    let play = r#"{"jsonrpc":"2.0","id":1,"method":"play","params":{"player":"ws_test"}}"#;
+    socket.send(Message::text(play)).await?;
+    let mut reply = next(&mut server, &mut socket).await;
+    while reply.get("id").is_none() {
+        reply = next(&mut server, &mut socket).await;
+    }
+    assert_eq!(reply["error"]["code"], lib::rpc::METHOD_NOT_FOUND);
+
+    let mut server = server.allow_control(true);
+    socket.send(Message::text(play)).await?;
+    let mut reply = next(&mut server, &mut socket).await;
+    while reply.get("id").is_none() {
+        reply = next(&mut server, &mut socket).await;
+    }
+    assert_eq!(reply["result"], Value::Null);
+    assert_eq!(
+        service.capabilities().await?.playback_status,
+        PlaybackStatus::Playing
+    );
+    Ok(())
+}
+
+#[test]
+fn lets_in_local_pages() {
+    let allowed = ["https://example.com".to_string()];
+    for origin in [
+        None,
+        Some("http://localhost:8080"),
+        Some("http://127.0.0.1"),
+        Some("https://[::1]:3000"),
+        Some("https://example.com"),
+    ] {
+        assert!(origin_allowed(origin, &allowed), "{origin:?}");
+    }
+    for origin in [
+        "https://evil.example",
+        "http://localhost.evil.example",
+        "https://example.com:8443",
+        "file://",
+        "null",
+    ] {
+        assert!(!origin_allowed(Some(origin), &allowed), "{origin}");
+    }
+    // local files only with an explicit `null`
+    assert!(origin_allowed(Some("null"), &["null".to_string()]));
+}
+
+#[tokio::test]
+async fn refuses_other_origins() -> anyhow::Result<()> {
+    let bus = TestBus::start()?;
+    let server = WsServer::bind(bus.client().await?, "127.0.0.1:0".parse()?)
+        .await?
+        .allow_origins(["https://overlay.example".to_string()]);
+    let address = server.address();
+
+    let connect = |origin: &'static str| async move {
+        let mut request = format!("ws://{address}/").into_client_request()?;
+        request.headers_mut().insert("Origin", origin.parse()?);
+        let stream = TcpStream::connect(address).await?;
+        anyhow::Ok(tokio_tungstenite::client_async(request, stream).await)
+    };
+    match connect("https://evil.example").await? {
+        Err(Error::Http(response)) => assert_eq!(response.status(), 403),
+        other => panic!("expected a refusal, got {other:?}"),
+    }
+    assert!(connect("https://overlay.example").await?.is_ok());
+    assert!(connect("http://localhost:8000").await?.is_ok());
+    Ok(())
+}
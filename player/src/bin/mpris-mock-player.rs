@@ -0,0 +1,390 @@
+//! a fake player for developing against, driven by a script or by commands on stdin
+//!
+//! every line is a command, `#` starts a comment:
+//!
+//! ```text
+//! track title="Blue in Green" artist="Miles Davis" album="Kind of Blue" length=337
+//! status playing
+//! sleep 5
+//! seek 120
+//! volume 0.3
+//! can next off
+//! loop playlist
+//! shuffle on
+//! identity "Fake Player"
+//! clear
+//! exit
+//! ```
+//!
+//! the player keeps running once the input ends, until `exit` or ctrl-c. calls from clients are
+//! printed as they come in.
+
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::{Context, anyhow, bail};
+use clap::Parser;
+use lib::{
+    player::{Capabilities, LoopStatus, Metadata, MetadataBuilder, PlaybackStatus, TrackId},
+    service::{MprisService, PlayerHandler},
+};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
+
+#[derive(Debug, Parser)]
+#[command(name = "mpris-mock-player")]
+struct Cli {
+    /// what comes after `org.mpris.MediaPlayer2.`
+    #[arg(long, default_value = "mock")]
+    name: String,
+    /// the script to run, commands are read from stdin without one
+    script: Option<PathBuf>,
+}
+
+/// behaves like a simple player so widgets can be clicked through
+struct Mock;
+
+impl Mock {
+    fn log(call: &str) {
+        println!("< {call}");
+    }
+}
+
+impl PlayerHandler for Mock {
+    fn play(&mut self, state: &mut Capabilities) -> anyhow::Result<()> {
+        Self::log("Play");
+        state.playback_status = PlaybackStatus::Playing;
+        Ok(())
+    }
+
+    fn pause(&mut self, state: &mut Capabilities) -> anyhow::Result<()> {
+        Self::log("Pause");
+        state.playback_status = PlaybackStatus::Paused;
+        Ok(())
+    }
+
+    fn stop(&mut self, state: &mut Capabilities) -> anyhow::Result<()> {
+        Self::log("Stop");
+        state.playback_status = PlaybackStatus::Stopped;
+        state.position = 0;
+        Ok(())
+    }
+
+    fn next(&mut self, state: &mut Capabilities) -> anyhow::Result<()> {
+        Self::log("Next");
+        state.position = 0;
+        Ok(())
+    }
+
+    fn previous(&mut self, state: &mut Capabilities) -> anyhow::Result<()> {
+        Self::log("Previous");
+        state.position = 0;
+        Ok(())
+    }
+
+    fn seek(&mut self, state: &mut Capabilities, offset: i64) -> anyhow::Result<()> {
+        Self::log(&format!("Seek {offset}"));
+        state.position = state.position.saturating_add_signed(offset);
+        Ok(())
+    }
+
+    fn set_position(
+        &mut self,
+        state: &mut Capabilities,
+        track: &TrackId,
+        position: u64,
+    ) -> anyhow::Result<()> {
+        Self::log(&format!("SetPosition {track} {position}"));
+        if state.metadata.track_id() == Some(track) {
+            state.position = position;
+        }
+        Ok(())
+    }
+
+    fn open_uri(&mut self, _: &mut Capabilities, uri: &str) -> anyhow::Result<()> {
+        Self::log(&format!("OpenUri {uri}"));
+        Ok(())
+    }
+
+    fn set_volume(&mut self, state: &mut Capabilities, volume: f64) -> anyhow::Result<()> {
+        Self::log(&format!("Volume = {volume}"));
+        state.volume = Some(volume.clamp(0.0, 1.0));
+        Ok(())
+    }
+
+    fn set_rate(&mut self, state: &mut Capabilities, rate: f64) -> anyhow::Result<()> {
+        Self::log(&format!("Rate = {rate}"));
+        state.rate = rate;
+        Ok(())
+    }
+
+    fn set_loop_status(
+        &mut self,
+        state: &mut Capabilities,
+        status: LoopStatus,
+    ) -> anyhow::Result<()> {
+        Self::log(&format!("LoopStatus = {status}"));
+        state.loop_status = Some(status);
+        Ok(())
+    }
+
+    fn set_shuffle(&mut self, state: &mut Capabilities, shuffle: bool) -> anyhow::Result<()> {
+        Self::log(&format!("Shuffle = {shuffle}"));
+        state.shuffle = Some(shuffle);
+        Ok(())
+    }
+
+    fn raise(&mut self) -> anyhow::Result<()> {
+        Self::log("Raise");
+        Ok(())
+    }
+
+    fn quit(&mut self) -> anyhow::Result<()> {
+        Self::log("Quit");
+        Ok(())
+    }
+}
+
+/// splits a line into words, double quotes keep spaces and `\` escapes the next character
+fn words(line: &str) -> anyhow::Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quoted = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.get_or_insert_default();
+            }
+            '\\' => {
+                let escaped = chars.next().ok_or_else(|| anyhow!("nothing to escape"))?;
+                word.get_or_insert_default().push(escaped);
+            }
+            '#' if !quoted && word.is_none() => break,
+            c if c.is_whitespace() && !quoted => words.extend(word.take()),
+            c => word.get_or_insert_default().push(c),
+        }
+    }
+    if quoted {
+        bail!("unclosed quote");
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+fn seconds(s: &str) -> anyhow::Result<u64> {
+    let seconds: f64 = s.parse().with_context(|| format!("{s} is not a number"))?;
+    Ok((seconds * 1_000_000.0) as u64)
+}
+
+fn switch(s: &str) -> anyhow::Result<bool> {
+    match s {
+        "on" | "true" | "yes" => Ok(true),
+        "off" | "false" | "no" => Ok(false),
+        _ => bail!("expected on or off, got {s}"),
+    }
+}
+
+/// `key=value` pairs as metadata, tracks without a `trackid` get a new one
+fn track(args: &[String], count: &mut u32) -> anyhow::Result<Metadata> {
+    *count += 1;
+    let mut builder = MetadataBuilder::default();
+    let mut artists = Vec::new();
+    let mut trackid = format!("/org/mpris/MediaPlayer2/mock/track/{count}");
+    for arg in args {
+        let (key, value) = arg
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected key=value, got {arg}"))?;
+        let value = value.to_string();
+        builder = match key {
+            "title" => builder.title(value),
+            "album" => builder.album(value),
+            "artist" => {
+                artists.push(value);
+                builder
+            }
+            "album_artist" => builder.album_artists(vec![value]),
+            "length" => builder.length(seconds(&value)?),
+            "art" => builder.art_url(value),
+            "url" => builder.url(value),
+            "track_number" => builder.track_number(value.parse()?),
+            "trackid" => {
+                trackid = value;
+                builder
+            }
+            _ => bail!("unknown track field {key}"),
+        };
+    }
+    if !artists.is_empty() {
+        builder = builder.artists(artists);
+    }
+
+    Ok(builder.trackid(trackid).finish())
+}
+
+struct Script {
+    service: MprisService,
+    tracks: u32,
+}
+
+impl Script {
+    /// runs one line, `false` once the script says `exit`
+    async fn run(&mut self, line: &str) -> anyhow::Result<bool> {
+        let words = words(line)?;
+        let Some((command, args)) = words.split_first() else {
+            return Ok(true);
+        };
+        let arg = |i: usize| -> anyhow::Result<&str> {
+            args.get(i)
+                .map(String::as_str)
+                .ok_or_else(|| anyhow!("{command} needs more arguments"))
+        };
+
+        let service = &self.service;
+        match command.as_str() {
+            "track" => {
+                let metadata = track(args, &mut self.tracks)?;
+                service
+                    .update(|state| {
+                        state.metadata = metadata;
+                        state.position = 0;
+                    })
+                    .await?
+            }
+            "clear" => {
+                service
+                    .update(|state| {
+                        state.metadata = Metadata::default();
+                        state.position = 0;
+                    })
+                    .await?
+            }
+            "status" => {
+                let status = match arg(0)? {
+                    "playing" | "Playing" => PlaybackStatus::Playing,
+                    "paused" | "Paused" => PlaybackStatus::Paused,
+                    "stopped" | "Stopped" => PlaybackStatus::Stopped,
+                    status => bail!("unknown status {status}"),
+                };
+                service.set_playback_status(status).await?
+            }
+            "position" => service.set_position(seconds(arg(0)?)?).await?,
+            "seek" => service.seeked(seconds(arg(0)?)?).await?,
+            "volume" => service.set_volume(arg(0)?.parse()?).await?,
+            "rate" => service.set_rate(arg(0)?.parse()?).await?,
+            "loop" => {
+                let status = match arg(0)? {
+                    "none" => LoopStatus::None,
+                    "track" => LoopStatus::Track,
+                    "playlist" => LoopStatus::Playlist,
+                    status => status.parse()?,
+                };
+                service.set_loop_status(status).await?
+            }
+            "shuffle" => service.set_shuffle(switch(arg(0)?)?).await?,
+            "can" => {
+                let on = switch(arg(1)?)?;
+                let capability = arg(0)?;
+                let field = |state: &mut Capabilities| -> anyhow::Result<()> {
+                    let field = match capability {
+                        "control" => &mut state.can_control,
+                        "next" => &mut state.can_next,
+                        "previous" => &mut state.can_previous,
+                        "play" => &mut state.can_play,
+                        "pause" => &mut state.can_pause,
+                        "seek" => &mut state.can_seek,
+                        _ => bail!("unknown capability {capability}"),
+                    };
+                    *field = on;
+                    Ok(())
+                };
+                let mut result = Ok(());
+                service.update(|state| result = field(state)).await?;
+                result?
+            }
+            "identity" => {
+                let identity = arg(0)?.to_string();
+                service
+                    .update_root(|root| root.identity = Some(identity))
+                    .await?
+            }
+            "sleep" => tokio::time::sleep(Duration::from_micros(seconds(arg(0)?)?)).await,
+            "exit" => return Ok(false),
+            _ => bail!("unknown command {command}"),
+        }
+        Ok(true)
+    }
+
+    /// moves the position along while playing, like a real player would
+    async fn tick(&self, elapsed: Duration) -> anyhow::Result<()> {
+        self.service
+            .update(|state| {
+                if state.playback_status == PlaybackStatus::Playing {
+                    let elapsed = elapsed.as_micros() as f64 * state.rate;
+                    state.position = state.position.saturating_add(elapsed as u64);
+                }
+            })
+            .await
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let input: Box<dyn AsyncBufRead + Unpin> = match &cli.script {
+        Some(path) => Box::new(BufReader::new(
+            tokio::fs::File::open(path)
+                .await
+                .with_context(|| format!("failed to open {}", path.display()))?,
+        )),
+        None => Box::new(BufReader::new(tokio::io::stdin())),
+    };
+
+    let service = MprisService::builder(&cli.name, Mock)
+        .capabilities(Capabilities {
+            can_control: true,
+            can_next: true,
+            can_previous: true,
+            can_pause: true,
+            can_play: true,
+            can_seek: true,
+            loop_status: Some(LoopStatus::None),
+            rate: 1.0,
+            shuffle: Some(false),
+            volume: Some(1.0),
+            ..Default::default()
+        })
+        .root(lib::player::RootProperties {
+            identity: Some("Mock Player".to_string()),
+            can_raise: true,
+            can_quit: true,
+            ..Default::default()
+        })
+        .serve()
+        .await?;
+    eprintln!("serving {}", service.name());
+
+    let mut script = Script { service, tracks: 0 };
+    let mut lines = input.lines();
+    let mut number = 0;
+    let mut reading = true;
+    let interval = Duration::from_secs(1);
+    let mut ticks = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            line = lines.next_line(), if reading => {
+                let Some(line) = line? else {
+                    reading = false;
+                    continue;
+                };
+                number += 1;
+                match script.run(&line).await {
+                    Ok(true) => {}
+                    Ok(false) => return Ok(()),
+                    Err(e) => eprintln!("line {number}: {e:#}"),
+                }
+            }
+            _ = ticks.tick() => script.tick(interval).await?,
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
@@ -240,5 +240,5 @@ async fn main() {
     let name = WellKnownName::from_static_str_unchecked("org.mpris.MediaPlayer2.controller");
     conn.request_name(&name).await.unwrap();
 
-    loop {}
+    std::future::pending::<()>().await;
 }
@@ -1,19 +1,23 @@
 use std::{
     io::{ErrorKind, Read, Write},
     os::unix::net::UnixListener,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-#[cfg(feature = "owner_changed")]
-use lib::init_owner_changed_signal;
-
-use lib::{Client, MprisClient, client::Message};
+use lib::{Bus, Client, MprisClient, client::Message, systemd};
 use prost::Message as _;
-use tracing::{info, level_filters::LevelFilter};
-use zbus::Connection;
+use tokio::signal::unix::{SignalKind, signal};
+use tracing::{info, level_filters::LevelFilter, warn};
 
 #[tokio::main]
 async fn main() {
+    // `server --user-unit > ~/.config/systemd/user/mpris-controller.service`
+    if std::env::args().skip(1).any(|arg| arg == "--user-unit") {
+        let exe = std::env::current_exe().unwrap();
+        print!("{}", systemd::user_unit("mpris-controller server", &exe));
+        return;
+    }
+
     let _guard = tracing::subscriber::set_global_default(
         tracing_subscriber::FmtSubscriber::builder()
             .with_max_level(LevelFilter::INFO)
@@ -24,21 +28,45 @@ async fn main() {
         std::fs::remove_file(path).unwrap();
     }
     let server = UnixListener::bind("/tmp/mpris-controller.sock").unwrap();
+    // accepting can't block, the loop also has to see signals and feed the watchdog
+    server.set_nonblocking(true).unwrap();
+    let mut sigterm = signal(SignalKind::terminate()).unwrap();
+    let mut sigint = signal(SignalKind::interrupt()).unwrap();
 
     let mut bytes = [0; 512];
     let mut send = vec![];
 
-    let mut client = MprisClient::new().unwrap();
-    let conn = Connection::session().await.unwrap();
-    client.get_all(&conn).await.unwrap();
+    // `server --bus system` for players on another bus, see `Bus` for what it takes
+    let bus: Bus = match std::env::args().skip_while(|arg| arg != "--bus").nth(1) {
+        Some(bus) => bus.parse().unwrap(),
+        None => Bus::default(),
+    };
+    let mut client = MprisClient::connect_to(bus).await.unwrap();
+    client.get_all().await.unwrap();
 
     #[cfg(feature = "owner_changed")]
-    init_owner_changed_signal().await;
+    client.follow_owner_changes().await.unwrap();
 
     let mut player = None;
     let mut socket = None;
 
+    notify(systemd::ready());
+    let watchdog = systemd::watchdog_interval();
+    let mut fed = Instant::now();
+
     loop {
+        tokio::select! {
+            _ = sigterm.recv() => break,
+            _ = sigint.recv() => break,
+            _ = tokio::time::sleep(Duration::from_millis(5)) => {}
+        }
+        if let Some(interval) = watchdog
+            && fed.elapsed() >= interval
+        {
+            notify(systemd::watchdog());
+            fed = Instant::now();
+        }
+
         match socket {
             None => match server.accept() {
                 Ok((sock, _)) => {
@@ -51,7 +79,6 @@ async fn main() {
                     if e.kind() != ErrorKind::WouldBlock {
                         panic!("{e:?}");
                     }
-                    continue;
                 }
             },
             Some(ref mut sock) => match sock.read(&mut bytes) {
@@ -65,15 +92,13 @@ async fn main() {
                                     player = client.get_id(&name)
                                 }
                                 lib::server::Command::GetPlayer(_) => {
-                                    client.event(&conn).await;
+                                    client.event().await;
 
-                                    if player.is_none()
-                                        && let Some(p) = client.currently_playing()
-                                    {
-                                        player = Some(client.get_id(p.name()).unwrap())
-                                    }
+                                    // without an explicit focus, follow the active player
+                                    let focused =
+                                        player.or_else(|| client.active_player().map(|p| p.id()));
 
-                                    let msg = match player {
+                                    let msg = match focused {
                                         None => Client {
                                             message: Some(Message::CouldNotFindPlayer(true)),
                                         },
@@ -111,4 +136,15 @@ async fn main() {
             },
         }
     }
+
+    info!("shutting down");
+    notify(systemd::stopping());
+    client.shutdown().await;
+    _ = std::fs::remove_file(path);
+}
+
+fn notify(sent: std::io::Result<bool>) {
+    if let Err(e) = sent {
+        warn!("failed to notify systemd: {e}");
+    }
 }